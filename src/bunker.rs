@@ -0,0 +1,589 @@
+/*
+ * bunker.rs
+ * Copyright (C) 2026 Chris Burdess
+ *
+ * This file is part of Plume, a Nostr desktop client.
+ *
+ * Plume is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Plume is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Plume.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! NIP-46 remote signer ("bunker") for our own profiles. Other Nostr apps connect with the
+//! bunker:// URI shown in Settings, then send signing and encryption requests as kind 24133
+//! events on the relays the user chose. Each connected app has a policy: which event kinds it
+//! may have signed, whether it may encrypt and decrypt, and how many requests a minute it may
+//! make. Anything else is refused. A new app gets the default policy; the permissions it asks
+//! for when connecting are only shown to the user, who decides whether to grant them.
+//!
+//! Relays, the connect secret and app policies are kept per profile in `bunker.json`. A bunker
+//! signs with its profile's key as unlocked for the session, so several profiles can be served
+//! at once; all of them stop when the keys are locked.
+
+use bytes::BytesMut;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex, OnceLock};
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::{Duration, Instant};
+
+use crate::config::escape_json_string;
+use crate::crypto;
+use crate::debug_log;
+use crate::json::{JsonContentHandler, JsonNumber, JsonParser};
+use crate::nip46::{self, BunkerUri};
+use crate::nostr::{self, Event};
+use crate::relay::RelayMessage;
+use crate::relay_pool::{relay_pool, SupervisedSubscription};
+use crate::warn_log;
+
+const SETTINGS_FILE: &str = "bunker.json";
+
+/// Kinds a newly connected app may have signed until the user changes its policy (notes,
+/// reposts, reactions).
+const DEFAULT_ALLOWED_KINDS: [u32; 3] = [1, 6, 7];
+
+const DEFAULT_REQUESTS_PER_MINUTE: u32 = 30;
+
+/// How long to wait for a relay to accept a response (seconds).
+const PUBLISH_TIMEOUT_SECS: u64 = 10;
+
+/// Request ids remembered, so a request seen on several relays is answered once.
+const SEEN_REQUESTS_CAPACITY: usize = 1000;
+
+/// What a connected app may ask for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppPolicy {
+    /// The app's NIP-46 client public key (hex).
+    pub pubkey: String,
+    pub name: String,
+    pub allowed_kinds: Vec<u32>,
+    pub allow_encryption: bool,
+    /// 0 means no limit.
+    pub requests_per_minute: u32,
+    /// Kinds the app asked to sign when it connected, not granted until the user approves.
+    pub requested_kinds: Vec<u32>,
+    /// The app asked to encrypt and decrypt; not granted until the user approves.
+    pub requested_encryption: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct BunkerSettings {
+    pub relays: Vec<String>,
+    /// One-time secret in the bunker URI; replaced each time an app connects with it.
+    pub secret: String,
+    pub apps: Vec<AppPolicy>,
+}
+
+// ============================================================
+// Settings file
+// ============================================================
+
+#[derive(PartialEq)]
+enum SettingsArray {
+    None,
+    Relays,
+    Apps,
+    AllowedKinds,
+    RequestedKinds,
+}
+
+struct SettingsHandler {
+    settings: BunkerSettings,
+    depth: i32,
+    current_key: Option<String>,
+    array: SettingsArray,
+    app: Option<AppPolicy>,
+}
+
+impl JsonContentHandler for SettingsHandler {
+    fn start_object(&mut self) {
+        self.depth += 1;
+        if self.depth == 3 && self.array == SettingsArray::Apps {
+            self.app = Some(AppPolicy {
+                pubkey: String::new(),
+                name: String::new(),
+                allowed_kinds: Vec::new(),
+                allow_encryption: false,
+                requests_per_minute: DEFAULT_REQUESTS_PER_MINUTE,
+                requested_kinds: Vec::new(),
+                requested_encryption: false,
+            });
+        }
+    }
+    fn end_object(&mut self) {
+        if self.depth == 3 {
+            if let Some(app) = self.app.take().filter(|a| !a.pubkey.is_empty()) {
+                self.settings.apps.push(app);
+            }
+        }
+        self.depth -= 1;
+    }
+    fn start_array(&mut self) {
+        self.depth += 1;
+        self.array = match (self.depth, self.current_key.as_deref()) {
+            (2, Some("relays")) => SettingsArray::Relays,
+            (2, Some("apps")) => SettingsArray::Apps,
+            (4, Some("allowed_kinds")) => SettingsArray::AllowedKinds,
+            (4, Some("requested_kinds")) => SettingsArray::RequestedKinds,
+            _ => SettingsArray::None,
+        };
+    }
+    fn end_array(&mut self) {
+        self.depth -= 1;
+        self.array = if self.depth == 3 && self.app.is_some() { SettingsArray::Apps } else { SettingsArray::None };
+    }
+    fn key(&mut self, key: &str) {
+        self.current_key = Some(key.to_string());
+    }
+    fn string_value(&mut self, value: &str) {
+        if self.array == SettingsArray::Relays {
+            self.settings.relays.push(value.to_string());
+            return;
+        }
+        match (self.depth, self.current_key.as_deref(), self.app.as_mut()) {
+            (1, Some("secret"), _) => self.settings.secret = value.to_string(),
+            (3, Some("pubkey"), Some(app)) => app.pubkey = value.to_lowercase(),
+            (3, Some("name"), Some(app)) => app.name = value.to_string(),
+            _ => {}
+        }
+    }
+    fn number_value(&mut self, number: JsonNumber) {
+        let n = number.as_f64().max(0.0) as u32;
+        match (self.depth, self.current_key.as_deref(), self.app.as_mut()) {
+            (4, _, Some(app)) if self.array == SettingsArray::AllowedKinds => app.allowed_kinds.push(n),
+            (4, _, Some(app)) if self.array == SettingsArray::RequestedKinds => app.requested_kinds.push(n),
+            (3, Some("requests_per_minute"), Some(app)) => app.requests_per_minute = n,
+            _ => {}
+        }
+    }
+    fn boolean_value(&mut self, value: bool) {
+        match (self.depth, self.current_key.as_deref(), self.app.as_mut()) {
+            (3, Some("allow_encryption"), Some(app)) => app.allow_encryption = value,
+            (3, Some("requested_encryption"), Some(app)) => app.requested_encryption = value,
+            _ => {}
+        }
+    }
+    fn null_value(&mut self) {}
+}
+
+fn parse_settings(json: &str) -> Result<BunkerSettings, String> {
+    let mut handler = SettingsHandler {
+        settings: BunkerSettings::default(),
+        depth: 0,
+        current_key: None,
+        array: SettingsArray::None,
+        app: None,
+    };
+    let mut parser = JsonParser::new();
+    let mut buf = BytesMut::from(json.as_bytes());
+    parser.receive(&mut buf, &mut handler).map_err(|e| format!("JSON parse error: {}", e))?;
+    parser.close(&mut handler).map_err(|e| format!("JSON parse error: {}", e))?;
+    Ok(handler.settings)
+}
+
+pub fn settings_to_json(settings: &BunkerSettings) -> String {
+    let mut json = String::from("{\n  \"relays\": [");
+    json.push_str(&string_list(&settings.relays));
+    json.push_str("],\n  \"secret\": \"");
+    json.push_str(&escape_json_string(&settings.secret));
+    json.push_str("\",\n  \"apps\": [");
+    for (i, app) in settings.apps.iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        let kinds: Vec<String> = app.allowed_kinds.iter().map(|k| k.to_string()).collect();
+        let requested: Vec<String> = app.requested_kinds.iter().map(|k| k.to_string()).collect();
+        json.push_str(&format!(
+            "\n    {{\"pubkey\":\"{}\",\"name\":\"{}\",\"allowed_kinds\":[{}],\"allow_encryption\":{},\"requests_per_minute\":{},\"requested_kinds\":[{}],\"requested_encryption\":{}}}",
+            escape_json_string(&app.pubkey),
+            escape_json_string(&app.name),
+            kinds.join(","),
+            app.allow_encryption,
+            app.requests_per_minute,
+            requested.join(","),
+            app.requested_encryption
+        ));
+    }
+    json.push_str("\n  ]\n}");
+    json
+}
+
+fn settings_path(config_dir: &str) -> String {
+    Path::new(config_dir).join(SETTINGS_FILE).to_string_lossy().to_string()
+}
+
+/// The profile's bunker settings; defaults (no relays, a fresh secret) if it has none yet.
+pub fn load_settings(config_dir: &str) -> Result<BunkerSettings, String> {
+    let mut settings = match fs::read_to_string(settings_path(config_dir)) {
+        Ok(contents) => parse_settings(&contents)?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => BunkerSettings::default(),
+        Err(e) => return Err(format!("Could not read bunker settings: {}", e)),
+    };
+    if settings.secret.is_empty() {
+        settings.secret = nip46::new_request_id();
+    }
+    Ok(settings)
+}
+
+pub fn save_settings(config_dir: &str, settings: &BunkerSettings) -> Result<(), String> {
+    fs::write(settings_path(config_dir), settings_to_json(settings))
+        .map_err(|e| format!("Could not write bunker settings: {}", e))
+}
+
+/// The URI other apps connect with, including the current secret.
+pub fn bunker_uri(settings: &BunkerSettings, user_pubkey_hex: &str) -> String {
+    nip46::bunker_uri_to_string(&BunkerUri {
+        remote_pubkey: user_pubkey_hex.to_string(),
+        relays: settings.relays.clone(),
+        secret: Some(settings.secret.clone()),
+    })
+}
+
+/// Settings, URI and running state for the Settings panel, as JSON.
+pub fn status_to_json(config_dir: &str, user_pubkey_hex: &str) -> Result<String, String> {
+    let settings = load_settings(config_dir)?;
+    let uri = if settings.relays.is_empty() { String::new() } else { bunker_uri(&settings, user_pubkey_hex) };
+    Ok(format!(
+        "{{\"running\":{},\"uri\":\"{}\",\"settings\":{}}}",
+        is_running(config_dir),
+        escape_json_string(&uri),
+        settings_to_json(&settings)
+    ))
+}
+
+// ============================================================
+// Running bunkers
+// ============================================================
+
+/// Called with an app's client public key when it connects.
+pub type AppConnectedHandler = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// Stop signals of the running bunkers, by profile directory.
+fn running_bunkers() -> &'static Mutex<HashMap<String, oneshot::Sender<()>>> {
+    static INSTANCE: OnceLock<Mutex<HashMap<String, oneshot::Sender<()>>>> = OnceLock::new();
+    INSTANCE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Recent request times by app, for rate limiting.
+fn request_times() -> &'static Mutex<HashMap<String, VecDeque<Instant>>> {
+    static INSTANCE: OnceLock<Mutex<HashMap<String, VecDeque<Instant>>>> = OnceLock::new();
+    INSTANCE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+pub fn is_running(config_dir: &str) -> bool {
+    running_bunkers()
+        .lock()
+        .unwrap()
+        .get(config_dir)
+        .is_some_and(|stop| !stop.is_closed())
+}
+
+/// Start serving the profile in `config_dir` with its unlocked secret key, on its bunker relays.
+/// A bunker already running for the profile is restarted, so relay changes apply.
+pub fn start(config_dir: &str, secret_key_hex: &str, on_app_connected: AppConnectedHandler) -> Result<(), String> {
+    let settings = load_settings(config_dir)?;
+    if settings.relays.is_empty() {
+        return Err(String::from("Choose at least one relay for the bunker"));
+    }
+    // Keep the secret that was shown, even if it was only just generated
+    save_settings(config_dir, &settings)?;
+    let bunker = Bunker {
+        config_dir: config_dir.to_string(),
+        secret_key: secret_key_hex.to_string(),
+        user_pubkey: crypto::get_public_key_from_secret(secret_key_hex)?,
+        relays: settings.relays,
+        on_app_connected,
+    };
+    let (stop_tx, stop_rx) = oneshot::channel();
+    if let Some(previous) = running_bunkers().lock().unwrap().insert(config_dir.to_string(), stop_tx) {
+        let _ = previous.send(());
+    }
+    std::thread::spawn(move || {
+        let rt = match tokio::runtime::Runtime::new() {
+            Ok(r) => r,
+            Err(e) => {
+                warn_log!("[bunker] failed to create runtime: {}", e);
+                return;
+            }
+        };
+        rt.block_on(bunker.run(stop_rx));
+    });
+    Ok(())
+}
+
+pub fn stop(config_dir: &str) {
+    if let Some(stop) = running_bunkers().lock().unwrap().remove(config_dir) {
+        let _ = stop.send(());
+    }
+}
+
+/// Stop every bunker, when the session's keys are locked.
+pub fn stop_all() {
+    for (_, stop) in running_bunkers().lock().unwrap().drain() {
+        let _ = stop.send(());
+    }
+}
+
+struct Bunker {
+    config_dir: String,
+    secret_key: String,
+    user_pubkey: String,
+    relays: Vec<String>,
+    on_app_connected: AppConnectedHandler,
+}
+
+impl Bunker {
+    async fn run(self, mut stop: oneshot::Receiver<()>) {
+        debug_log!("[bunker] serving {} on {:?}", self.user_pubkey, self.relays);
+        let filter = nostr::filter_nostr_connect(&self.user_pubkey, None, crypto::now_secs());
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        for relay_url in &self.relays {
+            let mut sub = SupervisedSubscription::new(relay_url, "plume_bunker_", vec![filter.clone()]);
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                loop {
+                    let message = sub.recv().await;
+                    if tx.is_closed() {
+                        return;
+                    }
+                    if let Some(RelayMessage::Event { event, .. }) = message {
+                        let _ = tx.send(event);
+                    }
+                }
+            });
+        }
+        drop(tx);
+
+        let mut seen: HashSet<String> = HashSet::new();
+        let mut seen_order: VecDeque<String> = VecDeque::new();
+        loop {
+            let event = tokio::select! {
+                _ = &mut stop => break,
+                event = rx.recv() => match event {
+                    Some(event) => event,
+                    None => break,
+                },
+            };
+            if !seen.insert(event.id.clone()) {
+                continue;
+            }
+            seen_order.push_back(event.id.clone());
+            if seen_order.len() > SEEN_REQUESTS_CAPACITY {
+                if let Some(old) = seen_order.pop_front() {
+                    seen.remove(&old);
+                }
+            }
+            if let Err(e) = self.answer(&event).await {
+                debug_log!("[bunker] request {} not answered: {}", event.id, e);
+            }
+        }
+        debug_log!("[bunker] stopped serving {}", self.user_pubkey);
+        // Dropping the runtime ends the relay tasks, which closes their subscriptions
+    }
+
+    async fn answer(&self, event: &Event) -> Result<(), String> {
+        let message = nip46::open_message_event(event, &self.secret_key)?;
+        let Some(method) = message.method.as_deref() else {
+            return Ok(()); // a response, not meant for us
+        };
+        let client = event.pubkey.to_lowercase();
+        let json = match self.handle_request(&client, method, &message.params) {
+            Ok(result) => nip46::response_to_json(&message.id, &result, None),
+            Err(e) => {
+                debug_log!("[bunker] refused {} from {}: {}", method, client, e);
+                nip46::response_to_json(&message.id, "", Some(&e))
+            }
+        };
+        let response = nip46::create_message_event(&json, &self.secret_key, &client)?;
+        for relay_url in &self.relays {
+            let relay_url = relay_url.clone();
+            let response = response.clone();
+            tokio::spawn(async move {
+                let timeout = Duration::from_secs(PUBLISH_TIMEOUT_SECS);
+                match tokio::time::timeout(timeout, relay_pool().publish(&relay_url, &response)).await {
+                    Ok(Ok((true, _))) => {}
+                    Ok(Ok((false, message))) => debug_log!("[bunker] {} refused response: {}", relay_url, message),
+                    Ok(Err(e)) => debug_log!("[bunker] response to {}: {}", relay_url, e),
+                    Err(_) => debug_log!("[bunker] response to {} timed out", relay_url),
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// Carry out a request from `client` if its policy allows; the result or an error for the app.
+    fn handle_request(&self, client: &str, method: &str, params: &[String]) -> Result<String, String> {
+        let mut settings = load_settings(&self.config_dir)?;
+        if method == "connect" {
+            return self.connect(&mut settings, client, params);
+        }
+        let app = settings
+            .apps
+            .iter()
+            .find(|a| a.pubkey == client)
+            .ok_or_else(|| String::from("Not connected; connect with the bunker URI first"))?;
+        if !allow_request(client, app.requests_per_minute) {
+            return Err(String::from("Rate limit exceeded"));
+        }
+        let param = |i: usize| params.get(i).map(String::as_str).ok_or_else(|| format!("Missing parameter for {}", method));
+        match method {
+            "ping" => Ok(String::from("pong")),
+            "get_public_key" => Ok(self.user_pubkey.clone()),
+            "sign_event" => {
+                let mut event = nostr::parse_unsigned_event(param(0)?)?;
+                if !app.allowed_kinds.contains(&event.kind) {
+                    return Err(format!("Not allowed to sign events of kind {}", event.kind));
+                }
+                event.pubkey = self.user_pubkey.clone();
+                if event.created_at == 0 {
+                    event.created_at = crypto::now_secs();
+                }
+                crypto::sign_event(&mut event, &self.secret_key)?;
+                Ok(nostr::event_to_json(&event))
+            }
+            "nip04_encrypt" | "nip04_decrypt" | "nip44_encrypt" | "nip44_decrypt" => {
+                if !app.allow_encryption {
+                    return Err(String::from("Not allowed to encrypt or decrypt"));
+                }
+                let (peer, text) = (param(0)?, param(1)?);
+                match method {
+                    "nip04_encrypt" => crypto::nip04_encrypt(text, &self.secret_key, peer),
+                    "nip04_decrypt" => crypto::nip04_decrypt(text, &self.secret_key, peer),
+                    "nip44_encrypt" => crypto::nip44_encrypt(text, &self.secret_key, peer),
+                    _ => crypto::nip44_decrypt(text, &self.secret_key, peer),
+                }
+            }
+            _ => Err(format!("Unsupported method: {}", method)),
+        }
+    }
+
+    /// connect [our pubkey, secret, requested permissions]. An app presenting the current secret
+    /// is added with the default policy, its requested permissions kept for the user to review,
+    /// and the secret is replaced.
+    fn connect(&self, settings: &mut BunkerSettings, client: &str, params: &[String]) -> Result<String, String> {
+        if settings.apps.iter().any(|a| a.pubkey == client) {
+            return Ok(String::from("ack"));
+        }
+        if params.get(1).map(String::as_str) != Some(settings.secret.as_str()) {
+            return Err(String::from("Invalid or expired secret"));
+        }
+        let mut app = app_policy_from_perms(client, params.get(2).map(String::as_str).unwrap_or(""));
+        app.name = format!("App {}", &client[..8.min(client.len())]);
+        settings.apps.push(app);
+        settings.secret = nip46::new_request_id();
+        save_settings(&self.config_dir, settings)?;
+        (self.on_app_connected)(client);
+        Ok(String::from("ack"))
+    }
+}
+
+/// Policy for a new app: the defaults, with the permissions it requested
+/// ("sign_event:1,nip44_encrypt,...") recorded but not granted.
+fn app_policy_from_perms(client: &str, perms: &str) -> AppPolicy {
+    let mut requested_kinds: Vec<u32> = Vec::new();
+    let mut requested_encryption = false;
+    for perm in perms.split(',').map(str::trim) {
+        let (method, kind) = perm.split_once(':').unwrap_or((perm, ""));
+        match method {
+            "sign_event" => {
+                if let Ok(kind) = kind.parse::<u32>() {
+                    if !requested_kinds.contains(&kind) {
+                        requested_kinds.push(kind);
+                    }
+                }
+            }
+            "nip04_encrypt" | "nip04_decrypt" | "nip44_encrypt" | "nip44_decrypt" => requested_encryption = true,
+            _ => {}
+        }
+    }
+    AppPolicy {
+        pubkey: client.to_string(),
+        name: String::new(),
+        allowed_kinds: DEFAULT_ALLOWED_KINDS.to_vec(),
+        allow_encryption: false,
+        requests_per_minute: DEFAULT_REQUESTS_PER_MINUTE,
+        requested_kinds,
+        requested_encryption,
+    }
+}
+
+/// Count a request from `client` and say whether it is within `per_minute` (0 = unlimited).
+fn allow_request(client: &str, per_minute: u32) -> bool {
+    if per_minute == 0 {
+        return true;
+    }
+    let now = Instant::now();
+    let mut times = request_times().lock().unwrap();
+    let window = times.entry(client.to_string()).or_default();
+    while window.front().is_some_and(|t| now.duration_since(*t) >= Duration::from_secs(60)) {
+        window.pop_front();
+    }
+    if window.len() >= per_minute as usize {
+        return false;
+    }
+    window.push_back(now);
+    true
+}
+
+fn string_list(items: &[String]) -> String {
+    items
+        .iter()
+        .map(|s| format!("\"{}\"", escape_json_string(s)))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_requested_perms_are_not_granted() {
+        // What an app asks for is only a suggestion; it starts with the defaults
+        let app = app_policy_from_perms("aa", "sign_event:1,sign_event:30023,nip44_encrypt");
+        assert_eq!(app.allowed_kinds, DEFAULT_ALLOWED_KINDS.to_vec());
+        assert!(!app.allow_encryption);
+        assert_eq!(app.requested_kinds, vec![1, 30023]);
+        assert!(app.requested_encryption);
+    }
+
+    #[test]
+    fn test_no_perms_requested() {
+        let app = app_policy_from_perms("bb", "");
+        assert_eq!(app.allowed_kinds, DEFAULT_ALLOWED_KINDS.to_vec());
+        assert!(app.requested_kinds.is_empty());
+        assert!(!app.requested_encryption);
+    }
+
+    #[test]
+    fn test_settings_round_trip() {
+        let settings = BunkerSettings {
+            relays: vec![String::from("wss://relay.example")],
+            secret: String::from("s3cret"),
+            apps: vec![
+                app_policy_from_perms("aa", "sign_event:1,sign_event:30023,nip44_encrypt"),
+                app_policy_from_perms("bb", ""),
+            ],
+        };
+        assert_eq!(parse_settings(&settings_to_json(&settings)).unwrap(), settings);
+    }
+
+    #[test]
+    fn test_allow_request_per_minute() {
+        assert!(allow_request("cc", 2));
+        assert!(allow_request("cc", 2));
+        assert!(!allow_request("cc", 2));
+        assert!(allow_request("cc", 0));
+    }
+}
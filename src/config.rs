@@ -18,22 +18,34 @@
  * along with Plume.  If not, see <http://www.gnu.org/licenses/>.
  */
 
+use std::collections::HashMap;
 use std::fs;
 use std::io;
 use std::path::Path;
+use std::sync::{Mutex, OnceLock};
 
 use crate::debug_log;
 
 use bytes::BytesMut;
 use crate::json::{JsonContentHandler, JsonNumber, JsonParser};
-use crate::nostr;
+use crate::{crypto, keys, nostr, pow};
 
 // The main configuration structure.
 // Profile fields (name, about, picture, nip05, banner, website, lud16) are stored
 // directly rather than embedded as a JSON string, matching the Nostr kind 0 field names.
 pub struct Config {
     pub public_key: String,
+    /// Secret key (hex), only while unlocked for this session. Never written to disk once the
+    /// profile has an encrypted key; older configs may still carry it in plaintext until the
+    /// next unlock with a password.
     pub private_key: Option<String>,
+    /// NIP-49 encrypted secret key (ncryptsec1...): the only form stored on disk.
+    pub encrypted_private_key: Option<String>,
+    /// NIP-46 remote signer (bunker:// URI without its secret), for profiles whose key is held
+    /// elsewhere.
+    pub remote_signer: Option<String>,
+    /// Secret key (hex) of our NIP-46 client keypair; it only identifies this app to the signer.
+    pub remote_signer_client_key: Option<String>,
     pub relays: Vec<String>,
     // Profile fields (Nostr kind 0)
     pub name: String,
@@ -51,10 +63,20 @@ pub struct Config {
     pub muted_words: Vec<String>,
     pub muted_hashtags: Vec<String>,
     pub bookmarks: Vec<String>,
+    /// Relays we must not answer NIP-42 AUTH challenges for (auto-auth is on for all others).
+    pub auth_denied_relays: Vec<String>,
+    /// NIP-13 proof of work to mine into events we publish to a relay, by relay URL. Relays
+    /// not listed need none beyond what their NIP-11 document asks for.
+    pub relay_pow_difficulty: Vec<(String, u32)>,
+    /// Firehose notes whose NIP-13 difficulty is below this are dropped as spam; 0 keeps all.
+    pub min_feed_pow: u32,
     pub default_zap_amount: u32,
+    /// Number of relays that must accept a published event before the post returns;
+    /// 0 waits for every relay. The others keep being tried in the background.
+    pub publish_quorum: u32,
     pub hide_encrypted_notes: bool,
-    /// Unix timestamp of the last time the user read their DMs.
-    /// Messages with created_at > this value are considered unread.
+    /// Unix timestamp of the last time the user read their DMs, from before read markers were
+    /// kept per conversation (messages_store). Carried over to each conversation and reset to 0.
     pub dm_last_read_at: u64,
 }
 
@@ -63,6 +85,9 @@ impl Config {
         Config {
             public_key: String::new(),
             private_key: None,
+            encrypted_private_key: None,
+            remote_signer: None,
+            remote_signer_client_key: None,
             relays: vec![
                 String::from("wss://relay.damus.io"),
                 String::from("wss://nos.lol"),
@@ -82,11 +107,24 @@ impl Config {
             muted_words: Vec::new(),
             muted_hashtags: Vec::new(),
             bookmarks: Vec::new(),
+            auth_denied_relays: Vec::new(),
+            relay_pow_difficulty: Vec::new(),
+            min_feed_pow: 0,
             default_zap_amount: 42,
+            publish_quorum: 1,
             hide_encrypted_notes: true,
             dm_last_read_at: 0,
         }
     }
+
+    /// Our proof of work setting for `relay_url`, 0 if it has none.
+    pub fn relay_pow_difficulty(&self, relay_url: &str) -> u32 {
+        self.relay_pow_difficulty
+            .iter()
+            .find(|(url, _)| url == relay_url)
+            .map(|(_, difficulty)| *difficulty)
+            .unwrap_or(0)
+    }
 }
 
 // ============================================================
@@ -103,15 +141,21 @@ enum ConfigArrayField {
     MutedWords,
     MutedHashtags,
     Bookmarks,
+    AuthDeniedRelays,
 }
 
 struct ConfigHandler {
     depth: i32,
     current_field: Option<String>,
     array_field: ConfigArrayField,
+    /// Inside the relay_pow_difficulty object (depth 2), whose keys are relay URLs.
+    in_relay_pow: bool,
     // Scalar fields
     public_key: String,
     private_key: Option<String>,
+    encrypted_private_key: Option<String>,
+    remote_signer: Option<String>,
+    remote_signer_client_key: Option<String>,
     name: String,
     about: Option<String>,
     picture: Option<String>,
@@ -122,8 +166,11 @@ struct ConfigHandler {
     home_feed_mode: String,
     media_server_url: String,
     default_zap_amount: u32,
+    publish_quorum: u32,
     hide_encrypted_notes: bool,
     dm_last_read_at: u64,
+    min_feed_pow: u32,
+    relay_pow_difficulty: Vec<(String, u32)>,
     // Array fields
     relays: Vec<String>,
     following: Vec<String>,
@@ -131,6 +178,7 @@ struct ConfigHandler {
     muted_words: Vec<String>,
     muted_hashtags: Vec<String>,
     bookmarks: Vec<String>,
+    auth_denied_relays: Vec<String>,
     // Legacy field for backward compatibility (old configs stored profile as embedded JSON string)
     profile_metadata_raw: Option<String>,
 }
@@ -141,8 +189,12 @@ impl ConfigHandler {
             depth: 0,
             current_field: None,
             array_field: ConfigArrayField::None,
+            in_relay_pow: false,
             public_key: String::new(),
             private_key: None,
+            encrypted_private_key: None,
+            remote_signer: None,
+            remote_signer_client_key: None,
             name: String::from("Anonymous"),
             about: None,
             picture: None,
@@ -153,14 +205,18 @@ impl ConfigHandler {
             home_feed_mode: String::from("firehose"),
             media_server_url: String::from("https://blossom.primal.net"),
             default_zap_amount: 42,
+            publish_quorum: 1,
             hide_encrypted_notes: true,
             dm_last_read_at: 0,
+            min_feed_pow: 0,
+            relay_pow_difficulty: Vec::new(),
             relays: Vec::new(),
             following: Vec::new(),
             muted_users: Vec::new(),
             muted_words: Vec::new(),
             muted_hashtags: Vec::new(),
             bookmarks: Vec::new(),
+            auth_denied_relays: Vec::new(),
             profile_metadata_raw: None,
         }
     }
@@ -211,6 +267,9 @@ impl ConfigHandler {
         Config {
             public_key: self.public_key,
             private_key: self.private_key,
+            encrypted_private_key: self.encrypted_private_key,
+            remote_signer: self.remote_signer,
+            remote_signer_client_key: self.remote_signer_client_key,
             relays,
             name: self.name,
             about: self.about,
@@ -222,11 +281,15 @@ impl ConfigHandler {
             home_feed_mode,
             media_server_url: self.media_server_url,
             default_zap_amount: self.default_zap_amount,
+            publish_quorum: self.publish_quorum,
             following: self.following,
             muted_users: self.muted_users,
             muted_words: self.muted_words,
             muted_hashtags: self.muted_hashtags,
             bookmarks: self.bookmarks,
+            auth_denied_relays: self.auth_denied_relays,
+            relay_pow_difficulty: self.relay_pow_difficulty,
+            min_feed_pow: self.min_feed_pow,
             hide_encrypted_notes: self.hide_encrypted_notes,
             dm_last_read_at: self.dm_last_read_at,
         }
@@ -236,8 +299,14 @@ impl ConfigHandler {
 impl JsonContentHandler for ConfigHandler {
     fn start_object(&mut self) {
         self.depth += 1;
+        if self.depth == 2 && self.current_field.as_deref() == Some("relay_pow_difficulty") {
+            self.in_relay_pow = true;
+        }
     }
     fn end_object(&mut self) {
+        if self.depth == 2 {
+            self.in_relay_pow = false;
+        }
         self.depth -= 1;
     }
     fn start_array(&mut self) {
@@ -251,6 +320,7 @@ impl JsonContentHandler for ConfigHandler {
                     "muted_words" => ConfigArrayField::MutedWords,
                     "muted_hashtags" => ConfigArrayField::MutedHashtags,
                     "bookmarks" => ConfigArrayField::Bookmarks,
+                    "auth_denied_relays" => ConfigArrayField::AuthDeniedRelays,
                     _ => ConfigArrayField::None,
                 };
             }
@@ -277,6 +347,7 @@ impl JsonContentHandler for ConfigHandler {
                 ConfigArrayField::MutedWords => &mut self.muted_words,
                 ConfigArrayField::MutedHashtags => &mut self.muted_hashtags,
                 ConfigArrayField::Bookmarks => &mut self.bookmarks,
+                ConfigArrayField::AuthDeniedRelays => &mut self.auth_denied_relays,
                 ConfigArrayField::None => return,
             };
             vec.push(value.to_string());
@@ -288,6 +359,9 @@ impl JsonContentHandler for ConfigHandler {
                 match f.as_str() {
                     "public_key" => self.public_key = value.to_string(),
                     "private_key" => self.private_key = Some(value.to_string()),
+                    "encrypted_private_key" => self.encrypted_private_key = Some(value.to_string()),
+                    "remote_signer" => self.remote_signer = Some(value.to_string()),
+                    "remote_signer_client_key" => self.remote_signer_client_key = Some(value.to_string()),
                     // New field names (Nostr kind 0)
                     "name" => self.name = value.to_string(),
                     "about" => self.about = Some(value.to_string()),
@@ -320,6 +394,15 @@ impl JsonContentHandler for ConfigHandler {
     }
 
     fn number_value(&mut self, number: JsonNumber) {
+        if self.depth == 2 && self.in_relay_pow {
+            if let Some(ref url) = self.current_field {
+                let difficulty = number.as_f64().clamp(0.0, pow::MAX_DIFFICULTY as f64) as u32;
+                if difficulty > 0 {
+                    self.relay_pow_difficulty.push((url.clone(), difficulty));
+                }
+            }
+            return;
+        }
         if self.depth == 1 {
             if let Some(ref f) = self.current_field {
                 if f == "default_zap_amount" {
@@ -327,8 +410,12 @@ impl JsonContentHandler for ConfigHandler {
                     if n >= 1 && n <= 1_000_000 {
                         self.default_zap_amount = n;
                     }
+                } else if f == "publish_quorum" {
+                    self.publish_quorum = number.as_f64().max(0.0) as u32;
                 } else if f == "dm_last_read_at" {
                     self.dm_last_read_at = number.as_f64().max(0.0) as u64;
+                } else if f == "min_feed_pow" {
+                    self.min_feed_pow = number.as_f64().clamp(0.0, 256.0) as u32;
                 }
             }
         }
@@ -351,6 +438,10 @@ impl JsonContentHandler for ConfigHandler {
 // ============================================================
 
 pub fn config_to_json(config: &Config) -> String {
+    config_to_json_with_key(config, true)
+}
+
+fn config_to_json_with_key(config: &Config, include_private_key: bool) -> String {
     let mut json = String::new();
     json.push_str("{\n");
     
@@ -360,14 +451,19 @@ pub fn config_to_json(config: &Config) -> String {
     
     json.push_str("  \"private_key\": ");
     match &config.private_key {
-        Some(key) => {
+        Some(key) if include_private_key => {
             json.push_str("\"");
             json.push_str(&escape_json_string(key));
             json.push_str("\"");
         }
-        None => json.push_str("null"),
+        _ => json.push_str("null"),
     }
     json.push_str(",\n");
+
+    write_optional_string(&mut json, "encrypted_private_key", &config.encrypted_private_key);
+    write_optional_string(&mut json, "remote_signer", &config.remote_signer);
+    write_optional_string(&mut json, "remote_signer_client_key", &config.remote_signer_client_key);
+    json.push_str(",\n");
     
     json.push_str("  \"relays\": [\n");
     for (index, relay) in config.relays.iter().enumerate() {
@@ -423,11 +519,33 @@ pub fn config_to_json(config: &Config) -> String {
     json.push_str(",\n");
     write_string_array(&mut json, "bookmarks", &config.bookmarks);
     json.push_str(",\n");
+    write_string_array(&mut json, "auth_denied_relays", &config.auth_denied_relays);
+    json.push_str(",\n");
+
+    json.push_str("  \"relay_pow_difficulty\": {");
+    for (i, (url, difficulty)) in config.relay_pow_difficulty.iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        json.push_str("\n    \"");
+        json.push_str(&escape_json_string(url));
+        json.push_str("\": ");
+        json.push_str(&difficulty.to_string());
+    }
+    json.push_str(if config.relay_pow_difficulty.is_empty() { "},\n" } else { "\n  },\n" });
+
+    json.push_str("  \"min_feed_pow\": ");
+    json.push_str(&config.min_feed_pow.to_string());
+    json.push_str(",\n");
 
     json.push_str("  \"default_zap_amount\": ");
     json.push_str(&config.default_zap_amount.to_string());
     json.push_str(",\n");
 
+    json.push_str("  \"publish_quorum\": ");
+    json.push_str(&config.publish_quorum.to_string());
+    json.push_str(",\n");
+
     json.push_str("  \"hide_encrypted_notes\": ");
     json.push_str(if config.hide_encrypted_notes { "true" } else { "false" });
     json.push_str(",\n");
@@ -488,6 +606,7 @@ pub fn escape_json_string(input: &str) -> String {
             '\n' => output.push_str("\\n"),
             '\r' => output.push_str("\\r"),
             '\t' => output.push_str("\\t"),
+            c if (c as u32) < 0x20 => output.push_str(&format!("\\u{:04x}", c as u32)),
             _ => output.push(character),
         }
     }
@@ -533,12 +652,17 @@ pub fn load_config(config_dir: &str) -> Result<Config, String> {
         Ok(c) => c,
         Err(e) => return Err(format!("Could not read config file: {}", e)),
     };
-    return json_to_config(&contents);
+    let mut config = json_to_config(&contents)?;
+    if config.encrypted_private_key.is_some() {
+        config.private_key = session_private_key(&config.public_key);
+    }
+    Ok(config)
 }
 
+/// Write config.json. Once the profile has an encrypted key the plaintext one is left out.
 pub fn save_config(config_dir: &str, config: &Config) -> Result<(), String> {
     let config_file = get_config_file_path(config_dir);
-    let json = config_to_json(config);
+    let json = config_to_json_with_key(config, config.encrypted_private_key.is_none());
     match fs::write(&config_file, json) {
         Ok(()) => {
             debug_log!("Saved config to: {}", config_file);
@@ -550,6 +674,70 @@ pub fn save_config(config_dir: &str, config: &Config) -> Result<(), String> {
     }
 }
 
+// ============================================================
+// Private key: NIP-49 encrypted at rest, unlocked for the session
+// ============================================================
+
+/// Secret keys (hex) unlocked this session, by public key (hex). Never written to disk.
+fn session_keys() -> &'static Mutex<HashMap<String, String>> {
+    static INSTANCE: OnceLock<Mutex<HashMap<String, String>>> = OnceLock::new();
+    INSTANCE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn session_private_key(public_key_hex: &str) -> Option<String> {
+    session_keys().lock().unwrap().get(&public_key_hex.to_lowercase()).cloned()
+}
+
+/// Forget every unlocked key (logout).
+pub fn lock_private_keys() {
+    session_keys().lock().unwrap().clear();
+}
+
+/// Check that a secret key belongs to the config's public key, then keep it for the session.
+fn unlock_with_secret(config: &mut Config, secret_hex: String) -> Result<(), String> {
+    let public_key = crypto::get_public_key_from_secret(&secret_hex)?;
+    if config.public_key.is_empty() {
+        config.public_key = public_key.clone();
+    } else if !config.public_key.eq_ignore_ascii_case(&public_key) {
+        return Err(String::from("Private key does not match the public key"));
+    }
+    session_keys().lock().unwrap().insert(public_key, secret_hex.clone());
+    config.private_key = Some(secret_hex);
+    Ok(())
+}
+
+/// Set the profile's secret key (hex), encrypted with `password`, and unlock it.
+pub fn set_private_key(config: &mut Config, secret_hex: &str, password: &str) -> Result<(), String> {
+    if password.is_empty() {
+        return Err(String::from("A password is required to store a private key"));
+    }
+    let encrypted = keys::hex_to_ncryptsec(secret_hex, password)?;
+    unlock_with_secret(config, secret_hex.to_lowercase())?;
+    config.encrypted_private_key = Some(encrypted);
+    Ok(())
+}
+
+/// Import an ncryptsec as the profile's key: decrypt it with `password` and store it as given.
+pub fn import_ncryptsec(config: &mut Config, ncryptsec: &str, password: &str) -> Result<(), String> {
+    let secret_hex = keys::ncryptsec_to_hex(ncryptsec, password)?;
+    unlock_with_secret(config, secret_hex)?;
+    config.encrypted_private_key = Some(ncryptsec.to_string());
+    Ok(())
+}
+
+/// Unlock the profile's encrypted key with `password` for this session. A plaintext key from
+/// an older config is encrypted with the password instead; the caller saves the config.
+pub fn unlock_private_key(config: &mut Config, password: &str) -> Result<(), String> {
+    match (config.encrypted_private_key.clone(), config.private_key.clone()) {
+        (Some(encrypted), _) => {
+            let secret_hex = keys::ncryptsec_to_hex(&encrypted, password)?;
+            unlock_with_secret(config, secret_hex)
+        }
+        (None, Some(plaintext)) => set_private_key(config, &plaintext, password),
+        (None, None) => Err(String::from("No private key stored for this profile")),
+    }
+}
+
 // ============================================================
 // App-level configuration (plume.json) - multi-profile support
 // ============================================================
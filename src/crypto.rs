@@ -26,7 +26,7 @@ use secp256k1::{schnorr, Keypair, Parity, PublicKey, Secp256k1, SecretKey, XOnly
 use sha2::{Digest, Sha256};
 
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
-use crate::nostr::{Event, KIND_DM, KIND_ZAP_REQUEST};
+use crate::nostr::{parse_event, parse_rumor, Event, KIND_APP_DATA, KIND_AUTH, KIND_DELETION, KIND_DM, KIND_GIFT_WRAP, KIND_SEAL, KIND_ZAP_REQUEST};
 
 use aes::cipher::block_padding::Pkcs7;
 use aes::cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit};
@@ -34,6 +34,15 @@ use cbc::{Decryptor, Encryptor};
 type Aes256CbcEnc = Encryptor<aes::Aes256>;
 type Aes256CbcDec = Decryptor<aes::Aes256>;
 
+use chacha20::cipher::StreamCipher;
+use chacha20::ChaCha20;
+use chacha20poly1305::aead::{Aead, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use unicode_normalization::UnicodeNormalization;
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+type HmacSha256 = Hmac<Sha256>;
+
 // ============================================================
 // Event ID Computation
 // ============================================================
@@ -56,7 +65,7 @@ pub fn compute_event_id(event: &Event) -> Result<String, String> {
 
 // Serialize an event for ID computation
 // Format: [0, pubkey, created_at, kind, tags, content]
-fn serialize_event_for_id(event: &Event) -> Result<String, String> {
+pub fn serialize_event_for_id(event: &Event) -> Result<String, String> {
     let mut json = String::new();
     
     // Start array
@@ -232,68 +241,6 @@ pub fn verification_result_to_json(result: &VerificationResult) -> String {
     return json;
 }
 
-// ============================================================
-// Key Generation
-// ============================================================
-
-// Generate a new random key pair
-// Returns (secret_key_hex, public_key_hex)
-pub fn generate_keypair() -> Result<(String, String), String> {
-    use std::time::{SystemTime, UNIX_EPOCH};
-    
-    // Get some entropy from system time and random-ish sources
-    let now = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default();
-    
-    // Create a seed from multiple sources
-    let mut seed = [0u8; 32];
-    
-    // Mix in nanoseconds
-    let nanos = now.as_nanos();
-    for i in 0..16 {
-        seed[i] = ((nanos >> (i * 8)) & 0xff) as u8;
-    }
-    
-    // Mix in process ID and thread ID for more entropy
-    let pid = std::process::id();
-    seed[16] = (pid & 0xff) as u8;
-    seed[17] = ((pid >> 8) & 0xff) as u8;
-    seed[18] = ((pid >> 16) & 0xff) as u8;
-    seed[19] = ((pid >> 24) & 0xff) as u8;
-    
-    // Mix in some memory address randomness
-    let stack_addr = &seed as *const _ as usize;
-    for i in 0..8 {
-        seed[20 + i] = ((stack_addr >> (i * 8)) & 0xff) as u8;
-    }
-    
-    // Hash the seed to get uniform randomness
-    let mut hasher = Sha256::new();
-    hasher.update(&seed);
-    let hash_result = hasher.finalize();
-    
-    let mut secret_bytes = [0u8; 32];
-    secret_bytes.copy_from_slice(&hash_result);
-    
-    // Create the secret key
-    let secret_key = match SecretKey::from_slice(&secret_bytes) {
-        Ok(sk) => sk,
-        Err(e) => return Err(format!("Failed to create secret key: {}", e)),
-    };
-    
-    // Derive the public key
-    let secp = Secp256k1::new();
-    let keypair = Keypair::from_secret_key(&secp, &secret_key);
-    let (xonly_pubkey, _parity) = XOnlyPublicKey::from_keypair(&keypair);
-    
-    // Convert to hex
-    let secret_hex = bytes_to_hex(&secret_bytes);
-    let pubkey_hex = bytes_to_hex(&xonly_pubkey.serialize());
-    
-    return Ok((secret_hex, pubkey_hex));
-}
-
 // ============================================================
 // Event Signing
 // ============================================================
@@ -376,111 +323,86 @@ pub fn sign_event(event: &mut Event, secret_key_hex: &str) -> Result<(), String>
     return Ok(());
 }
 
-// Create and sign a new text note (kind 1)
-pub fn create_signed_note(
-    content: &str,
-    secret_key_hex: &str,
-    tags: Vec<Vec<String>>,
-) -> Result<Event, String> {
-    // Get public key from secret
-    let pubkey = get_public_key_from_secret(secret_key_hex)?;
-    
-    // Get current timestamp
-    let created_at = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs();
-    
-    // Create the event structure
-    let mut event = Event {
-        id: String::new(),  // Will be computed
-        pubkey: pubkey,
-        created_at: created_at,
-        kind: 1,  // Text note
-        tags: tags,
+/// A new unsigned event by `pubkey_hex`, created now. Sign it with sign_event, or hand it to a
+/// signer::Signer when the key may be held by a remote signer.
+pub fn new_event(pubkey_hex: &str, kind: u32, tags: Vec<Vec<String>>, content: &str) -> Event {
+    Event {
+        id: String::new(),
+        pubkey: pubkey_hex.to_string(),
+        created_at: now_secs(),
+        kind,
+        tags,
         content: content.to_string(),
-        sig: String::new(),  // Will be computed
-    };
-    
-    // Sign the event
-    sign_event(&mut event, secret_key_hex)?;
-    
-    return Ok(event);
+        sig: String::new(),
+    }
 }
 
-/// Create and sign a kind 7 (reaction) event. NIP-25: tags ["e", event_id], ["p", author_pubkey]; content = emoji (e.g. "❤️" or "+").
-pub fn create_signed_reaction(
-    event_id: &str,
-    author_pubkey: &str,
-    content: &str,
-    secret_key_hex: &str,
-) -> Result<Event, String> {
-    let pubkey = get_public_key_from_secret(secret_key_hex)?;
-    let created_at = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs();
+/// Unsigned kind 7 (reaction) event. NIP-25: tags ["e", event_id], ["p", author_pubkey]; content = emoji (e.g. "❤️" or "+").
+pub fn create_reaction(pubkey_hex: &str, event_id: &str, author_pubkey: &str, content: &str) -> Event {
     let tags = vec![
         vec![String::from("e"), event_id.to_string()],
         vec![String::from("p"), author_pubkey.to_string()],
     ];
-    let mut event = Event {
-        id: String::new(),
-        pubkey,
-        created_at,
-        kind: 7,
-        tags,
-        content: content.to_string(),
-        sig: String::new(),
-    };
-    sign_event(&mut event, secret_key_hex)?;
-    Ok(event)
+    new_event(pubkey_hex, 7, tags, content)
 }
 
-/// Create and sign a kind 6 (repost) event. NIP-18: tags ["e", event_id], ["p", author_pubkey]; content empty or stringified original event.
-pub fn create_signed_repost(
-    event_id: &str,
-    author_pubkey: &str,
-    content: &str,
+/// Unsigned kind 6 (repost) event. NIP-18: tags ["e", event_id], ["p", author_pubkey]; content empty or stringified original event.
+pub fn create_repost(pubkey_hex: &str, event_id: &str, author_pubkey: &str, content: &str) -> Event {
+    let tags = vec![
+        vec![String::from("e"), event_id.to_string()],
+        vec![String::from("p"), author_pubkey.to_string()],
+    ];
+    new_event(pubkey_hex, 6, tags, content)
+}
+
+/// Unsigned kind 5 (deletion request) event. NIP-09: ["e", id] for each event, ["k", kind] for
+/// each distinct kind among them; content is the optional reason.
+pub fn create_deletion(pubkey_hex: &str, targets: &[(String, u32)], reason: &str) -> Event {
+    let mut tags: Vec<Vec<String>> = Vec::new();
+    let mut kinds: Vec<u32> = Vec::new();
+    for (id, kind) in targets {
+        tags.push(vec![String::from("e"), id.clone()]);
+        if !kinds.contains(kind) {
+            kinds.push(*kind);
+        }
+    }
+    for kind in kinds {
+        tags.push(vec![String::from("k"), kind.to_string()]);
+    }
+    new_event(pubkey_hex, KIND_DELETION, tags, reason)
+}
+
+/// Unsigned kind 30078 (NIP-78 application data) event with identifier `d_tag`.
+pub fn create_app_data(pubkey_hex: &str, d_tag: &str, content: &str) -> Event {
+    new_event(pubkey_hex, KIND_APP_DATA, vec![vec![String::from("d"), d_tag.to_string()]], content)
+}
+
+/// Create and sign a kind 22242 (client authentication) event. NIP-42: tags ["relay", relay_url], ["challenge", challenge]; content empty.
+pub fn create_signed_auth_event(
+    relay_url: &str,
+    challenge: &str,
     secret_key_hex: &str,
 ) -> Result<Event, String> {
     let pubkey = get_public_key_from_secret(secret_key_hex)?;
-    let created_at = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs();
     let tags = vec![
-        vec![String::from("e"), event_id.to_string()],
-        vec![String::from("p"), author_pubkey.to_string()],
+        vec![String::from("relay"), relay_url.to_string()],
+        vec![String::from("challenge"), challenge.to_string()],
     ];
-    let mut event = Event {
-        id: String::new(),
-        pubkey,
-        created_at,
-        kind: 6,
-        tags,
-        content: content.to_string(),
-        sig: String::new(),
-    };
+    let mut event = new_event(&pubkey, KIND_AUTH, tags, "");
     sign_event(&mut event, secret_key_hex)?;
     Ok(event)
 }
 
-/// Create and sign a kind 9734 (zap request) event. NIP-57.
+/// Unsigned kind 9734 (zap request) event. NIP-57.
 /// relay_urls: relays for the recipient to publish zap receipt; target_pubkey: recipient; event_id: optional note being zapped; amount_msats: millisatoshis; content: optional message.
-pub fn create_signed_zap_request(
+pub fn create_zap_request(
+    pubkey_hex: &str,
     relay_urls: &[String],
     target_pubkey: &str,
     event_id: Option<&str>,
     amount_msats: u64,
     content: &str,
-    secret_key_hex: &str,
-) -> Result<Event, String> {
-    let pubkey = get_public_key_from_secret(secret_key_hex)?;
-    let created_at = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs();
+) -> Event {
     let mut relay_tag = vec![String::from("relays")];
     relay_tag.extend(relay_urls.iter().cloned());
     let mut tags: Vec<Vec<String>> = vec![
@@ -493,64 +415,26 @@ pub fn create_signed_zap_request(
             tags.insert(1, vec![String::from("e"), eid.to_string()]);
         }
     }
-    let mut event = Event {
-        id: String::new(),
-        pubkey,
-        created_at,
-        kind: KIND_ZAP_REQUEST,
-        tags,
-        content: content.to_string(),
-        sig: String::new(),
-    };
-    sign_event(&mut event, secret_key_hex)?;
-    Ok(event)
+    new_event(pubkey_hex, KIND_ZAP_REQUEST, tags, content)
 }
 
-/// Create and sign a kind 3 (contact list) event. Tags: ["p", pubkey] for each followed user; content empty.
-pub fn create_signed_contact_list(pubkeys: &[String], secret_key_hex: &str) -> Result<Event, String> {
-    let pubkey = get_public_key_from_secret(secret_key_hex)?;
-    let created_at = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs();
+/// Unsigned kind 3 (contact list) event. Tags: ["p", pubkey] for each followed user; content empty.
+pub fn create_contact_list(pubkey_hex: &str, pubkeys: &[String]) -> Event {
     let tags: Vec<Vec<String>> = pubkeys
         .iter()
         .map(|p| vec![String::from("p"), p.clone()])
         .collect();
-    let mut event = Event {
-        id: String::new(),
-        pubkey,
-        created_at,
-        kind: 3,
-        tags,
-        content: String::new(),
-        sig: String::new(),
-    };
-    sign_event(&mut event, secret_key_hex)?;
-    Ok(event)
+    new_event(pubkey_hex, 3, tags, "")
 }
 
-/// Create and sign a kind 0 (metadata) event.
-pub fn create_signed_metadata_event(content: &str, secret_key_hex: &str) -> Result<Event, String> {
-    let pubkey = get_public_key_from_secret(secret_key_hex)?;
-    let created_at = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs();
-    let mut event = Event {
-        id: String::new(),
-        pubkey: pubkey,
-        created_at: created_at,
-        kind: 0,
-        tags: Vec::new(),
-        content: content.to_string(),
-        sig: String::new(),
-    };
-    sign_event(&mut event, secret_key_hex)?;
-    Ok(event)
+/// Unsigned kind 0 (metadata) event.
+pub fn create_metadata_event(pubkey_hex: &str, content: &str) -> Event {
+    new_event(pubkey_hex, 0, Vec::new(), content)
 }
 
 /// Create and sign a kind 4 (NIP-04) encrypted DM. Encrypts content for recipient, tags ["p", recipient_pubkey].
+/// Legacy: DMs are now sent as NIP-17 gift wraps (create_private_dm); kind 4 is still read.
+#[allow(dead_code)]
 pub fn create_signed_dm(
     recipient_pubkey_hex: &str,
     plaintext: &str,
@@ -650,6 +534,366 @@ pub fn nip04_decrypt(content: &str, our_secret_hex: &str, their_public_hex: &str
     String::from_utf8(decrypted.to_vec()).map_err(|e| format!("Invalid UTF-8: {}", e))
 }
 
+// ============================================================
+// NIP-44 Versioned Encryption (v2)
+// ============================================================
+
+/// NIP-44 payload version byte.
+const NIP44_VERSION: u8 = 2;
+
+/// Longest plaintext NIP-44 v2 can carry (bytes).
+const NIP44_MAX_PLAINTEXT: usize = 65535;
+
+/// NIP-44 conversation key: HKDF-extract(salt = "nip44-v2", IKM = ECDH shared x).
+/// The same for both parties, so it can be computed once per conversation.
+pub fn nip44_conversation_key(our_secret_hex: &str, their_public_hex: &str) -> Result<[u8; 32], String> {
+    let shared_x = nip04_shared_secret(our_secret_hex, their_public_hex)?;
+    let (prk, _) = Hkdf::<Sha256>::extract(Some(b"nip44-v2"), &shared_x);
+    let mut key = [0u8; 32];
+    key.copy_from_slice(&prk);
+    Ok(key)
+}
+
+struct Nip44MessageKeys {
+    chacha_key: [u8; 32],
+    chacha_nonce: [u8; 12],
+    hmac_key: [u8; 32],
+}
+
+/// Per-message keys: HKDF-expand(conversation key, info = nonce, 76 bytes) split into the
+/// ChaCha20 key, ChaCha20 nonce and HMAC key.
+fn nip44_message_keys(conversation_key: &[u8; 32], nonce: &[u8; 32]) -> Result<Nip44MessageKeys, String> {
+    let hkdf = Hkdf::<Sha256>::from_prk(conversation_key).map_err(|_| String::from("Invalid conversation key"))?;
+    let mut okm = [0u8; 76];
+    hkdf.expand(nonce, &mut okm).map_err(|_| String::from("HKDF expand failed"))?;
+    let mut keys = Nip44MessageKeys {
+        chacha_key: [0u8; 32],
+        chacha_nonce: [0u8; 12],
+        hmac_key: [0u8; 32],
+    };
+    keys.chacha_key.copy_from_slice(&okm[0..32]);
+    keys.chacha_nonce.copy_from_slice(&okm[32..44]);
+    keys.hmac_key.copy_from_slice(&okm[44..76]);
+    Ok(keys)
+}
+
+/// Padded length for a plaintext of `unpadded_len` bytes: powers of two up to 256, then
+/// chunks of an eighth of the next power of two; never below 32.
+fn nip44_padded_len(unpadded_len: usize) -> usize {
+    if unpadded_len <= 32 {
+        return 32;
+    }
+    let next_power = 1usize << (usize::BITS - (unpadded_len - 1).leading_zeros());
+    let chunk = if next_power <= 256 { 32 } else { next_power / 8 };
+    chunk * ((unpadded_len - 1) / chunk + 1)
+}
+
+/// Big-endian u16 length prefix, plaintext, zero padding.
+fn nip44_pad(plaintext: &str) -> Result<Vec<u8>, String> {
+    let len = plaintext.len();
+    if len == 0 || len > NIP44_MAX_PLAINTEXT {
+        return Err(format!("NIP-44 plaintext must be 1 to {} bytes", NIP44_MAX_PLAINTEXT));
+    }
+    let mut padded = Vec::with_capacity(2 + nip44_padded_len(len));
+    padded.extend_from_slice(&(len as u16).to_be_bytes());
+    padded.extend_from_slice(plaintext.as_bytes());
+    padded.resize(2 + nip44_padded_len(len), 0);
+    Ok(padded)
+}
+
+fn nip44_unpad(padded: &[u8]) -> Result<String, String> {
+    if padded.len() < 2 {
+        return Err(String::from("Invalid padding"));
+    }
+    let len = u16::from_be_bytes([padded[0], padded[1]]) as usize;
+    if len == 0 || padded.len() != 2 + nip44_padded_len(len) {
+        return Err(String::from("Invalid padding"));
+    }
+    String::from_utf8(padded[2..2 + len].to_vec()).map_err(|e| format!("Invalid UTF-8: {}", e))
+}
+
+fn nip44_hmac(hmac_key: &[u8; 32], nonce: &[u8; 32], ciphertext: &[u8]) -> Result<HmacSha256, String> {
+    let mut mac = HmacSha256::new_from_slice(hmac_key).map_err(|_| String::from("Invalid HMAC key"))?;
+    mac.update(nonce);
+    mac.update(ciphertext);
+    Ok(mac)
+}
+
+/// Encrypt with a given conversation key and nonce. Returns the base64 payload
+/// version || nonce || ciphertext || mac.
+fn nip44_encrypt_with_nonce(plaintext: &str, conversation_key: &[u8; 32], nonce: &[u8; 32]) -> Result<String, String> {
+    let keys = nip44_message_keys(conversation_key, nonce)?;
+    let mut ciphertext = nip44_pad(plaintext)?;
+    ChaCha20::new((&keys.chacha_key).into(), (&keys.chacha_nonce).into()).apply_keystream(&mut ciphertext);
+    let mac = nip44_hmac(&keys.hmac_key, nonce, &ciphertext)?.finalize().into_bytes();
+
+    let mut payload = Vec::with_capacity(1 + 32 + ciphertext.len() + 32);
+    payload.push(NIP44_VERSION);
+    payload.extend_from_slice(nonce);
+    payload.extend_from_slice(&ciphertext);
+    payload.extend_from_slice(&mac);
+    Ok(BASE64.encode(payload))
+}
+
+/// NIP-44 v2 encrypt with a conversation key and a random nonce.
+pub fn nip44_encrypt_with_key(plaintext: &str, conversation_key: &[u8; 32]) -> Result<String, String> {
+    let nonce: [u8; 32] = rand::random();
+    nip44_encrypt_with_nonce(plaintext, conversation_key, &nonce)
+}
+
+/// NIP-44 v2 decrypt a base64 payload with a conversation key.
+pub fn nip44_decrypt_with_key(payload: &str, conversation_key: &[u8; 32]) -> Result<String, String> {
+    if payload.starts_with('#') {
+        return Err(String::from("Unsupported NIP-44 encryption version"));
+    }
+    if payload.len() < 132 || payload.len() > 87472 {
+        return Err(String::from("Invalid NIP-44 payload length"));
+    }
+    let data = BASE64.decode(payload).map_err(|e| format!("Invalid base64 payload: {}", e))?;
+    if data.len() < 99 || data.len() > 65603 {
+        return Err(String::from("Invalid NIP-44 payload length"));
+    }
+    if data[0] != NIP44_VERSION {
+        return Err(format!("Unsupported NIP-44 encryption version {}", data[0]));
+    }
+    let mut nonce = [0u8; 32];
+    nonce.copy_from_slice(&data[1..33]);
+    let ciphertext = &data[33..data.len() - 32];
+    let mac = &data[data.len() - 32..];
+
+    let keys = nip44_message_keys(conversation_key, &nonce)?;
+    nip44_hmac(&keys.hmac_key, &nonce, ciphertext)?
+        .verify_slice(mac)
+        .map_err(|_| String::from("Invalid MAC (wrong key or corrupted data)"))?;
+
+    let mut padded = ciphertext.to_vec();
+    ChaCha20::new((&keys.chacha_key).into(), (&keys.chacha_nonce).into()).apply_keystream(&mut padded);
+    nip44_unpad(&padded)
+}
+
+/// NIP-44 v2 encrypt from our secret key to their public key (hex).
+pub fn nip44_encrypt(plaintext: &str, our_secret_hex: &str, their_public_hex: &str) -> Result<String, String> {
+    let key = nip44_conversation_key(our_secret_hex, their_public_hex)?;
+    nip44_encrypt_with_key(plaintext, &key)
+}
+
+/// NIP-44 v2 decrypt a payload sent between our secret key and their public key (hex).
+pub fn nip44_decrypt(payload: &str, our_secret_hex: &str, their_public_hex: &str) -> Result<String, String> {
+    let key = nip44_conversation_key(our_secret_hex, their_public_hex)?;
+    nip44_decrypt_with_key(payload, &key)
+}
+
+// ============================================================
+// NIP-59 Gift Wrap / NIP-17 Private Direct Messages
+// ============================================================
+
+/// Seals and gift wraps are backdated by up to this many seconds so their timestamps do not
+/// reveal when the message was sent.
+const GIFT_WRAP_MAX_BACKDATE: u64 = 2 * 24 * 60 * 60;
+
+pub fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// A timestamp up to two days in the past, for seals and gift wraps.
+fn random_past_timestamp() -> u64 {
+    now_secs().saturating_sub(rand::random::<u64>() % GIFT_WRAP_MAX_BACKDATE)
+}
+
+/// A fresh random secret key (hex), for one-off gift wrap and NIP-46 client keys.
+pub fn random_secret_key_hex() -> String {
+    loop {
+        let bytes: [u8; 32] = rand::random();
+        if SecretKey::from_slice(&bytes).is_ok() {
+            return bytes_to_hex(&bytes);
+        }
+    }
+}
+
+/// A rumor: an event with its id computed but never signed, so it cannot be proven to a third
+/// party if it leaks.
+pub fn create_rumor(kind: u32, content: &str, tags: Vec<Vec<String>>, pubkey_hex: &str) -> Result<Event, String> {
+    let mut rumor = new_event(pubkey_hex, kind, tags, content);
+    rumor.id = compute_event_id(&rumor)?;
+    Ok(rumor)
+}
+
+/// Unsigned seal (kind 13) around a rumor already NIP-44 encrypted by its author to the recipient.
+/// The author signs it.
+pub fn create_seal(pubkey_hex: &str, encrypted_rumor: String) -> Event {
+    let mut seal = new_event(pubkey_hex, KIND_SEAL, Vec::new(), "");
+    seal.created_at = random_past_timestamp();
+    seal.content = encrypted_rumor;
+    seal
+}
+
+/// Gift wrap a seal for a recipient: kind 1059, the seal NIP-44 encrypted to them and signed
+/// with a random one-off key, tagged with the recipient so their relays can route it.
+pub fn create_gift_wrap(seal: &Event, recipient_pubkey_hex: &str) -> Result<Event, String> {
+    let ephemeral_secret = random_secret_key_hex();
+    let content = nip44_encrypt(&crate::nostr::event_to_json(seal), &ephemeral_secret, recipient_pubkey_hex)?;
+    let mut wrap = Event {
+        id: String::new(),
+        pubkey: get_public_key_from_secret(&ephemeral_secret)?,
+        created_at: random_past_timestamp(),
+        kind: KIND_GIFT_WRAP,
+        tags: vec![vec![String::from("p"), recipient_pubkey_hex.to_string()]],
+        content,
+        sig: String::new(),
+    };
+    sign_event(&mut wrap, &ephemeral_secret)?;
+    Ok(wrap)
+}
+
+/// Parse a seal decrypted from a gift wrap, checking its kind, id and signature.
+pub fn open_seal(seal_json: &str) -> Result<Event, String> {
+    let seal = parse_event(seal_json)?;
+    if seal.kind != KIND_SEAL {
+        return Err(format!("Gift wrap does not contain a seal (kind {})", seal.kind));
+    }
+    if !verify_event_id(&seal)? || !verify_event_signature(&seal)? {
+        return Err(String::from("Seal signature is invalid"));
+    }
+    Ok(seal)
+}
+
+/// Parse a rumor decrypted from a seal. The seal must be signed by the rumor's author, otherwise
+/// anyone could wrap a message claiming to be someone else.
+pub fn open_rumor(seal: &Event, rumor_json: &str) -> Result<Event, String> {
+    let rumor = parse_rumor(rumor_json)?;
+    if rumor.pubkey.to_lowercase() != seal.pubkey.to_lowercase() {
+        return Err(String::from("Rumor author does not match seal signer"));
+    }
+    if !verify_event_id(&rumor)? {
+        return Err(String::from("Rumor id is invalid"));
+    }
+    Ok(rumor)
+}
+
+// ============================================================
+// NIP-49 Private Key Encryption
+// ============================================================
+
+/// NIP-49 payload version byte.
+const NIP49_VERSION: u8 = 0x02;
+
+/// scrypt cost (log2 of N) for keys we encrypt: 64 MiB of memory, around a second to unlock.
+pub const NIP49_DEFAULT_LOG_N: u8 = 16;
+
+/// Highest scrypt cost accepted (1 GiB of memory, a few seconds); anything above is refused
+/// before deriving the key rather than letting an imported string exhaust memory.
+const NIP49_MAX_LOG_N: u8 = 20;
+
+/// Key security byte: we cannot tell how the key was handled before it was encrypted.
+const NIP49_KEY_SECURITY_UNKNOWN: u8 = 0x02;
+
+/// Length of an encrypted key: version, log_n, salt, nonce, key security, ciphertext and tag.
+const NIP49_PAYLOAD_LEN: usize = 1 + 1 + 16 + 24 + 1 + 32 + 16;
+
+/// scrypt(NFKC(password), salt, N = 2^log_n, r = 8, p = 1) -> 32-byte symmetric key.
+fn nip49_symmetric_key(password: &str, salt: &[u8], log_n: u8) -> Result<[u8; 32], String> {
+    let password: String = password.nfkc().collect();
+    let params = scrypt::Params::new(log_n, 8, 1, 32).map_err(|e| format!("scrypt parameters: {}", e))?;
+    let mut key = [0u8; 32];
+    scrypt::scrypt(password.as_bytes(), salt, &params, &mut key).map_err(|e| format!("scrypt: {}", e))?;
+    Ok(key)
+}
+
+// KeyInit is not imported: HmacSha256 implements it too, which would make new_from_slice ambiguous
+fn nip49_cipher(key: &[u8; 32]) -> XChaCha20Poly1305 {
+    <XChaCha20Poly1305 as chacha20poly1305::KeyInit>::new(key.into())
+}
+
+/// Encrypt a secret key (hex) with a password. Returns the raw NIP-49 payload, to be
+/// bech32-encoded as ncryptsec.
+pub fn nip49_encrypt(secret_key_hex: &str, password: &str, log_n: u8) -> Result<Vec<u8>, String> {
+    if log_n > NIP49_MAX_LOG_N {
+        return Err(format!("Encrypted key cost too high (log_n {})", log_n));
+    }
+    let secret = hex_to_bytes(secret_key_hex)?;
+    if secret.len() != 32 {
+        return Err(String::from("Invalid secret key length"));
+    }
+    let salt: [u8; 16] = rand::random();
+    let nonce: [u8; 24] = rand::random();
+    let key = nip49_symmetric_key(password, &salt, log_n)?;
+    let key_security = [NIP49_KEY_SECURITY_UNKNOWN];
+    let ciphertext = nip49_cipher(&key)
+        .encrypt(XNonce::from_slice(&nonce), Payload { msg: &secret, aad: &key_security })
+        .map_err(|_| String::from("Encryption failed"))?;
+
+    let mut out = Vec::with_capacity(NIP49_PAYLOAD_LEN);
+    out.push(NIP49_VERSION);
+    out.push(log_n);
+    out.extend_from_slice(&salt);
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&key_security);
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+/// Decrypt a raw NIP-49 payload with a password. Returns the secret key (hex).
+pub fn nip49_decrypt(payload: &[u8], password: &str) -> Result<String, String> {
+    if payload.len() != NIP49_PAYLOAD_LEN {
+        return Err(format!("Invalid encrypted key length: {}", payload.len()));
+    }
+    if payload[0] != NIP49_VERSION {
+        return Err(format!("Unsupported encrypted key version: {}", payload[0]));
+    }
+    let log_n = payload[1];
+    if log_n > NIP49_MAX_LOG_N {
+        return Err(format!("Encrypted key cost too high (log_n {})", log_n));
+    }
+    let key = nip49_symmetric_key(password, &payload[2..18], log_n)?;
+    let secret = nip49_cipher(&key)
+        .decrypt(XNonce::from_slice(&payload[18..42]), Payload { msg: &payload[43..], aad: &payload[42..43] })
+        .map_err(|_| String::from("Wrong password or corrupted key"))?;
+    Ok(bytes_to_hex(&secret))
+}
+
+// ============================================================
+// Local data at rest
+// ============================================================
+
+/// Key for encrypting local data at rest, derived from the account's secret key:
+/// HKDF-SHA256(salt = "plume-storage", IKM = secret key, info = `purpose`). Nothing to store,
+/// and each purpose gets a different key.
+pub fn derive_storage_key(secret_key_hex: &str, purpose: &str) -> Result<[u8; 32], String> {
+    let secret = hex_to_bytes(secret_key_hex)?;
+    if secret.len() != 32 {
+        return Err(String::from("Invalid secret key length"));
+    }
+    let hkdf = Hkdf::<Sha256>::new(Some(b"plume-storage"), &secret);
+    let mut key = [0u8; 32];
+    hkdf.expand(purpose.as_bytes(), &mut key).map_err(|_| String::from("HKDF expand failed"))?;
+    Ok(key)
+}
+
+/// Encrypt local data with XChaCha20-Poly1305 under a random nonce. Returns nonce || ciphertext.
+pub fn encrypt_at_rest(key: &[u8; 32], plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    let nonce: [u8; 24] = rand::random();
+    let ciphertext = nip49_cipher(key)
+        .encrypt(XNonce::from_slice(&nonce), plaintext)
+        .map_err(|_| String::from("Encryption failed"))?;
+    let mut out = Vec::with_capacity(nonce.len() + ciphertext.len());
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+/// Decrypt data from encrypt_at_rest.
+pub fn decrypt_at_rest(key: &[u8; 32], data: &[u8]) -> Result<Vec<u8>, String> {
+    if data.len() < 24 + 16 {
+        return Err(String::from("Encrypted data too short"));
+    }
+    nip49_cipher(key)
+        .decrypt(XNonce::from_slice(&data[..24]), &data[24..])
+        .map_err(|_| String::from("Wrong key or corrupted data"))
+}
+
 // ============================================================
 // Helper Functions
 // ============================================================
@@ -773,6 +1017,198 @@ mod tests {
         assert_eq!(hex, "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824");
     }
     
+    // get_conversation_key vectors from the NIP-44 v2 test vectors
+    #[test]
+    fn test_nip44_conversation_key_vectors() {
+        let valid = [
+            (
+                "315e59ff51cb9209768cf7da80791ddcaae56ac9775eb25b6dee1234bc5d2268",
+                "c2f9d9948dc8c7c38321e4b85c8558872eafa0641cd269db76848a6073e69133",
+                "3dfef0ce2a4d80a25e7a328accf73448ef67096f65f79588e358d9a0eb9013f1",
+            ),
+            (
+                "a1e37752c9fdc1273be53f68c5f74be7c8905728e8de75800b94262f9497c86e",
+                "03bb7947065dde12ba991ea045132581d0954f042c84e06d8c00066e23c1a800",
+                "4d14f36e81b8452128da64fe6f1eae873baae2f444b02c950b90e43553f2178b",
+            ),
+            (
+                "98a5902fd67518a0c900f0fb62158f278f94a21d6f9d33d30cd3091195500311",
+                "aae65c15f98e5e677b5050de82e3aba47a6fe49b3dab7863cf35d9478ba9f7d1",
+                "9c00b769d5f54d02bf175b7284a1cbd28b6911b06cda6666b2243561ac96bad7",
+            ),
+        ];
+        for (sec1, pub2, conversation_key) in valid {
+            assert_eq!(bytes_to_hex(&nip44_conversation_key(sec1, pub2).unwrap()), conversation_key);
+        }
+
+        let pub1 = get_public_key_from_secret("0000000000000000000000000000000000000000000000000000000000000001").unwrap();
+        let invalid = [
+            // Secret key above the curve order, equal to it, or zero
+            ("ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff", pub1.as_str()),
+            ("fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141", pub1.as_str()),
+            ("0000000000000000000000000000000000000000000000000000000000000000", pub1.as_str()),
+            // Public key not on the curve
+            ("0000000000000000000000000000000000000000000000000000000000000002", "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff"),
+            ("0000000000000000000000000000000000000000000000000000000000000002", "0000000000000000000000000000000000000000000000000000000000000000"),
+        ];
+        for (sec1, pub2) in invalid {
+            assert!(nip44_conversation_key(sec1, pub2).is_err(), "sec1 {} pub2 {}", sec1, pub2);
+        }
+    }
+
+    // encrypt_decrypt and encrypt_decrypt_long_msg vectors from the NIP-44 v2 test vectors
+    #[test]
+    fn test_nip44_encrypt_decrypt_vectors() {
+        let vectors = [
+            (
+                "0000000000000000000000000000000000000000000000000000000000000001",
+                "0000000000000000000000000000000000000000000000000000000000000002",
+                "c41c775356fd92eadc63ff5a0dc1da211b268cbea22316767095b2871ea1412d",
+                "0000000000000000000000000000000000000000000000000000000000000001",
+                "a",
+                "AgAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAABee0G5VSK0/9YypIObAtDKfYEAjD35uVkHyB0F4DwrcNaCXlCWZKaArsGrY6M9wnuTMxWfp1RTN9Xga8no+kF5Vsb",
+            ),
+            (
+                "0000000000000000000000000000000000000000000000000000000000000002",
+                "0000000000000000000000000000000000000000000000000000000000000001",
+                "c41c775356fd92eadc63ff5a0dc1da211b268cbea22316767095b2871ea1412d",
+                "f00000000000000000000000000000f00000000000000000000000000000000f",
+                "🍕🫃",
+                "AvAAAAAAAAAAAAAAAAAAAPAAAAAAAAAAAAAAAAAAAAAPSKSK6is9ngkX2+cSq85Th16oRTISAOfhStnixqZziKMDvB0QQzgFZdjLTPicCJaV8nDITO+QfaQ61+KbWQIOO2Yj",
+            ),
+            (
+                "5c0c523f52a5b6fad39ed2403092df8cebc36318b39383bca6c00808626fab3a",
+                "4b22aa260e4acb7021e32f38a6cdf4b673c6a277755bfce287e370c924dc936d",
+                "3e2b52a63be47d34fe0a80e34e73d436d6963bc8f39827f327057a9986c20a45",
+                "b635236c42db20f021bb8d1cdff5ca75dd1a0cc72ea742ad750f33010b24f73b",
+                "表ポあA鷗ŒéＢ逍Üßªąñ丂㐀𠀀",
+                "ArY1I2xC2yDwIbuNHN/1ynXdGgzHLqdCrXUPMwELJPc7s7JqlCMJBAIIjfkpHReBPXeoMCyuClwgbT419jUWU1PwaNl4FEQYKCDKVJz+97Mp3K+Q2YGa77B6gpxB/lr1QgoqpDf7wDVrDmOqGoiPjWDqy8KzLueKDcm9BVP8xeTJIxs=",
+            ),
+        ];
+        for (sec1, sec2, conversation_key, nonce, plaintext, payload) in vectors {
+            let pub2 = get_public_key_from_secret(sec2).unwrap();
+            let key = nip44_conversation_key(sec1, &pub2).unwrap();
+            assert_eq!(bytes_to_hex(&key), conversation_key);
+            let nonce: [u8; 32] = hex_to_bytes(nonce).unwrap().try_into().unwrap();
+            assert_eq!(nip44_encrypt_with_nonce(plaintext, &key, &nonce).unwrap(), payload);
+            // Symmetric: the other side derives the same key and decrypts
+            let pub1 = get_public_key_from_secret(sec1).unwrap();
+            assert_eq!(nip44_decrypt(payload, sec2, &pub1).unwrap(), plaintext);
+        }
+
+        let long_vectors = [
+            (
+                "8fc262099ce0d0bb9b89bac05bb9e04f9bc0090acc181fef6840ccee470371ed",
+                "326bcb2c943cd6bb717588c9e5a7e738edf6ed14ec5f5344caa6ef56f0b9cff7",
+                "x",
+                65535,
+                "09ab7495d3e61a76f0deb12cb0306f0696cbb17ffc12131368c7a939f12f56d3",
+                "90714492225faba06310bff2f249ebdc2a5e609d65a629f1c87f2d4ffc55330a",
+            ),
+            (
+                "56adbe3720339363ab9c3b8526ffce9fd77600927488bfc4b59f7a68ffe5eae0",
+                "ad68da81833c2a8ff609c3d2c0335fd44fe5954f85bb580c6a8d467aa9fc5dd0",
+                "!",
+                65535,
+                "6af297793b72ae092c422e552c3bb3cbc310da274bd1cf9e31023a7fe4a2d75e",
+                "8013e45a109fad3362133132b460a2d5bce235fe71c8b8f4014793fb52a49844",
+            ),
+        ];
+        for (conversation_key, nonce, pattern, repeat, plaintext_sha256, payload_sha256) in long_vectors {
+            let key: [u8; 32] = hex_to_bytes(conversation_key).unwrap().try_into().unwrap();
+            let nonce: [u8; 32] = hex_to_bytes(nonce).unwrap().try_into().unwrap();
+            let plaintext = pattern.repeat(repeat);
+            assert_eq!(bytes_to_hex(&sha256_hash(plaintext.as_bytes())), plaintext_sha256);
+            let payload = nip44_encrypt_with_nonce(&plaintext, &key, &nonce).unwrap();
+            assert_eq!(bytes_to_hex(&sha256_hash(payload.as_bytes())), payload_sha256);
+            assert_eq!(nip44_decrypt_with_key(&payload, &key).unwrap(), plaintext);
+        }
+        // One byte over the limit cannot be encrypted
+        let key = [1u8; 32];
+        assert!(nip44_encrypt_with_nonce(&"x".repeat(65536), &key, &[2u8; 32]).is_err());
+    }
+
+    // The kinds of payload in the NIP-44 v2 invalid decrypt vectors. Each is sealed with a
+    // valid MAC where the failure under test comes after the MAC check.
+    #[test]
+    fn test_nip44_invalid_payloads() {
+        let key = [7u8; 32];
+        let nonce = [9u8; 32];
+        let seal = |version: u8, padded: &[u8]| {
+            let keys = nip44_message_keys(&key, &nonce).unwrap();
+            let mut ciphertext = padded.to_vec();
+            ChaCha20::new((&keys.chacha_key).into(), (&keys.chacha_nonce).into()).apply_keystream(&mut ciphertext);
+            let mac = nip44_hmac(&keys.hmac_key, &nonce, &ciphertext).unwrap().finalize().into_bytes();
+            let mut data = vec![version];
+            data.extend_from_slice(&nonce);
+            data.extend_from_slice(&ciphertext);
+            data.extend_from_slice(&mac);
+            BASE64.encode(data)
+        };
+        let mut padded = vec![0u8, 5];
+        padded.extend_from_slice(b"hello");
+        padded.resize(34, 0);
+        assert_eq!(nip44_decrypt_with_key(&seal(2, &padded), &key).unwrap(), "hello");
+
+        // Unknown version: '#' marker, or a version byte other than 2
+        let valid = seal(2, &padded);
+        assert!(nip44_decrypt_with_key(&format!("#{}", &valid[1..]), &key).is_err());
+        assert!(nip44_decrypt_with_key(&seal(1, &padded), &key).is_err());
+        assert!(nip44_decrypt_with_key(&seal(3, &padded), &key).is_err());
+
+        // Bad MAC
+        let mut data = BASE64.decode(&valid).unwrap();
+        let last = data.len() - 1;
+        data[last] ^= 1;
+        assert!(nip44_decrypt_with_key(&BASE64.encode(data), &key).is_err());
+        assert!(nip44_decrypt_with_key(&valid, &[8u8; 32]).is_err());
+
+        // Bad padding: more padding than the length calls for
+        let mut long = padded.clone();
+        long.resize(66, 0);
+        assert!(nip44_decrypt_with_key(&seal(2, &long), &key).is_err());
+
+        // Zero length
+        let mut zero = padded.clone();
+        zero[1] = 0;
+        assert!(nip44_decrypt_with_key(&seal(2, &zero), &key).is_err());
+
+        // Length longer than the padded plaintext
+        let mut oversized = padded.clone();
+        oversized[1] = 40;
+        assert!(nip44_decrypt_with_key(&seal(2, &oversized), &key).is_err());
+
+        // Empty, too short, and not base64
+        assert!(nip44_decrypt_with_key("", &key).is_err());
+        assert!(nip44_decrypt_with_key(&valid[..100], &key).is_err());
+        assert!(nip44_decrypt_with_key(&valid.replace('A', "%"), &key).is_err());
+    }
+
+    #[test]
+    fn test_nip44_padded_len() {
+        let vectors = [
+            (16, 32), (32, 32), (33, 64), (37, 64), (45, 64), (49, 64), (64, 64), (65, 96),
+            (100, 128), (111, 128), (200, 224), (250, 256), (320, 320), (383, 384), (384, 384),
+            (400, 448), (500, 512), (512, 512), (515, 640), (700, 768), (800, 896), (900, 1024),
+            (1020, 1024), (65536, 65536),
+        ];
+        for (unpadded, padded) in vectors {
+            assert_eq!(nip44_padded_len(unpadded), padded, "unpadded length {}", unpadded);
+        }
+    }
+
+    #[test]
+    fn test_nip49_cost_cap() {
+        let secret = "3501454135014541350145413501453fefb02227e449e57cf4d3a3ce05378683";
+        assert!(nip49_encrypt(secret, "nostr", NIP49_MAX_LOG_N + 1).is_err());
+        // A payload asking for more is refused without running scrypt
+        let mut payload = nip49_encrypt(secret, "nostr", 1).unwrap();
+        payload[1] = NIP49_MAX_LOG_N + 1;
+        assert!(nip49_decrypt(&payload, "nostr").unwrap_err().contains("cost too high"));
+        payload[1] = 1;
+        assert_eq!(nip49_decrypt(&payload, "nostr").unwrap(), secret);
+    }
+
     #[test]
     fn test_hex_conversion() {
         let original = "deadbeef";
@@ -0,0 +1,638 @@
+/*
+ * dm_search.rs
+ * Copyright (C) 2026 Chris Burdess
+ *
+ * This file is part of Plume, a Nostr desktop client.
+ *
+ * Plume is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Plume is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Plume.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Full-text search over decrypted direct messages.
+//!
+//! An inverted index maps each word (lowercased, split on anything that is not a letter or
+//! digit) to the messages and word positions it occurs at, so queries can match phrases
+//! ("quoted words") and prefixes (word*). Messages are added when a conversation is decrypted;
+//! before a search, conversations with messages the index hasn't seen are decrypted too.
+//!
+//! The index holds plaintext, so it is kept in {profile_dir}/dm_search.idx encrypted with
+//! XChaCha20-Poly1305. The key is derived from the account's secret key; with a remote signer
+//! we never see that, so it is derived from a random key kept in dm_search.key, NIP-44
+//! encrypted to ourselves.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use crate::config::escape_json_string;
+use crate::crypto;
+use crate::debug_log;
+use crate::messages_store::{self, DecryptedMessage};
+use crate::signer::Signer;
+use crate::warn_log;
+
+const INDEX_FILE: &str = "dm_search.idx";
+const KEY_FILE: &str = "dm_search.key";
+
+/// HKDF purpose for the index key; see crypto::derive_storage_key.
+const KEY_PURPOSE: &str = "dm-search-index";
+
+/// First line of the (decrypted) index file.
+const INDEX_HEADER: &str = "plume-dm-search 1";
+
+/// Most results returned by a search, newest first.
+const MAX_RESULTS: usize = 100;
+
+/// Characters of context either side of the match in a snippet.
+const SNIPPET_CONTEXT_CHARS: usize = 40;
+
+/// How long after a change the index is saved, so a run of decrypted conversations is
+/// written once rather than once each.
+const SAVE_DELAY: Duration = Duration::from_secs(5);
+
+/// A message found by a search.
+#[derive(Debug, Clone)]
+pub struct SearchHit {
+    /// Conversation partner (hex pubkey).
+    pub conversation: String,
+    pub id: String,
+    pub created_at: u64,
+    /// The matching text with some context either side.
+    pub snippet: String,
+}
+
+struct Doc {
+    id: String,
+    conversation: String,
+    created_at: u64,
+    text: String,
+}
+
+/// A word of text and where it is (byte offsets).
+struct Token {
+    start: usize,
+    end: usize,
+    term: String,
+}
+
+fn tokenize(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut start: Option<usize> = None;
+    for (i, c) in text.char_indices() {
+        match (c.is_alphanumeric(), start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                tokens.push(Token { start: s, end: i, term: text[s..i].to_lowercase() });
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        tokens.push(Token { start: s, end: text.len(), term: text[s..].to_lowercase() });
+    }
+    tokens
+}
+
+struct QueryTerm {
+    text: String,
+    prefix: bool,
+}
+
+/// Split a query into phrases: words in double quotes make one phrase, every other word is a
+/// phrase of its own. A word followed by `*` matches as a prefix. All phrases must match.
+fn parse_query(query: &str) -> Vec<Vec<QueryTerm>> {
+    let mut phrases = Vec::new();
+    for (i, segment) in query.split('"').enumerate() {
+        let terms: Vec<QueryTerm> = tokenize(segment)
+            .into_iter()
+            .map(|t| QueryTerm { prefix: segment[t.end..].starts_with('*'), text: t.term })
+            .collect();
+        if i % 2 == 1 {
+            if !terms.is_empty() {
+                phrases.push(terms);
+            }
+        } else {
+            phrases.extend(terms.into_iter().map(|t| vec![t]));
+        }
+    }
+    phrases
+}
+
+#[derive(Default)]
+struct SearchIndex {
+    docs: Vec<Doc>,
+    /// Message id to position in docs.
+    ids: HashMap<String, u32>,
+    /// Word to (doc, word position) of every occurrence, in the order they were added.
+    postings: BTreeMap<String, Vec<(u32, u32)>>,
+    /// Messages of each conversation the index has been given, to tell when it is behind.
+    covered: HashMap<String, u64>,
+}
+
+impl SearchIndex {
+    fn add(&mut self, doc: Doc) -> bool {
+        if self.ids.contains_key(&doc.id) {
+            return false;
+        }
+        let n = self.docs.len() as u32;
+        for (pos, token) in tokenize(&doc.text).into_iter().enumerate() {
+            self.postings.entry(token.term).or_default().push((n, pos as u32));
+        }
+        self.ids.insert(doc.id.clone(), n);
+        self.docs.push(doc);
+        true
+    }
+
+    /// Add a decrypted conversation. Returns whether the index changed.
+    fn add_conversation(&mut self, conversation: &str, messages: &[DecryptedMessage]) -> bool {
+        let mut changed = false;
+        for m in messages {
+            if m.content == messages_store::UNDECRYPTABLE {
+                continue;
+            }
+            changed |= self.add(Doc {
+                id: m.id.to_lowercase(),
+                conversation: conversation.to_string(),
+                created_at: m.created_at,
+                text: m.content.clone(),
+            });
+        }
+        let count = messages.len() as u64;
+        if self.covered.get(conversation) != Some(&count) {
+            self.covered.insert(conversation.to_string(), count);
+            changed = true;
+        }
+        changed
+    }
+
+    /// Every (doc, position) a query word occurs at.
+    fn positions(&self, term: &QueryTerm) -> HashSet<(u32, u32)> {
+        if term.prefix {
+            self.postings
+                .range(term.text.clone()..)
+                .take_while(|(word, _)| word.starts_with(&term.text))
+                .flat_map(|(_, p)| p.iter().copied())
+                .collect()
+        } else {
+            self.postings.get(&term.text).map(|p| p.iter().copied().collect()).unwrap_or_default()
+        }
+    }
+
+    /// Docs containing a phrase, with the position of its first occurrence in each.
+    fn match_phrase(&self, phrase: &[QueryTerm]) -> HashMap<u32, u32> {
+        let mut found = self.positions(&phrase[0]);
+        for (i, term) in phrase.iter().enumerate().skip(1) {
+            let next = self.positions(term);
+            found.retain(|&(doc, pos)| next.contains(&(doc, pos + i as u32)));
+        }
+        let mut first: HashMap<u32, u32> = HashMap::new();
+        for (doc, pos) in found {
+            let p = first.entry(doc).or_insert(pos);
+            *p = (*p).min(pos);
+        }
+        first
+    }
+
+    fn search(&self, query: &str, max_results: usize) -> Vec<SearchHit> {
+        let phrases = parse_query(query);
+        let Some((first, rest)) = phrases.split_first() else { return Vec::new() };
+        let mut found = self.match_phrase(first);
+        for phrase in rest {
+            let other = self.match_phrase(phrase);
+            found.retain(|doc, _| other.contains_key(doc));
+        }
+        let mut found: Vec<(u32, u32)> = found.into_iter().collect();
+        found.sort_by_key(|&(doc, _)| std::cmp::Reverse(self.docs[doc as usize].created_at));
+        found.truncate(max_results);
+        found
+            .into_iter()
+            .map(|(doc, pos)| {
+                let d = &self.docs[doc as usize];
+                SearchHit {
+                    conversation: d.conversation.clone(),
+                    id: d.id.clone(),
+                    created_at: d.created_at,
+                    snippet: snippet(&d.text, pos as usize, first.len()),
+                }
+            })
+            .collect()
+    }
+
+    fn to_text(&self) -> String {
+        let mut out = String::from(INDEX_HEADER);
+        out.push('\n');
+        let mut conversations: Vec<(&String, &u64)> = self.covered.iter().collect();
+        conversations.sort();
+        for (conversation, count) in conversations {
+            out.push_str(&format!("c {} {}\n", conversation, count));
+        }
+        for d in &self.docs {
+            out.push_str(&format!("d {} {} {} {}\n", d.id, d.conversation, d.created_at, escape_line(&d.text)));
+        }
+        for (term, postings) in &self.postings {
+            out.push_str("t ");
+            out.push_str(term);
+            for (doc, pos) in postings {
+                out.push_str(&format!(" {}:{}", doc, pos));
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    fn from_text(text: &str) -> Result<SearchIndex, String> {
+        let mut lines = text.lines();
+        if lines.next() != Some(INDEX_HEADER) {
+            return Err(String::from("Unknown search index format"));
+        }
+        let bad = |line: &str| format!("Bad search index line: {}", line.chars().take(40).collect::<String>());
+        let mut index = SearchIndex::default();
+        for line in lines {
+            match line.split_once(' ') {
+                Some(("c", rest)) => {
+                    let (conversation, count) = rest.split_once(' ').ok_or_else(|| bad(line))?;
+                    let count = count.parse().map_err(|_| bad(line))?;
+                    index.covered.insert(conversation.to_string(), count);
+                }
+                Some(("d", rest)) => {
+                    let mut fields = rest.splitn(4, ' ');
+                    let (Some(id), Some(conversation), Some(created_at), Some(text)) =
+                        (fields.next(), fields.next(), fields.next(), fields.next())
+                    else {
+                        return Err(bad(line));
+                    };
+                    index.ids.insert(id.to_string(), index.docs.len() as u32);
+                    index.docs.push(Doc {
+                        id: id.to_string(),
+                        conversation: conversation.to_string(),
+                        created_at: created_at.parse().map_err(|_| bad(line))?,
+                        text: unescape_line(text),
+                    });
+                }
+                Some(("t", rest)) => {
+                    let mut fields = rest.split(' ');
+                    let term = fields.next().ok_or_else(|| bad(line))?;
+                    let mut postings = Vec::new();
+                    for field in fields {
+                        let (doc, pos) = field.split_once(':').ok_or_else(|| bad(line))?;
+                        let doc: u32 = doc.parse().map_err(|_| bad(line))?;
+                        if doc as usize >= index.docs.len() {
+                            return Err(bad(line));
+                        }
+                        postings.push((doc, pos.parse().map_err(|_| bad(line))?));
+                    }
+                    index.postings.insert(term.to_string(), postings);
+                }
+                _ => return Err(bad(line)),
+            }
+        }
+        Ok(index)
+    }
+}
+
+/// The text around word `pos` (and the `len` words of the phrase starting there), on one line.
+fn snippet(text: &str, pos: usize, len: usize) -> String {
+    let tokens = tokenize(text);
+    let (start, end) = match (tokens.get(pos), tokens.get(pos + len.max(1) - 1)) {
+        (Some(first), Some(last)) => (first.start, last.end),
+        _ => (0, 0),
+    };
+    let from = text[..start].char_indices().rev().nth(SNIPPET_CONTEXT_CHARS - 1).map_or(0, |(i, _)| i);
+    let to = text[end..].char_indices().nth(SNIPPET_CONTEXT_CHARS).map_or(text.len(), |(i, _)| end + i);
+    let mut out = String::new();
+    if from > 0 {
+        out.push('…');
+    }
+    out.push_str(&text[from..to].split_whitespace().collect::<Vec<_>>().join(" "));
+    if to < text.len() {
+        out.push('…');
+    }
+    out
+}
+
+/// Message text on one line of the index file.
+fn escape_line(s: &str) -> String {
+    let mut o = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => o.push_str("\\\\"),
+            '\n' => o.push_str("\\n"),
+            '\r' => o.push_str("\\r"),
+            _ => o.push(c),
+        }
+    }
+    o
+}
+
+fn unescape_line(s: &str) -> String {
+    let mut o = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            o.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => o.push('\n'),
+            Some('r') => o.push('\r'),
+            Some(other) => o.push(other),
+            None => o.push('\\'),
+        }
+    }
+    o
+}
+
+// ============================================================
+// Index on disk
+// ============================================================
+
+struct LoadedIndex {
+    config_dir: String,
+    key: [u8; 32],
+    index: SearchIndex,
+    /// Changed since it was last saved.
+    dirty: bool,
+    /// A save_later task is running for it.
+    save_pending: bool,
+}
+
+fn loaded_index() -> &'static Mutex<Option<LoadedIndex>> {
+    static INSTANCE: OnceLock<Mutex<Option<LoadedIndex>>> = OnceLock::new();
+    INSTANCE.get_or_init(|| Mutex::new(None))
+}
+
+/// Held while an index file is written, so two saves never share the temporary file.
+fn write_lock() -> &'static Mutex<()> {
+    static INSTANCE: OnceLock<Mutex<()>> = OnceLock::new();
+    INSTANCE.get_or_init(|| Mutex::new(()))
+}
+
+fn index_path(config_dir: &str, name: &str) -> PathBuf {
+    Path::new(config_dir).join(name)
+}
+
+/// Key the index is encrypted with.
+async fn index_key(config_dir: &str, signer: &Signer) -> Result<[u8; 32], String> {
+    if let Signer::Local { secret_key, .. } = signer {
+        return crypto::derive_storage_key(secret_key, KEY_PURPOSE);
+    }
+    let our_pubkey = signer.public_key().to_string();
+    let key_path = index_path(config_dir, KEY_FILE);
+    let root = match fs::read_to_string(&key_path) {
+        Ok(encrypted) => signer.nip44_decrypt(encrypted.trim(), &our_pubkey).await?,
+        Err(_) => {
+            let root = crypto::random_secret_key_hex();
+            let encrypted = signer.nip44_encrypt(&root, &our_pubkey).await?;
+            fs::write(&key_path, encrypted).map_err(|e| format!("Save search index key: {}", e))?;
+            root
+        }
+    };
+    crypto::derive_storage_key(&root, KEY_PURPOSE)
+}
+
+fn read_index(config_dir: &str, key: &[u8; 32]) -> Result<SearchIndex, String> {
+    let data = fs::read(index_path(config_dir, INDEX_FILE)).map_err(|e| e.to_string())?;
+    let text = crypto::decrypt_at_rest(key, &data)?;
+    SearchIndex::from_text(&String::from_utf8(text).map_err(|_| String::from("Search index is not UTF-8"))?)
+}
+
+/// Replace the index file: write a temporary file, fsync it, rename it over the old one.
+/// Blocking; call it from spawn_blocking.
+fn write_index(config_dir: &str, key: &[u8; 32], text: &str) -> Result<(), String> {
+    let data = crypto::encrypt_at_rest(key, text.as_bytes())?;
+    let _guard = write_lock().lock().unwrap();
+    let path = index_path(config_dir, INDEX_FILE);
+    let tmp_path = path.with_extension("idx.tmp");
+    let write = || -> std::io::Result<()> {
+        let mut out = File::create(&tmp_path)?;
+        out.write_all(&data)?;
+        out.sync_all()?;
+        drop(out);
+        fs::rename(&tmp_path, &path)
+    };
+    write().map_err(|e| format!("Save search index: {}", e))
+}
+
+/// Encrypt and write an index snapshot off the async runtime.
+async fn save(config_dir: String, key: [u8; 32], text: String) {
+    match tokio::task::spawn_blocking(move || write_index(&config_dir, &key, &text)).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => {
+            warn_log!("[dm-search] {}", e);
+        }
+        Err(e) => {
+            warn_log!("[dm-search] Save search index: {}", e);
+        }
+    }
+}
+
+/// Note that the index changed and make sure it gets saved. Only the text is serialized
+/// under the lock; encryption and the write happen in save. An index that isn't saved before
+/// the app quits is only behind, and catches up on the next search.
+fn mark_dirty(loaded: &mut LoadedIndex) {
+    loaded.dirty = true;
+    if !loaded.save_pending {
+        loaded.save_pending = true;
+        tokio::spawn(save_later(loaded.config_dir.clone()));
+    }
+}
+
+/// Save the profile's index once changes stop arriving for SAVE_DELAY.
+async fn save_later(config_dir: String) {
+    loop {
+        tokio::time::sleep(SAVE_DELAY).await;
+        let snapshot = {
+            let mut slot = loaded_index().lock().unwrap();
+            let Some(loaded) = slot.as_mut().filter(|l| l.config_dir == config_dir) else { return };
+            if !loaded.dirty {
+                loaded.save_pending = false;
+                return;
+            }
+            loaded.dirty = false;
+            (loaded.key, loaded.index.to_text())
+        };
+        let (key, text) = snapshot;
+        save(config_dir.clone(), key, text).await;
+    }
+}
+
+/// Load the profile's index if it isn't already. One that can't be read is started again;
+/// every conversation then gets indexed on the next search.
+async fn ensure_loaded(config_dir: &str, signer: &Signer) -> Result<(), String> {
+    let is_loaded = |slot: &Option<LoadedIndex>| slot.as_ref().is_some_and(|l| l.config_dir == config_dir);
+    if is_loaded(&loaded_index().lock().unwrap()) {
+        return Ok(());
+    }
+    let key = index_key(config_dir, signer).await?;
+    let index = match read_index(config_dir, &key) {
+        Ok(index) => index,
+        Err(e) => {
+            debug_log!("DM search index not loaded, starting a new one: {}", e);
+            SearchIndex::default()
+        }
+    };
+    let unsaved = {
+        let mut slot = loaded_index().lock().unwrap();
+        if is_loaded(&slot) {
+            return Ok(());
+        }
+        let loaded = LoadedIndex { config_dir: config_dir.to_string(), key, index, dirty: false, save_pending: false };
+        // The previous profile's index, if it has changes its save_later hasn't written yet.
+        slot.replace(loaded).filter(|old| old.dirty).map(|old| (old.config_dir, old.key, old.index.to_text()))
+    };
+    if let Some((old_dir, old_key, text)) = unsaved {
+        save(old_dir, old_key, text).await;
+    }
+    Ok(())
+}
+
+/// Add a conversation's messages, as just decrypted, to the index.
+pub async fn index_conversation(
+    config_dir: &str,
+    signer: &Signer,
+    other_pubkey_hex: &str,
+    messages: &[DecryptedMessage],
+) -> Result<(), String> {
+    ensure_loaded(config_dir, signer).await?;
+    let mut slot = loaded_index().lock().unwrap();
+    let Some(loaded) = slot.as_mut() else { return Ok(()) };
+    if loaded.index.add_conversation(&other_pubkey_hex.to_lowercase(), messages) {
+        mark_dirty(loaded);
+    }
+    Ok(())
+}
+
+/// Search every conversation, first indexing messages the index hasn't seen yet.
+pub async fn search(config_dir: &str, signer: &Signer, our_pubkey_hex: &str, query: &str) -> Result<Vec<SearchHit>, String> {
+    ensure_loaded(config_dir, signer).await?;
+    let behind: Vec<String> = {
+        let slot = loaded_index().lock().unwrap();
+        let covered = slot.as_ref().map(|l| &l.index.covered);
+        messages_store::list_conversations(config_dir)?
+            .into_iter()
+            .map(|pk| pk.to_lowercase())
+            .filter(|pk| {
+                let indexed = covered.and_then(|c| c.get(pk)).copied().unwrap_or(0);
+                messages_store::message_count(config_dir, pk) > indexed
+            })
+            .collect()
+    };
+    for other in &behind {
+        let messages = messages_store::get_messages(config_dir, signer, our_pubkey_hex, other).await?;
+        if let Some(loaded) = loaded_index().lock().unwrap().as_mut() {
+            if loaded.index.add_conversation(other, &messages) {
+                mark_dirty(loaded);
+            }
+        }
+    }
+    let slot = loaded_index().lock().unwrap();
+    let Some(loaded) = slot.as_ref() else { return Ok(Vec::new()) };
+    Ok(loaded.index.search(query, MAX_RESULTS))
+}
+
+pub fn hits_to_json(hits: &[SearchHit]) -> String {
+    let mut out = String::from("[");
+    for (i, h) in hits.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        out.push_str(&format!(
+            r#"{{"conversation":"{}","id":"{}","created_at":{},"snippet":"{}"}}"#,
+            escape_json_string(&h.conversation),
+            escape_json_string(&h.id),
+            h.created_at,
+            escape_json_string(&h.snippet)
+        ));
+    }
+    out.push(']');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(id: &str, created_at: u64, content: &str) -> DecryptedMessage {
+        DecryptedMessage {
+            id: id.to_string(),
+            pubkey: "b".repeat(64),
+            created_at,
+            content: content.to_string(),
+            is_outgoing: false,
+        }
+    }
+
+    fn sample_index() -> SearchIndex {
+        let mut index = SearchIndex::default();
+        index.add_conversation(&"b".repeat(64), &[
+            message("01", 100, "Here is the invoice: lnbc2500u1pvjluez\nThanks!"),
+            message("02", 200, "Ship it to 12 Baker Street, London"),
+            message("03", 300, "Is Baker on the invoice?"),
+        ]);
+        index
+    }
+
+    fn ids(index: &SearchIndex, query: &str) -> Vec<String> {
+        index.search(query, 10).into_iter().map(|h| h.id).collect()
+    }
+
+    #[test]
+    fn test_add_conversation_only_changes_once() {
+        let mut index = SearchIndex::default();
+        let messages = [message("01", 100, "hello")];
+        assert!(index.add_conversation("bb", &messages));
+        assert!(!index.add_conversation("bb", &messages));
+    }
+
+    #[test]
+    fn test_search_word_newest_first() {
+        assert_eq!(ids(&sample_index(), "invoice"), vec!["03", "01"]);
+    }
+
+    #[test]
+    fn test_search_phrase_in_order() {
+        let index = sample_index();
+        assert_eq!(ids(&index, "\"baker street\""), vec!["02"]);
+        assert_eq!(ids(&index, "\"street baker\""), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_search_prefix() {
+        assert_eq!(ids(&sample_index(), "lnbc*"), vec!["01"]);
+    }
+
+    #[test]
+    fn test_search_all_terms_must_match() {
+        assert_eq!(ids(&sample_index(), "bak* invoice"), vec!["03"]);
+    }
+
+    #[test]
+    fn test_hit_snippet_on_one_line() {
+        let hit = &sample_index().search("lnbc*", 10)[0];
+        assert_eq!(hit.conversation, "b".repeat(64));
+        assert_eq!(hit.snippet, "Here is the invoice: lnbc2500u1pvjluez Thanks!");
+    }
+
+    #[test]
+    fn test_index_text_round_trip() {
+        let index = sample_index();
+        let loaded = SearchIndex::from_text(&index.to_text()).unwrap();
+        assert_eq!(ids(&loaded, "\"baker street\""), vec!["02"]);
+        assert_eq!(loaded.docs[0].text, index.docs[0].text);
+        assert_eq!(loaded.covered, index.covered);
+    }
+}
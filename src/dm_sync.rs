@@ -0,0 +1,599 @@
+/*
+ * dm_sync.rs
+ * Copyright (C) 2026 Chris Burdess
+ *
+ * This file is part of Plume, a Nostr desktop client.
+ *
+ * Plume is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Plume is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Plume.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Per-relay DM sync cursors, kept in {profile_dir}/dm_sync.json.
+//!
+//! For each relay and each DM stream (kind 4 received, kind 4 sent, gift wraps) we remember
+//! the newest and oldest created_at the relay has given us, and whether it has no history
+//! older than that. The live subscription asks only for events since the newest; backfill
+//! pages backwards with until = oldest until the relay runs out. An event that could not be
+//! stored holds the next live subscription back to its time, so it is fetched again. When
+//! the live subscription returns a full batch, the span between its oldest event and where it
+//! started is recorded as a gap, which backfill fills in before going on with older history.
+//!
+//! Timestamps are those of the events on the wire: for gift wraps that is the wrap, whose
+//! created_at is randomised up to two days into the past, so live sync reaches back further.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+
+use bytes::BytesMut;
+use crate::config::escape_json_string;
+use crate::json::{JsonContentHandler, JsonNumber, JsonParser};
+use crate::nostr::{self, Event, Filter};
+use crate::relay::GIFT_WRAP_SINCE_SLACK;
+use crate::relay_info;
+
+/// Most events asked for per stream when subscribing for live sync.
+pub const LIVE_LIMIT: u32 = 500;
+
+/// Most events asked for per backfill page; lowered to a relay's advertised max_limit.
+pub const BACKFILL_PAGE_LIMIT: u32 = 200;
+
+/// Seconds to wait for one backfill page before giving up on the relay for this session.
+pub const BACKFILL_PAGE_TIMEOUT_SECS: u32 = 20;
+
+const SYNC_FILE: &str = "dm_sync.json";
+
+/// The subscriptions a DM sync is made of. Each has its own cursor, since a relay may hold
+/// far more of one than another.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DmStream {
+    Received,
+    Sent,
+    GiftWraps,
+}
+
+impl DmStream {
+    pub const ALL: [DmStream; 3] = [DmStream::Received, DmStream::Sent, DmStream::GiftWraps];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DmStream::Received => "received",
+            DmStream::Sent => "sent",
+            DmStream::GiftWraps => "gift_wraps",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<DmStream> {
+        DmStream::ALL.into_iter().find(|s| s.as_str() == name)
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Filter for this stream, without since or until.
+    fn filter(self, our_pubkey_hex: &str, limit: u32) -> Filter {
+        match self {
+            DmStream::Received => nostr::filter_dms_received(our_pubkey_hex, limit, None),
+            DmStream::Sent => nostr::filter_dms_sent(our_pubkey_hex, limit, None),
+            DmStream::GiftWraps => nostr::filter_gift_wraps(our_pubkey_hex, limit, None),
+        }
+    }
+}
+
+/// Which stream a DM event as delivered by a relay belongs to.
+pub fn stream_for(event: &Event, our_pubkey_hex: &str) -> DmStream {
+    if event.kind == nostr::KIND_GIFT_WRAP {
+        DmStream::GiftWraps
+    } else if event.pubkey.eq_ignore_ascii_case(our_pubkey_hex) {
+        DmStream::Sent
+    } else {
+        DmStream::Received
+    }
+}
+
+/// How much of one stream we have from one relay.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SyncCursor {
+    /// created_at of the newest event the relay has given us.
+    pub newest: Option<u64>,
+    /// Everything from here up to newest has been fetched (apart from any gap, see InitialSync).
+    pub oldest: Option<u64>,
+    /// The relay has nothing older than oldest.
+    pub exhausted: bool,
+    /// created_at of the oldest event we could not store; live sync starts here again.
+    pub retry_from: Option<u64>,
+    /// (until, floor) of a gap a full live batch left above what we already had. It is
+    /// backfilled before older history, from until down to floor.
+    pub gap: Option<(u64, u64)>,
+}
+
+impl SyncCursor {
+    /// Where live sync for `stream` starts: the newest event, or the oldest we failed to
+    /// store, less the backdating slack for gift wraps.
+    fn live_since(&self, stream: DmStream) -> Option<u64> {
+        let since = self.newest.map(|newest| self.retry_from.map_or(newest, |retry| newest.min(retry)));
+        since.map(|since| match stream {
+            DmStream::GiftWraps => since.saturating_sub(GIFT_WRAP_SINCE_SLACK),
+            _ => since,
+        })
+    }
+}
+
+/// Sync cursors for every relay we sync DMs from.
+#[derive(Clone, Debug, Default)]
+pub struct SyncCursors {
+    relays: HashMap<String, [SyncCursor; 3]>,
+}
+
+impl SyncCursors {
+    /// Load the cursors saved in the profile directory; a missing or unreadable file means
+    /// we start from scratch.
+    pub fn load(config_dir: &str) -> SyncCursors {
+        match fs::read_to_string(Path::new(config_dir).join(SYNC_FILE)) {
+            Ok(contents) => parse_cursors(&contents).unwrap_or_default(),
+            Err(_) => SyncCursors::default(),
+        }
+    }
+
+    /// Save the cursors: write a temporary file, fsync it, rename it over the old one.
+    pub fn save(&self, config_dir: &str) -> Result<(), String> {
+        let path = Path::new(config_dir).join(SYNC_FILE);
+        let tmp_path = path.with_extension("json.tmp");
+        let write = || -> std::io::Result<()> {
+            let mut out = File::create(&tmp_path)?;
+            out.write_all(self.to_json().as_bytes())?;
+            out.sync_all()?;
+            drop(out);
+            fs::rename(&tmp_path, &path)
+        };
+        write().map_err(|e| format!("Save DM sync cursors: {}", e))
+    }
+
+    pub fn get(&self, relay_url: &str, stream: DmStream) -> SyncCursor {
+        self.relays.get(relay_url).map(|c| c[stream.index()]).unwrap_or_default()
+    }
+
+    fn get_mut(&mut self, relay_url: &str, stream: DmStream) -> &mut SyncCursor {
+        &mut self.relays.entry(relay_url.to_string()).or_default()[stream.index()]
+    }
+
+    /// Forget relays we no longer sync from.
+    pub fn retain_relays(&mut self, relay_urls: &[String]) {
+        self.relays.retain(|url, _| relay_urls.contains(url));
+    }
+
+    /// Record an event the relay gave us for a stream.
+    pub fn saw(&mut self, relay_url: &str, stream: DmStream, created_at: u64) {
+        let c = self.get_mut(relay_url, stream);
+        c.newest = Some(c.newest.map_or(created_at, |n| n.max(created_at)));
+        c.oldest = Some(c.oldest.map_or(created_at, |o| o.min(created_at)));
+    }
+
+    /// Record an event from the relay that could not be stored but might be later.
+    pub fn failed(&mut self, relay_url: &str, stream: DmStream, created_at: u64) {
+        let c = self.get_mut(relay_url, stream);
+        c.retry_from = Some(c.retry_from.map_or(created_at, |r| r.min(created_at)));
+    }
+
+    /// Filters for the live subscription to a relay: each stream since its newest event, or
+    /// the oldest it failed to store.
+    pub fn live_filters(&self, relay_url: &str, our_pubkey_hex: &str) -> Vec<Filter> {
+        DmStream::ALL
+            .iter()
+            .map(|&stream| {
+                let mut filter = stream.filter(our_pubkey_hex, LIVE_LIMIT);
+                filter.since = self.get(relay_url, stream).live_since(stream);
+                filter
+            })
+            .collect()
+    }
+
+    /// Filter for the next page of history of a stream (the gap first, if there is one), or
+    /// None if the relay has no more.
+    pub fn backfill_filter(&self, relay_url: &str, stream: DmStream, our_pubkey_hex: &str, limit: u32) -> Option<Filter> {
+        let c = self.get(relay_url, stream);
+        let mut filter = stream.filter(our_pubkey_hex, limit);
+        if let Some((until, floor)) = c.gap {
+            filter.until = Some(until);
+            filter.since = Some(floor);
+            return Some(filter);
+        }
+        if c.exhausted {
+            return None;
+        }
+        filter.until = c.oldest;
+        Some(filter)
+    }
+
+    /// Move a stream's cursor on after a backfill page of `count` events (already passed to
+    /// saw), the oldest at `page_oldest`, that was asked for with until = `until`. A page with
+    /// nothing older means the relay is exhausted, unless it was full, in which case there are
+    /// more events in that one second than fit in a page and we step past it. A gap is closed
+    /// by a page that is not full or that reaches its floor.
+    pub fn finish_page(&mut self, relay_url: &str, stream: DmStream, until: Option<u64>, page_oldest: Option<u64>, count: usize, limit: u32) {
+        let c = self.get_mut(relay_url, stream);
+        if let Some((gap_until, floor)) = c.gap {
+            if until != Some(gap_until) {
+                return;
+            }
+            c.gap = match page_oldest {
+                Some(oldest) if count >= limit as usize => {
+                    let next = if oldest < gap_until { oldest } else { oldest.saturating_sub(1) };
+                    (next > floor).then_some((next, floor))
+                }
+                _ => None,
+            };
+            return;
+        }
+        if c.oldest != until {
+            return;
+        }
+        match c.oldest {
+            Some(oldest) if count >= limit as usize && oldest > 0 => c.oldest = Some(oldest - 1),
+            _ => c.exhausted = true,
+        }
+    }
+
+    /// Whether every stream from this relay has been backfilled, gaps included.
+    pub fn is_exhausted(&self, relay_url: &str) -> bool {
+        DmStream::ALL.iter().all(|&stream| {
+            let c = self.get(relay_url, stream);
+            c.exhausted && c.gap.is_none()
+        })
+    }
+
+    /// The oldest event we have from a relay, across all streams.
+    pub fn oldest(&self, relay_url: &str) -> Option<u64> {
+        DmStream::ALL.iter().filter_map(|&stream| self.get(relay_url, stream).oldest).min()
+    }
+
+    fn to_json(&self) -> String {
+        let mut urls: Vec<&String> = self.relays.keys().collect();
+        urls.sort();
+        let mut out = String::from("{");
+        for (i, url) in urls.into_iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            out.push_str(&format!("\"{}\":{{", escape_json_string(url)));
+            for (j, stream) in DmStream::ALL.iter().enumerate() {
+                let c = self.relays[url][stream.index()];
+                if j > 0 {
+                    out.push(',');
+                }
+                out.push_str(&format!("\"{}\":{{", stream.as_str()));
+                let mut fields: Vec<String> = Vec::new();
+                if let Some(n) = c.newest {
+                    fields.push(format!("\"newest\":{}", n));
+                }
+                if let Some(o) = c.oldest {
+                    fields.push(format!("\"oldest\":{}", o));
+                }
+                if let Some(r) = c.retry_from {
+                    fields.push(format!("\"retry_from\":{}", r));
+                }
+                if let Some((until, floor)) = c.gap {
+                    fields.push(format!("\"gap_until\":{},\"gap_floor\":{}", until, floor));
+                }
+                fields.push(format!("\"exhausted\":{}", c.exhausted));
+                out.push_str(&fields.join(","));
+                out.push('}');
+            }
+            out.push('}');
+        }
+        out.push('}');
+        out
+    }
+}
+
+/// Counts what each relay sends for each stream before its first EOSE. A relay that fills
+/// the live limit may have had more since our newest event than it sent; the events between
+/// those it sent and where live sync started are then fetched by backfill as a gap. Also
+/// notes which streams had events that could not be stored, so earlier retry points are only
+/// dropped once the relay has sent everything again without a failure.
+pub struct InitialSync {
+    seen: HashMap<(String, usize), (u32, u64)>,
+    failed: HashSet<(String, usize)>,
+    /// Where live sync started for each relay and stream: the floor of any gap.
+    since: HashMap<(String, usize), u64>,
+}
+
+impl InitialSync {
+    /// Start counting live sync from `relay_urls`, whose subscriptions were made from `cursors`.
+    pub fn new(cursors: &SyncCursors, relay_urls: &[String]) -> InitialSync {
+        let mut since = HashMap::new();
+        for relay_url in relay_urls {
+            for stream in DmStream::ALL {
+                if let Some(s) = cursors.get(relay_url, stream).live_since(stream) {
+                    since.insert((relay_url.clone(), stream.index()), s);
+                }
+            }
+        }
+        InitialSync {
+            seen: HashMap::new(),
+            failed: HashSet::new(),
+            since,
+        }
+    }
+
+    pub fn saw(&mut self, relay_url: &str, stream: DmStream, created_at: u64) {
+        let entry = self.seen.entry((relay_url.to_string(), stream.index())).or_insert((0, created_at));
+        entry.0 += 1;
+        entry.1 = entry.1.min(created_at);
+    }
+
+    pub fn failed(&mut self, relay_url: &str, stream: DmStream) {
+        self.failed.insert((relay_url.to_string(), stream.index()));
+    }
+
+    /// The relay has sent its stored events: record the gap a full batch left, and forget
+    /// retry points it has now delivered again. With nothing synced before, there is no gap;
+    /// ordinary backfill carries on from the batch's oldest event.
+    pub fn finish(&mut self, relay_url: &str, cursors: &mut SyncCursors) {
+        for stream in DmStream::ALL {
+            let key = (relay_url.to_string(), stream.index());
+            if !self.failed.remove(&key) {
+                cursors.get_mut(relay_url, stream).retry_from = None;
+            }
+            let floor = self.since.remove(&key);
+            if let (Some((count, oldest)), Some(floor)) = (self.seen.remove(&key), floor) {
+                if count >= LIVE_LIMIT && oldest > floor {
+                    let c = cursors.get_mut(relay_url, stream);
+                    // An unfinished earlier gap lies below this one; cover both
+                    let floor = c.gap.map_or(floor, |(_, earlier)| earlier.min(floor));
+                    c.gap = Some((oldest, floor));
+                }
+            }
+        }
+    }
+}
+
+/// Backfill page size for a relay: our page limit, or less if the relay says it returns less.
+pub fn backfill_page_limit(relay_url: &str) -> u32 {
+    let max_limit = relay_info::cached_relay_info(relay_url).and_then(|info| info.limitation.max_limit);
+    match max_limit {
+        Some(m) if m > 0 => BACKFILL_PAGE_LIMIT.min(m.min(u32::MAX as u64) as u32),
+        _ => BACKFILL_PAGE_LIMIT,
+    }
+}
+
+/// Payload of the dm-backfill-progress event.
+pub fn progress_to_json(relay_url: &str, fetched: u64, oldest: Option<u64>, done: bool) -> String {
+    format!(
+        r#"{{"relay_url":"{}","fetched":{},"oldest":{},"done":{}}}"#,
+        escape_json_string(relay_url),
+        fetched,
+        oldest.map_or_else(|| "null".to_string(), |o| o.to_string()),
+        done
+    )
+}
+
+// ============================================================
+// Parsing
+// ============================================================
+
+/// Push handler for dm_sync.json: relay URL keys at depth 1, stream names at depth 2,
+/// cursor fields at depth 3.
+#[derive(Default)]
+struct CursorsHandler {
+    depth: i32,
+    relay: Option<String>,
+    stream: Option<DmStream>,
+    field: Option<String>,
+    cursors: SyncCursors,
+}
+
+impl CursorsHandler {
+    fn cursor(&mut self) -> Option<&mut SyncCursor> {
+        let relay = self.relay.as_deref()?;
+        let stream = self.stream?;
+        Some(self.cursors.get_mut(relay, stream))
+    }
+}
+
+impl JsonContentHandler for CursorsHandler {
+    fn start_object(&mut self) {
+        self.depth += 1;
+    }
+
+    fn end_object(&mut self) {
+        self.depth -= 1;
+        match self.depth {
+            1 => self.relay = None,
+            2 => self.stream = None,
+            _ => {}
+        }
+    }
+
+    fn start_array(&mut self) {
+        self.depth += 1;
+    }
+
+    fn end_array(&mut self) {
+        self.depth -= 1;
+    }
+
+    fn key(&mut self, key: &str) {
+        match self.depth {
+            1 => self.relay = Some(key.to_string()),
+            2 => self.stream = DmStream::from_name(key),
+            3 => self.field = Some(key.to_string()),
+            _ => {}
+        }
+    }
+
+    fn string_value(&mut self, _value: &str) {}
+
+    fn number_value(&mut self, number: JsonNumber) {
+        if self.depth != 3 {
+            return;
+        }
+        let n = number.as_f64().max(0.0) as u64;
+        let field = self.field.take();
+        if let Some(c) = self.cursor() {
+            match field.as_deref() {
+                Some("newest") => c.newest = Some(n),
+                Some("oldest") => c.oldest = Some(n),
+                Some("retry_from") => c.retry_from = Some(n),
+                Some("gap_until") => c.gap = Some((n, c.gap.map_or(0, |(_, floor)| floor))),
+                Some("gap_floor") => c.gap = Some((c.gap.map_or(0, |(until, _)| until), n)),
+                _ => {}
+            }
+        }
+    }
+
+    fn boolean_value(&mut self, value: bool) {
+        if self.depth != 3 {
+            return;
+        }
+        let field = self.field.take();
+        if let Some(c) = self.cursor() {
+            if field.as_deref() == Some("exhausted") {
+                c.exhausted = value;
+            }
+        }
+    }
+
+    fn null_value(&mut self) {}
+}
+
+fn parse_cursors(json: &str) -> Result<SyncCursors, String> {
+    let mut handler = CursorsHandler::default();
+    let mut parser = JsonParser::new();
+    let mut buf = BytesMut::from(json.as_bytes());
+    parser.receive(&mut buf, &mut handler).map_err(|e| format!("DM sync cursors: {}", e))?;
+    parser.close(&mut handler).map_err(|e| format!("DM sync cursors: {}", e))?;
+    Ok(handler.cursors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RELAY: &str = "wss://relay.example.com";
+
+    fn our() -> String {
+        "a".repeat(64)
+    }
+
+    /// Received seen at 1_000_000 and 900_000, gift wraps at 1_000_000, sent never.
+    fn seen_cursors() -> SyncCursors {
+        let mut cursors = SyncCursors::default();
+        cursors.saw(RELAY, DmStream::Received, 1_000_000);
+        cursors.saw(RELAY, DmStream::Received, 900_000);
+        cursors.saw(RELAY, DmStream::GiftWraps, 1_000_000);
+        cursors
+    }
+
+    #[test]
+    fn test_live_filters_start_unbounded() {
+        let cursors = SyncCursors::default();
+        assert!(cursors.live_filters(RELAY, &our()).iter().all(|f| f.since.is_none()));
+    }
+
+    #[test]
+    fn test_live_filters_since_newest_seen() {
+        let filters = seen_cursors().live_filters(RELAY, &our());
+        assert_eq!(filters[0].since, Some(1_000_000));
+        assert_eq!(filters[1].since, None);
+        assert_eq!(filters[2].since, Some(1_000_000 - GIFT_WRAP_SINCE_SLACK));
+    }
+
+    #[test]
+    fn test_full_page_steps_past_crowded_second() {
+        let mut cursors = seen_cursors();
+        let until = cursors.backfill_filter(RELAY, DmStream::Received, &our(), 2).unwrap().until;
+        assert_eq!(until, Some(900_000));
+        // A full page with nothing older than the second it started at
+        cursors.saw(RELAY, DmStream::Received, 900_000);
+        cursors.finish_page(RELAY, DmStream::Received, until, Some(900_000), 2, 2);
+        assert_eq!(cursors.get(RELAY, DmStream::Received).oldest, Some(899_999));
+    }
+
+    #[test]
+    fn test_empty_page_ends_backfill() {
+        let mut cursors = seen_cursors();
+        cursors.finish_page(RELAY, DmStream::Received, Some(900_000), None, 0, 2);
+        assert!(cursors.backfill_filter(RELAY, DmStream::Received, &our(), 2).is_none());
+    }
+
+    #[test]
+    fn test_failed_message_holds_live_sync_back() {
+        let mut cursors = seen_cursors();
+        cursors.failed(RELAY, DmStream::Received, 950_000);
+        assert_eq!(cursors.live_filters(RELAY, &our())[0].since, Some(950_000));
+    }
+
+    #[test]
+    fn test_cursors_json_round_trip() {
+        let mut cursors = seen_cursors();
+        cursors.failed(RELAY, DmStream::Received, 950_000);
+        cursors.finish_page(RELAY, DmStream::Received, Some(900_000), None, 0, 2);
+        let loaded = parse_cursors(&cursors.to_json()).unwrap();
+        assert_eq!(loaded.get(RELAY, DmStream::Received), cursors.get(RELAY, DmStream::Received));
+        assert_eq!(loaded.get(RELAY, DmStream::GiftWraps), cursors.get(RELAY, DmStream::GiftWraps));
+        assert_eq!(loaded.get(RELAY, DmStream::Sent), SyncCursor::default());
+    }
+
+    #[test]
+    fn test_initial_sync_clears_retry_of_completed_streams() {
+        let mut cursors = seen_cursors();
+        cursors.failed(RELAY, DmStream::Received, 950_000);
+        let mut initial = InitialSync::new(&cursors, &[RELAY.to_string()]);
+        initial.failed(RELAY, DmStream::GiftWraps);
+        initial.finish(RELAY, &mut cursors);
+        assert_eq!(cursors.get(RELAY, DmStream::Received).retry_from, None);
+        assert_eq!(cursors.live_filters(RELAY, &our())[0].since, Some(1_000_000));
+    }
+
+    #[test]
+    fn test_full_live_batch_backfills_only_the_gap() {
+        let relay = RELAY;
+        let our = our();
+        let mut cursors = SyncCursors::default();
+        // History was fully synced up to 1000
+        cursors.saw(relay, DmStream::Received, 1000);
+        cursors.saw(relay, DmStream::Received, 500);
+        cursors.finish_page(relay, DmStream::Received, Some(500), Some(500), 0, 10);
+        assert!(cursors.get(relay, DmStream::Received).exhausted);
+
+        // After a long absence the live batch is full, its oldest at 5000
+        let mut initial = InitialSync::new(&cursors, &[relay.to_string()]);
+        for i in 0..LIVE_LIMIT {
+            let created_at = 5000 + u64::from(i);
+            initial.saw(relay, DmStream::Received, created_at);
+            cursors.saw(relay, DmStream::Received, created_at);
+        }
+        initial.finish(relay, &mut cursors);
+        let c = cursors.get(relay, DmStream::Received);
+        assert_eq!(c.gap, Some((5000, 1000)));
+        assert_eq!(c.oldest, Some(500));
+        assert!(!cursors.is_exhausted(relay));
+
+        // The gap is paged from its top down to its floor
+        let filter = cursors.backfill_filter(relay, DmStream::Received, &our, 10).unwrap();
+        assert_eq!((filter.until, filter.since), (Some(5000), Some(1000)));
+        cursors.finish_page(relay, DmStream::Received, filter.until, Some(3000), 10, 10);
+        assert_eq!(cursors.get(relay, DmStream::Received).gap, Some((3000, 1000)));
+        assert_eq!(parse_cursors(&cursors.to_json()).unwrap().get(relay, DmStream::Received), cursors.get(relay, DmStream::Received));
+
+        // A page that is not full closes it, and the stream is exhausted again
+        cursors.finish_page(relay, DmStream::Received, Some(3000), Some(1200), 4, 10);
+        let c = cursors.get(relay, DmStream::Received);
+        assert_eq!(c.gap, None);
+        assert_eq!(c.oldest, Some(500));
+        assert!(cursors.backfill_filter(relay, DmStream::Received, &our, 10).is_none());
+    }
+}
@@ -0,0 +1,834 @@
+/*
+ * event_store.rs
+ * Copyright (C) 2026 Chris Burdess
+ *
+ * This file is part of Plume, a Nostr desktop client.
+ *
+ * Plume is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Plume is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Plume.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Local event database: every event received from or published to a relay is kept in
+//! {profile_dir}/events/events.log, one compact JSON event per line. The log is append-only;
+//! indexes by id, author, kind, created_at and #e/#p tags are rebuilt in memory when the store
+//! is opened, and queries answer a `nostr::Filter` from them, newest first.
+//!
+//! Replaceable (kinds 0, 3, 10000–19999) and parameterized replaceable (30000–39999) events
+//! keep only the latest version per author, kind and `d` tag.
+//!
+//! The store keeps at most MAX_EVENTS events; past that the oldest are evicted. Lines of
+//! superseded, deleted and evicted events are dropped when the log is compacted, which happens
+//! once they take up more of it than the live events do.
+//!
+//! Deletion requests (NIP-09, kind 5) are kept like any other event. Their `e` and `a` targets
+//! by the same author are dropped from the indexes, and remembered so a deleted event that
+//! arrives later is refused.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+use crate::debug_log;
+use crate::nostr::{self, Event, Filter};
+use crate::warn_log;
+
+/// Compact once dropped lines take up more than this many bytes and more than the live
+/// events do.
+const COMPACT_MIN_DEAD_BYTES: u64 = 1024 * 1024;
+
+/// Most events kept. Past this the oldest are evicted down to nine tenths of it, except
+/// deletion requests, which are kept so their tombstones survive.
+const MAX_EVENTS: usize = 200_000;
+
+/// Index key: newest-first iteration is a reverse walk of a set of these.
+type Key = (u64, String);
+
+/// Where an event's line sits in the log, plus the fields the indexes and filters need.
+struct IndexedEvent {
+    offset: u64,
+    len: usize,
+    pubkey: String,
+    kind: u32,
+    created_at: u64,
+    e_tags: Vec<String>,
+    p_tags: Vec<String>,
+}
+
+pub struct EventStore {
+    log_path: PathBuf,
+    log: File,
+    log_len: u64,
+    /// Bytes of the log taken by superseded, deleted and evicted events.
+    dead_bytes: u64,
+    max_events: usize,
+    events: HashMap<String, IndexedEvent>,
+    by_created_at: BTreeSet<Key>,
+    by_author: HashMap<String, BTreeSet<Key>>,
+    by_kind: HashMap<u32, BTreeSet<Key>>,
+    by_e_tag: HashMap<String, BTreeSet<Key>>,
+    by_p_tag: HashMap<String, BTreeSet<Key>>,
+    /// Latest version of each replaceable event, by (pubkey, kind, d tag).
+    replaceable: HashMap<(String, u32, String), Key>,
+    /// Event ids named in deletion requests, with every pubkey that asked.
+    deleted: HashMap<String, HashSet<String>>,
+    /// Addresses named in deletion requests: versions created up to this time are deleted.
+    deleted_addresses: HashMap<(String, u32, String), u64>,
+}
+
+fn is_replaceable(kind: u32) -> bool {
+    kind == nostr::KIND_METADATA || kind == nostr::KIND_CONTACTS || (10000..20000).contains(&kind)
+}
+
+fn is_parameterized_replaceable(kind: u32) -> bool {
+    (30000..40000).contains(&kind)
+}
+
+/// (pubkey, kind, d tag) identity of a replaceable event, None for regular events.
+fn replaceable_key(event: &Event) -> Option<(String, u32, String)> {
+    if is_replaceable(event.kind) {
+        return Some((event.pubkey.clone(), event.kind, String::new()));
+    }
+    if is_parameterized_replaceable(event.kind) {
+        let d = event.tags.iter()
+            .find(|t| t.len() >= 2 && t[0] == "d")
+            .map(|t| t[1].clone())
+            .unwrap_or_default();
+        return Some((event.pubkey.clone(), event.kind, d));
+    }
+    None
+}
+
+fn tag_values(event: &Event, name: &str) -> Vec<String> {
+    event.tags.iter()
+        .filter(|t| t.len() >= 2 && t[0] == name)
+        .map(|t| t[1].to_lowercase())
+        .collect()
+}
+
+/// Event as a single line of JSON. Newlines inside strings are escaped by event_to_json,
+/// so the only raw newlines are its own layout.
+fn event_line(event: &Event) -> String {
+    let mut line: String = nostr::event_to_json(event).lines().map(|l| l.trim()).collect();
+    line.push('\n');
+    line
+}
+
+impl EventStore {
+    /// Open (or create) the store in `config_dir` and rebuild its indexes from the log.
+    pub fn open(config_dir: &str) -> Result<EventStore, String> {
+        let dir = Path::new(config_dir).join("events");
+        fs::create_dir_all(&dir).map_err(|e| format!("Create events directory: {}", e))?;
+        let log_path = dir.join("events.log");
+        let log = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&log_path)
+            .map_err(|e| format!("Open event log: {}", e))?;
+        let mut store = EventStore {
+            log_path,
+            log,
+            log_len: 0,
+            dead_bytes: 0,
+            max_events: MAX_EVENTS,
+            events: HashMap::new(),
+            by_created_at: BTreeSet::new(),
+            by_author: HashMap::new(),
+            by_kind: HashMap::new(),
+            by_e_tag: HashMap::new(),
+            by_p_tag: HashMap::new(),
+            replaceable: HashMap::new(),
+            deleted: HashMap::new(),
+            deleted_addresses: HashMap::new(),
+        };
+        store.load().map_err(|e| format!("Read event log: {}", e))?;
+        store.evict();
+        if store.needs_compaction() {
+            store.compact().map_err(|e| format!("Compact event log: {}", e))?;
+        }
+        debug_log!("[events] opened {} ({} events)", store.log_path.display(), store.events.len());
+        Ok(store)
+    }
+
+    fn load(&mut self) -> io::Result<()> {
+        let mut reader = BufReader::new(File::open(&self.log_path)?);
+        let mut offset: u64 = 0;
+        let mut line = String::new();
+        loop {
+            line.clear();
+            let n = reader.read_line(&mut line)?;
+            if n == 0 {
+                break;
+            }
+            if !line.ends_with('\n') {
+                // Torn write at the end of the log: drop it so the next append starts clean.
+                warn_log!("[events] truncating partial record at offset {}", offset);
+                self.log.set_len(offset)?;
+                break;
+            }
+            match nostr::parse_event(line.trim_end()) {
+                Ok(event) => {
+                    if !self.index(&event, offset, n) {
+                        self.dead_bytes += n as u64;
+                    }
+                }
+                Err(e) => {
+                    debug_log!("[events] skipping unreadable record at offset {}: {}", offset, e);
+                    self.dead_bytes += n as u64;
+                }
+            }
+            offset += n as u64;
+        }
+        self.log_len = offset;
+        Ok(())
+    }
+
+    fn needs_compaction(&self) -> bool {
+        let live_bytes = self.log_len - self.dead_bytes;
+        self.dead_bytes > COMPACT_MIN_DEAD_BYTES && self.dead_bytes > live_bytes
+    }
+
+    /// Rewrite the log with only the live events, in their original order. The indexes and
+    /// tombstones stay as they are; only the offsets move.
+    fn compact(&mut self) -> io::Result<()> {
+        let mut live: Vec<(u64, usize, String)> = self.events.iter()
+            .map(|(id, e)| (e.offset, e.len, id.clone()))
+            .collect();
+        live.sort();
+        let tmp_path = self.log_path.with_extension("log.tmp");
+        let mut out = File::create(&tmp_path)?;
+        let mut buf = Vec::new();
+        let mut moved: Vec<(String, u64)> = Vec::with_capacity(live.len());
+        let mut new_len: u64 = 0;
+        for (offset, len, id) in live {
+            buf.resize(len, 0);
+            self.log.seek(SeekFrom::Start(offset))?;
+            self.log.read_exact(&mut buf)?;
+            out.write_all(&buf)?;
+            moved.push((id, new_len));
+            new_len += len as u64;
+        }
+        out.sync_all()?;
+        drop(out);
+        fs::rename(&tmp_path, &self.log_path)?;
+        debug_log!("[events] compacted log, dropped {} bytes", self.dead_bytes);
+
+        self.log = OpenOptions::new().read(true).append(true).open(&self.log_path)?;
+        for (id, offset) in moved {
+            if let Some(e) = self.events.get_mut(&id) {
+                e.offset = offset;
+            }
+        }
+        self.log_len = new_len;
+        self.dead_bytes = 0;
+        Ok(())
+    }
+
+    /// Evict the oldest events once there are more than max_events.
+    fn evict(&mut self) {
+        if self.events.len() <= self.max_events {
+            return;
+        }
+        let excess = self.events.len() - self.max_events * 9 / 10;
+        let oldest: Vec<String> = self.by_created_at.iter()
+            .filter(|(_, id)| self.events.get(id).is_some_and(|e| e.kind != nostr::KIND_DELETION))
+            .take(excess)
+            .map(|(_, id)| id.clone())
+            .collect();
+        for id in &oldest {
+            self.remove(id);
+        }
+        self.replaceable.retain(|_, (_, id)| self.events.contains_key(id));
+        self.by_author.retain(|_, set| !set.is_empty());
+        self.by_e_tag.retain(|_, set| !set.is_empty());
+        self.by_p_tag.retain(|_, set| !set.is_empty());
+        debug_log!("[events] evicted {} old events", oldest.len());
+    }
+
+    /// Add an event to the indexes. Returns false if it is already known or superseded by a
+    /// newer version of the same replaceable event.
+    fn index(&mut self, event: &Event, offset: u64, len: usize) -> bool {
+        let id = event.id.to_lowercase();
+        if self.events.contains_key(&id) || self.is_deleted(event) {
+            return false;
+        }
+        let key: Key = (event.created_at, id.clone());
+        if let Some(rkey) = replaceable_key(event) {
+            if let Some(existing) = self.replaceable.get(&rkey) {
+                if *existing >= key {
+                    return false;
+                }
+                let old_id = existing.1.clone();
+                self.remove(&old_id);
+            }
+            self.replaceable.insert(rkey, key.clone());
+        }
+
+        let indexed = IndexedEvent {
+            offset,
+            len,
+            pubkey: event.pubkey.to_lowercase(),
+            kind: event.kind,
+            created_at: event.created_at,
+            e_tags: tag_values(event, "e"),
+            p_tags: tag_values(event, "p"),
+        };
+        self.by_created_at.insert(key.clone());
+        self.by_author.entry(indexed.pubkey.clone()).or_default().insert(key.clone());
+        self.by_kind.entry(indexed.kind).or_default().insert(key.clone());
+        for e in &indexed.e_tags {
+            self.by_e_tag.entry(e.clone()).or_default().insert(key.clone());
+        }
+        for p in &indexed.p_tags {
+            self.by_p_tag.entry(p.clone()).or_default().insert(key.clone());
+        }
+        self.events.insert(id, indexed);
+        if event.kind == nostr::KIND_DELETION {
+            self.apply_deletion(event);
+        }
+        true
+    }
+
+    /// Record a deletion request's targets and drop those already stored. Only events by the
+    /// request's author are affected, and deletion requests themselves can't be deleted.
+    fn apply_deletion(&mut self, deletion: &Event) {
+        let author = deletion.pubkey.to_lowercase();
+        for id in tag_values(deletion, "e") {
+            if self.events.get(&id).is_some_and(|e| e.pubkey == author && e.kind != nostr::KIND_DELETION) {
+                self.remove(&id);
+            }
+            self.deleted.entry(id).or_default().insert(author.clone());
+        }
+        for tag in deletion.tags.iter().filter(|t| t.len() >= 2 && t[0] == "a") {
+            // "kind:pubkey:d", where d may itself contain colons
+            let mut parts = tag[1].splitn(3, ':');
+            let rkey = match (parts.next().and_then(|k| k.parse::<u32>().ok()), parts.next(), parts.next()) {
+                (Some(kind), Some(pubkey), Some(d)) if pubkey.eq_ignore_ascii_case(&author) => {
+                    (author.clone(), kind, d.to_string())
+                }
+                _ => continue,
+            };
+            if let Some((created_at, id)) = self.replaceable.get(&rkey).cloned() {
+                if created_at <= deletion.created_at {
+                    self.remove(&id);
+                    self.replaceable.remove(&rkey);
+                }
+            }
+            let until = self.deleted_addresses.entry(rkey).or_insert(0);
+            *until = (*until).max(deletion.created_at);
+        }
+    }
+
+    /// Whether a stored deletion request by the event's author covers it.
+    pub fn is_deleted(&self, event: &Event) -> bool {
+        if event.kind == nostr::KIND_DELETION {
+            return false;
+        }
+        let pubkey = event.pubkey.to_lowercase();
+        if self.deleted.get(&event.id.to_lowercase()).is_some_and(|authors| authors.contains(&pubkey)) {
+            return true;
+        }
+        match replaceable_key(event) {
+            Some((_, kind, d)) => self.deleted_addresses.get(&(pubkey, kind, d)).is_some_and(|until| event.created_at <= *until),
+            None => false,
+        }
+    }
+
+    /// Drop an event from the indexes; its line stays in the log until the next compaction.
+    fn remove(&mut self, id: &str) {
+        let indexed = match self.events.remove(id) {
+            Some(e) => e,
+            None => return,
+        };
+        let key: Key = (indexed.created_at, id.to_string());
+        self.by_created_at.remove(&key);
+        if let Some(set) = self.by_author.get_mut(&indexed.pubkey) {
+            set.remove(&key);
+        }
+        if let Some(set) = self.by_kind.get_mut(&indexed.kind) {
+            set.remove(&key);
+        }
+        for e in &indexed.e_tags {
+            if let Some(set) = self.by_e_tag.get_mut(e) {
+                set.remove(&key);
+            }
+        }
+        for p in &indexed.p_tags {
+            if let Some(set) = self.by_p_tag.get_mut(p) {
+                set.remove(&key);
+            }
+        }
+        self.dead_bytes += indexed.len as u64;
+    }
+
+    /// Append an event to the log. Returns Ok(false) if it was already stored or is older
+    /// than the stored version of the same replaceable event.
+    pub fn insert(&mut self, event: &Event) -> Result<bool, String> {
+        let id = event.id.to_lowercase();
+        if self.events.contains_key(&id) || self.is_deleted(event) {
+            return Ok(false);
+        }
+        if let Some(rkey) = replaceable_key(event) {
+            if let Some(existing) = self.replaceable.get(&rkey) {
+                if *existing >= (event.created_at, id) {
+                    return Ok(false);
+                }
+            }
+        }
+        let line = event_line(event);
+        self.log.write_all(line.as_bytes()).map_err(|e| format!("Write event log: {}", e))?;
+        let offset = self.log_len;
+        self.log_len += line.len() as u64;
+        let added = self.index(event, offset, line.len());
+        self.evict();
+        if self.needs_compaction() {
+            self.compact().map_err(|e| format!("Compact event log: {}", e))?;
+        }
+        Ok(added)
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.events.contains_key(&id.to_lowercase())
+    }
+
+    fn read_event(&mut self, id: &str) -> Result<Event, String> {
+        let (offset, len) = match self.events.get(id) {
+            Some(e) => (e.offset, e.len),
+            None => return Err(format!("Event {} not in store", id)),
+        };
+        let mut buf = vec![0u8; len];
+        self.log.seek(SeekFrom::Start(offset)).map_err(|e| format!("Seek event log: {}", e))?;
+        self.log.read_exact(&mut buf).map_err(|e| format!("Read event log: {}", e))?;
+        let line = String::from_utf8(buf).map_err(|e| format!("Event log: {}", e))?;
+        nostr::parse_event(line.trim_end())
+    }
+
+    /// Whether an indexed event satisfies every condition of the filter.
+    fn matches(&self, id: &str, filter: &Filter) -> bool {
+        let e = match self.events.get(id) {
+            Some(e) => e,
+            None => return false,
+        };
+        if let Some(ref ids) = filter.ids {
+            if !ids.iter().any(|i| i.eq_ignore_ascii_case(id)) {
+                return false;
+            }
+        }
+        if let Some(ref authors) = filter.authors {
+            if !authors.iter().any(|a| a.eq_ignore_ascii_case(&e.pubkey)) {
+                return false;
+            }
+        }
+        if let Some(ref kinds) = filter.kinds {
+            if !kinds.contains(&e.kind) {
+                return false;
+            }
+        }
+        if filter.since.is_some_and(|since| e.created_at < since) {
+            return false;
+        }
+        if filter.until.is_some_and(|until| e.created_at > until) {
+            return false;
+        }
+        if let Some(ref tags) = filter.e_tags {
+            if !tags.iter().any(|t| e.e_tags.contains(&t.to_lowercase())) {
+                return false;
+            }
+        }
+        if let Some(ref tags) = filter.p_tags {
+            if !tags.iter().any(|t| e.p_tags.contains(&t.to_lowercase())) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Candidate keys for a filter, taken from its most selective index.
+    fn candidates(&self, filter: &Filter) -> Vec<Key> {
+        if let Some(ref ids) = filter.ids {
+            return ids.iter()
+                .filter_map(|id| {
+                    let id = id.to_lowercase();
+                    self.events.get(&id).map(|e| (e.created_at, id))
+                })
+                .collect();
+        }
+        let mut choices: Vec<Vec<&BTreeSet<Key>>> = Vec::new();
+        if let Some(ref authors) = filter.authors {
+            choices.push(authors.iter().filter_map(|a| self.by_author.get(&a.to_lowercase())).collect());
+        }
+        if let Some(ref kinds) = filter.kinds {
+            choices.push(kinds.iter().filter_map(|k| self.by_kind.get(k)).collect());
+        }
+        if let Some(ref tags) = filter.e_tags {
+            choices.push(tags.iter().filter_map(|t| self.by_e_tag.get(&t.to_lowercase())).collect());
+        }
+        if let Some(ref tags) = filter.p_tags {
+            choices.push(tags.iter().filter_map(|t| self.by_p_tag.get(&t.to_lowercase())).collect());
+        }
+        let sets = choices.into_iter()
+            .min_by_key(|sets| sets.iter().map(|s| s.len()).sum::<usize>())
+            .unwrap_or_else(|| vec![&self.by_created_at]);
+
+        let low: Key = (filter.since.unwrap_or(0), String::new());
+        let mut keys: Vec<Key> = Vec::new();
+        for set in sets {
+            for key in set.range(low.clone()..) {
+                if filter.until.is_some_and(|until| key.0 > until) {
+                    break;
+                }
+                keys.push(key.clone());
+            }
+        }
+        keys
+    }
+
+    /// Events matching the filter, newest first, at most `filter.limit` of them.
+    pub fn query(&mut self, filter: &Filter) -> Vec<Event> {
+        let mut keys = self.candidates(filter);
+        keys.sort_by(|a, b| b.cmp(a));
+        keys.dedup();
+        let limit = filter.limit.map(|l| l as usize).unwrap_or(usize::MAX);
+        let mut events: Vec<Event> = Vec::new();
+        for (_, id) in keys {
+            if events.len() >= limit {
+                break;
+            }
+            if !self.matches(&id, filter) {
+                continue;
+            }
+            match self.read_event(&id) {
+                Ok(event) => events.push(event),
+                Err(e) => {
+                    warn_log!("[events] {}", e);
+                }
+            }
+        }
+        events
+    }
+}
+
+// ============================================================
+// Active profile's store
+// ============================================================
+
+fn active_store() -> &'static Mutex<Option<EventStore>> {
+    static INSTANCE: OnceLock<Mutex<Option<EventStore>>> = OnceLock::new();
+    INSTANCE.get_or_init(|| Mutex::new(None))
+}
+
+/// Bumped by each open_store, so a slow open that has been overtaken by a later one is
+/// discarded.
+static STORE_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Make the store in `config_dir` the one relay traffic is recorded in (on startup and on
+/// profile switch). The log is read on a background thread; until it is loaded there is no
+/// active store, so queries come back empty and events are not recorded. On failure the app
+/// carries on without a local cache.
+pub fn open_store(config_dir: &str) {
+    let generation = STORE_GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    *active_store().lock().unwrap() = None;
+    let config_dir = config_dir.to_string();
+    std::thread::spawn(move || {
+        let store = match EventStore::open(&config_dir) {
+            Ok(s) => Some(s),
+            Err(e) => {
+                warn_log!("[events] {}", e);
+                None
+            }
+        };
+        let mut active = active_store().lock().unwrap();
+        if STORE_GENERATION.load(Ordering::SeqCst) == generation {
+            *active = store;
+        }
+    });
+}
+
+/// Record an event in the active store. Returns true if it was new.
+pub fn store_event(event: &Event) -> bool {
+    let mut guard = active_store().lock().unwrap();
+    let store = match guard.as_mut() {
+        Some(s) => s,
+        None => return false,
+    };
+    match store.insert(event) {
+        Ok(added) => added,
+        Err(e) => {
+            warn_log!("[events] {}", e);
+            false
+        }
+    }
+}
+
+/// Whether the active store holds an event with this id.
+pub fn has_event(id: &str) -> bool {
+    active_store().lock().unwrap().as_ref().is_some_and(|s| s.contains(id))
+}
+
+/// Whether the active store holds a deletion request by the event's author that covers it.
+pub fn is_deleted(event: &Event) -> bool {
+    active_store().lock().unwrap().as_ref().is_some_and(|s| s.is_deleted(event))
+}
+
+/// Answer a filter from the active store (empty if no store is open).
+pub fn query(filter: &Filter) -> Vec<Event> {
+    match active_store().lock().unwrap().as_mut() {
+        Some(store) => store.query(filter),
+        None => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(id: &str, pubkey: &str, kind: u32, created_at: u64, tags: Vec<Vec<String>>) -> Event {
+        Event {
+            id: id.to_string(),
+            pubkey: pubkey.to_string(),
+            created_at,
+            kind,
+            tags,
+            content: String::from("line one\nline two"),
+            sig: String::new(),
+        }
+    }
+
+    /// A fresh directory for one test's store.
+    fn store_dir(name: &str) -> (std::path::PathBuf, String) {
+        let dir = std::env::temp_dir().join(format!("plume-event-store-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let dir_str = dir.to_string_lossy().to_string();
+        (dir, dir_str)
+    }
+
+    /// Notes aa, bb (a reply to aa) and cc, and two versions of p1's metadata, reopened from disk.
+    fn sample_store(dir: &str) -> EventStore {
+        {
+            let mut store = EventStore::open(dir).unwrap();
+            let reply_tag = vec![vec![String::from("e"), String::from("aa")]];
+            store.insert(&event("aa", "p1", 1, 100, Vec::new())).unwrap();
+            store.insert(&event("bb", "p1", 1, 200, reply_tag)).unwrap();
+            store.insert(&event("cc", "p2", 1, 300, Vec::new())).unwrap();
+            store.insert(&event("m1", "p1", 0, 100, Vec::new())).unwrap();
+            store.insert(&event("m2", "p1", 0, 150, Vec::new())).unwrap();
+        }
+        EventStore::open(dir).unwrap()
+    }
+
+    fn query_ids(store: &mut EventStore, filter: &Filter) -> Vec<String> {
+        store.query(filter).into_iter().map(|e| e.id).collect()
+    }
+
+    #[test]
+    fn test_insert_refuses_duplicates() {
+        let (dir, dir_str) = store_dir("duplicates");
+        let mut store = EventStore::open(&dir_str).unwrap();
+        assert!(store.insert(&event("aa", "p1", 1, 100, Vec::new())).unwrap());
+        assert!(!store.insert(&event("aa", "p1", 1, 100, Vec::new())).unwrap());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_insert_refuses_older_replaceable() {
+        let (dir, dir_str) = store_dir("replaceable");
+        let mut store = EventStore::open(&dir_str).unwrap();
+        assert!(store.insert(&event("m1", "p1", 0, 100, Vec::new())).unwrap());
+        assert!(store.insert(&event("m2", "p1", 0, 150, Vec::new())).unwrap());
+        assert!(!store.insert(&event("m0", "p1", 0, 50, Vec::new())).unwrap());
+        assert!(!store.contains("m1"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_query_by_author_and_kind() {
+        let (dir, dir_str) = store_dir("authors");
+        let mut store = sample_store(&dir_str);
+        let mut filter = Filter::new();
+        filter.authors = Some(vec![String::from("p1")]);
+        filter.kinds = Some(vec![1]);
+        assert_eq!(query_ids(&mut store, &filter), vec!["bb", "aa"]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_query_by_e_tag() {
+        let (dir, dir_str) = store_dir("e-tags");
+        let mut store = sample_store(&dir_str);
+        let mut filter = Filter::new();
+        filter.e_tags = Some(vec![String::from("aa")]);
+        let found = store.query(&filter);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].id, "bb");
+        assert_eq!(found[0].content, "line one\nline two");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_query_replaceable_returns_newest() {
+        let (dir, dir_str) = store_dir("newest");
+        let mut store = sample_store(&dir_str);
+        let mut filter = Filter::new();
+        filter.kinds = Some(vec![0]);
+        assert_eq!(query_ids(&mut store, &filter), vec!["m2"]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_query_since_and_limit() {
+        let (dir, dir_str) = store_dir("since");
+        let mut store = sample_store(&dir_str);
+        let mut filter = Filter::new();
+        filter.since = Some(150);
+        filter.limit = Some(2);
+        assert_eq!(query_ids(&mut store, &filter), vec!["cc", "bb"]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    fn e_tag(id: &str) -> Vec<String> {
+        vec![String::from("e"), id.to_string()]
+    }
+
+    fn article(id: &str, created_at: u64) -> Event {
+        event(id, "p1", 30023, created_at, vec![vec![String::from("d"), String::from("x:y")]])
+    }
+
+    /// p1's note aa and article l1, p2's note bb, and a request by p1 to delete its own note and
+    /// article, p2's note and a note not seen yet.
+    fn store_with_deletion(dir: &str) -> EventStore {
+        let mut store = EventStore::open(dir).unwrap();
+        store.insert(&event("aa", "p1", 1, 100, Vec::new())).unwrap();
+        store.insert(&event("bb", "p2", 1, 100, Vec::new())).unwrap();
+        store.insert(&article("l1", 100)).unwrap();
+        let a_tag = vec![String::from("a"), String::from("30023:p1:x:y")];
+        let deletion = event("d1", "p1", 5, 200, vec![e_tag("aa"), e_tag("bb"), e_tag("cc"), a_tag]);
+        assert!(store.insert(&deletion).unwrap());
+        store
+    }
+
+    #[test]
+    fn test_deletion_drops_only_authors_events() {
+        let (dir, dir_str) = store_dir("deletion-drops");
+        let store = store_with_deletion(&dir_str);
+        assert!(!store.contains("aa"));
+        assert!(store.contains("bb"));
+        assert!(!store.contains("l1"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_deletion_refuses_events_arriving_later() {
+        let (dir, dir_str) = store_dir("deletion-refuses");
+        let mut store = store_with_deletion(&dir_str);
+        assert!(!store.insert(&event("cc", "p1", 1, 150, Vec::new())).unwrap());
+        assert!(!store.is_deleted(&event("cc", "p2", 1, 150, Vec::new())));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_address_deletion_covers_older_versions_only() {
+        let (dir, dir_str) = store_dir("deletion-address");
+        let mut store = store_with_deletion(&dir_str);
+        assert!(!store.insert(&article("l0", 50)).unwrap());
+        assert!(store.insert(&article("l2", 300)).unwrap());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_deletion_by_someone_else_does_not_undo() {
+        let (dir, dir_str) = store_dir("deletion-others");
+        let mut store = store_with_deletion(&dir_str);
+        assert!(store.insert(&event("d2", "p3", 5, 400, vec![e_tag("aa")])).unwrap());
+        assert!(!store.insert(&event("aa", "p1", 1, 100, Vec::new())).unwrap());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_deletions_survive_reopen() {
+        let (dir, dir_str) = store_dir("deletion-reopen");
+        {
+            let mut store = store_with_deletion(&dir_str);
+            store.insert(&article("l2", 300)).unwrap();
+            // Someone else naming the same id later is in the log too
+            store.insert(&event("d2", "p3", 5, 400, vec![e_tag("aa")])).unwrap();
+        }
+        let store = EventStore::open(&dir_str).unwrap();
+        assert!(!store.contains("aa"));
+        assert!(store.contains("bb"));
+        assert!(store.contains("l2"));
+        assert!(store.is_deleted(&event("cc", "p1", 1, 150, Vec::new())));
+        assert!(!store.is_deleted(&event("cc", "p2", 1, 150, Vec::new())));
+        assert!(store.is_deleted(&event("aa", "p1", 1, 100, Vec::new())));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_evict_oldest_keeps_deletions() {
+        let (dir, dir_str) = store_dir("evict");
+        let mut store = EventStore::open(&dir_str).unwrap();
+        store.max_events = 10;
+        let deletion = event("d1", "p1", 5, 0, vec![vec![String::from("e"), String::from("zz")]]);
+        assert!(store.insert(&deletion).unwrap());
+        for i in 1..=10 {
+            assert!(store.insert(&event(&format!("n{}", i), "p1", 1, i, Vec::new())).unwrap());
+        }
+        // 11 events: the two oldest notes go, the older deletion request stays
+        assert_eq!(store.events.len(), 9);
+        assert!(!store.contains("n1"));
+        assert!(!store.contains("n2"));
+        assert!(store.contains("n3"));
+        assert!(store.contains("d1"));
+        assert!(store.is_deleted(&event("zz", "p1", 1, 5, Vec::new())));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_compact_keeps_live_events() {
+        let (dir, dir_str) = store_dir("compact");
+        {
+            let mut store = EventStore::open(&dir_str).unwrap();
+            assert!(store.insert(&event("m1", "p1", 0, 100, Vec::new())).unwrap());
+            assert!(store.insert(&event("aa", "p1", 1, 100, Vec::new())).unwrap());
+            assert!(store.insert(&event("m2", "p1", 0, 200, Vec::new())).unwrap());
+            assert!(store.insert(&event("bb", "p1", 1, 300, Vec::new())).unwrap());
+            assert!(store.dead_bytes > 0);
+            store.compact().unwrap();
+            assert_eq!(store.dead_bytes, 0);
+            assert_eq!(fs::metadata(&store.log_path).unwrap().len(), store.log_len);
+
+            // Reads use the new offsets, and appends go after the compacted lines
+            assert_eq!(query_ids(&mut store, &Filter::new()), vec!["bb", "m2", "aa"]);
+            assert!(store.insert(&event("cc", "p1", 1, 400, Vec::new())).unwrap());
+        }
+        let mut store = EventStore::open(&dir_str).unwrap();
+        assert_eq!(store.dead_bytes, 0);
+        assert_eq!(query_ids(&mut store, &Filter::new()), vec!["cc", "bb", "m2", "aa"]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
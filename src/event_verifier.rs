@@ -0,0 +1,237 @@
+/*
+ * event_verifier.rs
+ * Copyright (C) 2026 Chris Burdess
+ *
+ * This file is part of Plume, a Nostr desktop client.
+ *
+ * Plume is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Plume is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Plume.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Verification of events received from relays. Every EVENT's id and Schnorr signature are
+//! checked before it reaches a subscriber (feed, DM store, event store); forged events are
+//! dropped and counted against the relay that sent them.
+//!
+//! Ids are cheap to recompute and are checked inline. Signatures are checked on the blocking
+//! thread pool, a batch split across cores, so a burst of events does not stall the connection
+//! task. Signatures already checked are remembered by id, so an event seen on several relays
+//! is only verified once.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+
+use crate::config::escape_json_string;
+use crate::crypto;
+use crate::nostr::Event;
+use crate::relay::RelayMessage;
+use crate::warn_log;
+
+/// Number of verified (id, signature) pairs remembered.
+const VERIFIED_CACHE_CAPACITY: usize = 50_000;
+
+/// Batches up to this size are verified inline rather than handed to the blocking pool.
+const INLINE_BATCH_MAX: usize = 4;
+
+/// Verified signatures by event id, oldest evicted first.
+struct VerifiedCache {
+    sigs: HashMap<String, String>,
+    order: VecDeque<String>,
+}
+
+fn verified_cache() -> &'static Mutex<VerifiedCache> {
+    static INSTANCE: OnceLock<Mutex<VerifiedCache>> = OnceLock::new();
+    INSTANCE.get_or_init(|| Mutex::new(VerifiedCache {
+        sigs: HashMap::new(),
+        order: VecDeque::new(),
+    }))
+}
+
+/// Forged events received, by relay URL.
+fn forged_counts() -> &'static Mutex<HashMap<String, u64>> {
+    static INSTANCE: OnceLock<Mutex<HashMap<String, u64>>> = OnceLock::new();
+    INSTANCE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Whether this exact signature was already verified for this id. The id itself must have been
+/// checked against the content, so a hit means the same content and signature.
+fn is_verified(event: &Event) -> bool {
+    let cache = verified_cache().lock().unwrap();
+    cache.sigs.get(&event.id) == Some(&event.sig)
+}
+
+fn remember_verified(event: &Event) {
+    let mut cache = verified_cache().lock().unwrap();
+    if cache.sigs.insert(event.id.clone(), event.sig.clone()).is_none() {
+        cache.order.push_back(event.id.clone());
+        while cache.order.len() > VERIFIED_CACHE_CAPACITY {
+            if let Some(old) = cache.order.pop_front() {
+                cache.sigs.remove(&old);
+            }
+        }
+    }
+}
+
+fn signature_valid(event: &Event) -> bool {
+    let valid = crypto::verify_event_signature(event).unwrap_or(false);
+    if valid {
+        remember_verified(event);
+    }
+    valid
+}
+
+/// Check the signatures of (index, event) pairs, in parallel chunks on the blocking pool.
+async fn check_signatures(mut events: Vec<(usize, Event)>) -> Vec<(usize, bool)> {
+    if events.len() <= INLINE_BATCH_MAX {
+        return events.iter().map(|(i, e)| (*i, signature_valid(e))).collect();
+    }
+    let workers = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(2);
+    let chunk_size = events.len().div_ceil(workers).max(INLINE_BATCH_MAX);
+    let mut handles = Vec::new();
+    while !events.is_empty() {
+        let rest = events.split_off(chunk_size.min(events.len()));
+        let chunk = std::mem::replace(&mut events, rest);
+        handles.push(tokio::task::spawn_blocking(move || {
+            chunk.iter().map(|(i, e)| (*i, signature_valid(e))).collect::<Vec<_>>()
+        }));
+    }
+    let mut results = Vec::new();
+    for handle in handles {
+        match handle.await {
+            Ok(chunk_results) => results.extend(chunk_results),
+            Err(e) => {
+                warn_log!("[verify] signature worker failed: {}", e);
+            }
+        }
+    }
+    results
+}
+
+/// Drop EVENT messages whose id or signature is wrong from a batch read from `relay_url`,
+/// keeping the order of everything else.
+pub async fn verify_relay_messages(relay_url: &str, messages: Vec<RelayMessage>) -> Vec<RelayMessage> {
+    let mut valid = vec![true; messages.len()];
+    let mut to_check: Vec<(usize, Event)> = Vec::new();
+    for (i, msg) in messages.iter().enumerate() {
+        if let RelayMessage::Event { event, .. } = msg {
+            if !crypto::verify_event_id(event).unwrap_or(false) {
+                valid[i] = false;
+            } else if !is_verified(event) {
+                // Unverified until the signature check says otherwise
+                valid[i] = false;
+                to_check.push((i, event.clone()));
+            }
+        }
+    }
+    if !to_check.is_empty() {
+        for (i, ok) in check_signatures(to_check).await {
+            valid[i] = ok;
+        }
+    }
+
+    let mut forged = 0u64;
+    let mut kept = Vec::with_capacity(messages.len());
+    for (msg, ok) in messages.into_iter().zip(valid) {
+        if ok {
+            kept.push(msg);
+        } else if let RelayMessage::Event { event, .. } = msg {
+            warn_log!("[verify] dropping forged event {} (kind {}) from {}", event.id, event.kind, relay_url);
+            forged += 1;
+        }
+    }
+    if forged > 0 {
+        *forged_counts().lock().unwrap().entry(relay_url.to_string()).or_insert(0) += forged;
+    }
+    kept
+}
+
+/// Forged event counts per relay, as a JSON object {relay_url: count}.
+pub fn forged_counts_to_json() -> String {
+    let counts = forged_counts().lock().unwrap();
+    let mut entries: Vec<(&String, &u64)> = counts.iter().collect();
+    entries.sort();
+    let mut json = String::from("{");
+    for (i, (relay_url, count)) in entries.iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        json.push('"');
+        json.push_str(&escape_json_string(relay_url));
+        json.push_str("\":");
+        json.push_str(&count.to_string());
+    }
+    json.push('}');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signed_note(content: &str, secret: &str) -> Event {
+        let pubkey = crypto::get_public_key_from_secret(secret).unwrap();
+        let mut event = crypto::new_event(&pubkey, 1, Vec::new(), content);
+        crypto::sign_event(&mut event, secret).unwrap();
+        event
+    }
+
+    fn event_message(event: Event) -> RelayMessage {
+        RelayMessage::Event { subscription_id: String::from("s"), event }
+    }
+
+    fn verify(relay_url: &str, events: Vec<Event>) -> Vec<RelayMessage> {
+        let messages = events.into_iter().map(event_message).collect();
+        let rt = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        rt.block_on(verify_relay_messages(relay_url, messages))
+    }
+
+    fn forged_count(relay_url: &str) -> Option<u64> {
+        forged_counts().lock().unwrap().get(relay_url).copied()
+    }
+
+    const SECRET: &str = "0000000000000000000000000000000000000000000000000000000000000003";
+
+    #[test]
+    fn test_valid_event_is_kept_and_remembered() {
+        let good = signed_note("hello", SECRET);
+        let kept = verify("wss://honest.example", vec![good.clone()]);
+        assert_eq!(kept.len(), 1);
+        assert!(matches!(&kept[0], RelayMessage::Event { event, .. } if event.id == good.id));
+        assert!(is_verified(&good));
+        assert_eq!(forged_count("wss://honest.example"), None);
+    }
+
+    #[test]
+    fn test_changed_content_is_dropped_and_counted() {
+        let mut forged = signed_note("hello", SECRET);
+        forged.content = String::from("forged");
+        assert!(verify("wss://content.example", vec![forged]).is_empty());
+        assert_eq!(forged_count("wss://content.example"), Some(1));
+    }
+
+    #[test]
+    fn test_borrowed_signature_is_dropped_and_counted() {
+        let good = signed_note("hello", SECRET);
+        let mut forged = signed_note("other", SECRET);
+        forged.sig = good.sig.clone();
+        let kept = verify("wss://signature.example", vec![good.clone(), forged]);
+        assert_eq!(kept.len(), 1);
+        assert!(matches!(&kept[0], RelayMessage::Event { event, .. } if event.id == good.id));
+        assert_eq!(forged_count("wss://signature.example"), Some(1));
+    }
+
+    #[test]
+    fn test_forged_counts_to_json_escapes_urls() {
+        forged_counts().lock().unwrap().insert(String::from("wss://quote.example/\"q\""), 3);
+        assert!(forged_counts_to_json().contains("\"wss://quote.example/\\\"q\\\"\":3"));
+    }
+}
@@ -30,6 +30,7 @@ use bech32::{Bech32, Hrp};
 // Human-readable parts for Nostr keys
 const HRP_PUBLIC_KEY: &str = "npub";
 const HRP_SECRET_KEY: &str = "nsec";
+const HRP_NCRYPTSEC: &str = "ncryptsec";
 
 // ============================================================
 // Validation Functions
@@ -244,6 +245,39 @@ pub fn secret_key_to_hex(key: &str) -> Result<String, String> {
     }
 }
 
+// ============================================================
+// NIP-49 Encrypted Secret Keys (ncryptsec)
+// ============================================================
+
+pub fn is_ncryptsec(key: &str) -> bool {
+    key.starts_with("ncryptsec1")
+}
+
+/// Encrypt a hex secret key with a password and encode it as ncryptsec1...
+pub fn hex_to_ncryptsec(hex_key: &str, password: &str) -> Result<String, String> {
+    if !is_valid_hex_key(hex_key) {
+        return Err(String::from("Invalid hex key: must be 64 hex characters"));
+    }
+    let payload = crate::crypto::nip49_encrypt(hex_key, password, crate::crypto::NIP49_DEFAULT_LOG_N)?;
+    let hrp = Hrp::parse(HRP_NCRYPTSEC).map_err(|e| format!("Failed to create HRP: {}", e))?;
+    bech32::encode::<Bech32>(hrp, &payload).map_err(|e| format!("Bech32 encoding failed: {}", e))
+}
+
+/// Decode an ncryptsec1... string and decrypt it with the password to a hex secret key.
+pub fn ncryptsec_to_hex(ncryptsec: &str, password: &str) -> Result<String, String> {
+    if !is_ncryptsec(ncryptsec) {
+        return Err(String::from("Not an ncryptsec: must start with 'ncryptsec1'"));
+    }
+    let (hrp, bytes) = match bech32::decode(ncryptsec) {
+        Ok(result) => result,
+        Err(e) => return Err(format!("Invalid bech32: {}", e)),
+    };
+    if hrp.as_str() != HRP_NCRYPTSEC {
+        return Err(format!("Wrong prefix: expected '{}', got '{}'", HRP_NCRYPTSEC, hrp));
+    }
+    crate::crypto::nip49_decrypt(&bytes, password)
+}
+
 // ============================================================
 // Helper Functions
 // ============================================================
@@ -356,78 +390,96 @@ pub fn shorten_npub(npub: &str) -> String {
 }
 
 // ============================================================
-// NIP-19 TLV Decoding (nevent, nprofile, note)
+// NIP-19 Shareable Identifiers (note, nevent, nprofile, naddr, nrelay)
 // ============================================================
 
 // Human-readable parts for NIP-19 shareable identifiers
 const HRP_NOTE: &str = "note";
 const HRP_NEVENT: &str = "nevent";
 const HRP_NPROFILE: &str = "nprofile";
+const HRP_NADDR: &str = "naddr";
+const HRP_NRELAY: &str = "nrelay";
 
 // TLV type constants (NIP-19)
-const TLV_SPECIAL: u8 = 0;  // event id (nevent) or pubkey (nprofile)
+const TLV_SPECIAL: u8 = 0;  // event id (nevent), pubkey (nprofile), d-tag (naddr) or relay (nrelay)
 const TLV_RELAY: u8 = 1;    // relay URL (UTF-8)
-const TLV_AUTHOR: u8 = 2;   // author pubkey (32 bytes, nevent only)
+const TLV_AUTHOR: u8 = 2;   // author pubkey (32 bytes)
+const TLV_KIND: u8 = 3;     // event kind (32-bit big-endian)
+
+/// Relay hints put in identifiers we encode; more make links long without helping much.
+pub const MAX_RELAY_HINTS: usize = 3;
 
-/// Decoded nevent: event ID + optional relay hints + optional author
+/// Decoded nevent: event ID + optional relay hints, author and kind
+#[derive(Debug, PartialEq)]
 pub struct DecodedNevent {
     pub event_id: String,       // hex
     pub relays: Vec<String>,
     pub author: Option<String>, // hex
+    pub kind: Option<u32>,
 }
 
 /// Decoded nprofile: pubkey + optional relay hints
+#[derive(Debug, PartialEq)]
 pub struct DecodedNprofile {
     pub pubkey: String,         // hex
     pub relays: Vec<String>,
 }
 
+/// Decoded naddr: the coordinate of an addressable event (kind, author, d-tag) + relay hints
+#[derive(Debug, PartialEq)]
+pub struct DecodedNaddr {
+    pub identifier: String,     // d-tag value, may be empty
+    pub pubkey: String,         // hex
+    pub kind: u32,
+    pub relays: Vec<String>,
+}
+
+/// Encode a hex event ID as note1... (simple 32-byte encoding, no TLV)
+#[allow(dead_code)]
+pub fn hex_to_note(event_id: &str) -> Result<String, String> {
+    if !is_valid_hex_key(event_id) {
+        return Err(String::from("Invalid event ID: must be 64 hex characters"));
+    }
+    encode_bech32(HRP_NOTE, &hex_to_bytes(event_id)?)
+}
+
 /// Decode a note1... bech32 string to a hex event ID (simple 32-byte encoding, no TLV)
 pub fn note_to_hex(note: &str) -> Result<String, String> {
-    if !note.starts_with("note1") {
-        return Err(String::from("Not a note: must start with 'note1'"));
-    }
-    let (hrp, bytes) = match bech32::decode(note) {
-        Ok(result) => result,
-        Err(e) => return Err(format!("Invalid bech32: {}", e)),
-    };
-    if hrp.as_str() != HRP_NOTE {
-        return Err(format!("Wrong prefix: expected '{}', got '{}'", HRP_NOTE, hrp));
-    }
+    let bytes = decode_bech32(HRP_NOTE, note)?;
     if bytes.len() != 32 {
         return Err(format!("Invalid event ID length: expected 32 bytes, got {}", bytes.len()));
     }
     Ok(bytes_to_hex(&bytes))
 }
 
-/// Decode an nevent1... bech32 string using the NIP-19 TLV format
-pub fn decode_nevent(nevent: &str) -> Result<DecodedNevent, String> {
-    if !nevent.starts_with("nevent1") {
-        return Err(String::from("Not an nevent: must start with 'nevent1'"));
+/// Encode an event reference as nevent1..., with up to MAX_RELAY_HINTS relays
+pub fn encode_nevent(event_id: &str, relays: &[String], author: Option<&str>, kind: Option<u32>) -> Result<String, String> {
+    if !is_valid_hex_key(event_id) {
+        return Err(String::from("Invalid event ID: must be 64 hex characters"));
+    }
+    let mut tlv = Vec::new();
+    push_tlv(&mut tlv, TLV_SPECIAL, &hex_to_bytes(event_id)?)?;
+    push_relay_hints(&mut tlv, relays)?;
+    if let Some(author) = author {
+        if !is_valid_hex_key(author) {
+            return Err(String::from("Invalid author: must be 64 hex characters"));
+        }
+        push_tlv(&mut tlv, TLV_AUTHOR, &hex_to_bytes(author)?)?;
     }
-    let (hrp, bytes) = match bech32::decode(nevent) {
-        Ok(result) => result,
-        Err(e) => return Err(format!("Invalid bech32: {}", e)),
-    };
-    if hrp.as_str() != HRP_NEVENT {
-        return Err(format!("Wrong prefix: expected '{}', got '{}'", HRP_NEVENT, hrp));
+    if let Some(kind) = kind {
+        push_tlv(&mut tlv, TLV_KIND, &kind.to_be_bytes())?;
     }
+    encode_bech32(HRP_NEVENT, &tlv)
+}
+
+/// Decode an nevent1... bech32 string using the NIP-19 TLV format
+pub fn decode_nevent(nevent: &str) -> Result<DecodedNevent, String> {
+    let bytes = decode_bech32(HRP_NEVENT, nevent)?;
     let mut event_id: Option<String> = None;
     let mut relays: Vec<String> = Vec::new();
     let mut author: Option<String> = None;
-    let mut pos = 0;
-    while pos < bytes.len() {
-        if pos + 2 > bytes.len() {
-            return Err(String::from("Truncated TLV data"));
-        }
-        let tlv_type = bytes[pos];
-        let tlv_len = bytes[pos + 1] as usize;
-        pos += 2;
-        if pos + tlv_len > bytes.len() {
-            return Err(format!("TLV value overflows buffer: need {} bytes at offset {}, have {}", tlv_len, pos, bytes.len()));
-        }
-        let tlv_value = &bytes[pos..pos + tlv_len];
-        pos += tlv_len;
+    let mut kind: Option<u32> = None;
+    for (tlv_type, tlv_value) in parse_tlv(&bytes)? {
         match tlv_type {
             TLV_SPECIAL => {
                 if tlv_value.len() != 32 {
@@ -435,40 +487,140 @@ pub fn decode_nevent(nevent: &str) -> Result<DecodedNevent, String> {
                 }
                 event_id = Some(bytes_to_hex(tlv_value));
             }
-            TLV_RELAY => {
-                match std::str::from_utf8(tlv_value) {
-                    Ok(url) => relays.push(url.to_string()),
-                    Err(_) => {} // skip invalid UTF-8 relay hints
+            TLV_RELAY => push_relay_hint(&mut relays, tlv_value),
+            TLV_AUTHOR if tlv_value.len() == 32 => author = Some(bytes_to_hex(tlv_value)),
+            TLV_KIND => kind = Some(tlv_kind(tlv_value)?),
+            _ => {} // ignore unknown TLV types
+        }
+    }
+    match event_id {
+        Some(id) => Ok(DecodedNevent { event_id: id, relays, author, kind }),
+        None => Err(String::from("nevent missing required event ID (TLV type 0)")),
+    }
+}
+
+/// Encode a profile reference as nprofile1..., with up to MAX_RELAY_HINTS relays
+pub fn encode_nprofile(pubkey: &str, relays: &[String]) -> Result<String, String> {
+    if !is_valid_hex_key(pubkey) {
+        return Err(String::from("Invalid public key: must be 64 hex characters"));
+    }
+    let mut tlv = Vec::new();
+    push_tlv(&mut tlv, TLV_SPECIAL, &hex_to_bytes(pubkey)?)?;
+    push_relay_hints(&mut tlv, relays)?;
+    encode_bech32(HRP_NPROFILE, &tlv)
+}
+
+/// Decode an nprofile1... bech32 string using the NIP-19 TLV format
+pub fn decode_nprofile(nprofile: &str) -> Result<DecodedNprofile, String> {
+    let bytes = decode_bech32(HRP_NPROFILE, nprofile)?;
+    let mut pubkey: Option<String> = None;
+    let mut relays: Vec<String> = Vec::new();
+    for (tlv_type, tlv_value) in parse_tlv(&bytes)? {
+        match tlv_type {
+            TLV_SPECIAL => {
+                if tlv_value.len() != 32 {
+                    return Err(format!("Invalid pubkey length in TLV: expected 32, got {}", tlv_value.len()));
                 }
+                pubkey = Some(bytes_to_hex(tlv_value));
+            }
+            TLV_RELAY => push_relay_hint(&mut relays, tlv_value),
+            _ => {} // ignore unknown TLV types (nprofile doesn't use types 2 or 3)
+        }
+    }
+    match pubkey {
+        Some(pk) => Ok(DecodedNprofile { pubkey: pk, relays }),
+        None => Err(String::from("nprofile missing required pubkey (TLV type 0)")),
+    }
+}
+
+/// Encode the coordinate of an addressable event (articles, lists, ...) as naddr1...
+pub fn encode_naddr(identifier: &str, pubkey: &str, kind: u32, relays: &[String]) -> Result<String, String> {
+    if !is_valid_hex_key(pubkey) {
+        return Err(String::from("Invalid public key: must be 64 hex characters"));
+    }
+    let mut tlv = Vec::new();
+    push_tlv(&mut tlv, TLV_SPECIAL, identifier.as_bytes())?;
+    push_relay_hints(&mut tlv, relays)?;
+    push_tlv(&mut tlv, TLV_AUTHOR, &hex_to_bytes(pubkey)?)?;
+    push_tlv(&mut tlv, TLV_KIND, &kind.to_be_bytes())?;
+    encode_bech32(HRP_NADDR, &tlv)
+}
+
+/// Decode an naddr1... bech32 string; author and kind are required
+pub fn decode_naddr(naddr: &str) -> Result<DecodedNaddr, String> {
+    let bytes = decode_bech32(HRP_NADDR, naddr)?;
+    let mut identifier: Option<String> = None;
+    let mut pubkey: Option<String> = None;
+    let mut kind: Option<u32> = None;
+    let mut relays: Vec<String> = Vec::new();
+    for (tlv_type, tlv_value) in parse_tlv(&bytes)? {
+        match tlv_type {
+            TLV_SPECIAL => {
+                let d = std::str::from_utf8(tlv_value).map_err(|_| String::from("Invalid identifier in naddr: not UTF-8"))?;
+                identifier = Some(d.to_string());
             }
+            TLV_RELAY => push_relay_hint(&mut relays, tlv_value),
             TLV_AUTHOR => {
-                if tlv_value.len() == 32 {
-                    author = Some(bytes_to_hex(tlv_value));
+                if tlv_value.len() != 32 {
+                    return Err(format!("Invalid author length in TLV: expected 32, got {}", tlv_value.len()));
                 }
+                pubkey = Some(bytes_to_hex(tlv_value));
             }
+            TLV_KIND => kind = Some(tlv_kind(tlv_value)?),
             _ => {} // ignore unknown TLV types
         }
     }
-    match event_id {
-        Some(id) => Ok(DecodedNevent { event_id: id, relays, author }),
-        None => Err(String::from("nevent missing required event ID (TLV type 0)")),
+    let identifier = identifier.ok_or("naddr missing required identifier (TLV type 0)")?;
+    let pubkey = pubkey.ok_or("naddr missing required author (TLV type 2)")?;
+    let kind = kind.ok_or("naddr missing required kind (TLV type 3)")?;
+    Ok(DecodedNaddr { identifier, pubkey, kind, relays })
+}
+
+/// Encode a relay URL as nrelay1... (deprecated in NIP-19, kept for old links)
+#[allow(dead_code)]
+pub fn encode_nrelay(relay_url: &str) -> Result<String, String> {
+    let mut tlv = Vec::new();
+    push_tlv(&mut tlv, TLV_SPECIAL, relay_url.as_bytes())?;
+    encode_bech32(HRP_NRELAY, &tlv)
+}
+
+/// Decode an nrelay1... bech32 string to its relay URL
+pub fn decode_nrelay(nrelay: &str) -> Result<String, String> {
+    let bytes = decode_bech32(HRP_NRELAY, nrelay)?;
+    for (tlv_type, tlv_value) in parse_tlv(&bytes)? {
+        if tlv_type == TLV_SPECIAL {
+            return std::str::from_utf8(tlv_value)
+                .map(str::to_string)
+                .map_err(|_| String::from("Invalid relay URL in nrelay: not UTF-8"));
+        }
     }
+    Err(String::from("nrelay missing required relay URL (TLV type 0)"))
 }
 
-/// Decode an nprofile1... bech32 string using the NIP-19 TLV format
-pub fn decode_nprofile(nprofile: &str) -> Result<DecodedNprofile, String> {
-    if !nprofile.starts_with("nprofile1") {
-        return Err(String::from("Not an nprofile: must start with 'nprofile1'"));
+// Bech32-encode `data` with the given human-readable part
+fn encode_bech32(hrp: &str, data: &[u8]) -> Result<String, String> {
+    let hrp = Hrp::parse(hrp).map_err(|e| format!("Failed to create HRP: {}", e))?;
+    bech32::encode::<Bech32>(hrp, data).map_err(|e| format!("Bech32 encoding failed: {}", e))
+}
+
+// Bech32-decode `value`, checking that its human-readable part is `hrp`
+fn decode_bech32(hrp: &str, value: &str) -> Result<Vec<u8>, String> {
+    if !value.starts_with(&format!("{}1", hrp)) {
+        return Err(format!("Not a NIP-19 {}: must start with '{}1'", hrp, hrp));
     }
-    let (hrp, bytes) = match bech32::decode(nprofile) {
+    let (decoded_hrp, bytes) = match bech32::decode(value) {
         Ok(result) => result,
         Err(e) => return Err(format!("Invalid bech32: {}", e)),
     };
-    if hrp.as_str() != HRP_NPROFILE {
-        return Err(format!("Wrong prefix: expected '{}', got '{}'", HRP_NPROFILE, hrp));
+    if decoded_hrp.as_str() != hrp {
+        return Err(format!("Wrong prefix: expected '{}', got '{}'", hrp, decoded_hrp));
     }
-    let mut pubkey: Option<String> = None;
-    let mut relays: Vec<String> = Vec::new();
+    Ok(bytes)
+}
+
+// Split NIP-19 TLV data into (type, value) entries
+fn parse_tlv(bytes: &[u8]) -> Result<Vec<(u8, &[u8])>, String> {
+    let mut entries = Vec::new();
     let mut pos = 0;
     while pos < bytes.len() {
         if pos + 2 > bytes.len() {
@@ -480,27 +632,149 @@ pub fn decode_nprofile(nprofile: &str) -> Result<DecodedNprofile, String> {
         if pos + tlv_len > bytes.len() {
             return Err(format!("TLV value overflows buffer: need {} bytes at offset {}, have {}", tlv_len, pos, bytes.len()));
         }
-        let tlv_value = &bytes[pos..pos + tlv_len];
+        entries.push((tlv_type, &bytes[pos..pos + tlv_len]));
         pos += tlv_len;
-        match tlv_type {
-            TLV_SPECIAL => {
-                if tlv_value.len() != 32 {
-                    return Err(format!("Invalid pubkey length in TLV: expected 32, got {}", tlv_value.len()));
-                }
-                pubkey = Some(bytes_to_hex(tlv_value));
-            }
-            TLV_RELAY => {
-                match std::str::from_utf8(tlv_value) {
-                    Ok(url) => relays.push(url.to_string()),
-                    Err(_) => {} // skip invalid UTF-8 relay hints
-                }
+    }
+    Ok(entries)
+}
+
+fn push_tlv(tlv: &mut Vec<u8>, tlv_type: u8, value: &[u8]) -> Result<(), String> {
+    let len = u8::try_from(value.len()).map_err(|_| format!("TLV value too long: {} bytes (max 255)", value.len()))?;
+    tlv.push(tlv_type);
+    tlv.push(len);
+    tlv.extend_from_slice(value);
+    Ok(())
+}
+
+fn push_relay_hints(tlv: &mut Vec<u8>, relays: &[String]) -> Result<(), String> {
+    for relay in relays.iter().filter(|r| !r.is_empty()).take(MAX_RELAY_HINTS) {
+        push_tlv(tlv, TLV_RELAY, relay.as_bytes())?;
+    }
+    Ok(())
+}
+
+fn push_relay_hint(relays: &mut Vec<String>, value: &[u8]) {
+    // skip invalid UTF-8 relay hints
+    if let Ok(url) = std::str::from_utf8(value) {
+        relays.push(url.to_string());
+    }
+}
+
+fn tlv_kind(value: &[u8]) -> Result<u32, String> {
+    let bytes: [u8; 4] = value
+        .try_into()
+        .map_err(|_| format!("Invalid kind length in TLV: expected 4, got {}", value.len()))?;
+    Ok(u32::from_be_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test vector from NIP-49 (log_n = 16)
+    #[test]
+    fn test_ncryptsec_vector() {
+        let ncryptsec = "ncryptsec1qgg9947rlpvqu76pj5ecreduf9jxhselq2nae2kghhvd5g7dgjtcxfqtd67p9m0w57lspw8gsq6yphnm8623nsl8xn9j4jdzz84zm3frztj3z7s35vpzmqf6ksu8r89qk5z2zxfmu5gv8th8wclt0h4p";
+        assert_eq!(
+            ncryptsec_to_hex(ncryptsec, "nostr").unwrap(),
+            "3501454135014541350145413501453fefb02227e449e57cf4d3a3ce05378683"
+        );
+        assert!(ncryptsec_to_hex(ncryptsec, "nostr2").is_err());
+    }
+
+    // Vectors from NIP-19
+    const PUBKEY: &str = "3bf0c63fcb93463407af97a5e5ee64fa883d107ef9e558472c4eb9aaaefa459d";
+
+    /// A spread of (seed, id, kind, identifier, relays) for round-trip properties.
+    fn samples() -> Vec<(u8, String, u32, String, Vec<String>)> {
+        let relay_sets: Vec<Vec<String>> = vec![
+            vec![],
+            vec![String::from("wss://relay.example")],
+            (0..5).map(|i| format!("wss://relay{}.example/path?x=\"é\"", i)).collect(),
+        ];
+        let mut samples = Vec::new();
+        for seed in 0u8..16 {
+            let id: String = (0..32u8).map(|i| format!("{:02x}", seed.wrapping_mul(37).wrapping_add(i.wrapping_mul(11)))).collect();
+            let kind = (seed as u32) * 4099;
+            let identifier = "d-tag ✓ ".repeat(seed as usize);
+            for relays in &relay_sets {
+                samples.push((seed, id.clone(), kind, identifier.clone(), relays.clone()));
             }
-            _ => {} // ignore unknown TLV types (nprofile doesn't use type 2)
         }
+        samples
     }
-    match pubkey {
-        Some(pk) => Ok(DecodedNprofile { pubkey: pk, relays }),
-        None => Err(String::from("nprofile missing required pubkey (TLV type 0)")),
+
+    fn hints(relays: &[String]) -> Vec<String> {
+        relays.iter().take(MAX_RELAY_HINTS).cloned().collect()
+    }
+
+    #[test]
+    fn test_nprofile_vector() {
+        let nprofile = "nprofile1qqsrhuxx8l9ex335q7he0f09aej04zpazpl0ne2cgukyawd24mayt8gpp4mhxue69uhhytnc9e3k7mgpz4mhxue69uhkg6nzv9ejuumpv34kytnrdaksjlyr9p";
+        let decoded = decode_nprofile(nprofile).unwrap();
+        assert_eq!(decoded.pubkey, PUBKEY);
+        assert_eq!(decoded.relays, vec!["wss://r.x.com", "wss://djbas.sadkb.com"]);
+        assert_eq!(encode_nprofile(&decoded.pubkey, &decoded.relays).unwrap(), nprofile);
+    }
+
+    #[test]
+    fn test_npub_vector() {
+        assert_eq!(hex_to_npub(PUBKEY).unwrap(), "npub180cvv07tjdrrgpa0j7j7tmnyl2yr6yr7l8j4s3evf6u64th6gkwsyjh6w6");
+    }
+
+    #[test]
+    fn test_note_round_trip() {
+        for (_, id, _, _, _) in samples() {
+            assert_eq!(note_to_hex(&hex_to_note(&id).unwrap()).unwrap(), id);
+        }
+    }
+
+    #[test]
+    fn test_nevent_round_trip() {
+        for (seed, id, kind, _, relays) in samples() {
+            let author = if seed % 2 == 0 { Some(PUBKEY) } else { None };
+            let kind = if seed % 3 == 0 { None } else { Some(kind) };
+            let nevent = encode_nevent(&id, &relays, author, kind).unwrap();
+            assert_eq!(
+                decode_nevent(&nevent).unwrap(),
+                DecodedNevent { event_id: id.clone(), relays: hints(&relays), author: author.map(String::from), kind }
+            );
+        }
+    }
+
+    #[test]
+    fn test_nprofile_round_trip() {
+        for (_, id, _, _, relays) in samples() {
+            let nprofile = encode_nprofile(&id, &relays).unwrap();
+            assert_eq!(decode_nprofile(&nprofile).unwrap(), DecodedNprofile { pubkey: id.clone(), relays: hints(&relays) });
+        }
     }
-}
 
+    #[test]
+    fn test_naddr_round_trip() {
+        for (_, id, kind, identifier, relays) in samples() {
+            let naddr = encode_naddr(&identifier, &id, kind, &relays).unwrap();
+            assert_eq!(
+                decode_naddr(&naddr).unwrap(),
+                DecodedNaddr { identifier: identifier.clone(), pubkey: id.clone(), kind, relays: hints(&relays) }
+            );
+        }
+    }
+
+    #[test]
+    fn test_nrelay_round_trip() {
+        assert_eq!(decode_nrelay(&encode_nrelay("wss://relay.example").unwrap()).unwrap(), "wss://relay.example");
+    }
+
+    #[test]
+    fn test_identifier_of_other_type_rejected() {
+        let nevent = encode_nevent(PUBKEY, &[], None, None).unwrap();
+        assert!(decode_nprofile(&nevent).is_err());
+        assert!(decode_naddr(&nevent).is_err());
+    }
+
+    #[test]
+    fn test_naddr_identifier_too_long() {
+        assert!(encode_naddr(&"x".repeat(256), PUBKEY, 30023, &[]).is_err());
+    }
+}
@@ -22,14 +22,26 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 // Import our modules
+mod bunker;
 mod config;
 mod crypto;
 mod debug;
+mod dm_search;
+mod dm_sync;
+mod event_store;
+mod event_verifier;
 mod json;
 mod keys;
 mod messages_store;
+mod nip05;
+mod nip06;
+mod nip46;
 mod nostr;
+mod pow;
 mod relay;
+mod relay_info;
+mod relay_pool;
+mod signer;
 mod websocket;
 
 // Import what we need from external crates
@@ -37,7 +49,7 @@ use tauri::{Emitter, Manager};
 use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
 use qrcode::{QrCode, render::svg};
 
-use std::sync::RwLock;
+use std::sync::{Arc, Mutex, RwLock};
 
 use bytes::BytesMut;
 use crate::json::{JsonContentHandler, JsonNumber, JsonParser};
@@ -53,10 +65,31 @@ impl AppState {
         self.active_config_dir.read().unwrap().clone()
     }
     fn set_config_dir(&self, dir: String) {
+        install_auth_signer(dir.clone());
+        event_store::open_store(&dir);
         *self.active_config_dir.write().unwrap() = dir;
     }
 }
 
+/// Let the relay pool answer NIP-42 AUTH challenges with the key of the profile in `config_dir`.
+/// The config is read at challenge time, so key and per-relay setting changes apply at once.
+fn install_auth_signer(config_dir: String) {
+    relay_pool::relay_pool().set_auth_signer(Some(std::sync::Arc::new(move |relay_url: &str, challenge: &str| {
+        let cfg = config::load_config(&config_dir).ok()?;
+        if cfg.auth_denied_relays.iter().any(|r| r == relay_url) {
+            return None;
+        }
+        let secret_key = cfg.private_key.as_deref()?;
+        match crypto::create_signed_auth_event(relay_url, challenge, secret_key) {
+            Ok(event) => Some(event),
+            Err(e) => {
+                warn_log!("AUTH event for {}: {}", relay_url, e);
+                None
+            }
+        }
+    })));
+}
+
 // ============================================================
 // Configuration Commands
 // ============================================================
@@ -87,8 +120,14 @@ fn save_config(state: tauri::State<AppState>, config_json: String) -> Result<(),
         Ok(c) => c,
         Err(e) => return Err(format!("Invalid config JSON: {}", e)),
     };
+    // Keys are only changed through login_with_keys / set_private_key, never from the frontend's copy
+    let existing = config::load_config(&config_dir).ok();
+    cfg.private_key = existing.as_ref().and_then(|c| c.private_key.clone());
+    cfg.encrypted_private_key = existing.as_ref().and_then(|c| c.encrypted_private_key.clone());
+    cfg.remote_signer = existing.as_ref().and_then(|c| c.remote_signer.clone());
+    cfg.remote_signer_client_key = existing.as_ref().and_then(|c| c.remote_signer_client_key.clone());
     // Preserve existing profile fields if the incoming config doesn't set them
-    if let Ok(existing) = config::load_config(&config_dir) {
+    if let Some(existing) = existing {
         if cfg.name == "Anonymous" && existing.name != "Anonymous" {
             cfg.name = existing.name.clone();
         }
@@ -142,8 +181,18 @@ fn convert_hex_to_nsec(hex_key: String) -> Result<String, String> {
 }
 
 #[tauri::command]
-fn parse_key(key: String) -> Result<String, String> {
+async fn parse_key(key: String) -> Result<String, String> {
     let trimmed = key.trim();
+
+    // NIP-05 name@domain: resolve to the public key and relay hints the domain lists
+    if nip05::is_identifier(trimmed) {
+        let record = nip05::resolve(trimmed).await?;
+        let npub = keys::hex_to_npub(&record.pubkey)?;
+        return Ok(format!(
+            "{{\"type\":\"nip05\",\"hex\":\"{}\",\"npub\":\"{}\",\"nip05\":\"{}\",\"relays\":{}}}",
+            record.pubkey, npub, trimmed.to_lowercase(), nip05::relays_to_json(&record.relays)
+        ));
+    }
     
     if keys::is_npub(trimmed) {
         match keys::npub_to_hex(trimmed) {
@@ -179,10 +228,18 @@ fn parse_key(key: String) -> Result<String, String> {
         ));
     }
     
-    return Err(String::from("Invalid key format. Expected npub1..., nsec1..., or 64-char hex"));
+    return Err(String::from("Invalid key format. Expected npub1..., nsec1..., 64-char hex, or name@domain"));
 }
 
-// Decode a NIP-19 bech32 entity (nevent, nprofile, note, npub).
+/// Check a profile's NIP-05 identifier against its public key (hex). Returns
+/// {"identifier","status":"verified"|"mismatch"|"unreachable","pubkey","relays"}; lookups are cached.
+#[tauri::command]
+async fn verify_nip05(identifier: String, pubkey: String) -> Result<String, String> {
+    let (status, record) = nip05::verify(&identifier, &pubkey).await?;
+    Ok(nip05::status_to_json(identifier.trim(), status, record.as_ref()))
+}
+
+// Decode a NIP-19 bech32 entity (nevent, nprofile, naddr, note, npub, nrelay).
 // Accepts the raw bech32 string (without the "nostr:" prefix).
 #[tauri::command(rename_all = "snake_case")]
 fn decode_nostr_uri(bech32_str: String) -> Result<String, String> {
@@ -192,29 +249,18 @@ fn decode_nostr_uri(bech32_str: String) -> Result<String, String> {
         let decoded = keys::decode_nevent(trimmed)?;
         let mut json = String::from("{\"type\":\"nevent\",\"event_id\":\"");
         json.push_str(&decoded.event_id);
-        json.push_str("\",\"relays\":[");
-        for (i, r) in decoded.relays.iter().enumerate() {
-            if i > 0 {
-                json.push(',');
-            }
-            json.push('"');
-            // Escape any quotes in relay URL
-            for ch in r.chars() {
-                if ch == '"' {
-                    json.push_str("\\\"");
-                } else if ch == '\\' {
-                    json.push_str("\\\\");
-                } else {
-                    json.push(ch);
-                }
-            }
-            json.push('"');
-        }
-        json.push_str("],\"author\":");
+        json.push_str("\",\"relays\":");
+        json.push_str(&relays_to_json(&decoded.relays));
+        json.push_str(",\"author\":");
         match &decoded.author {
             Some(a) => { json.push('"'); json.push_str(a); json.push('"'); }
             None => json.push_str("null"),
         }
+        json.push_str(",\"kind\":");
+        match decoded.kind {
+            Some(k) => json.push_str(&k.to_string()),
+            None => json.push_str("null"),
+        }
         json.push('}');
         return Ok(json);
     }
@@ -223,24 +269,23 @@ fn decode_nostr_uri(bech32_str: String) -> Result<String, String> {
         let decoded = keys::decode_nprofile(trimmed)?;
         let mut json = String::from("{\"type\":\"nprofile\",\"pubkey\":\"");
         json.push_str(&decoded.pubkey);
-        json.push_str("\",\"relays\":[");
-        for (i, r) in decoded.relays.iter().enumerate() {
-            if i > 0 {
-                json.push(',');
-            }
-            json.push('"');
-            for ch in r.chars() {
-                if ch == '"' {
-                    json.push_str("\\\"");
-                } else if ch == '\\' {
-                    json.push_str("\\\\");
-                } else {
-                    json.push(ch);
-                }
-            }
-            json.push('"');
-        }
-        json.push_str("]}");
+        json.push_str("\",\"relays\":");
+        json.push_str(&relays_to_json(&decoded.relays));
+        json.push('}');
+        return Ok(json);
+    }
+
+    if trimmed.starts_with("naddr1") {
+        let decoded = keys::decode_naddr(trimmed)?;
+        let mut json = String::from("{\"type\":\"naddr\",\"kind\":");
+        json.push_str(&decoded.kind.to_string());
+        json.push_str(",\"pubkey\":\"");
+        json.push_str(&decoded.pubkey);
+        json.push_str("\",\"identifier\":\"");
+        json.push_str(&config::escape_json_string(&decoded.identifier));
+        json.push_str("\",\"relays\":");
+        json.push_str(&relays_to_json(&decoded.relays));
+        json.push('}');
         return Ok(json);
     }
 
@@ -254,7 +299,44 @@ fn decode_nostr_uri(bech32_str: String) -> Result<String, String> {
         return Ok(format!("{{\"type\":\"npub\",\"pubkey\":\"{}\"}}", pubkey));
     }
 
-    Err(String::from("Unsupported NIP-19 entity. Expected nevent1..., nprofile1..., note1..., or npub1..."))
+    if trimmed.starts_with("nrelay1") {
+        let relay = keys::decode_nrelay(trimmed)?;
+        return Ok(format!("{{\"type\":\"nrelay\",\"relays\":{}}}", relays_to_json(&[relay])));
+    }
+
+    Err(String::from("Unsupported NIP-19 entity. Expected nevent1..., nprofile1..., naddr1..., note1..., npub1... or nrelay1..."))
+}
+
+// JSON array of relay URLs
+fn relays_to_json(relays: &[String]) -> String {
+    let items: Vec<String> = relays.iter().map(|r| format!("\"{}\"", config::escape_json_string(r))).collect();
+    format!("[{}]", items.join(","))
+}
+
+/// nostr:nevent link for sharing a note, with our relays as hints.
+#[tauri::command(rename_all = "snake_case")]
+fn share_note(state: tauri::State<AppState>, event_id: String, author: Option<String>, kind: Option<u32>) -> Result<String, String> {
+    let cfg = config::load_config(&state.config_dir())?;
+    let nevent = keys::encode_nevent(&event_id.to_lowercase(), &cfg.relays, author.as_deref(), kind)?;
+    Ok(format!("nostr:{}", nevent))
+}
+
+/// nostr:nprofile link for sharing a profile, with our relays as hints.
+#[tauri::command]
+fn share_profile(state: tauri::State<AppState>, pubkey: String) -> Result<String, String> {
+    let cfg = config::load_config(&state.config_dir())?;
+    let hex = keys::public_key_to_hex(&pubkey).map_err(|e| format!("Invalid public key: {}", e))?;
+    let nprofile = keys::encode_nprofile(&hex, &cfg.relays)?;
+    Ok(format!("nostr:{}", nprofile))
+}
+
+/// nostr:naddr link for sharing an addressable event (article, list, ...), with our relays as hints.
+#[tauri::command(rename_all = "snake_case")]
+fn share_address(state: tauri::State<AppState>, kind: u32, pubkey: String, identifier: String) -> Result<String, String> {
+    let cfg = config::load_config(&state.config_dir())?;
+    let hex = keys::public_key_to_hex(&pubkey).map_err(|e| format!("Invalid public key: {}", e))?;
+    let naddr = keys::encode_naddr(&identifier, &hex, kind, &cfg.relays)?;
+    Ok(format!("nostr:{}", naddr))
 }
 
 // ============================================================
@@ -312,12 +394,15 @@ async fn fetch_notes_from_relays(
         }
     }
 
-    if fail_count == relay_count {
+    // Anything we already have locally fills gaps, and keeps the feed usable offline
+    let cached = event_store::query(&filter);
+    if fail_count == relay_count && cached.is_empty() {
         return Err(format!(
             "Could not reach any of the {} configured relays. Check your connection and relay settings.",
             relay_count
         ));
     }
+    all_events.extend(cached);
 
     all_events.sort_by(|a, b| b.created_at.cmp(&a.created_at));
 
@@ -346,6 +431,7 @@ fn start_feed_stream(
     authors: Option<Vec<String>>,
     since: Option<u64>,
     stream_context: Option<String>,
+    min_pow: Option<u32>,
 ) -> Result<(), String> {
     if relay_urls.is_empty() {
         return Err(String::from("No relays provided. Configure relays in Settings."));
@@ -373,6 +459,8 @@ fn start_feed_stream(
         ("feed-note".to_string(), "feed-eose".to_string())
     };
 
+    // Spam filter: drop notes with less NIP-13 proof of work than asked for
+    let min_pow = min_pow.unwrap_or(0);
     let num_relays = relay_urls.len() as u32;
     std::thread::spawn(move || {
         let rt = match tokio::runtime::Runtime::new() {
@@ -383,8 +471,17 @@ fn start_feed_stream(
             }
         };
         rt.block_on(async move {
+            // Render from the local store straight away; relays top the feed up below
+            for event in event_store::query(&filter) {
+                if pow::difficulty(&event) < min_pow {
+                    continue;
+                }
+                let json = nostr::event_to_json(&event);
+                let _ = app.emit(&note_event, &json);
+            }
+
             let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
-            for relay_url in relay_urls {
+            for relay_url in relay_urls.clone() {
                 let filter = filter.clone();
                 let tx = tx.clone();
                 tokio::spawn(async move {
@@ -393,12 +490,17 @@ fn start_feed_stream(
             }
             drop(tx);
 
+            let mut shown: Vec<nostr::Event> = Vec::new();
             let mut eose_count = 0u32;
             while let Some(msg) = rx.recv().await {
                 match msg {
                     relay::StreamMessage::Event(event) => {
+                        if pow::difficulty(&event) < min_pow {
+                            continue;
+                        }
                         let json = nostr::event_to_json(&event);
                         let _ = app.emit(&note_event, &json);
+                        shown.push(event);
                     }
                     relay::StreamMessage::Eose => {
                         eose_count += 1;
@@ -410,14 +512,57 @@ fn start_feed_stream(
                     relay::StreamMessage::Notice(msg) => {
                         debug_log!("Relay notice: {}", msg);
                     }
+                    relay::StreamMessage::Closed { relay_url, prefix, message } => {
+                        // Let the UI show why a relay refused the subscription
+                        let reason = prefix.map(|p| p.as_str());
+                        let _ = app.emit("relay-closed", (relay_url, reason, message));
+                    }
                 }
             }
+
+            // Take down notes whose authors have asked for them to be deleted
+            let deleted = fetch_deleted_event_ids(&relay_urls, &shown).await;
+            if !deleted.is_empty() {
+                let _ = app.emit("notes-deleted", deleted);
+            }
         });
     });
 
     Ok(())
 }
 
+/// NIP-09: ids of `events` that their authors have asked to delete, according to the relays.
+/// The deletion requests found are stored, so the event store drops those events too.
+async fn fetch_deleted_event_ids(relay_urls: &[String], events: &[nostr::Event]) -> Vec<String> {
+    if events.is_empty() {
+        return Vec::new();
+    }
+    let ids: Vec<String> = events.iter().map(|e| e.id.clone()).collect();
+    let mut authors: Vec<String> = events.iter().map(|e| e.pubkey.clone()).collect();
+    authors.sort();
+    authors.dedup();
+    let filter = nostr::filter_deletions_for_events(ids, authors);
+    let mut handles = Vec::new();
+    for relay_url in relay_urls {
+        let relay_url = relay_url.clone();
+        let filter = filter.clone();
+        handles.push(tokio::spawn(async move {
+            relay::fetch_notes_from_relay(&relay_url, &filter, 10).await.unwrap_or_default()
+        }));
+    }
+    let mut deletions: Vec<nostr::Event> = Vec::new();
+    for handle in handles {
+        if let Ok(events) = handle.await {
+            deletions.extend(events);
+        }
+    }
+    events
+        .iter()
+        .filter(|event| deletions.iter().any(|deletion| nostr::is_deleted_by(event, deletion)))
+        .map(|event| event.id.clone())
+        .collect()
+}
+
 #[tauri::command(rename_all = "snake_case")]
 async fn fetch_replies_to_event(relay_urls: Vec<String>, event_id: String, limit: u32) -> Result<String, String> {
     if event_id.is_empty() {
@@ -427,7 +572,7 @@ async fn fetch_replies_to_event(relay_urls: Vec<String>, event_id: String, limit
         return Err(String::from("No relays configured. Add relays in Settings."));
     }
     let filter = nostr::filter_replies_to_event(event_id, limit);
-    let mut all_events: Vec<nostr::Event> = Vec::new();
+    let mut all_events: Vec<nostr::Event> = event_store::query(&filter);
     for relay_url in &relay_urls {
         match relay::fetch_notes_from_relay(relay_url, &filter, 10).await {
             Ok(events) => {
@@ -459,8 +604,14 @@ async fn fetch_events_by_ids(relay_urls: Vec<String>, ids: Vec<String>) -> Resul
     if relay_urls.is_empty() {
         return Err(String::from("No relays configured. Add relays in Settings."));
     }
-    let filter = nostr::filter_events_by_ids(ids);
-    let mut all_events: Vec<nostr::Event> = Vec::new();
+    // Only ask relays for the events we do not already have
+    let mut all_events: Vec<nostr::Event> = event_store::query(&nostr::filter_events_by_ids(ids.clone()));
+    let missing: Vec<String> = ids.into_iter().filter(|id| !event_store::has_event(id)).collect();
+    if missing.is_empty() {
+        all_events.sort_by_key(|e| std::cmp::Reverse(e.created_at));
+        return Ok(events_to_json_array(&all_events));
+    }
+    let filter = nostr::filter_events_by_ids(missing);
     for relay_url in relay_urls {
         match relay::fetch_notes_from_relay(&relay_url, &filter, 10).await {
             Ok(events) => {
@@ -484,6 +635,30 @@ async fn fetch_events_by_ids(relay_urls: Vec<String>, ids: Vec<String>) -> Resul
     Ok(events_to_json_array(&unique))
 }
 
+/// Latest version of the addressable event at `kind:pubkey:identifier` (an naddr), as a JSON
+/// array of zero or one events. Relays are asked for the author's events of that kind and the
+/// d-tag is matched here.
+#[tauri::command(rename_all = "snake_case")]
+async fn fetch_addressable_event(relay_urls: Vec<String>, kind: u32, pubkey: String, identifier: String) -> Result<String, String> {
+    let pubkey = keys::public_key_to_hex(&pubkey).map_err(|e| format!("Invalid public key: {}", e))?;
+    let mut filter = nostr::Filter::new();
+    filter.authors = Some(vec![pubkey]);
+    filter.kinds = Some(vec![kind]);
+    filter.limit = Some(100);
+    let mut candidates = event_store::query(&filter);
+    for relay_url in &relay_urls {
+        match relay::fetch_notes_from_relay(relay_url, &filter, 10).await {
+            Ok(events) => candidates.extend(events),
+            Err(e) => debug_log!("Error fetching addressable event from {}: {}", relay_url, e),
+        }
+    }
+    let latest = candidates
+        .into_iter()
+        .filter(|e| nostr::d_tag(e).unwrap_or("") == identifier)
+        .max_by_key(|e| e.created_at);
+    Ok(events_to_json_array(&latest.into_iter().collect()))
+}
+
 #[tauri::command(rename_all = "snake_case")]
 fn generate_qr_svg(data: String) -> Result<String, String> {
     let code = QrCode::new(data.as_bytes()).map_err(|e| e.to_string())?;
@@ -491,6 +666,13 @@ fn generate_qr_svg(data: String) -> Result<String, String> {
     Ok(svg_xml)
 }
 
+/// NIP-11 information document for a relay, as JSON (cached per relay).
+#[tauri::command]
+async fn fetch_relay_info(relay_url: String) -> Result<String, String> {
+    let info = relay_info::fetch_relay_info(&relay_url).await?;
+    Ok(relay_info::relay_info_to_json(&relay_url, &info))
+}
+
 #[tauri::command]
 async fn test_relay_connection(relay_url: String) -> Result<String, String> {
     debug_log!("Testing connection to: {}", relay_url);
@@ -596,6 +778,12 @@ fn verify_event_signature(event_json: String) -> Result<bool, String> {
     return crypto::verify_event_signature(&event);
 }
 
+/// Number of forged events (bad id or signature) each relay has sent, as JSON {relay_url: count}.
+#[tauri::command]
+fn get_forged_event_counts() -> String {
+    event_verifier::forged_counts_to_json()
+}
+
 #[tauri::command]
 fn compute_event_id(event_json: String) -> Result<String, String> {
     let event = match nostr::parse_event(&event_json) {
@@ -652,6 +840,7 @@ async fn fetch_own_following(state: tauri::State<'_, AppState>) -> Result<String
 
 #[tauri::command]
 async fn update_contact_list(
+    app: tauri::AppHandle,
     state: tauri::State<'_, AppState>,
     add: bool,
     target_pubkey: String,
@@ -661,10 +850,8 @@ async fn update_contact_list(
         Ok(c) => c,
         Err(e) => return Err(format!("Failed to load config: {}", e)),
     };
-    let secret_key = match &cfg.private_key {
-        Some(k) => k.clone(),
-        None => return Err(String::from("No private key configured. Add your nsec in Settings to follow users.")),
-    };
+    let signer = signer::signer_for(&cfg)
+        .map_err(|_| String::from("No private key configured. Add your nsec in Settings to follow users."))?;
     if cfg.public_key.is_empty() {
         return Err(String::from("No public key configured"));
     }
@@ -682,8 +869,7 @@ async fn update_contact_list(
     } else {
         pubkeys.retain(|p| p != &target_hex);
     }
-    let event = crypto::create_signed_contact_list(&pubkeys, &secret_key)?;
-    let results = relay::publish_event_to_relays(&cfg.relays, &event, 10).await;
+    let results = sign_and_publish(&app, &cfg, &signer, crypto::create_contact_list(signer.public_key(), &pubkeys)).await?;
     let success_count = results.iter().filter(|r| r.success).count();
     if success_count == 0 {
         return Err(String::from("Failed to publish contact list to any relay"));
@@ -694,20 +880,17 @@ async fn update_contact_list(
     if let Err(e) = config::save_config(&config_dir, &cfg) {
         warn_log!("Warning: published contact list but failed to save locally: {}", e);
     }
-    Ok(relay::publish_results_to_json(&results))
+    Ok(relay::publish_results_to_json(&results, cfg.relays.len()))
 }
 
 #[tauri::command(rename_all = "snake_case")]
-async fn set_contact_list(state: tauri::State<'_, AppState>, pubkeys: Vec<String>) -> Result<String, String> {
+async fn set_contact_list(app: tauri::AppHandle, state: tauri::State<'_, AppState>, pubkeys: Vec<String>) -> Result<String, String> {
     let config_dir = state.config_dir();
     let mut cfg = match config::load_config(&config_dir) {
         Ok(c) => c,
         Err(e) => return Err(format!("Failed to load config: {}", e)),
     };
-    let secret_key = match &cfg.private_key {
-        Some(k) => k.clone(),
-        None => return Err(String::from("No private key configured.")),
-    };
+    let signer = signer::signer_for(&cfg)?;
     if cfg.public_key.is_empty() {
         return Err(String::from("No public key configured"));
     }
@@ -716,8 +899,7 @@ async fn set_contact_list(state: tauri::State<'_, AppState>, pubkeys: Vec<String
         let hex = keys::public_key_to_hex(p).map_err(|e| format!("Invalid pubkey {}: {}", p, e))?;
         hex_pubkeys.push(hex);
     }
-    let event = crypto::create_signed_contact_list(&hex_pubkeys, &secret_key)?;
-    let results = relay::publish_event_to_relays(&cfg.relays, &event, 10).await;
+    let results = sign_and_publish(&app, &cfg, &signer, crypto::create_contact_list(signer.public_key(), &hex_pubkeys)).await?;
     let success_count = results.iter().filter(|r| r.success).count();
     if success_count == 0 {
         return Err(String::from("Failed to publish contact list to any relay"));
@@ -727,7 +909,7 @@ async fn set_contact_list(state: tauri::State<'_, AppState>, pubkeys: Vec<String
     if let Err(e) = config::save_config(&config_dir, &cfg) {
         warn_log!("Warning: published contact list but failed to save locally: {}", e);
     }
-    Ok(relay::publish_results_to_json(&results))
+    Ok(relay::publish_results_to_json(&results, cfg.relays.len()))
 }
 
 #[tauri::command]
@@ -788,6 +970,7 @@ async fn fetch_relay_list(pubkey: String, relay_urls: Vec<String>) -> Result<Str
 
 #[tauri::command]
 async fn post_note(
+    app: tauri::AppHandle,
     state: tauri::State<'_, AppState>,
     content: String,
     reply_to_event_id: Option<String>,
@@ -798,10 +981,8 @@ async fn post_note(
         Ok(c) => c,
         Err(e) => return Err(format!("Failed to load config: {}", e)),
     };
-    let secret_key = match cfg.private_key {
-        Some(key) => key,
-        None => return Err(String::from("No private key configured. Add your nsec in Settings to post notes.")),
-    };
+    let signer = signer::signer_for(&cfg)
+        .map_err(|_| String::from("No private key configured. Add your nsec in Settings to post notes."))?;
     if cfg.relays.is_empty() {
         return Err(String::from("No relays configured"));
     }
@@ -812,20 +993,21 @@ async fn post_note(
             tags.push(vec![String::from("p"), pk]);
         }
     }
-    let event = match crypto::create_signed_note(&content, &secret_key, tags) {
-        Ok(e) => e,
+    let note = crypto::new_event(signer.public_key(), nostr::KIND_TEXT_NOTE, tags, &content);
+    let results = match sign_and_publish(&app, &cfg, &signer, note).await {
+        Ok(r) => r,
         Err(e) => return Err(format!("Failed to create note: {}", e)),
     };
-    let results = relay::publish_event_to_relays(&cfg.relays, &event, 10).await;
     let success_count = results.iter().filter(|r| r.success).count();
     if success_count == 0 {
         return Err(String::from("Failed to publish to any relay"));
     }
-    Ok(relay::publish_results_to_json(&results))
+    Ok(relay::publish_results_to_json(&results, cfg.relays.len()))
 }
 
 #[tauri::command]
 async fn post_reaction(
+    app: tauri::AppHandle,
     state: tauri::State<'_, AppState>,
     event_id: String,
     author_pubkey: String,
@@ -836,25 +1018,22 @@ async fn post_reaction(
         Ok(c) => c,
         Err(e) => return Err(format!("Failed to load config: {}", e)),
     };
-    let secret_key = match &cfg.private_key {
-        Some(k) => k.clone(),
-        None => return Err(String::from("No private key configured.")),
-    };
+    let signer = signer::signer_for(&cfg)?;
     if event_id.is_empty() || author_pubkey.is_empty() {
         return Err(String::from("event_id and author_pubkey are required"));
     }
     let content = emoji.as_deref().filter(|s| !s.is_empty()).unwrap_or("❤️");
-    let event = crypto::create_signed_reaction(&event_id, &author_pubkey, content, &secret_key)?;
-    let results = relay::publish_event_to_relays(&cfg.relays, &event, 10).await;
+    let results = sign_and_publish(&app, &cfg, &signer, crypto::create_reaction(signer.public_key(), &event_id, &author_pubkey, content)).await?;
     let success_count = results.iter().filter(|r| r.success).count();
     if success_count == 0 {
         return Err(String::from("Failed to publish reaction to any relay"));
     }
-    Ok(relay::publish_results_to_json(&results))
+    Ok(relay::publish_results_to_json(&results, cfg.relays.len()))
 }
 
 #[tauri::command]
 async fn post_repost(
+    app: tauri::AppHandle,
     state: tauri::State<'_, AppState>,
     event_id: String,
     author_pubkey: String,
@@ -865,85 +1044,277 @@ async fn post_repost(
         Ok(c) => c,
         Err(e) => return Err(format!("Failed to load config: {}", e)),
     };
-    let secret_key = match &cfg.private_key {
-        Some(k) => k.clone(),
-        None => return Err(String::from("No private key configured.")),
-    };
+    let signer = signer::signer_for(&cfg)?;
     if event_id.is_empty() || author_pubkey.is_empty() {
         return Err(String::from("event_id and author_pubkey are required"));
     }
     let content = content_optional.as_deref().unwrap_or("");
-    let event = crypto::create_signed_repost(&event_id, &author_pubkey, content, &secret_key)?;
-    let results = relay::publish_event_to_relays(&cfg.relays, &event, 10).await;
+    let results = sign_and_publish(&app, &cfg, &signer, crypto::create_repost(signer.public_key(), &event_id, &author_pubkey, content)).await?;
     let success_count = results.iter().filter(|r| r.success).count();
     if success_count == 0 {
         return Err(String::from("Failed to publish repost to any relay"));
     }
-    Ok(relay::publish_results_to_json(&results))
+    Ok(relay::publish_results_to_json(&results, cfg.relays.len()))
 }
 
 // ============================================================
 // Direct Messages (NIP-04) Commands
 // ============================================================
 
+/// Conversations, newest first, with each one's read marker and unread message count.
 #[tauri::command]
 fn get_conversations(state: tauri::State<AppState>) -> Result<String, String> {
     let config_dir = state.config_dir();
     messages_store::ensure_messages_dir(&config_dir).map_err(|e| e.to_string())?;
+    apply_legacy_dm_read_marker(&config_dir)?;
     messages_store::list_conversations_json(&config_dir)
 }
 
-/// Count conversations with unread messages (messages newer than dm_last_read_at).
+/// Count unread messages across all conversations.
 #[tauri::command]
-fn count_unread_dms(state: tauri::State<AppState>) -> Result<u32, String> {
+fn count_unread_dms(state: tauri::State<AppState>) -> Result<u64, String> {
     let config_dir = state.config_dir();
-    let cfg = config::load_config(&config_dir).map_err(|e| format!("Config: {}", e))?;
-    Ok(messages_store::count_unread_conversations(&config_dir, cfg.dm_last_read_at))
+    apply_legacy_dm_read_marker(&config_dir)?;
+    Ok(messages_store::count_unread_messages(&config_dir))
 }
 
-/// Mark DMs as read by updating dm_last_read_at to the current time.
-#[tauri::command]
-fn mark_dms_read(state: tauri::State<AppState>) -> Result<(), String> {
+/// Mark a conversation read up to now. If that marked any messages read, the read markers
+/// are synced to our relays in the background.
+#[tauri::command(rename_all = "snake_case")]
+async fn mark_conversation_read(state: tauri::State<'_, AppState>, other_pubkey_hex: String) -> Result<(), String> {
     let config_dir = state.config_dir();
-    let mut cfg = config::load_config(&config_dir).map_err(|e| format!("Config: {}", e))?;
-    cfg.dm_last_read_at = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs();
-    config::save_config(&config_dir, &cfg)
+    let other_hex = keys::public_key_to_hex(other_pubkey_hex.trim()).map_err(|e| format!("Invalid other_pubkey: {}", e))?;
+    if messages_store::mark_conversation_read(&config_dir, &other_hex, crypto::now_secs())? == 0 {
+        return Ok(());
+    }
+    let cfg = config::load_config(&config_dir).map_err(|e| format!("Config: {}", e))?;
+    let Ok(signer) = signer::signer_for(&cfg) else { return Ok(()) };
+    if cfg.relays.is_empty() {
+        return Ok(());
+    }
+    tokio::spawn(async move {
+        if let Err(e) = publish_read_markers(&config_dir, &cfg, &signer).await {
+            debug_log!("DM read markers not synced: {}", e);
+        }
+    });
+    Ok(())
+}
+
+/// Before read markers were kept per conversation, one dm_last_read_at covered them all.
+/// Carry it over to every conversation once, then clear it.
+fn apply_legacy_dm_read_marker(config_dir: &str) -> Result<(), String> {
+    let mut cfg = config::load_config(config_dir).map_err(|e| format!("Config: {}", e))?;
+    if cfg.dm_last_read_at == 0 {
+        return Ok(());
+    }
+    messages_store::mark_all_read(config_dir, cfg.dm_last_read_at)?;
+    cfg.dm_last_read_at = 0;
+    config::save_config(config_dir, &cfg)
+}
+
+/// Publish our DM read markers as a NIP-78 application data event, NIP-44 encrypted to
+/// ourselves, so our other devices can pick them up. The event replaces the one they
+/// published, so theirs are merged in first and none of their markers move back.
+async fn publish_read_markers(config_dir: &str, cfg: &config::Config, signer: &signer::Signer) -> Result<(), String> {
+    let our_pubkey = signer.public_key().to_string();
+    fetch_read_markers(config_dir, cfg, signer).await?;
+    let markers = messages_store::read_markers_to_json(&messages_store::read_markers(config_dir));
+    let content = signer.nip44_encrypt(&markers, &our_pubkey).await?;
+    let event = crypto::create_app_data(&our_pubkey, messages_store::READ_MARKERS_D_TAG, &content);
+    let event = signer.sign_event(event).await?;
+    let results = relay::publish_event_to_relays(&cfg.relays, &event, 10, 0, None).await;
+    if !results.iter().any(|r| r.success) {
+        return Err(String::from("No relay accepted the read markers"));
+    }
+    Ok(())
+}
+
+/// Fetch the read markers our other devices published and merge them into ours.
+/// Returns how many messages that marked read.
+async fn fetch_read_markers(config_dir: &str, cfg: &config::Config, signer: &signer::Signer) -> Result<u64, String> {
+    let our_pubkey = signer.public_key().to_string();
+    let event = relay::fetch_app_data_from_relays(&cfg.relays, &our_pubkey, messages_store::READ_MARKERS_D_TAG, 5).await;
+    let Some(event) = event else { return Ok(0) };
+    let markers = signer.nip44_decrypt(&event.content, &our_pubkey).await?;
+    Ok(messages_store::merge_read_markers(config_dir, &messages_store::parse_read_markers(&markers)?))
 }
 
 #[tauri::command(rename_all = "snake_case")]
-fn get_messages(state: tauri::State<AppState>, other_pubkey_hex: String) -> Result<String, String> {
+async fn get_messages(state: tauri::State<'_, AppState>, other_pubkey_hex: String) -> Result<String, String> {
     let config_dir = state.config_dir();
     let cfg = config::load_config(&config_dir).map_err(|e| format!("Config: {}", e))?;
-    let secret_hex = cfg.private_key.as_ref()
-        .ok_or("No private key configured. Add your nsec in Settings to read messages.")?;
+    let signer = signer::signer_for(&cfg)
+        .map_err(|_| String::from("No private key configured. Add your nsec in Settings to read messages."))?;
     let our_pubkey = keys::public_key_to_hex(&cfg.public_key).map_err(|e| format!("Public key: {}", e))?;
     let other_hex = keys::public_key_to_hex(other_pubkey_hex.trim()).map_err(|e| format!("Invalid other_pubkey: {}", e))?;
-    let messages = messages_store::get_messages(&config_dir, secret_hex, &our_pubkey, &other_hex)?;
+    let messages = messages_store::get_messages(&config_dir, &signer, &our_pubkey, &other_hex).await?;
+    if let Err(e) = dm_search::index_conversation(&config_dir, &signer, &other_hex, &messages).await {
+        warn_log!("DM search index not updated: {}", e);
+    }
     Ok(messages_store::messages_to_json(&messages))
 }
 
+/// Search all conversations. Query words must all occur; "quoted words" match as a phrase and
+/// word* as a prefix. Returns [{conversation, id, created_at, snippet}], newest first.
+#[tauri::command]
+async fn search_messages(state: tauri::State<'_, AppState>, query: String) -> Result<String, String> {
+    let config_dir = state.config_dir();
+    let cfg = config::load_config(&config_dir).map_err(|e| format!("Config: {}", e))?;
+    let signer = signer::signer_for(&cfg)
+        .map_err(|_| String::from("No private key configured. Add your nsec in Settings to read messages."))?;
+    let our_pubkey = keys::public_key_to_hex(&cfg.public_key).map_err(|e| format!("Public key: {}", e))?;
+    let hits = dm_search::search(&config_dir, &signer, &our_pubkey, query.trim()).await?;
+    Ok(dm_search::hits_to_json(&hits))
+}
+
 #[tauri::command(rename_all = "snake_case")]
-async fn send_dm(state: tauri::State<'_, AppState>, recipient_pubkey: String, plaintext: String) -> Result<String, String> {
+async fn send_dm(app: tauri::AppHandle, state: tauri::State<'_, AppState>, recipient_pubkey: String, plaintext: String) -> Result<String, String> {
     let config_dir = state.config_dir();
     messages_store::ensure_messages_dir(&config_dir).map_err(|e| e.to_string())?;
     let cfg = config::load_config(&config_dir).map_err(|e| format!("Config: {}", e))?;
-    let secret_hex = cfg.private_key.as_ref()
-        .ok_or("No private key configured.")?
-        .clone();
+    let signer = signer::signer_for(&cfg)?;
     let recipient_hex = keys::public_key_to_hex(recipient_pubkey.trim()).map_err(|e| format!("Invalid recipient: {}", e))?;
-    let event = crypto::create_signed_dm(&recipient_hex, &plaintext, &secret_hex)?;
-    let results = relay::publish_event_to_relays(&cfg.relays, &event, 10).await;
-    let success_count = results.iter().filter(|r| r.success).count();
-    if success_count == 0 {
+    // NIP-17: one gift wrap to the recipient's DM relays (ours if they have none), one to ours
+    let dm = signer::create_private_dm(&signer, &recipient_hex, &plaintext).await?;
+    let mut recipient_relays = relay::fetch_dm_relays_from_relays(&cfg.relays, &recipient_hex, 5).await;
+    if recipient_relays.is_empty() {
+        recipient_relays = cfg.relays.clone();
+    }
+    let results = publish_with_progress_to(&app, &recipient_relays, 1, &dm.recipient_wrap).await;
+    if !results.iter().any(|r| r.success) {
         return Err(String::from("Failed to publish DM to any relay"));
     }
-    let raw_json = nostr::event_to_json(&event);
+    let self_results = publish_with_progress(&app, &cfg, &dm.self_wrap).await;
+    if !self_results.iter().any(|r| r.success) {
+        warn_log!("DM sent, but our own copy was not accepted by any relay");
+    }
+    let raw_json = nostr::rumor_to_json(&dm.rumor);
     messages_store::append_raw_event(&config_dir, &recipient_hex, &raw_json)
         .map_err(|e| format!("Published but failed to save locally: {}", e))?;
-    Ok(nostr::event_to_json(&event))
+    Ok(raw_json)
+}
+
+/// What became of a DM event a relay delivered.
+enum DmStoreOutcome {
+    /// New to the store: the conversation partner and the stored JSON.
+    Stored(String, String),
+    /// Already stored, or nothing for us to store.
+    Known,
+    /// Not stored, but it might be another time (no signer yet, remote signer didn't answer,
+    /// write failed); sync cursors must not move past it.
+    Retry,
+}
+
+/// Store a DM event as a relay delivered it: gift wraps as the rumor inside, kind 4 events as
+/// they are.
+async fn store_dm_event(
+    config_dir: &str,
+    our_pubkey_hex: &str,
+    signer: Option<&signer::Signer>,
+    event: nostr::Event,
+) -> DmStoreOutcome {
+    let (event, raw) = if event.kind == nostr::KIND_GIFT_WRAP {
+        let Some(signer) = signer else { return DmStoreOutcome::Retry };
+        match signer::unwrap_gift_wrap(signer, &event).await {
+            Ok(rumor) if rumor.kind == nostr::KIND_CHAT_MESSAGE => {
+                let raw = nostr::rumor_to_json(&rumor);
+                (rumor, raw)
+            }
+            Ok(_) => return DmStoreOutcome::Known,
+            Err(e) => {
+                debug_log!("Gift wrap {} not opened: {}", event.id, e);
+                // Our own key failing to open it won't change; a remote signer may just be away
+                return match signer {
+                    signer::Signer::Local { .. } => DmStoreOutcome::Known,
+                    signer::Signer::Remote(_) => DmStoreOutcome::Retry,
+                };
+            }
+        }
+    } else {
+        let raw = nostr::event_to_json(&event);
+        (event, raw)
+    };
+    let Some(other) = nostr::other_pubkey_in_dm(&event, our_pubkey_hex) else { return DmStoreOutcome::Known };
+    match messages_store::append_raw_event(config_dir, &other, &raw) {
+        Ok(true) => DmStoreOutcome::Stored(other, raw),
+        Ok(false) => DmStoreOutcome::Known, // duplicate from another relay
+        Err(e) => {
+            warn_log!("DM store append error: {}", e);
+            DmStoreOutcome::Retry
+        }
+    }
+}
+
+fn save_dm_sync_cursors(config_dir: &str, cursors: &Mutex<dm_sync::SyncCursors>) {
+    let result = cursors.lock().map_err(|e| e.to_string()).and_then(|c| c.save(config_dir));
+    if let Err(e) = result {
+        warn_log!("{}", e);
+    }
+}
+
+/// Walk one relay's DM history backwards, a page at a time, until it has nothing older or
+/// stops answering. Returns the number of messages new to the store.
+async fn backfill_dms_from_relay(
+    app: &tauri::AppHandle,
+    config_dir: &str,
+    our_pubkey_hex: &str,
+    signer: Option<&signer::Signer>,
+    relay_url: &str,
+    cursors: &Mutex<dm_sync::SyncCursors>,
+) -> u64 {
+    let limit = dm_sync::backfill_page_limit(relay_url);
+    let mut fetched = 0u64;
+    'streams: for stream in dm_sync::DmStream::ALL {
+        if stream == dm_sync::DmStream::GiftWraps && signer.is_none() {
+            continue; // can't open them; leave them for when we can
+        }
+        loop {
+            let filter = match cursors.lock() {
+                Ok(c) => c.backfill_filter(relay_url, stream, our_pubkey_hex, limit),
+                Err(_) => None,
+            };
+            let Some(filter) = filter else { break };
+            let page = match relay::fetch_page_from_relay(relay_url, &filter, dm_sync::BACKFILL_PAGE_TIMEOUT_SECS).await {
+                Ok(page) => page,
+                Err(e) => {
+                    debug_log!("DM backfill from {} stopped: {}", relay_url, e);
+                    break 'streams;
+                }
+            };
+            let count = page.len();
+            let created: Vec<u64> = page.iter().map(|e| e.created_at).collect();
+            let mut retry = false;
+            for event in page {
+                match store_dm_event(config_dir, our_pubkey_hex, signer, event).await {
+                    DmStoreOutcome::Stored(..) => fetched += 1,
+                    DmStoreOutcome::Known => {}
+                    DmStoreOutcome::Retry => retry = true,
+                }
+            }
+            if retry {
+                // Leave the cursor where it was so the page is fetched again next time
+                debug_log!("DM backfill from {} paused: some messages could not be stored", relay_url);
+                break 'streams;
+            }
+            if let Ok(mut c) = cursors.lock() {
+                let page_oldest = created.iter().min().copied();
+                for created_at in created {
+                    c.saw(relay_url, stream, created_at);
+                }
+                c.finish_page(relay_url, stream, filter.until, page_oldest, count, limit);
+            }
+            save_dm_sync_cursors(config_dir, cursors);
+            let oldest = cursors.lock().ok().and_then(|c| c.oldest(relay_url));
+            let _ = app.emit("dm-backfill-progress", dm_sync::progress_to_json(relay_url, fetched, oldest, false));
+        }
+    }
+    let (oldest, exhausted) = match cursors.lock() {
+        Ok(c) => (c.oldest(relay_url), c.is_exhausted(relay_url)),
+        Err(_) => (None, false),
+    };
+    debug_log!("DM backfill from {}: {} new messages, exhausted: {}", relay_url, fetched, exhausted);
+    let _ = app.emit("dm-backfill-progress", dm_sync::progress_to_json(relay_url, fetched, oldest, true));
+    fetched
 }
 
 #[tauri::command(rename_all = "snake_case")]
@@ -954,8 +1325,10 @@ fn start_dm_stream(app: tauri::AppHandle, state: tauri::State<AppState>) -> Resu
     if our_pubkey_hex.is_empty() || cfg.relays.is_empty() {
         return Ok(());
     }
-    let filter_received = nostr::filter_dms_received(&our_pubkey_hex, 500, None);
-    let filter_sent = nostr::filter_dms_sent(&our_pubkey_hex, 500, None);
+    let mut cursors = dm_sync::SyncCursors::load(&config_dir);
+    cursors.retain_relays(&cfg.relays);
+    let cursors = Arc::new(Mutex::new(cursors));
+    let signer = signer::signer_for(&cfg).ok();
 
     std::thread::spawn(move || {
         let rt = match tokio::runtime::Runtime::new() {
@@ -967,51 +1340,90 @@ fn start_dm_stream(app: tauri::AppHandle, state: tauri::State<AppState>) -> Resu
         };
         rt.block_on(async move {
             let num_relays = cfg.relays.len() as u32;
-            let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+            let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<(String, relay::StreamMessage)>();
             for relay_url in &cfg.relays {
+                // Each relay is asked only for what arrived since its cursors
+                let filters = match cursors.lock() {
+                    Ok(c) => c.live_filters(relay_url, &our_pubkey_hex),
+                    Err(_) => continue,
+                };
+                let (relay_tx, mut relay_rx) = tokio::sync::mpsc::unbounded_channel();
+                let url = relay_url.clone();
+                tokio::spawn(async move {
+                    relay::run_relay_dm_stream(url, filters, relay_tx).await;
+                });
                 let tx = tx.clone();
                 let url = relay_url.clone();
-                let f1 = filter_received.clone();
-                let f2 = filter_sent.clone();
                 tokio::spawn(async move {
-                    relay::run_relay_dm_stream(url, f1, f2, tx).await;
+                    while let Some(msg) = relay_rx.recv().await {
+                        if tx.send((url.clone(), msg)).is_err() {
+                            return;
+                        }
+                    }
                 });
             }
             drop(tx);
 
             let mut eose_count = 0u32;
             let mut initial_sync = true;
+            let mut initial = match cursors.lock() {
+                Ok(c) => dm_sync::InitialSync::new(&c, &cfg.relays),
+                Err(_) => return,
+            };
 
-            while let Some(msg) = rx.recv().await {
+            while let Some((relay_url, msg)) = rx.recv().await {
                 match msg {
                     relay::StreamMessage::Event(event) => {
-                        if let Some(other) = nostr::other_pubkey_in_dm(&event, &our_pubkey_hex) {
-                            let raw = nostr::event_to_json(&event);
-                            match messages_store::append_raw_event(&config_dir, &other, &raw) {
-                                Ok(true) => {
+                        let stream = dm_sync::stream_for(&event, &our_pubkey_hex);
+                        let created_at = event.created_at;
+                        if initial_sync {
+                            initial.saw(&relay_url, stream, created_at);
+                        }
+                        let outcome = store_dm_event(&config_dir, &our_pubkey_hex, signer.as_ref(), event).await;
+                        // Only what is safely stored (or never will be) counts as synced
+                        if let Ok(mut c) = cursors.lock() {
+                            match outcome {
+                                DmStoreOutcome::Retry => {
+                                    c.failed(&relay_url, stream, created_at);
                                     if initial_sync {
-                                        // During initial sync, don't emit per-event notifications.
-                                        // The frontend will re-count unread after dm-sync-done.
-                                    } else {
-                                        // Live message — notify frontend
-                                        let _ = app.emit("dm-received", (other.clone(), raw));
+                                        initial.failed(&relay_url, stream);
                                     }
                                 }
-                                Ok(false) => {
-                                    // Duplicate from another relay — skip emit
-                                }
-                                Err(e) => {
-                                    warn_log!("DM store append error: {}", e);
-                                }
+                                _ => c.saw(&relay_url, stream, created_at),
+                            }
+                        }
+                        if !initial_sync {
+                            save_dm_sync_cursors(&config_dir, &cursors);
+                            // Live message — notify frontend. During initial sync, don't emit
+                            // per-event notifications; the frontend re-counts after dm-sync-done.
+                            if let DmStoreOutcome::Stored(other, raw) = outcome {
+                                let _ = app.emit("dm-received", (other, raw));
                             }
                         }
                     }
                     relay::StreamMessage::Eose => {
+                        if let Ok(mut c) = cursors.lock() {
+                            initial.finish(&relay_url, &mut c);
+                        }
+                        save_dm_sync_cursors(&config_dir, &cursors);
                         eose_count += 1;
                         if initial_sync && eose_count >= num_relays {
                             initial_sync = false;
+                            // Messages are in; bring read markers from our other devices over them
+                            if let Some(signer) = signer.as_ref() {
+                                match fetch_read_markers(&config_dir, &cfg, signer).await {
+                                    Ok(n) if n > 0 => {
+                                        debug_log!("DM read markers from other devices marked {} messages read", n);
+                                    }
+                                    Ok(_) => {}
+                                    Err(e) => {
+                                        debug_log!("DM read markers not fetched: {}", e);
+                                    }
+                                }
+                            }
                             // Tell the frontend the initial DM sync is complete
                             let _ = app.emit("dm-sync-done", ());
+                            spawn_dm_backfill(&app, &config_dir, &our_pubkey_hex, signer.clone(), &cfg.relays, &cursors);
                         }
                     }
                     _ => {}
@@ -1022,6 +1434,46 @@ fn start_dm_stream(app: tauri::AppHandle, state: tauri::State<AppState>) -> Resu
     Ok(())
 }
 
+/// Backfill older DMs from every relay that still has history, concurrently, and emit
+/// dm-backfill-done with the number of new messages when all have finished.
+fn spawn_dm_backfill(
+    app: &tauri::AppHandle,
+    config_dir: &str,
+    our_pubkey_hex: &str,
+    signer: Option<signer::Signer>,
+    relay_urls: &[String],
+    cursors: &Arc<Mutex<dm_sync::SyncCursors>>,
+) {
+    let pending: Vec<String> = match cursors.lock() {
+        Ok(c) => relay_urls.iter().filter(|url| !c.is_exhausted(url)).cloned().collect(),
+        Err(_) => return,
+    };
+    if pending.is_empty() {
+        return;
+    }
+    let tasks: Vec<_> = pending
+        .into_iter()
+        .map(|relay_url| {
+            let app = app.clone();
+            let config_dir = config_dir.to_string();
+            let our_pubkey_hex = our_pubkey_hex.to_string();
+            let signer = signer.clone();
+            let cursors = Arc::clone(cursors);
+            tokio::spawn(async move {
+                backfill_dms_from_relay(&app, &config_dir, &our_pubkey_hex, signer.as_ref(), &relay_url, &cursors).await
+            })
+        })
+        .collect();
+    let app = app.clone();
+    tokio::spawn(async move {
+        let mut fetched = 0u64;
+        for task in tasks {
+            fetched += task.await.unwrap_or(0);
+        }
+        let _ = app.emit("dm-backfill-done", fetched);
+    });
+}
+
 // ============================================================
 // Zap Invoice (NIP-57) -- uses push JSON parser for LNURL responses
 // ============================================================
@@ -1134,10 +1586,7 @@ async fn request_zap_invoice(
         Ok(c) => c,
         Err(e) => return Err(format!("Failed to load config: {}", e)),
     };
-    let secret_key = match &cfg.private_key {
-        Some(k) => k.clone(),
-        None => return Err(String::from("No private key configured.")),
-    };
+    let signer = signer::signer_for(&cfg)?;
     if target_lud16.is_empty() || target_pubkey.is_empty() {
         return Err(String::from("target_lud16 and target_pubkey are required"));
     }
@@ -1174,14 +1623,15 @@ async fn request_zap_invoice(
     let amount_msats = amount_msats.clamp(lnurl_handler.min_sendable, lnurl_handler.max_sendable);
 
     let event_id_opt = if event_id.trim().is_empty() { None } else { Some(event_id.as_str()) };
-    let zap_event = crypto::create_signed_zap_request(
+    let zap_request = crypto::create_zap_request(
+        signer.public_key(),
         &cfg.relays,
         &target_pubkey,
         event_id_opt,
         amount_msats,
         "",
-        &secret_key,
-    )?;
+    );
+    let zap_event = signer.sign_event(zap_request).await?;
     let zap_json = nostr::event_to_json(&zap_event);
     let zap_b64 = BASE64.encode(zap_json.as_bytes());
 
@@ -1207,16 +1657,13 @@ async fn request_zap_invoice(
 // ============================================================
 
 #[tauri::command]
-async fn set_profile_metadata(state: tauri::State<'_, AppState>, profile_json: String) -> Result<String, String> {
+async fn set_profile_metadata(app: tauri::AppHandle, state: tauri::State<'_, AppState>, profile_json: String) -> Result<String, String> {
     let config_dir = state.config_dir();
     let mut cfg = match config::load_config(&config_dir) {
         Ok(c) => c,
         Err(e) => return Err(format!("Failed to load config: {}", e)),
     };
-    let secret_key = match cfg.private_key.as_ref() {
-        Some(k) => k.clone(),
-        None => return Err(String::from("No private key configured.")),
-    };
+    let signer = signer::signer_for(&cfg)?;
     if cfg.relays.is_empty() {
         return Err(String::from("No relays configured"));
     }
@@ -1225,11 +1672,10 @@ async fn set_profile_metadata(state: tauri::State<'_, AppState>, profile_json: S
         Err(e) => return Err(format!("Invalid profile JSON: {}", e)),
     };
     let content = nostr::profile_to_content(&profile);
-    let event = match crypto::create_signed_metadata_event(&content, &secret_key) {
-        Ok(e) => e,
+    let results = match sign_and_publish(&app, &cfg, &signer, crypto::create_metadata_event(signer.public_key(), &content)).await {
+        Ok(r) => r,
         Err(e) => return Err(format!("Failed to create profile event: {}", e)),
     };
-    let results = relay::publish_event_to_relays(&cfg.relays, &event, 10).await;
     let success_count = results.iter().filter(|r| r.success).count();
     if success_count == 0 {
         return Err(String::from("Failed to publish profile to any relay"));
@@ -1246,31 +1692,106 @@ async fn set_profile_metadata(state: tauri::State<'_, AppState>, profile_json: S
     if let Err(e) = config::save_config(&config_dir, &cfg) {
         return Err(format!("Profile published but failed to save local config: {}", e));
     }
-    Ok(relay::publish_results_to_json(&results))
+    Ok(relay::publish_results_to_json(&results, cfg.relays.len()))
 }
 
 #[tauri::command]
-fn sign_event(state: tauri::State<AppState>, event_json: String) -> Result<String, String> {
+async fn sign_event(state: tauri::State<'_, AppState>, event_json: String) -> Result<String, String> {
     let config_dir = state.config_dir();
     let cfg = match config::load_config(&config_dir) {
         Ok(c) => c,
         Err(e) => return Err(format!("Failed to load config: {}", e)),
     };
-    let secret_key = match cfg.private_key {
-        Some(key) => key,
-        None => return Err(String::from("No private key configured")),
-    };
-    let mut event = match nostr::parse_event(&event_json) {
+    let signer = signer::signer_for(&cfg)?;
+    let event = match nostr::parse_event(&event_json) {
         Ok(e) => e,
         Err(e) => return Err(format!("Invalid event JSON: {}", e)),
     };
-    match crypto::sign_event(&mut event, &secret_key) {
-        Ok(()) => {}
+    let event = match signer.sign_event(event).await {
+        Ok(e) => e,
         Err(e) => return Err(format!("Failed to sign event: {}", e)),
     };
     return Ok(nostr::event_to_json(&event));
 }
 
+/// Ask the relays to delete one of our events (NIP-09). Waits for every relay, so the result
+/// says which ones acknowledged the deletion.
+#[tauri::command(rename_all = "snake_case")]
+async fn delete_event(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    event_id: String,
+    kind: u32,
+    reason: Option<String>,
+) -> Result<String, String> {
+    let config_dir = state.config_dir();
+    let cfg = match config::load_config(&config_dir) {
+        Ok(c) => c,
+        Err(e) => return Err(format!("Failed to load config: {}", e)),
+    };
+    let signer = signer::signer_for(&cfg)?;
+    if event_id.is_empty() {
+        return Err(String::from("event_id is required"));
+    }
+    let mut filter = nostr::Filter::new();
+    filter.ids = Some(vec![event_id.clone()]);
+    if let Some(stored) = event_store::query(&filter).into_iter().next() {
+        if !stored.pubkey.eq_ignore_ascii_case(signer.public_key()) {
+            return Err(String::from("You can only delete your own events"));
+        }
+    }
+    publish_deletion(&app, &cfg, &signer, &[(event_id, kind)], reason.as_deref().unwrap_or("")).await
+}
+
+/// Retract our reactions (kind 7) or reposts (kind 6) of an event: every one in the event store
+/// is named in a single deletion request.
+#[tauri::command(rename_all = "snake_case")]
+async fn undo_response(app: tauri::AppHandle, state: tauri::State<'_, AppState>, event_id: String, kind: u32) -> Result<String, String> {
+    let config_dir = state.config_dir();
+    let cfg = match config::load_config(&config_dir) {
+        Ok(c) => c,
+        Err(e) => return Err(format!("Failed to load config: {}", e)),
+    };
+    let signer = signer::signer_for(&cfg)?;
+    if kind != nostr::KIND_REACTION && kind != nostr::KIND_REPOST {
+        return Err(String::from("Only reactions and reposts can be retracted"));
+    }
+    let mut filter = nostr::Filter::new();
+    filter.authors = Some(vec![signer.public_key().to_string()]);
+    filter.kinds = Some(vec![kind]);
+    filter.e_tags = Some(vec![event_id]);
+    let targets: Vec<(String, u32)> = event_store::query(&filter).into_iter().map(|e| (e.id, e.kind)).collect();
+    if targets.is_empty() {
+        return Err(String::from("Nothing to retract"));
+    }
+    publish_deletion(&app, &cfg, &signer, &targets, "").await
+}
+
+/// Publish a deletion request for `targets` (id, kind) to every configured relay.
+async fn publish_deletion(
+    app: &tauri::AppHandle,
+    cfg: &config::Config,
+    signer: &signer::Signer,
+    targets: &[(String, u32)],
+    reason: &str,
+) -> Result<String, String> {
+    if cfg.relays.is_empty() {
+        return Err(String::from("No relays configured"));
+    }
+    let deletion = crypto::create_deletion(signer.public_key(), targets, reason);
+    let results = sign_and_publish_to(app, cfg, signer, &cfg.relays, 0, deletion).await?;
+    if !results.iter().any(|r| r.success) {
+        return Err(String::from("Failed to publish deletion to any relay"));
+    }
+    Ok(relay::publish_results_to_json(&results, cfg.relays.len()))
+}
+
+/// Stop mining proof of work for events being posted; those posts fail.
+#[tauri::command]
+fn cancel_pow() {
+    pow::cancel_all();
+}
+
 #[tauri::command]
 fn get_derived_public_key(state: tauri::State<AppState>) -> Result<String, String> {
     let config_dir = state.config_dir();
@@ -1278,27 +1799,32 @@ fn get_derived_public_key(state: tauri::State<AppState>) -> Result<String, Strin
         Ok(c) => c,
         Err(e) => return Err(format!("Failed to load config: {}", e)),
     };
-    let secret_key = match cfg.private_key {
-        Some(key) => key,
-        None => return Err(String::from("No private key configured")),
-    };
-    let pubkey = crypto::get_public_key_from_secret(&secret_key)?;
+    let signer = signer::signer_for(&cfg)?;
+    let pubkey = signer.public_key().to_string();
     let npub = keys::hex_to_npub(&pubkey).unwrap_or_default();
     Ok(format!("{{\"hex\":\"{}\",\"npub\":\"{}\"}}", pubkey, npub))
 }
 
+/// Create a new identity from a fresh NIP-06 seed phrase in the word list for `locale`. The
+/// phrase is returned once for the user to write down; it is not stored.
 #[tauri::command]
-fn generate_keypair(state: tauri::State<AppState>) -> Result<String, String> {
-    let (secret_hex, pubkey_hex) = crypto::generate_keypair()?;
+async fn generate_keypair(state: tauri::State<'_, AppState>, password: String, locale: Option<String>) -> Result<String, String> {
+    let mnemonic = nip06::generate_mnemonic(locale.as_deref().unwrap_or("en"))?;
+    let secret_hex = nip06::mnemonic_to_secret_key(&mnemonic, "", 0)?;
+    let pubkey_hex = crypto::get_public_key_from_secret(&secret_hex)?;
     let npub = keys::hex_to_npub(&pubkey_hex).unwrap_or_default();
     let nsec = keys::hex_to_nsec(&secret_hex).unwrap_or_default();
 
+    let (public_key, secret) = (pubkey_hex.clone(), secret_hex.clone());
+    let cfg = run_key_task(move || {
+        let mut cfg = config::Config::new();
+        cfg.public_key = public_key;
+        config::set_private_key(&mut cfg, &secret, &password)?;
+        Ok(cfg)
+    }).await?;
+
     // Create profile directory for the new identity
     let profile_dir = config::ensure_profile_dir(&state.base_dir, &npub)?;
-
-    let mut cfg = config::Config::new();
-    cfg.public_key = pubkey_hex.clone();
-    cfg.private_key = Some(secret_hex.clone());
     config::save_config(&profile_dir, &cfg)?;
 
     // Update app config — use unwrap_or for missing file (Ok path), propagate parse errors
@@ -1314,8 +1840,8 @@ fn generate_keypair(state: tauri::State<AppState>) -> Result<String, String> {
     let _ = messages_store::ensure_messages_dir(&profile_dir);
 
     Ok(format!(
-        "{{\"public_key_hex\":\"{}\",\"private_key_hex\":\"{}\",\"npub\":\"{}\",\"nsec\":\"{}\"}}",
-        pubkey_hex, secret_hex, npub, nsec
+        "{{\"public_key_hex\":\"{}\",\"private_key_hex\":\"{}\",\"npub\":\"{}\",\"nsec\":\"{}\",\"mnemonic\":\"{}\"}}",
+        pubkey_hex, secret_hex, npub, nsec, mnemonic
     ))
 }
 
@@ -1329,64 +1855,155 @@ fn get_app_config(state: tauri::State<AppState>) -> Result<String, String> {
         .map(|c| config::app_config_to_json(&c))
 }
 
+/// Set (or unlock) the key in `cfg` from what the user entered: an ncryptsec is decrypted with
+/// the password, an nsec or hex key is encrypted with it, and with no key the stored one is
+/// unlocked. With neither key nor password the profile stays read-only.
+fn apply_private_key(cfg: &mut config::Config, private_key: Option<&str>, password: &str) -> Result<(), String> {
+    match private_key.map(str::trim).filter(|k| !k.is_empty()) {
+        Some(key) if keys::is_ncryptsec(key) => config::import_ncryptsec(cfg, key, password),
+        Some(key) => {
+            let hex = keys::secret_key_to_hex(key).map_err(|e| format!("Invalid private key: {}", e))?;
+            config::set_private_key(cfg, &hex, password)
+        }
+        None if !password.is_empty() => config::unlock_private_key(cfg, password),
+        None => Ok(()),
+    }
+}
+
+/// Run key work that may include a NIP-49 scrypt (around a second) on the blocking pool,
+/// so login and unlock don't freeze the UI.
+async fn run_key_task<T: Send + 'static>(task: impl FnOnce() -> Result<T, String> + Send + 'static) -> Result<T, String> {
+    tokio::task::spawn_blocking(task)
+        .await
+        .map_err(|e| format!("Key task failed: {}", e))?
+}
+
+/// Log in with a public key and optionally a private key, or recover both from a NIP-06 seed
+/// phrase (with its optional passphrase and the account index of the profile).
 #[tauri::command(rename_all = "snake_case")]
-fn login_with_keys(
-    state: tauri::State<AppState>,
+async fn login_with_keys(
+    state: tauri::State<'_, AppState>,
     public_key: String,
     private_key: Option<String>,
+    password: Option<String>,
+    mnemonic: Option<String>,
+    mnemonic_passphrase: Option<String>,
+    account: Option<u32>,
 ) -> Result<String, String> {
-    // Validate and convert public key
-    let pub_hex = keys::public_key_to_hex(&public_key)
-        .map_err(|e| format!("Invalid public key: {}", e))?;
-    let npub = keys::hex_to_npub(&pub_hex)
-        .map_err(|e| format!("Failed to convert to npub: {}", e))?;
-
-    // Validate private key if provided
-    let priv_hex = match &private_key {
-        Some(key) if !key.trim().is_empty() => {
-            Some(keys::secret_key_to_hex(key.trim())
-                .map_err(|e| format!("Invalid private key: {}", e))?)
+    let base_dir = state.base_dir.clone();
+    let (npub, cfg) = run_key_task(move || {
+        let mut private_key = private_key;
+        let mut public_key = public_key;
+        if let Some(phrase) = mnemonic.as_deref().filter(|m| !m.trim().is_empty()) {
+            let secret_hex = nip06::mnemonic_to_secret_key(
+                phrase,
+                mnemonic_passphrase.as_deref().unwrap_or(""),
+                account.unwrap_or(0),
+            )?;
+            let derived = crypto::get_public_key_from_secret(&secret_hex)?;
+            if !public_key.trim().is_empty() && keys::public_key_to_hex(&public_key).ok().as_deref() != Some(derived.as_str()) {
+                return Err(String::from("The seed phrase does not belong to this public key (check the account number)"));
+            }
+            if password.as_deref().unwrap_or("").is_empty() {
+                return Err(String::from("Choose a password to encrypt the recovered key"));
+            }
+            public_key = derived;
+            private_key = Some(secret_hex);
         }
-        _ => None,
-    };
 
-    // Create profile directory
-    let profile_dir = config::ensure_profile_dir(&state.base_dir, &npub)?;
+        // Validate and convert public key
+        let pub_hex = keys::public_key_to_hex(&public_key)
+            .map_err(|e| format!("Invalid public key: {}", e))?;
+        let npub = keys::hex_to_npub(&pub_hex)
+            .map_err(|e| format!("Failed to convert to npub: {}", e))?;
+
+        // Load existing config or create new
+        let profile_dir = config::get_profile_dir(&base_dir, &npub);
+        let mut cfg = match config::load_config(&profile_dir) {
+            Ok(c) => c,
+            Err(_) => config::Config::new(),
+        };
+        cfg.public_key = pub_hex;
+        apply_private_key(&mut cfg, private_key.as_deref(), password.as_deref().unwrap_or(""))?;
+        Ok((npub, cfg))
+    }).await?;
+    activate_profile(&state, &npub, &cfg)?;
+    Ok(config::config_to_json(&cfg))
+}
 
-    // Load existing config or create new
-    let mut cfg = match config::load_config(&profile_dir) {
-        Ok(c) => c,
-        Err(_) => config::Config::new(),
-    };
-    cfg.public_key = pub_hex;
-    if let Some(ref hex) = priv_hex {
-        cfg.private_key = Some(hex.clone());
-    }
-    config::save_config(&profile_dir, &cfg)?;
+/// Save `cfg` as the profile for `npub`, make it the active profile and switch to it.
+fn activate_profile(state: &AppState, npub: &str, cfg: &config::Config) -> Result<(), String> {
+    // Create profile directory
+    let profile_dir = config::ensure_profile_dir(&state.base_dir, npub)?;
+    config::save_config(&profile_dir, cfg)?;
 
     // Update app config — propagate errors instead of silently creating empty config
     let mut app_config = config::load_app_config(&state.base_dir)?;
-    app_config.active_profile = Some(npub.clone());
-    if !app_config.known_profiles.iter().any(|p| p == &npub) {
-        app_config.known_profiles.push(npub.clone());
+    app_config.active_profile = Some(npub.to_string());
+    if !app_config.known_profiles.iter().any(|p| p == npub) {
+        app_config.known_profiles.push(npub.to_string());
     }
     config::save_app_config(&state.base_dir, &app_config)?;
 
     // Switch to this profile
     state.set_config_dir(profile_dir.clone());
     let _ = messages_store::ensure_messages_dir(&profile_dir);
+    Ok(())
+}
 
+/// Log in as the user a NIP-46 remote signer signs for. The profile keeps no private key; it
+/// remembers the signer and our client key so it can reconnect after a restart.
+fn login_with_remote_signer(state: &AppState, remote: &signer::RemoteSigner) -> Result<String, String> {
+    let npub = keys::hex_to_npub(remote.user_pubkey())
+        .map_err(|e| format!("Failed to convert to npub: {}", e))?;
+    let profile_dir = config::get_profile_dir(&state.base_dir, &npub);
+    let mut cfg = match config::load_config(&profile_dir) {
+        Ok(c) => c,
+        Err(_) => config::Config::new(),
+    };
+    cfg.public_key = remote.user_pubkey().to_string();
+    cfg.remote_signer = Some(remote.bunker_uri());
+    cfg.remote_signer_client_key = Some(remote.client_secret().to_string());
+    activate_profile(state, &npub, &cfg)?;
     Ok(config::config_to_json(&cfg))
 }
 
+/// Log in through the remote signer in a bunker:// URI.
+#[tauri::command]
+async fn connect_remote_signer(state: tauri::State<'_, AppState>, uri: String) -> Result<String, String> {
+    let remote = signer::RemoteSigner::connect_bunker(&uri).await?;
+    login_with_remote_signer(&state, &remote)
+}
+
+/// Start a nostrconnect:// pairing on `relays`; returns the URI for the user's remote signer.
+#[tauri::command]
+async fn start_nostrconnect(relays: Vec<String>) -> Result<String, String> {
+    signer::start_nostrconnect(&relays)
+}
+
+/// Wait for the remote signer to connect to the URI from start_nostrconnect, then log in.
+#[tauri::command]
+async fn finish_nostrconnect(state: tauri::State<'_, AppState>) -> Result<String, String> {
+    let remote = signer::finish_nostrconnect().await?;
+    login_with_remote_signer(&state, &remote)
+}
+
 #[tauri::command(rename_all = "snake_case")]
-fn switch_profile(state: tauri::State<AppState>, npub: String) -> Result<String, String> {
+async fn switch_profile(state: tauri::State<'_, AppState>, npub: String, password: Option<String>) -> Result<String, String> {
     let profile_dir = config::get_profile_dir(&state.base_dir, &npub);
     if !std::path::Path::new(&profile_dir).join("config.json").exists() {
         return Err(format!("Profile not found: {}", npub));
     }
 
-    let cfg = config::load_config(&profile_dir)?;
+    let dir = profile_dir.clone();
+    let cfg = run_key_task(move || {
+        let mut cfg = config::load_config(&dir)?;
+        if let Some(password) = password.as_deref().filter(|p| !p.is_empty()) {
+            config::unlock_private_key(&mut cfg, password)?;
+            config::save_config(&dir, &cfg)?;
+        }
+        Ok(cfg)
+    }).await?;
 
     let mut app_config = config::load_app_config(&state.base_dir)?;
     app_config.active_profile = Some(npub.clone());
@@ -1409,13 +2026,161 @@ fn logout(state: tauri::State<AppState>) -> Result<(), String> {
     app_config.active_profile = None;
     config::save_app_config(&state.base_dir, &app_config)?;
     debug_log!("[logout] Saved app config, known_profiles preserved: {:?}", app_config.known_profiles);
+    bunker::stop_all();
+    config::lock_private_keys();
     state.set_config_dir(state.base_dir.clone());
     Ok(())
 }
 
+/// Unlock the active profile's encrypted key for this session (e.g. after a restart).
+#[tauri::command]
+async fn unlock_profile(state: tauri::State<'_, AppState>, password: String) -> Result<String, String> {
+    let config_dir = state.config_dir();
+    run_key_task(move || {
+        let mut cfg = config::load_config(&config_dir)?;
+        config::unlock_private_key(&mut cfg, &password)?;
+        config::save_config(&config_dir, &cfg)?;
+        Ok(config::config_to_json(&cfg))
+    }).await
+}
+
+/// Replace the active profile's private key (nsec, hex or ncryptsec), stored encrypted with `password`.
+#[tauri::command(rename_all = "snake_case")]
+async fn set_private_key(state: tauri::State<'_, AppState>, private_key: String, password: String) -> Result<String, String> {
+    let config_dir = state.config_dir();
+    if private_key.trim().is_empty() {
+        return Err(String::from("Private key is required"));
+    }
+    let dir = config_dir.clone();
+    let cfg = run_key_task(move || {
+        let mut cfg = config::load_config(&dir)?;
+        apply_private_key(&mut cfg, Some(&private_key), &password)?;
+        config::save_config(&dir, &cfg)?;
+        Ok(cfg)
+    }).await?;
+    bunker::stop(&config_dir);
+    Ok(config::config_to_json(&cfg))
+}
+
+/// The active profile's key as stored: an ncryptsec string, for backup or use in another client.
+#[tauri::command]
+fn export_ncryptsec(state: tauri::State<AppState>) -> Result<String, String> {
+    let cfg = config::load_config(&state.config_dir())?;
+    match cfg.encrypted_private_key {
+        Some(ncryptsec) => Ok(ncryptsec),
+        None if cfg.private_key.is_some() => Err(String::from("Unlock the profile with a password first")),
+        None => Err(String::from("No private key stored for this profile")),
+    }
+}
+
+// ============================================================
+// Bunker (NIP-46 remote signer for other apps)
+// ============================================================
+
+/// The active profile's bunker: running state, URI, relays and connected apps.
+#[tauri::command]
+fn get_bunker_status(state: tauri::State<AppState>) -> Result<String, String> {
+    let config_dir = state.config_dir();
+    let cfg = config::load_config(&config_dir)?;
+    bunker::status_to_json(&config_dir, &cfg.public_key)
+}
+
+/// Set the relays the bunker listens on; a running bunker moves to them.
+#[tauri::command]
+fn save_bunker_relays(app: tauri::AppHandle, state: tauri::State<AppState>, relays: Vec<String>) -> Result<String, String> {
+    let config_dir = state.config_dir();
+    let cfg = config::load_config(&config_dir)?;
+    let mut settings = bunker::load_settings(&config_dir)?;
+    settings.relays = relays
+        .iter()
+        .map(|r| r.trim().to_string())
+        .filter(|r| !r.is_empty())
+        .collect();
+    bunker::save_settings(&config_dir, &settings)?;
+    if bunker::is_running(&config_dir) {
+        if settings.relays.is_empty() {
+            bunker::stop(&config_dir);
+        } else if let Some(ref secret_key) = cfg.private_key {
+            bunker::start(&config_dir, secret_key, bunker_app_connected_handler(app))?;
+        }
+    }
+    bunker::status_to_json(&config_dir, &cfg.public_key)
+}
+
+/// Change what a connected app may do. Applies to its next request, and settles any
+/// permissions it asked for when connecting.
+#[tauri::command(rename_all = "snake_case")]
+fn set_bunker_app_policy(
+    state: tauri::State<AppState>,
+    app_pubkey: String,
+    name: String,
+    allowed_kinds: Vec<u32>,
+    allow_encryption: bool,
+    requests_per_minute: u32,
+) -> Result<String, String> {
+    let config_dir = state.config_dir();
+    let cfg = config::load_config(&config_dir)?;
+    let mut settings = bunker::load_settings(&config_dir)?;
+    let policy = settings
+        .apps
+        .iter_mut()
+        .find(|a| a.pubkey == app_pubkey)
+        .ok_or_else(|| format!("Unknown app: {}", app_pubkey))?;
+    if !name.trim().is_empty() {
+        policy.name = name.trim().to_string();
+    }
+    policy.allowed_kinds = allowed_kinds;
+    policy.allow_encryption = allow_encryption;
+    policy.requests_per_minute = requests_per_minute;
+    policy.requested_kinds.clear();
+    policy.requested_encryption = false;
+    bunker::save_settings(&config_dir, &settings)?;
+    bunker::status_to_json(&config_dir, &cfg.public_key)
+}
+
+/// Disconnect an app; it must connect again with a fresh bunker URI.
+#[tauri::command(rename_all = "snake_case")]
+fn remove_bunker_app(state: tauri::State<AppState>, app_pubkey: String) -> Result<String, String> {
+    let config_dir = state.config_dir();
+    let cfg = config::load_config(&config_dir)?;
+    let mut settings = bunker::load_settings(&config_dir)?;
+    settings.apps.retain(|a| a.pubkey != app_pubkey);
+    bunker::save_settings(&config_dir, &settings)?;
+    bunker::status_to_json(&config_dir, &cfg.public_key)
+}
+
+/// Serve the active profile's key to other apps. Needs the key unlocked in this session.
+#[tauri::command]
+fn start_bunker(app: tauri::AppHandle, state: tauri::State<AppState>) -> Result<String, String> {
+    let config_dir = state.config_dir();
+    let cfg = config::load_config(&config_dir)?;
+    let secret_key = cfg
+        .private_key
+        .as_deref()
+        .ok_or_else(|| String::from("Unlock the profile's private key to run a bunker"))?;
+    bunker::start(&config_dir, secret_key, bunker_app_connected_handler(app))?;
+    bunker::status_to_json(&config_dir, &cfg.public_key)
+}
+
+#[tauri::command]
+fn stop_bunker(state: tauri::State<AppState>) -> Result<String, String> {
+    let config_dir = state.config_dir();
+    let cfg = config::load_config(&config_dir)?;
+    bunker::stop(&config_dir);
+    bunker::status_to_json(&config_dir, &cfg.public_key)
+}
+
+/// Emit "bunker-app-connected" with the app's public key when a new app connects.
+fn bunker_app_connected_handler(app: tauri::AppHandle) -> bunker::AppConnectedHandler {
+    std::sync::Arc::new(move |app_pubkey: &str| {
+        let _ = app.emit("bunker-app-connected", app_pubkey.to_string());
+    })
+}
+
 #[tauri::command(rename_all = "snake_case")]
 fn delete_profile(state: tauri::State<AppState>, npub: String) -> Result<(), String> {
     let profile_dir = config::get_profile_dir(&state.base_dir, &npub);
+    bunker::stop(&profile_dir);
     if std::path::Path::new(&profile_dir).exists() {
         std::fs::remove_dir_all(&profile_dir)
             .map_err(|e| format!("Failed to delete profile directory: {}", e))?;
@@ -1430,8 +2195,9 @@ fn delete_profile(state: tauri::State<AppState>, npub: String) -> Result<(), Str
     Ok(())
 }
 
-/// List known profiles with name and picture resolved from each profile's config.json.
-/// Returns a JSON array of objects: [{ "npub": "...", "name": "...", "picture": "..." }, ...]
+/// List known profiles with name and picture resolved from each profile's config.json, and
+/// whether the profile has a private key (to ask for its password when switching).
+/// Returns a JSON array of objects: [{ "npub": "...", "name": "...", "picture": "...", "has_key": true }, ...]
 #[tauri::command]
 fn list_profiles(state: tauri::State<AppState>) -> Result<String, String> {
     let app_config = config::load_app_config(&state.base_dir)
@@ -1475,6 +2241,9 @@ fn list_profiles(state: tauri::State<AppState>) -> Result<String, String> {
             }
             None => json.push_str("null"),
         }
+        let has_key = cfg.as_ref().is_some_and(|c| c.encrypted_private_key.is_some() || c.private_key.is_some());
+        json.push_str(",\"has_key\":");
+        json.push_str(if has_key { "true" } else { "false" });
         json.push('}');
     }
     json.push(']');
@@ -1485,6 +2254,97 @@ fn list_profiles(state: tauri::State<AppState>) -> Result<String, String> {
 // Helper Functions
 // ============================================================
 
+/// Publish to the configured relays, emitting "publish-result" for each relay as its OK
+/// arrives. Returns once the configured quorum has accepted the event.
+async fn publish_with_progress(app: &tauri::AppHandle, cfg: &config::Config, event: &nostr::Event) -> Vec<relay::PublishResult> {
+    publish_with_progress_to(app, &cfg.relays, cfg.publish_quorum as usize, event).await
+}
+
+/// Mine the proof of work the configured relays need (NIP-13), sign, and publish with progress.
+/// Relays that still reject the event with "pow:" are sent it again, re-mined at the difficulty
+/// they ask for, up to pow::MAX_RETRIES times. The retried event has a new id, so relays that
+/// accepted the first one keep that one.
+async fn sign_and_publish(
+    app: &tauri::AppHandle,
+    cfg: &config::Config,
+    signer: &signer::Signer,
+    event: nostr::Event,
+) -> Result<Vec<relay::PublishResult>, String> {
+    sign_and_publish_to(app, cfg, signer, &cfg.relays, cfg.publish_quorum as usize, event).await
+}
+
+/// Like sign_and_publish, to a given set of relays and quorum.
+async fn sign_and_publish_to(
+    app: &tauri::AppHandle,
+    cfg: &config::Config,
+    signer: &signer::Signer,
+    relay_urls: &[String],
+    quorum: usize,
+    event: nostr::Event,
+) -> Result<Vec<relay::PublishResult>, String> {
+    let mut difficulty = pow::required_difficulty(cfg, relay_urls);
+    let mut event = signer.sign_event(mine_with_progress(app, event, difficulty).await?).await?;
+    let mut results = publish_with_progress_to(app, relay_urls, quorum, &event).await;
+    for _ in 0..pow::MAX_RETRIES {
+        let mut rejected: Vec<String> = Vec::new();
+        let mut retry_difficulty = difficulty;
+        for result in &results {
+            if result.prefix == Some(relay::MessagePrefix::Pow) {
+                rejected.push(result.relay_url.clone());
+                retry_difficulty = retry_difficulty.max(pow::difficulty_for_rejection(&result.message, difficulty));
+            }
+        }
+        if rejected.is_empty() || retry_difficulty > pow::MAX_DIFFICULTY {
+            break;
+        }
+        difficulty = retry_difficulty;
+        event = signer.sign_event(mine_with_progress(app, event, difficulty).await?).await?;
+        let retried = publish_with_progress_to(app, &rejected, 0, &event).await;
+        results.retain(|r| !rejected.contains(&r.relay_url));
+        results.extend(retried);
+    }
+    Ok(results)
+}
+
+/// Mine `difficulty` bits of proof of work into an unsigned event on the blocking pool,
+/// emitting "pow-progress" as it goes. Difficulty 0 returns the event unchanged.
+async fn mine_with_progress(app: &tauri::AppHandle, event: nostr::Event, difficulty: u32) -> Result<nostr::Event, String> {
+    if difficulty == 0 {
+        return Ok(event);
+    }
+    let app = app.clone();
+    tokio::task::spawn_blocking(move || {
+        let progress = |attempts: u64, done: bool| {
+            format!("{{\"difficulty\":{},\"attempts\":{},\"done\":{}}}", difficulty, attempts, done)
+        };
+        let result = pow::mine(event, difficulty, |attempts| {
+            let _ = app.emit("pow-progress", progress(attempts, false));
+        });
+        let _ = app.emit("pow-progress", progress(0, true));
+        result
+    })
+    .await
+    .map_err(|e| format!("Proof of work failed: {}", e))?
+}
+
+/// Like publish_with_progress, to a given set of relays and quorum.
+async fn publish_with_progress_to(
+    app: &tauri::AppHandle,
+    relay_urls: &[String],
+    quorum: usize,
+    event: &nostr::Event,
+) -> Vec<relay::PublishResult> {
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let app = app.clone();
+    let event_id = event.id.clone();
+    tokio::spawn(async move {
+        while let Some(result) = rx.recv().await {
+            let _ = app.emit("publish-result", relay::publish_result_to_json(&event_id, &result));
+        }
+    });
+    relay::publish_event_to_relays(relay_urls, event, 10, quorum, Some(tx)).await
+}
+
 fn events_to_json_array(events: &Vec<nostr::Event>) -> String {
     let mut json = String::from("[");
     for (index, event) in events.iter().enumerate() {
@@ -1570,6 +2430,8 @@ fn main() {
         None => base_dir.clone(),
     };
 
+    install_auth_signer(config_dir.clone());
+    event_store::open_store(&config_dir);
     let app_state = AppState {
         base_dir,
         active_config_dir: RwLock::new(config_dir),
@@ -1587,13 +2449,19 @@ fn main() {
             convert_hex_to_nsec,
             parse_key,
             decode_nostr_uri,
+            share_note,
+            share_profile,
+            share_address,
             fetch_notes,
             fetch_notes_from_relays,
             start_feed_stream,
             fetch_events_by_ids,
+            fetch_addressable_event,
             generate_qr_svg,
             fetch_replies_to_event,
             test_relay_connection,
+            fetch_relay_info,
+            verify_nip05,
             get_relay_backoff_status,
             fetch_profile,
             fetch_own_profile,
@@ -1602,6 +2470,7 @@ fn main() {
             verify_event_id,
             verify_event_signature,
             compute_event_id,
+            get_forged_event_counts,
             fetch_following,
             fetch_own_following,
             update_contact_list,
@@ -1614,18 +2483,34 @@ fn main() {
             post_repost,
             get_conversations,
             get_messages,
+            search_messages,
             send_dm,
             start_dm_stream,
             count_unread_dms,
-            mark_dms_read,
+            mark_conversation_read,
             request_zap_invoice,
             sign_event,
+            cancel_pow,
+            delete_event,
+            undo_response,
             get_derived_public_key,
             generate_keypair,
             get_app_config,
             login_with_keys,
+            connect_remote_signer,
+            start_nostrconnect,
+            finish_nostrconnect,
             switch_profile,
             logout,
+            unlock_profile,
+            set_private_key,
+            export_ncryptsec,
+            get_bunker_status,
+            save_bunker_relays,
+            set_bunker_app_policy,
+            remove_bunker_app,
+            start_bunker,
+            stop_bunker,
             delete_profile,
             list_profiles,
         ])
@@ -1636,6 +2521,10 @@ fn main() {
                 _window.open_devtools();
             }
             warn_log!("Plume is starting...");
+            let handle = app.handle().clone();
+            signer::set_auth_url_handler(Some(std::sync::Arc::new(move |url: &str| {
+                let _ = handle.emit("remote-signer-auth-url", url.to_string());
+            })));
             Ok(())
         })
         .run(tauri::generate_context!())
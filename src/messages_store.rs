@@ -18,32 +18,37 @@
  * along with Plume.  If not, see <http://www.gnu.org/licenses/>.
  */
 
-// One file per conversation: ~/.plume/messages/{other_pubkey_hex}.json
-// Each file = JSON array of raw kind 4 events (wire format, encrypted content).
+//! Direct message storage, one conversation per partner in {profile_dir}/messages/:
+//!
+//! - `{other_pubkey_hex}.log`: append-only, one compact JSON event per line. Raw kind 4
+//!   events (wire format, encrypted content) and unwrapped NIP-17 kind 14 rumors (unsigned,
+//!   plaintext content).
+//! - `{other_pubkey_hex}.idx`: one line per log record, `<id> <created_at> <offset> <len>
+//!   <author>`, so duplicates are caught and unread messages counted without reading the log.
+//! - `{other_pubkey_hex}.meta`: small JSON header with last_created_at, count, last_read,
+//!   unread and the log length it describes, for listing conversations without opening them.
+//!
+//! Appends are fsynced to the log, then to the index; the header is replaced atomically.
+//! Opening a conversation indexes any log records the index missed and truncates a torn last
+//! record. Conversations in the old single-array `{other_pubkey_hex}.json` format are migrated
+//! on first open.
+//!
+//! last_read is a per-conversation read marker: messages from the partner created after it are
+//! unread. The markers are synced between devices as a NIP-78 application data event whose
+//! content is NIP-44 encrypted to ourselves (see read_markers_to_json).
 
-use std::collections::HashMap;
-use std::fs;
-use std::io;
-use std::path::Path;
-use std::sync::{Mutex, OnceLock};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
 
 use bytes::BytesMut;
-use crate::crypto;
 use crate::debug_log;
 use crate::json::{JsonContentHandler, JsonNumber, JsonParser};
-
-/// Per-conversation file lock to prevent concurrent read-modify-write races
-/// (e.g. multiple relay DM stream tasks appending the same event).
-fn conversation_locks() -> &'static Mutex<HashMap<String, std::sync::Arc<Mutex<()>>>> {
-    static INSTANCE: OnceLock<Mutex<HashMap<String, std::sync::Arc<Mutex<()>>>>> = OnceLock::new();
-    INSTANCE.get_or_init(|| Mutex::new(HashMap::new()))
-}
-
-fn lock_conversation(path: &str) -> std::sync::Arc<Mutex<()>> {
-    let mut map = conversation_locks().lock().unwrap();
-    map.entry(path.to_string()).or_insert_with(|| std::sync::Arc::new(Mutex::new(()))).clone()
-}
-use crate::nostr;
+use crate::nostr::{self, Event};
+use crate::signer::Signer;
+use crate::warn_log;
 
 fn messages_dir(config_dir: &str) -> String {
     Path::new(config_dir).join("messages").to_string_lossy().to_string()
@@ -65,12 +70,13 @@ fn normalize_hex(s: &str) -> String {
     s.trim().to_lowercase()
 }
 
-fn conversation_file_path(config_dir: &str, other_pubkey_hex: &str) -> String {
-    let other = normalize_hex(other_pubkey_hex);
-    Path::new(&messages_dir(config_dir))
-        .join(format!("{}.json", other))
-        .to_string_lossy()
-        .to_string()
+fn is_hex_pubkey(s: &str) -> bool {
+    s.len() == 64 && s.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Path of a conversation file with the given extension (log, idx, meta, or json for the old format).
+fn conversation_path(config_dir: &str, other_pubkey_hex: &str, extension: &str) -> PathBuf {
+    Path::new(&messages_dir(config_dir)).join(format!("{}.{}", normalize_hex(other_pubkey_hex), extension))
 }
 
 /// List conversation partner pubkeys (hex) by listing files in messages/.
@@ -85,11 +91,12 @@ pub fn list_conversations(config_dir: &str) -> Result<Vec<String>, String> {
         let entry = entry.map_err(|e| format!("Read dir entry: {}", e))?;
         let name = entry.file_name();
         let name = name.to_str().ok_or("Invalid filename")?;
-        if name.ends_with(".json") {
-            let pk = name.trim_end_matches(".json");
-            if pk.len() == 64 && pk.chars().all(|c| c.is_ascii_hexdigit()) {
-                pubkeys.push(pk.to_string());
-            }
+        let pk = match name.strip_suffix(".log").or_else(|| name.strip_suffix(".json")) {
+            Some(pk) => pk,
+            None => continue,
+        };
+        if is_hex_pubkey(pk) && !pubkeys.iter().any(|p| p == pk) {
+            pubkeys.push(pk.to_string());
         }
     }
     Ok(pubkeys)
@@ -103,6 +110,417 @@ pub struct DecryptedMessage {
     pub is_outgoing: bool,
 }
 
+/// Event as a single line of JSON (rumors without "sig"). Newlines inside strings are escaped
+/// by the serializer, so the only raw newlines are its own layout.
+fn event_line(event: &Event) -> String {
+    let json = if event.sig.is_empty() { nostr::rumor_to_json(event) } else { nostr::event_to_json(event) };
+    let mut line: String = json.lines().map(|l| l.trim()).collect();
+    line.push('\n');
+    line
+}
+
+// ============================================================
+// Conversation header
+// ============================================================
+
+/// Contents of a conversation's .meta file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConversationMeta {
+    pub last_created_at: u64,
+    pub count: u64,
+    /// Messages created at or before this time have been read.
+    pub last_read: u64,
+    /// Messages from the partner created after last_read.
+    pub unread: u64,
+    /// Length of the log this header describes; a different length means it is stale.
+    pub log_len: u64,
+}
+
+struct MetaHandler {
+    depth: i32,
+    key: Option<String>,
+    meta: ConversationMeta,
+    /// Headers written before unread counts were kept don't have one.
+    has_unread: bool,
+}
+
+impl JsonContentHandler for MetaHandler {
+    fn start_object(&mut self) {
+        self.depth += 1;
+    }
+
+    fn end_object(&mut self) {
+        self.depth -= 1;
+    }
+
+    fn start_array(&mut self) {
+        self.depth += 1;
+    }
+
+    fn end_array(&mut self) {
+        self.depth -= 1;
+    }
+
+    fn key(&mut self, key: &str) {
+        if self.depth == 1 {
+            self.key = Some(key.to_string());
+        }
+    }
+
+    fn string_value(&mut self, _value: &str) {}
+
+    fn number_value(&mut self, number: JsonNumber) {
+        if self.depth != 1 {
+            return;
+        }
+        let n = number.as_f64().max(0.0) as u64;
+        match self.key.as_deref() {
+            Some("last_created_at") => self.meta.last_created_at = n,
+            Some("count") => self.meta.count = n,
+            Some("last_read") => self.meta.last_read = n,
+            Some("unread") => {
+                self.meta.unread = n;
+                self.has_unread = true;
+            }
+            Some("log_len") => self.meta.log_len = n,
+            _ => {}
+        }
+    }
+
+    fn boolean_value(&mut self, _value: bool) {}
+    fn null_value(&mut self) {}
+}
+
+fn read_meta(path: &Path) -> Option<ConversationMeta> {
+    let contents = fs::read_to_string(path).ok()?;
+    let mut handler = MetaHandler { depth: 0, key: None, meta: ConversationMeta::default(), has_unread: false };
+    let mut parser = JsonParser::new();
+    let mut buf = BytesMut::from(contents.as_bytes());
+    parser.receive(&mut buf, &mut handler).ok()?;
+    parser.close(&mut handler).ok()?;
+    if !handler.has_unread {
+        return None;
+    }
+    Some(handler.meta)
+}
+
+/// Replace the .meta file: write a temporary file, fsync it, rename it over the old one.
+fn write_meta(path: &Path, meta: &ConversationMeta) -> io::Result<()> {
+    let tmp_path = path.with_extension("meta.tmp");
+    let mut out = File::create(&tmp_path)?;
+    out.write_all(format!(
+        r#"{{"last_created_at":{},"count":{},"last_read":{},"unread":{},"log_len":{}}}"#,
+        meta.last_created_at, meta.count, meta.last_read, meta.unread, meta.log_len
+    ).as_bytes())?;
+    out.sync_all()?;
+    drop(out);
+    fs::rename(&tmp_path, path)
+}
+
+// ============================================================
+// Conversation log and index
+// ============================================================
+
+struct Conversation {
+    log_path: PathBuf,
+    idx_path: PathBuf,
+    meta_path: PathBuf,
+    log: File,
+    idx: File,
+    /// Partner pubkey (hex, lowercase); events by them are incoming.
+    other: String,
+    /// Ids (lowercase) of every event in the log.
+    ids: HashSet<String>,
+    /// created_at of every incoming message, for unread counts.
+    incoming: Vec<u64>,
+    meta: ConversationMeta,
+}
+
+impl Conversation {
+    /// Open (or create) a conversation, migrating an old .json file and bringing the index and
+    /// header up to date with the log.
+    fn open(config_dir: &str, other_pubkey_hex: &str) -> Result<Conversation, String> {
+        ensure_messages_dir(config_dir).map_err(|e| format!("Create messages directory: {}", e))?;
+        let log_path = conversation_path(config_dir, other_pubkey_hex, "log");
+        let idx_path = conversation_path(config_dir, other_pubkey_hex, "idx");
+        let meta_path = conversation_path(config_dir, other_pubkey_hex, "meta");
+        let json_path = conversation_path(config_dir, other_pubkey_hex, "json");
+        if json_path.exists() {
+            if !log_path.exists() {
+                migrate_json(&json_path, &log_path, &idx_path)?;
+            }
+            // The log is complete once it exists, so the old file can go
+            fs::remove_file(&json_path).map_err(|e| format!("Remove migrated conversation file: {}", e))?;
+        }
+
+        let open = |path: &Path| OpenOptions::new().create(true).read(true).append(true).open(path);
+        let log = open(&log_path).map_err(|e| format!("Open conversation log: {}", e))?;
+        let idx = open(&idx_path).map_err(|e| format!("Open conversation index: {}", e))?;
+        let stored_meta = read_meta(&meta_path);
+        let mut conversation = Conversation {
+            log_path,
+            idx_path,
+            meta_path,
+            log,
+            idx,
+            other: normalize_hex(other_pubkey_hex),
+            ids: HashSet::new(),
+            incoming: Vec::new(),
+            meta: ConversationMeta {
+                last_read: stored_meta.as_ref().map(|m| m.last_read).unwrap_or(0),
+                ..ConversationMeta::default()
+            },
+        };
+        let indexed_to = conversation.load_index().map_err(|e| format!("Read conversation index: {}", e))?;
+        conversation.scan_log(indexed_to).map_err(|e| format!("Read conversation log: {}", e))?;
+        if stored_meta.as_ref() != Some(&conversation.meta) {
+            write_meta(&conversation.meta_path, &conversation.meta)
+                .map_err(|e| format!("Write conversation header: {}", e))?;
+        }
+        Ok(conversation)
+    }
+
+    /// Read the index into ids and the header counts. Returns the log offset it covers up to.
+    fn load_index(&mut self) -> io::Result<u64> {
+        let log_len = self.log.metadata()?.len();
+        let mut reader = BufReader::new(File::open(&self.idx_path)?);
+        let mut line = String::new();
+        let mut idx_offset: u64 = 0;
+        let mut indexed_to: u64 = 0;
+        loop {
+            line.clear();
+            let n = reader.read_line(&mut line)?;
+            if n == 0 {
+                break;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let parsed = match (line.ends_with('\n'), fields.as_slice()) {
+                (true, [id, created_at, offset, len, author]) => {
+                    match (created_at.parse::<u64>(), offset.parse::<u64>(), len.parse::<u64>()) {
+                        (Ok(c), Ok(o), Ok(l)) if o + l <= log_len => Some((id.to_string(), c, o + l, author.to_string())),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            };
+            let Some((id, created_at, end, author)) = parsed else {
+                // Torn, stale or old-format entry: drop it and everything after; scan_log
+                // re-indexes the rest
+                warn_log!("[messages] truncating index {} at offset {}", self.idx_path.display(), idx_offset);
+                self.idx.set_len(idx_offset)?;
+                break;
+            };
+            self.count(id, created_at, &author);
+            indexed_to = indexed_to.max(end);
+            idx_offset += n as u64;
+        }
+        Ok(indexed_to)
+    }
+
+    /// Index log records from `offset` on (written before a crash could index them), and
+    /// truncate a torn last record.
+    fn scan_log(&mut self, offset: u64) -> io::Result<()> {
+        let mut reader = BufReader::new(File::open(&self.log_path)?);
+        reader.seek(SeekFrom::Start(offset))?;
+        let mut offset = offset;
+        let mut line = String::new();
+        let mut recovered = 0;
+        loop {
+            line.clear();
+            let n = reader.read_line(&mut line)?;
+            if n == 0 {
+                break;
+            }
+            if !line.ends_with('\n') {
+                warn_log!("[messages] truncating partial record in {} at offset {}", self.log_path.display(), offset);
+                self.log.set_len(offset)?;
+                break;
+            }
+            match nostr::parse_rumor(line.trim_end()) {
+                Ok(event) => {
+                    self.index(&event, offset, n as u64)?;
+                    recovered += 1;
+                }
+                Err(e) => debug_log!("[messages] skipping unreadable record at offset {}: {}", offset, e),
+            }
+            offset += n as u64;
+        }
+        if recovered > 0 {
+            self.idx.sync_data()?;
+            debug_log!("[messages] indexed {} records of {}", recovered, self.log_path.display());
+        }
+        self.meta.log_len = offset;
+        Ok(())
+    }
+
+    /// Append an index entry for a log record and count it.
+    fn index(&mut self, event: &Event, offset: u64, len: u64) -> io::Result<()> {
+        let id = event.id.to_lowercase();
+        let author = event.pubkey.to_lowercase();
+        self.idx.write_all(format!("{} {} {} {} {}\n", id, event.created_at, offset, len, author).as_bytes())?;
+        self.count(id, event.created_at, &author);
+        Ok(())
+    }
+
+    /// Add an indexed event to the ids and the header counts, unless it is already there.
+    fn count(&mut self, id: String, created_at: u64, author: &str) {
+        if !self.ids.insert(id) {
+            return;
+        }
+        self.meta.count += 1;
+        self.meta.last_created_at = self.meta.last_created_at.max(created_at);
+        if author == self.other {
+            self.incoming.push(created_at);
+            if created_at > self.meta.last_read {
+                self.meta.unread += 1;
+            }
+        }
+    }
+
+    /// Move the read marker forward to `read_at` and recount unread messages. Returns how many
+    /// messages that marked read (0 if the marker was already there or later).
+    fn mark_read(&mut self, read_at: u64) -> Result<u64, String> {
+        if read_at <= self.meta.last_read {
+            return Ok(0);
+        }
+        let unread_before = self.meta.unread;
+        self.meta.last_read = read_at;
+        self.meta.unread = self.incoming.iter().filter(|&&t| t > read_at).count() as u64;
+        write_meta(&self.meta_path, &self.meta).map_err(|e| format!("Write conversation header: {}", e))?;
+        Ok(unread_before - self.meta.unread)
+    }
+
+    /// Append an event unless its id is already stored. Returns Ok(true) if it was appended.
+    fn append(&mut self, event: &Event) -> Result<bool, String> {
+        if self.ids.contains(&event.id.to_lowercase()) {
+            return Ok(false);
+        }
+        let line = event_line(event);
+        let offset = self.meta.log_len;
+        self.log.write_all(line.as_bytes()).map_err(|e| format!("Write conversation log: {}", e))?;
+        self.log.sync_data().map_err(|e| format!("Sync conversation log: {}", e))?;
+        self.meta.log_len += line.len() as u64;
+        self.index(event, offset, line.len() as u64).map_err(|e| format!("Write conversation index: {}", e))?;
+        self.idx.sync_data().map_err(|e| format!("Sync conversation index: {}", e))?;
+        write_meta(&self.meta_path, &self.meta).map_err(|e| format!("Write conversation header: {}", e))?;
+        Ok(true)
+    }
+
+    /// Every event in the log, in the order stored.
+    fn read_events(&mut self) -> Result<Vec<Event>, String> {
+        let mut contents = String::new();
+        self.log.seek(SeekFrom::Start(0)).map_err(|e| format!("Seek conversation log: {}", e))?;
+        self.log.read_to_string(&mut contents).map_err(|e| format!("Read conversation log: {}", e))?;
+        Ok(contents.lines().filter_map(|l| nostr::parse_rumor(l).ok()).collect())
+    }
+}
+
+/// Write the events of an old-format .json conversation file to a new log. The log is written
+/// under a temporary name and renamed into place when complete, so a crash leaves the .json
+/// file to migrate again.
+fn migrate_json(json_path: &Path, log_path: &Path, idx_path: &Path) -> Result<(), String> {
+    let contents = fs::read_to_string(json_path).map_err(|e| format!("Read conversation file: {}", e))?;
+    let events = parse_event_array(&contents)?;
+    let tmp_path = log_path.with_extension("log.tmp");
+    let mut out = File::create(&tmp_path).map_err(|e| format!("Write conversation log: {}", e))?;
+    let mut seen: HashSet<String> = HashSet::new();
+    for event in &events {
+        if seen.insert(event.id.to_lowercase()) {
+            out.write_all(event_line(event).as_bytes()).map_err(|e| format!("Write conversation log: {}", e))?;
+        }
+    }
+    out.sync_all().map_err(|e| format!("Sync conversation log: {}", e))?;
+    drop(out);
+    // Any index left over belongs to no log; the new one is built when the log is opened
+    if idx_path.exists() {
+        fs::remove_file(idx_path).map_err(|e| format!("Remove conversation index: {}", e))?;
+    }
+    fs::rename(&tmp_path, log_path).map_err(|e| format!("Write conversation log: {}", e))?;
+    debug_log!("[messages] migrated {} ({} events)", json_path.display(), seen.len());
+    Ok(())
+}
+
+/// Most conversations kept open at once. Each holds its log and index files open.
+const MAX_OPEN_CONVERSATIONS: usize = 32;
+
+/// Recently used conversations, by log path, with the tick of their last use. Appends only
+/// check the in-memory id set; a conversation's mutex serializes appends from concurrent relay
+/// DM stream tasks.
+struct OpenConversations {
+    entries: HashMap<PathBuf, (Arc<Mutex<Conversation>>, u64)>,
+    tick: u64,
+}
+
+impl OpenConversations {
+    fn get(&mut self, log_path: &Path) -> Option<Arc<Mutex<Conversation>>> {
+        self.tick += 1;
+        let tick = self.tick;
+        self.entries.get_mut(log_path).map(|(c, used)| {
+            *used = tick;
+            c.clone()
+        })
+    }
+
+    /// Closes the least recently used conversation nobody else holds, once at the limit.
+    /// One still in use is never closed, or a second instance could be opened beside it.
+    fn insert(&mut self, log_path: PathBuf, c: Arc<Mutex<Conversation>>) {
+        if self.entries.len() >= MAX_OPEN_CONVERSATIONS {
+            let idle = self
+                .entries
+                .iter()
+                .filter(|(_, (c, _))| Arc::strong_count(c) == 1)
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(path, _)| path.clone());
+            if let Some(path) = idle {
+                self.entries.remove(&path);
+            }
+        }
+        self.tick += 1;
+        self.entries.insert(log_path, (c, self.tick));
+    }
+}
+
+fn open_conversations() -> &'static Mutex<OpenConversations> {
+    static INSTANCE: OnceLock<Mutex<OpenConversations>> = OnceLock::new();
+    INSTANCE.get_or_init(|| Mutex::new(OpenConversations { entries: HashMap::new(), tick: 0 }))
+}
+
+fn conversation(config_dir: &str, other_pubkey_hex: &str) -> Result<Arc<Mutex<Conversation>>, String> {
+    let log_path = conversation_path(config_dir, other_pubkey_hex, "log");
+    let mut open = open_conversations().lock().unwrap();
+    if let Some(c) = open.get(&log_path) {
+        return Ok(c);
+    }
+    let c = Arc::new(Mutex::new(Conversation::open(config_dir, other_pubkey_hex)?));
+    open.insert(log_path, c.clone());
+    Ok(c)
+}
+
+/// A conversation's header, read from its .meta file if that is current, otherwise by opening
+/// the conversation (which migrates, re-indexes and rewrites the header as needed).
+fn conversation_meta(config_dir: &str, other_pubkey_hex: &str) -> Option<ConversationMeta> {
+    let log_path = conversation_path(config_dir, other_pubkey_hex, "log");
+    let cached = open_conversations().lock().unwrap().get(&log_path);
+    if let Some(c) = cached {
+        return Some(c.lock().unwrap().meta.clone());
+    }
+    if let (Some(meta), Ok(md)) = (read_meta(&conversation_path(config_dir, other_pubkey_hex, "meta")), fs::metadata(&log_path)) {
+        if meta.log_len == md.len() {
+            return Some(meta);
+        }
+    }
+    match conversation(config_dir, other_pubkey_hex) {
+        Ok(c) => {
+            let meta = c.lock().unwrap().meta.clone();
+            Some(meta)
+        }
+        Err(e) => {
+            warn_log!("[messages] conversation {}: {}", other_pubkey_hex, e);
+            None
+        }
+    }
+}
+
 // ============================================================
 // Push-parser handler for parsing an array of event objects
 // ============================================================
@@ -166,10 +584,8 @@ impl JsonContentHandler for EventArrayHandler {
 
     fn end_object(&mut self) {
         if self.depth == 2 {
-            // Finished one event object
-            if let (Some(id), Some(pubkey), Some(sig)) = 
-                (self.event_id.clone(), self.event_pubkey.clone(), self.event_sig.clone()) 
-            {
+            // Finished one event object (rumors have no sig)
+            if let (Some(id), Some(pubkey)) = (self.event_id.clone(), self.event_pubkey.clone()) {
                 self.events.push(nostr::Event {
                     id,
                     pubkey,
@@ -177,7 +593,7 @@ impl JsonContentHandler for EventArrayHandler {
                     kind: self.event_kind,
                     tags: self.event_tags.clone(),
                     content: self.event_content.clone(),
-                    sig,
+                    sig: self.event_sig.clone().unwrap_or_default(),
                 });
             }
         }
@@ -257,40 +673,39 @@ fn parse_event_array(json_str: &str) -> Result<Vec<nostr::Event>, String> {
     Ok(handler.events)
 }
 
-/// Read conversation file, decrypt each event, return messages sorted by created_at.
-pub fn get_messages(
+/// Content given to messages that could not be decrypted.
+pub const UNDECRYPTABLE: &str = "[unable to decrypt]";
+
+/// Read a conversation, decrypt each kind 4 event (kind 14 rumors are already plaintext),
+/// return messages sorted by created_at.
+pub async fn get_messages(
     config_dir: &str,
-    our_secret_hex: &str,
+    signer: &Signer,
     our_pubkey_hex: &str,
     other_pubkey_hex: &str,
 ) -> Result<Vec<DecryptedMessage>, String> {
-    let path = conversation_file_path(config_dir, other_pubkey_hex);
     let our = normalize_hex(our_pubkey_hex);
     let other = normalize_hex(other_pubkey_hex);
-
-    let contents = match fs::read_to_string(&path) {
-        Ok(c) => c,
-        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
-        Err(e) => return Err(format!("Read conversation file: {}", e)),
+    let events = {
+        let c = conversation(config_dir, &other)?;
+        let mut c = c.lock().unwrap();
+        c.read_events()?
     };
 
-    let events = parse_event_array(&contents)?;
-
     let mut messages: Vec<DecryptedMessage> = Vec::new();
-    let mut seen_ids: std::collections::HashSet<String> = std::collections::HashSet::new();
     for event in &events {
-        if event.kind != nostr::KIND_DM {
-            continue;
-        }
-        // Deduplicate by event ID (safety net for any races that wrote dupes)
-        let id_lower = event.id.to_lowercase();
-        if !seen_ids.insert(id_lower) {
+        if event.kind != nostr::KIND_DM && event.kind != nostr::KIND_CHAT_MESSAGE {
             continue;
         }
         let is_outgoing = event.pubkey.to_lowercase() == our;
-        let sender_pubkey = if is_outgoing { other.as_str() } else { event.pubkey.as_str() };
-        let plaintext = crypto::nip04_decrypt(&event.content, our_secret_hex, sender_pubkey)
-            .unwrap_or_else(|_| String::from("[unable to decrypt]"));
+        let plaintext = if event.kind == nostr::KIND_CHAT_MESSAGE {
+            event.content.clone()
+        } else {
+            let sender_pubkey = if is_outgoing { other.as_str() } else { event.pubkey.as_str() };
+            signer.nip04_decrypt(&event.content, sender_pubkey)
+                .await
+                .unwrap_or_else(|_| String::from(UNDECRYPTABLE))
+        };
         messages.push(DecryptedMessage {
             id: event.id.clone(),
             pubkey: event.pubkey.clone(),
@@ -303,104 +718,176 @@ pub fn get_messages(
     Ok(messages)
 }
 
-/// Append a raw kind 4 event to the conversation file (dedupe by event id).
+/// Append a raw kind 4 event or kind 14 rumor to the conversation log (dedupe by event id).
 /// Returns Ok(true) if the event was actually appended, Ok(false) if duplicate.
 pub fn append_raw_event(
     config_dir: &str,
     other_pubkey_hex: &str,
     raw_event_json: &str,
 ) -> Result<bool, String> {
-    let path = conversation_file_path(config_dir, other_pubkey_hex);
-    let new_event = nostr::parse_event(raw_event_json).map_err(|e| format!("Parse event: {}", e))?;
-    if new_event.kind != nostr::KIND_DM {
-        return Err(String::from("Event is not kind 4"));
-    }
-
-    let new_id = new_event.id.to_lowercase();
-
-    // Hold a per-conversation lock for the entire read-check-write cycle
-    // to prevent concurrent relay stream tasks from duplicating events.
-    let lock = lock_conversation(&path);
-    let _guard = lock.lock().unwrap();
-
-    if Path::new(&path).exists() {
-        let contents = fs::read_to_string(&path).map_err(|e| format!("Read file: {}", e))?;
-        // Dedup: search for the event ID in the raw file text
-        let search_pattern = format!("\"id\":\"{}\"", new_id);
-        if contents.to_lowercase().contains(&search_pattern) {
-            return Ok(false); // already present — duplicate
-        }
-        // Append: strip trailing ] and add ,event]
-        let trimmed = contents.trim_end();
-        if trimmed.ends_with(']') {
-            let mut out = String::from(&trimmed[..trimmed.len() - 1]);
-            if out.trim_end().ends_with('}') {
-                out.push(',');
-            }
-            out.push_str(raw_event_json);
-            out.push(']');
-            fs::write(&path, out).map_err(|e| format!("Write file: {}", e))?;
-        } else {
-            // File is malformed, rewrite
-            let mut out = String::from("[");
-            out.push_str(raw_event_json);
-            out.push(']');
-            fs::write(&path, out).map_err(|e| format!("Write file: {}", e))?;
-        }
-    } else {
-        // New file
-        let out = format!("[{}]", raw_event_json);
-        fs::write(&path, out).map_err(|e| format!("Write file: {}", e))?;
-    }
-
-    Ok(true) // genuinely new event
-}
-
-/// Get last event's created_at from a conversation file. Scans for the last "created_at": number.
-fn last_created_at(config_dir: &str, other_pubkey_hex: &str) -> Option<u64> {
-    let path = conversation_file_path(config_dir, other_pubkey_hex);
-    let contents = fs::read_to_string(&path).ok()?;
-    // Find last occurrence of "created_at": followed by digits
-    let pattern = "\"created_at\":";
-    let pos = contents.rfind(pattern)?;
-    let after = &contents[pos + pattern.len()..];
-    let after = after.trim_start();
-    let digits: String = after.chars().take_while(|c| c.is_ascii_digit()).collect();
-    digits.parse::<u64>().ok()
-}
-
-/// Count conversations with messages newer than `since` (unix timestamp).
-/// Returns the number of conversations that have at least one event with
-/// created_at > since.  This gives a "conversations with unread" count.
-pub fn count_unread_conversations(config_dir: &str, since: u64) -> u32 {
+    let new_event = nostr::parse_rumor(raw_event_json).map_err(|e| format!("Parse event: {}", e))?;
+    match new_event.kind {
+        nostr::KIND_DM if new_event.sig.is_empty() => return Err(String::from("Kind 4 event is not signed")),
+        nostr::KIND_DM | nostr::KIND_CHAT_MESSAGE => {}
+        _ => return Err(String::from("Event is not kind 4 or kind 14")),
+    }
+    let c = conversation(config_dir, other_pubkey_hex)?;
+    let mut c = c.lock().unwrap();
+    c.append(&new_event)
+}
+
+/// Identifier ("d" tag) of the NIP-78 application data event holding our synced read markers.
+pub const READ_MARKERS_D_TAG: &str = "plume/dm-read-markers";
+
+/// Total unread messages across all conversations.
+pub fn count_unread_messages(config_dir: &str) -> u64 {
     let convos = match list_conversations(config_dir) {
         Ok(c) => c,
         Err(_) => return 0,
     };
-    let mut count = 0u32;
-    for pk in &convos {
-        if let Some(ts) = last_created_at(config_dir, pk) {
-            if ts > since {
-                count += 1;
+    convos.iter()
+        .filter_map(|pk| conversation_meta(config_dir, pk))
+        .map(|meta| meta.unread)
+        .sum()
+}
+
+/// Number of messages stored in a conversation.
+pub fn message_count(config_dir: &str, other_pubkey_hex: &str) -> u64 {
+    conversation_meta(config_dir, other_pubkey_hex).map_or(0, |meta| meta.count)
+}
+
+/// Move a conversation's read marker forward to `read_at`. Returns how many messages that
+/// marked read.
+pub fn mark_conversation_read(config_dir: &str, other_pubkey_hex: &str, read_at: u64) -> Result<u64, String> {
+    let c = conversation(config_dir, other_pubkey_hex)?;
+    let mut c = c.lock().unwrap();
+    c.mark_read(read_at)
+}
+
+/// Move every conversation's read marker forward to `read_at`.
+pub fn mark_all_read(config_dir: &str, read_at: u64) -> Result<(), String> {
+    for pk in list_conversations(config_dir)? {
+        mark_conversation_read(config_dir, &pk, read_at)?;
+    }
+    Ok(())
+}
+
+/// Read markers of all conversations that have one: (partner pubkey, last_read).
+pub fn read_markers(config_dir: &str) -> Vec<(String, u64)> {
+    list_conversations(config_dir)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|pk| {
+            let last_read = conversation_meta(config_dir, &pk)?.last_read;
+            if last_read > 0 { Some((pk, last_read)) } else { None }
+        })
+        .collect()
+}
+
+/// Apply read markers from another device. Markers only move forward, and markers for
+/// conversations we have no messages in are skipped. Returns how many messages that marked read.
+pub fn merge_read_markers(config_dir: &str, markers: &[(String, u64)]) -> u64 {
+    let mut marked = 0;
+    for (pk, read_at) in markers {
+        let pk = normalize_hex(pk);
+        if !is_hex_pubkey(&pk)
+            || !(conversation_path(config_dir, &pk, "log").exists() || conversation_path(config_dir, &pk, "json").exists())
+        {
+            continue;
+        }
+        match mark_conversation_read(config_dir, &pk, *read_at) {
+            Ok(n) => marked += n,
+            Err(e) => {
+                warn_log!("[messages] read marker for {}: {}", pk, e);
             }
         }
     }
-    count
+    marked
 }
 
-/// List conversations with last_created_at for sorting.
+/// Read markers as the JSON object synced between devices: {"<partner pubkey>": last_read, ...}.
+pub fn read_markers_to_json(markers: &[(String, u64)]) -> String {
+    let fields: Vec<String> = markers.iter()
+        .map(|(pk, ts)| format!("\"{}\":{}", escape_json(pk), ts))
+        .collect();
+    format!("{{{}}}", fields.join(","))
+}
+
+struct ReadMarkersHandler {
+    depth: i32,
+    key: Option<String>,
+    markers: Vec<(String, u64)>,
+}
+
+impl JsonContentHandler for ReadMarkersHandler {
+    fn start_object(&mut self) {
+        self.depth += 1;
+    }
+
+    fn end_object(&mut self) {
+        self.depth -= 1;
+    }
+
+    fn start_array(&mut self) {
+        self.depth += 1;
+    }
+
+    fn end_array(&mut self) {
+        self.depth -= 1;
+    }
+
+    fn key(&mut self, key: &str) {
+        if self.depth == 1 {
+            self.key = Some(key.to_string());
+        }
+    }
+
+    fn string_value(&mut self, _value: &str) {}
+
+    fn number_value(&mut self, number: JsonNumber) {
+        if self.depth == 1 {
+            if let Some(key) = self.key.take() {
+                self.markers.push((key, number.as_f64().max(0.0) as u64));
+            }
+        }
+    }
+
+    fn boolean_value(&mut self, _value: bool) {}
+    fn null_value(&mut self) {}
+}
+
+/// Parse the synced read markers object (see read_markers_to_json).
+pub fn parse_read_markers(json_str: &str) -> Result<Vec<(String, u64)>, String> {
+    let mut handler = ReadMarkersHandler { depth: 0, key: None, markers: Vec::new() };
+    let mut parser = JsonParser::new();
+    let mut buf = BytesMut::from(json_str.as_bytes());
+    parser.receive(&mut buf, &mut handler).map_err(|e| format!("JSON parse error: {}", e))?;
+    parser.close(&mut handler).map_err(|e| format!("JSON parse error: {}", e))?;
+    Ok(handler.markers)
+}
+
+/// List conversations with last_created_at for sorting, their read marker and unread count.
 pub fn list_conversations_json(config_dir: &str) -> Result<String, String> {
-    let mut list: Vec<(String, u64)> = list_conversations(config_dir)?
+    let mut list: Vec<(String, ConversationMeta)> = list_conversations(config_dir)?
         .into_iter()
-        .map(|pk| (pk.clone(), last_created_at(config_dir, &pk).unwrap_or(0)))
+        .map(|pk| {
+            let meta = conversation_meta(config_dir, &pk).unwrap_or_default();
+            (pk, meta)
+        })
         .collect();
-    list.sort_by(|a, b| b.1.cmp(&a.1));
+    list.sort_by(|a, b| b.1.last_created_at.cmp(&a.1.last_created_at));
     let mut out = String::from("[");
-    for (i, (pk, ts)) in list.iter().enumerate() {
+    for (i, (pk, meta)) in list.iter().enumerate() {
         if i > 0 {
             out.push(',');
         }
-        out.push_str(&format!(r#"{{"other_pubkey":"{}","last_created_at":{}}}"#, escape_json(pk), ts));
+        out.push_str(&format!(
+            r#"{{"other_pubkey":"{}","last_created_at":{},"last_read":{},"unread":{}}}"#,
+            escape_json(pk),
+            meta.last_created_at,
+            meta.last_read,
+            meta.unread
+        ));
     }
     out.push(']');
     Ok(out)
@@ -439,3 +926,151 @@ pub fn messages_to_json(messages: &[DecryptedMessage]) -> String {
     out.push(']');
     out
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rumor(id: char, created_at: u64, content: &str) -> Event {
+        Event {
+            id: id.to_string().repeat(64),
+            pubkey: "c".repeat(64),
+            created_at,
+            kind: nostr::KIND_CHAT_MESSAGE,
+            tags: vec![vec![String::from("p"), "b".repeat(64)]],
+            content: content.to_string(),
+            sig: String::new(),
+        }
+    }
+
+    /// A fresh messages directory for one test.
+    fn messages_dir(name: &str) -> (std::path::PathBuf, String) {
+        let dir = std::env::temp_dir().join(format!("plume-messages-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let dir_str = dir.to_string_lossy().to_string();
+        ensure_messages_dir(&dir_str).unwrap();
+        (dir, dir_str)
+    }
+
+    /// A conversation in the old JSON array format: two messages, the first one twice.
+    fn write_old_conversation(dir: &str, other: &str) {
+        let (first, second) = (rumor('1', 100, "hi\nthere"), rumor('2', 200, "\"quoted\""));
+        let old = format!(
+            "[{},\n{},{}]",
+            nostr::rumor_to_json(&first),
+            nostr::rumor_to_json(&first),
+            nostr::rumor_to_json(&second)
+        );
+        fs::write(conversation_path(dir, other, "json"), old).unwrap();
+    }
+
+    /// A conversation with messages at 100, 150, 200 and 300, all from `other`.
+    fn write_conversation(dir: &str, other: &str) {
+        let mut c = Conversation::open(dir, other).unwrap();
+        for (id, created_at) in [('1', 100), ('2', 200), ('3', 150), ('4', 300)] {
+            c.append(&rumor(id, created_at, "text")).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_migrate_json_conversation() {
+        let (dir, dir_str) = messages_dir("migrate");
+        let other = "c".repeat(64);
+        write_old_conversation(&dir_str, &other);
+        let mut c = Conversation::open(&dir_str, &other).unwrap();
+        assert!(!conversation_path(&dir_str, &other, "json").exists());
+        assert_eq!((c.meta.count, c.meta.last_created_at), (2, 200));
+        let events = c.read_events().unwrap();
+        assert_eq!(events[0].content, "hi\nthere");
+        assert_eq!(events[1].content, "\"quoted\"");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_append_skips_known_messages() {
+        let (dir, dir_str) = messages_dir("append");
+        let other = "c".repeat(64);
+        let mut c = Conversation::open(&dir_str, &other).unwrap();
+        assert!(c.append(&rumor('1', 100, "one")).unwrap());
+        assert!(c.append(&rumor('3', 150, "three")).unwrap());
+        assert!(!c.append(&rumor('1', 100, "one")).unwrap());
+        let events = c.read_events().unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!((c.meta.count, c.meta.last_created_at), (2, 150));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_recover_unindexed_and_torn_records() {
+        let (dir, dir_str) = messages_dir("recover");
+        let other = "c".repeat(64);
+        write_old_conversation(&dir_str, &other);
+        drop(Conversation::open(&dir_str, &other).unwrap());
+
+        // A record that reached the log but not the index, then a torn one
+        let log_path = conversation_path(&dir_str, &other, "log");
+        let mut log = OpenOptions::new().append(true).open(&log_path).unwrap();
+        log.write_all(event_line(&rumor('4', 300, "four")).as_bytes()).unwrap();
+        log.write_all(b"{\"id\":\"55").unwrap();
+        drop(log);
+        let c = Conversation::open(&dir_str, &other).unwrap();
+        assert_eq!((c.meta.count, c.meta.last_created_at), (3, 300));
+        assert_eq!(c.meta.log_len, fs::metadata(&log_path).unwrap().len());
+        assert_eq!(read_meta(&conversation_path(&dir_str, &other, "meta")), Some(c.meta.clone()));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_list_conversations() {
+        let (dir, dir_str) = messages_dir("list");
+        let other = "c".repeat(64);
+        write_conversation(&dir_str, &other);
+        assert_eq!(list_conversations(&dir_str).unwrap(), vec![other.clone()]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_read_markers_json_round_trip() {
+        let markers = vec![("c".repeat(64), 150), ("d".repeat(64), 9)];
+        assert_eq!(parse_read_markers(&read_markers_to_json(&markers)).unwrap(), markers);
+    }
+
+    #[test]
+    fn test_count_unread_messages() {
+        let (dir, dir_str) = messages_dir("unread");
+        let other = "c".repeat(64);
+        write_conversation(&dir_str, &other);
+        assert_eq!(count_unread_messages(&dir_str), 4);
+        assert_eq!(mark_conversation_read(&dir_str, &other, 150).unwrap(), 2);
+        assert_eq!(count_unread_messages(&dir_str), 2);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_merge_read_markers_skips_unknown_conversations() {
+        let (dir, dir_str) = messages_dir("merge-markers");
+        let other = "c".repeat(64);
+        write_conversation(&dir_str, &other);
+        assert_eq!(merge_read_markers(&dir_str, &[(other.clone(), 150), ("d".repeat(64), 9)]), 2);
+        assert_eq!(read_markers(&dir_str), vec![(other.clone(), 150)]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_read_marker_never_moves_back() {
+        let (dir, dir_str) = messages_dir("marker-back");
+        let other = "c".repeat(64);
+        write_conversation(&dir_str, &other);
+        mark_conversation_read(&dir_str, &other, 150).unwrap();
+        assert_eq!(mark_conversation_read(&dir_str, &other, 100).unwrap(), 0);
+        assert_eq!(read_markers(&dir_str), vec![(other.clone(), 150)]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
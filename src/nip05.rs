@@ -0,0 +1,393 @@
+/*
+ * nip05.rs
+ * Copyright (C) 2026 Chris Burdess
+ *
+ * This file is part of Plume, a Nostr desktop client.
+ *
+ * Plume is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Plume is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Plume.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! NIP-05 identifiers: `name@domain` is looked up at
+//! `https://<domain>/.well-known/nostr.json?name=<name>`, whose `names` map it to a public key
+//! and whose `relays` give hints for that key. Lookups are parsed with the push JSON parser and
+//! cached per identifier.
+
+use bytes::BytesMut;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::config::escape_json_string;
+use crate::debug_log;
+use crate::json::{JsonContentHandler, JsonNumber, JsonParser};
+use crate::keys;
+
+/// How long a lookup the domain answered stays fresh (seconds).
+const LOOKUP_TTL_SECS: u64 = 3600;
+
+/// How long to remember that a domain could not be reached before asking again (seconds).
+const LOOKUP_FAILURE_TTL_SECS: u64 = 300;
+
+/// HTTP timeout for the nostr.json fetch (seconds).
+const FETCH_TIMEOUT_SECS: u64 = 10;
+
+/// A `name@domain` identifier, lowercased. The name `_` is the domain itself.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Nip05Identifier {
+    pub name: String,
+    pub domain: String,
+}
+
+/// Canonical `name@domain` form, also used as the cache key.
+impl fmt::Display for Nip05Identifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}@{}", self.name, self.domain)
+    }
+}
+
+/// What an identifier resolves to: a public key (hex) and the relays its domain lists for it.
+#[derive(Clone, Debug, Default)]
+pub struct Nip05Record {
+    pub pubkey: String,
+    pub relays: Vec<String>,
+}
+
+/// Outcome of checking an identifier against the public key of the profile claiming it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Nip05Status {
+    /// The domain maps the name to the profile's key.
+    Verified,
+    /// The domain maps the name to another key, or doesn't list it.
+    Mismatch,
+    /// The domain could not be reached or didn't serve a usable document.
+    Unreachable,
+}
+
+impl Nip05Status {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Nip05Status::Verified => "verified",
+            Nip05Status::Mismatch => "mismatch",
+            Nip05Status::Unreachable => "unreachable",
+        }
+    }
+}
+
+/// Parse `name@domain`, or a bare domain (meaning `_@domain`). Names may contain `a-z0-9-_.`
+/// and are case-insensitive; the domain may not contain anything that would change the URL.
+pub fn parse_identifier(input: &str) -> Option<Nip05Identifier> {
+    let trimmed = input.trim().to_lowercase();
+    let (name, domain) = match trimmed.split_once('@') {
+        Some((name, domain)) => (name.to_string(), domain.to_string()),
+        None => (String::from("_"), trimmed),
+    };
+    let name_ok = !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
+    let domain_ok = domain.contains('.')
+        && !domain.starts_with('.')
+        && !domain.ends_with('.')
+        && domain.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.');
+    if name_ok && domain_ok {
+        Some(Nip05Identifier { name, domain })
+    } else {
+        None
+    }
+}
+
+/// Whether `input` looks like a `name@domain` identifier rather than a key.
+pub fn is_identifier(input: &str) -> bool {
+    input.contains('@') && parse_identifier(input).is_some()
+}
+
+// ============================================================
+// Cache
+// ============================================================
+
+struct CacheEntry {
+    fetched_at: Instant,
+    /// Ok(None) records that the domain answered without listing the name.
+    lookup: Result<Option<Nip05Record>, String>,
+}
+
+fn lookup_cache() -> &'static Mutex<HashMap<String, CacheEntry>> {
+    static INSTANCE: OnceLock<Mutex<HashMap<String, CacheEntry>>> = OnceLock::new();
+    INSTANCE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn cache_lookup(key: &str) -> Option<Result<Option<Nip05Record>, String>> {
+    let cache = lookup_cache().lock().unwrap();
+    let entry = cache.get(key)?;
+    let ttl = if entry.lookup.is_ok() { LOOKUP_TTL_SECS } else { LOOKUP_FAILURE_TTL_SECS };
+    if entry.fetched_at.elapsed() < Duration::from_secs(ttl) {
+        Some(entry.lookup.clone())
+    } else {
+        None
+    }
+}
+
+/// Look an identifier up, from the cache if fresh, otherwise over HTTP.
+/// Ok(None) means the domain answered but doesn't list the name; Err means it couldn't be asked.
+async fn lookup(identifier: &Nip05Identifier) -> Result<Option<Nip05Record>, String> {
+    let key = identifier.to_string();
+    if let Some(cached) = cache_lookup(&key) {
+        return cached;
+    }
+    let result = fetch_document(identifier).await.map(|doc| doc.record_for(&identifier.name));
+    if let Err(ref e) = result {
+        debug_log!("[nip05] {}: {}", key, e);
+    }
+    lookup_cache().lock().unwrap().insert(key, CacheEntry {
+        fetched_at: Instant::now(),
+        lookup: result.clone(),
+    });
+    result
+}
+
+/// Resolve an identifier to its public key and relay hints.
+pub async fn resolve(identifier: &str) -> Result<Nip05Record, String> {
+    let parsed = parse_identifier(identifier)
+        .ok_or_else(|| format!("Invalid NIP-05 identifier: {}", identifier))?;
+    match lookup(&parsed).await? {
+        Some(record) => Ok(record),
+        None => Err(format!("{} is not listed by {}", parsed, parsed.domain)),
+    }
+}
+
+/// Check an identifier claimed by the profile with public key `pubkey` (hex). Also returns the
+/// record the domain served, if any.
+pub async fn verify(identifier: &str, pubkey: &str) -> Result<(Nip05Status, Option<Nip05Record>), String> {
+    let parsed = parse_identifier(identifier)
+        .ok_or_else(|| format!("Invalid NIP-05 identifier: {}", identifier))?;
+    Ok(match lookup(&parsed).await {
+        Ok(Some(record)) if record.pubkey.eq_ignore_ascii_case(pubkey) => (Nip05Status::Verified, Some(record)),
+        Ok(record) => (Nip05Status::Mismatch, record),
+        Err(_) => (Nip05Status::Unreachable, None),
+    })
+}
+
+async fn fetch_document(identifier: &Nip05Identifier) -> Result<Nip05Document, String> {
+    let url = format!("https://{}/.well-known/nostr.json?name={}", identifier.domain, identifier.name);
+    // NIP-05 requires redirects to be ignored, so a 3xx is a failed lookup
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(FETCH_TIMEOUT_SECS))
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .map_err(|e| format!("HTTP client: {}", e))?;
+    let resp = client
+        .get(&url)
+        .header("Accept", "application/json")
+        .send()
+        .await
+        .map_err(|e| format!("NIP-05 fetch: {}", e))?;
+    let status = resp.status();
+    if !status.is_success() {
+        return Err(format!("NIP-05 fetch: HTTP {}", status));
+    }
+    let body = resp.text().await.map_err(|e| format!("NIP-05 body: {}", e))?;
+    parse_document(&body)
+}
+
+// ============================================================
+// Push-parser handler for nostr.json
+// ============================================================
+
+/// The parts of a nostr.json document we use: name → public key, and public key → relays.
+#[derive(Debug, Default)]
+pub struct Nip05Document {
+    pub names: HashMap<String, String>,
+    pub relays: HashMap<String, Vec<String>>,
+}
+
+impl Nip05Document {
+    /// The record for `name`, if the document lists it.
+    pub fn record_for(&self, name: &str) -> Option<Nip05Record> {
+        let pubkey = self.names.get(&name.to_lowercase())?;
+        Some(Nip05Record {
+            pubkey: pubkey.clone(),
+            relays: self.relays.get(pubkey).cloned().unwrap_or_default(),
+        })
+    }
+}
+
+struct Nip05Handler {
+    depth: i32,
+    /// Key of the current top-level member (depth 1).
+    top_key: Option<String>,
+    /// Name within names, or public key within relays (depth 2).
+    section_key: Option<String>,
+    document: Nip05Document,
+}
+
+impl Nip05Handler {
+    fn new() -> Self {
+        Self {
+            depth: 0,
+            top_key: None,
+            section_key: None,
+            document: Nip05Document::default(),
+        }
+    }
+
+    fn in_top(&self, key: &str) -> bool {
+        self.top_key.as_deref() == Some(key)
+    }
+}
+
+impl JsonContentHandler for Nip05Handler {
+    fn start_object(&mut self) {
+        self.depth += 1;
+    }
+
+    fn end_object(&mut self) {
+        self.depth -= 1;
+    }
+
+    fn start_array(&mut self) {
+        self.depth += 1;
+    }
+
+    fn end_array(&mut self) {
+        self.depth -= 1;
+    }
+
+    fn key(&mut self, key: &str) {
+        match self.depth {
+            1 => {
+                self.top_key = Some(key.to_string());
+                self.section_key = None;
+            }
+            2 => self.section_key = Some(key.to_lowercase()),
+            _ => {}
+        }
+    }
+
+    fn string_value(&mut self, value: &str) {
+        let section = match self.section_key.clone() {
+            Some(s) => s,
+            None => return,
+        };
+        if self.depth == 2 && self.in_top("names") && keys::is_valid_hex_key(value) {
+            self.document.names.insert(section, value.to_lowercase());
+        } else if self.depth == 3 && self.in_top("relays") {
+            let url = value.trim();
+            if url.starts_with("wss://") || url.starts_with("ws://") {
+                self.document.relays.entry(section).or_default().push(url.to_string());
+            }
+        }
+    }
+
+    fn number_value(&mut self, _number: JsonNumber) {}
+
+    fn boolean_value(&mut self, _value: bool) {}
+
+    fn null_value(&mut self) {}
+}
+
+/// Parse a nostr.json document. Names mapped to anything but a hex public key are dropped.
+pub fn parse_document(body: &str) -> Result<Nip05Document, String> {
+    let mut handler = Nip05Handler::new();
+    let mut parser = JsonParser::new();
+    let mut buf = BytesMut::from(body.as_bytes());
+    parser.receive(&mut buf, &mut handler).map_err(|e| format!("NIP-05 parse error: {}", e))?;
+    parser.close(&mut handler).map_err(|e| format!("NIP-05 parse error: {}", e))?;
+    Ok(handler.document)
+}
+
+// ============================================================
+// JSON output
+// ============================================================
+
+/// Relay hints as a JSON array of strings.
+pub fn relays_to_json(relays: &[String]) -> String {
+    let quoted: Vec<String> = relays.iter().map(|r| format!("\"{}\"", escape_json_string(r))).collect();
+    format!("[{}]", quoted.join(","))
+}
+
+/// Verification result as JSON for the frontend. `pubkey` is the key the domain lists, if any.
+pub fn status_to_json(identifier: &str, status: Nip05Status, record: Option<&Nip05Record>) -> String {
+    let pubkey = match record {
+        Some(r) => format!("\"{}\"", escape_json_string(&r.pubkey)),
+        None => String::from("null"),
+    };
+    let relays = record.map(|r| relays_to_json(&r.relays)).unwrap_or_else(|| String::from("[]"));
+    format!(
+        "{{\"identifier\":\"{}\",\"status\":\"{}\",\"pubkey\":{},\"relays\":{}}}",
+        escape_json_string(identifier),
+        status.as_str(),
+        pubkey,
+        relays
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOB: &str = "b0635d6a9851d3aed0cd6c495b282167acf761729078d975fc341b22650b07b9";
+
+    fn sample_document() -> Nip05Document {
+        let body = format!(
+            r#"{{"names":{{"bob":"{}","eve":"not-a-key","_":"{}"}},
+                "relays":{{"{}":["wss://relay.example.com","https://not-a-relay"]}},"extra":{{"names":{{"x":1}}}}}}"#,
+            BOB,
+            BOB.to_uppercase(),
+            BOB
+        );
+        parse_document(&body).unwrap()
+    }
+
+    #[test]
+    fn test_parse_identifier() {
+        let id = parse_identifier(" Bob@Example.com ").unwrap();
+        assert_eq!(id.to_string(), "bob@example.com");
+        assert_eq!(parse_identifier("example.com").unwrap().name, "_");
+    }
+
+    #[test]
+    fn test_parse_identifier_rejects_bad_domains_and_names() {
+        assert!(parse_identifier("bob@example.com/evil?x").is_none());
+        assert!(parse_identifier("bob@localhost").is_none());
+        assert!(parse_identifier("b b@example.com").is_none());
+    }
+
+    #[test]
+    fn test_is_identifier() {
+        assert!(is_identifier("bob@example.com"));
+        assert!(!is_identifier("npub1xyz"));
+    }
+
+    #[test]
+    fn test_document_record_with_relays() {
+        let record = sample_document().record_for("Bob").unwrap();
+        assert_eq!(record.pubkey, BOB);
+        assert_eq!(record.relays, vec![String::from("wss://relay.example.com")]);
+    }
+
+    #[test]
+    fn test_document_skips_bad_keys_and_normalises_case() {
+        let doc = sample_document();
+        assert_eq!(doc.record_for("_").unwrap().pubkey, BOB);
+        assert!(doc.record_for("eve").is_none());
+    }
+
+    #[test]
+    fn test_status_to_json() {
+        let record = sample_document().record_for("bob").unwrap();
+        assert_eq!(
+            status_to_json("bob@example.com", Nip05Status::Verified, Some(&record)),
+            format!(r#"{{"identifier":"bob@example.com","status":"verified","pubkey":"{}","relays":["wss://relay.example.com"]}}"#, BOB)
+        );
+    }
+}
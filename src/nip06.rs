@@ -0,0 +1,156 @@
+/*
+ * nip06.rs
+ * Copyright (C) 2026 Chris Burdess
+ *
+ * This file is part of Plume, a Nostr desktop client.
+ *
+ * Plume is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Plume is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Plume.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! NIP-06: keys from a BIP-39 mnemonic seed phrase, derived with BIP-32 along
+//! `m/44'/1237'/<account>'/0/0`. One phrase can back several profiles, one per account index.
+
+use bip39::{Language, Mnemonic};
+use hmac::{Hmac, Mac};
+use secp256k1::{PublicKey, Scalar, SecretKey, SECP256K1};
+use sha2::Sha512;
+
+type HmacSha512 = Hmac<Sha512>;
+
+/// SLIP-44 coin type registered for Nostr.
+const NOSTR_COIN_TYPE: u32 = 1237;
+
+const HARDENED: u32 = 0x8000_0000;
+
+/// Words in a new phrase (128 bits of entropy).
+const NEW_MNEMONIC_WORDS: usize = 12;
+
+/// BIP-39 word list for an app locale. There is no German list, so German gets English.
+fn language_for_locale(locale: &str) -> Language {
+    match locale.split(['-', '_']).next().unwrap_or("") {
+        "fr" => Language::French,
+        "es" => Language::Spanish,
+        "it" => Language::Italian,
+        _ => Language::English,
+    }
+}
+
+/// A new random 12-word phrase in the word list for `locale`.
+pub fn generate_mnemonic(locale: &str) -> Result<String, String> {
+    let entropy: [u8; NEW_MNEMONIC_WORDS / 3 * 4] = rand::random();
+    Mnemonic::from_entropy_in(language_for_locale(locale), &entropy)
+        .map(|m| m.to_string())
+        .map_err(|e| format!("Failed to generate mnemonic: {}", e))
+}
+
+/// Parse a phrase in any supported word list, checking its words and checksum.
+pub fn parse_mnemonic(phrase: &str) -> Result<Mnemonic, String> {
+    let words: Vec<&str> = phrase.split_whitespace().collect();
+    Mnemonic::parse(words.join(" ").to_lowercase())
+        .map_err(|e| format!("Invalid mnemonic: {}", e))
+}
+
+/// The secret key (hex) for `account` of the seed from `phrase` and optional `passphrase`.
+/// `account` is a hardened index, so it must be below 2^31.
+pub fn mnemonic_to_secret_key(phrase: &str, passphrase: &str, account: u32) -> Result<String, String> {
+    if account >= HARDENED {
+        return Err(format!("Invalid account number (must be below {})", HARDENED));
+    }
+    let mnemonic = parse_mnemonic(phrase)?;
+    let seed = mnemonic.to_seed(passphrase);
+    let path = [44 | HARDENED, NOSTR_COIN_TYPE | HARDENED, account | HARDENED, 0, 0];
+    let key = derive_path(&seed, &path)?;
+    Ok(key.display_secret().to_string())
+}
+
+/// BIP-32 private key derivation from `seed` along `path` (indices with the hardened bit set
+/// where hardened).
+fn derive_path(seed: &[u8], path: &[u32]) -> Result<SecretKey, String> {
+    let (mut key, mut chain_code) = split_hmac(b"Bitcoin seed", seed)?;
+    for &index in path {
+        let mut data = Vec::with_capacity(37);
+        if index & HARDENED != 0 {
+            data.push(0);
+            data.extend_from_slice(&key.secret_bytes());
+        } else {
+            data.extend_from_slice(&PublicKey::from_secret_key(SECP256K1, &key).serialize());
+        }
+        data.extend_from_slice(&index.to_be_bytes());
+        let (tweak, child_chain_code) = split_hmac(&chain_code, &data)?;
+        let tweak = Scalar::from(tweak);
+        key = key
+            .add_tweak(&tweak)
+            .map_err(|_| String::from("Invalid derived key; try another account index"))?;
+        chain_code = child_chain_code;
+    }
+    Ok(key)
+}
+
+/// HMAC-SHA512 of `data`, split into a key (left half) and a chain code (right half).
+fn split_hmac(hmac_key: &[u8], data: &[u8]) -> Result<(SecretKey, [u8; 32]), String> {
+    let mut mac = HmacSha512::new_from_slice(hmac_key).map_err(|e| format!("HMAC error: {}", e))?;
+    mac.update(data);
+    let output = mac.finalize().into_bytes();
+    let key = SecretKey::from_slice(&output[..32])
+        .map_err(|_| String::from("Invalid derived key; try another account index"))?;
+    let mut chain_code = [0u8; 32];
+    chain_code.copy_from_slice(&output[32..]);
+    Ok((key, chain_code))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto;
+
+    const PHRASE: &str = "leader monkey parrot ring guide accident before fence cannon height naive bean";
+    const SECRET: &str = "7f7ff03d123792d6ac594bfa67bf6d0c0ab55b6b1fdb6249303fe861f1ccba9a";
+
+    #[test]
+    fn test_nip06_vector() {
+        let secret = mnemonic_to_secret_key(PHRASE, "", 0).unwrap();
+        assert_eq!(secret, SECRET);
+        assert_eq!(
+            crypto::get_public_key_from_secret(&secret).unwrap(),
+            "17162c921dc4d2518f9a101db33695df1afb56ab82f5ff3e5da6eec3ca5cd917"
+        );
+    }
+
+    #[test]
+    fn test_whitespace_and_case_ignored() {
+        assert_eq!(mnemonic_to_secret_key(&format!("  {}\n", PHRASE.to_uppercase()), "", 0).unwrap(), SECRET);
+    }
+
+    #[test]
+    fn test_accounts_give_different_keys() {
+        assert_ne!(mnemonic_to_secret_key(PHRASE, "", 1).unwrap(), SECRET);
+        // Account 2^31 would alias account 0 once hardened
+        assert!(mnemonic_to_secret_key(PHRASE, "", HARDENED).is_err());
+    }
+
+    #[test]
+    fn test_bad_checksum_rejected() {
+        assert!(parse_mnemonic("leader monkey parrot ring guide accident before fence cannon height naive naive").is_err());
+    }
+
+    #[test]
+    fn test_generate_in_each_language() {
+        for locale in ["en", "fr", "es", "it", "de"] {
+            let generated = generate_mnemonic(locale).unwrap();
+            assert_eq!(generated.split_whitespace().count(), NEW_MNEMONIC_WORDS);
+            assert_eq!(parse_mnemonic(&generated).unwrap().language(), language_for_locale(locale));
+            assert!(mnemonic_to_secret_key(&generated, "", 0).is_ok());
+        }
+    }
+}
@@ -0,0 +1,298 @@
+/*
+ * nip46.rs
+ * Copyright (C) 2026 Chris Burdess
+ *
+ * This file is part of Plume, a Nostr desktop client.
+ *
+ * Plume is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Plume is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Plume.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! NIP-46 (Nostr Connect) protocol: JSON-RPC requests and responses exchanged between a client
+//! and a remote signer as NIP-44 encrypted kind 24133 events, and the `bunker://` and
+//! `nostrconnect://` URIs used to pair them.
+
+use bytes::BytesMut;
+
+use crate::config::escape_json_string;
+use crate::crypto;
+use crate::json::{JsonContentHandler, JsonNumber, JsonParser};
+use crate::nostr::{Event, KIND_NOSTR_CONNECT};
+
+/// A NIP-46 message: a request has a method and params, a response a result or an error.
+#[derive(Debug, Clone, Default)]
+pub struct Message {
+    pub id: String,
+    pub method: Option<String>,
+    pub params: Vec<String>,
+    pub result: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Push parser handler for a NIP-46 message. Params are strings; anything else is skipped.
+struct MessageHandler {
+    message: Message,
+    depth: usize,
+    current_key: Option<String>,
+    in_params: bool,
+}
+
+impl JsonContentHandler for MessageHandler {
+    fn start_object(&mut self) {
+        self.depth += 1;
+    }
+    fn end_object(&mut self) {
+        self.depth = self.depth.saturating_sub(1);
+    }
+    fn start_array(&mut self) {
+        self.depth += 1;
+        if self.depth == 2 && self.current_key.as_deref() == Some("params") {
+            self.in_params = true;
+        }
+    }
+    fn end_array(&mut self) {
+        if self.depth == 2 {
+            self.in_params = false;
+        }
+        self.depth = self.depth.saturating_sub(1);
+    }
+    fn key(&mut self, key: &str) {
+        if self.depth == 1 {
+            self.current_key = Some(key.to_string());
+        }
+    }
+    fn string_value(&mut self, value: &str) {
+        if self.in_params {
+            if self.depth == 2 {
+                self.message.params.push(value.to_string());
+            }
+            return;
+        }
+        if self.depth != 1 {
+            return;
+        }
+        match self.current_key.as_deref() {
+            Some("id") => self.message.id = value.to_string(),
+            Some("method") => self.message.method = Some(value.to_string()),
+            Some("result") => self.message.result = Some(value.to_string()),
+            Some("error") => self.message.error = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    fn number_value(&mut self, _number: JsonNumber) {}
+    fn boolean_value(&mut self, _value: bool) {}
+    fn null_value(&mut self) {}
+}
+
+pub fn parse_message(json: &str) -> Result<Message, String> {
+    let mut handler = MessageHandler {
+        message: Message::default(),
+        depth: 0,
+        current_key: None,
+        in_params: false,
+    };
+    let mut parser = JsonParser::new();
+    let mut buf = BytesMut::from(json.as_bytes());
+    parser.receive(&mut buf, &mut handler).map_err(|e| format!("JSON parse error: {}", e))?;
+    parser.close(&mut handler).map_err(|e| format!("JSON parse error: {}", e))?;
+    if handler.message.id.is_empty() {
+        return Err(String::from("NIP-46 message has no id"));
+    }
+    Ok(handler.message)
+}
+
+pub fn request_to_json(id: &str, method: &str, params: &[String]) -> String {
+    let mut json = format!(
+        "{{\"id\":\"{}\",\"method\":\"{}\",\"params\":[",
+        escape_json_string(id),
+        escape_json_string(method)
+    );
+    for (i, param) in params.iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        json.push('"');
+        json.push_str(&escape_json_string(param));
+        json.push('"');
+    }
+    json.push_str("]}");
+    json
+}
+
+pub fn response_to_json(id: &str, result: &str, error: Option<&str>) -> String {
+    let mut json = format!(
+        "{{\"id\":\"{}\",\"result\":\"{}\"",
+        escape_json_string(id),
+        escape_json_string(result)
+    );
+    if let Some(error) = error {
+        json.push_str(",\"error\":\"");
+        json.push_str(&escape_json_string(error));
+        json.push('"');
+    }
+    json.push('}');
+    json
+}
+
+/// Random id for a request.
+pub fn new_request_id() -> String {
+    let bytes: [u8; 16] = rand::random();
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Kind 24133 event carrying `json` from `secret_key_hex` to `recipient_pubkey_hex`, NIP-44
+/// encrypted and signed.
+pub fn create_message_event(json: &str, secret_key_hex: &str, recipient_pubkey_hex: &str) -> Result<Event, String> {
+    let content = crypto::nip44_encrypt(json, secret_key_hex, recipient_pubkey_hex)?;
+    let pubkey = crypto::get_public_key_from_secret(secret_key_hex)?;
+    let tags = vec![vec![String::from("p"), recipient_pubkey_hex.to_string()]];
+    let mut event = crypto::new_event(&pubkey, KIND_NOSTR_CONNECT, tags, &content);
+    crypto::sign_event(&mut event, secret_key_hex)?;
+    Ok(event)
+}
+
+/// Decrypt and parse the message in a kind 24133 event addressed to us. Older signers still
+/// use NIP-04, recognisable by its "?iv=" suffix.
+pub fn open_message_event(event: &Event, secret_key_hex: &str) -> Result<Message, String> {
+    if event.kind != KIND_NOSTR_CONNECT {
+        return Err(format!("Not a NIP-46 message (kind {})", event.kind));
+    }
+    let json = if event.content.contains("?iv=") {
+        crypto::nip04_decrypt(&event.content, secret_key_hex, &event.pubkey)?
+    } else {
+        crypto::nip44_decrypt(&event.content, secret_key_hex, &event.pubkey)?
+    };
+    parse_message(&json)
+}
+
+/// A parsed `bunker://<remote-signer-pubkey>?relay=...&secret=...` URI.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BunkerUri {
+    pub remote_pubkey: String,
+    pub relays: Vec<String>,
+    pub secret: Option<String>,
+}
+
+pub fn parse_bunker_uri(uri: &str) -> Result<BunkerUri, String> {
+    let rest = uri
+        .trim()
+        .strip_prefix("bunker://")
+        .ok_or_else(|| String::from("Not a bunker:// URI"))?;
+    let (pubkey, query) = rest.split_once('?').unwrap_or((rest, ""));
+    let remote_pubkey = pubkey.to_lowercase();
+    if remote_pubkey.len() != 64 || !remote_pubkey.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(String::from("Invalid remote signer public key in bunker URI"));
+    }
+    let mut relays = Vec::new();
+    let mut secret = None;
+    for (name, value) in parse_query(query) {
+        match name.as_str() {
+            "relay" if !value.is_empty() && !relays.contains(&value) => relays.push(value),
+            "secret" if !value.is_empty() => secret = Some(value),
+            _ => {}
+        }
+    }
+    if relays.is_empty() {
+        return Err(String::from("Bunker URI has no relay"));
+    }
+    Ok(BunkerUri { remote_pubkey, relays, secret })
+}
+
+pub fn bunker_uri_to_string(bunker: &BunkerUri) -> String {
+    let mut uri = format!("bunker://{}", bunker.remote_pubkey);
+    let mut params: Vec<(&str, &str)> = bunker.relays.iter().map(|r| ("relay", r.as_str())).collect();
+    if let Some(ref secret) = bunker.secret {
+        params.push(("secret", secret));
+    }
+    append_query(&mut uri, &params);
+    uri
+}
+
+/// `nostrconnect://` URI for a remote signer to scan or paste: it connects back to
+/// `client_pubkey_hex` on `relays` and proves itself by returning `secret`.
+pub fn nostrconnect_uri(client_pubkey_hex: &str, relays: &[String], secret: &str, name: &str) -> String {
+    let mut uri = format!("nostrconnect://{}", client_pubkey_hex);
+    let mut params: Vec<(&str, &str)> = relays.iter().map(|r| ("relay", r.as_str())).collect();
+    params.push(("secret", secret));
+    params.push(("name", name));
+    append_query(&mut uri, &params);
+    uri
+}
+
+fn parse_query(query: &str) -> Vec<(String, String)> {
+    query
+        .split('&')
+        .filter(|p| !p.is_empty())
+        .map(|p| {
+            let (name, value) = p.split_once('=').unwrap_or((p, ""));
+            let value = value.replace('+', " ");
+            let value = urlencoding::decode(&value).map(|v| v.into_owned()).unwrap_or(value);
+            (name.to_string(), value)
+        })
+        .collect()
+}
+
+fn append_query(uri: &mut String, params: &[(&str, &str)]) {
+    for (i, (name, value)) in params.iter().enumerate() {
+        uri.push(if i == 0 { '?' } else { '&' });
+        uri.push_str(name);
+        uri.push('=');
+        uri.push_str(&urlencoding::encode(value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PUBKEY: &str = "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+
+    #[test]
+    fn test_request_round_trip() {
+        let params = vec![String::from("{\"kind\":1,\"content\":\"a \\\"b\\\"\"}"), String::from("x")];
+        let request = parse_message(&request_to_json("r1", "sign_event", &params)).unwrap();
+        assert_eq!(request.id, "r1");
+        assert_eq!(request.method.as_deref(), Some("sign_event"));
+        assert_eq!(request.params, params);
+    }
+
+    #[test]
+    fn test_parse_auth_url_response() {
+        let response = parse_message("{\"id\":\"r1\",\"result\":\"auth_url\",\"error\":\"https://signer.example/a?b=1\"}").unwrap();
+        assert_eq!(response.result.as_deref(), Some("auth_url"));
+        assert_eq!(response.error.as_deref(), Some("https://signer.example/a?b=1"));
+    }
+
+    #[test]
+    fn test_parse_null_error() {
+        assert!(parse_message("{\"id\":\"r2\",\"result\":\"ack\",\"error\":null}").unwrap().error.is_none());
+    }
+
+    #[test]
+    fn test_bunker_uri_round_trip() {
+        let bunker = parse_bunker_uri(&format!(
+            "bunker://{}?relay=wss%3A%2F%2Frelay.example&relay=wss://other.example&secret=s3cret",
+            PUBKEY
+        ))
+        .unwrap();
+        assert_eq!(bunker.relays, vec!["wss://relay.example", "wss://other.example"]);
+        assert_eq!(bunker.secret.as_deref(), Some("s3cret"));
+        assert_eq!(parse_bunker_uri(&bunker_uri_to_string(&bunker)).unwrap(), bunker);
+    }
+
+    #[test]
+    fn test_bunker_uri_rejects_missing_relay_and_bad_pubkey() {
+        assert!(parse_bunker_uri(&format!("bunker://{}", PUBKEY)).is_err());
+        assert!(parse_bunker_uri("bunker://npub1xyz?relay=wss://relay.example").is_err());
+    }
+}
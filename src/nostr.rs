@@ -23,6 +23,7 @@ use crate::json::{JsonContentHandler, JsonNumber, JsonParser};
 
 // A Nostr event - the fundamental data structure in Nostr
 // See: https://github.com/nostr-protocol/nips/blob/master/01.md
+#[derive(Clone)]
 pub struct Event {
     // Unique identifier (32-byte hex, SHA256 of serialized event)
     pub id: String,
@@ -54,15 +55,29 @@ pub const KIND_RECOMMEND_RELAY: u32 = 2; // Relay recommendation
 pub const KIND_CONTACTS: u32 = 3;       // Contact list / follows
 /// NIP-04: Encrypted direct message
 pub const KIND_DM: u32 = 4;
-#[allow(dead_code)]
+/// NIP-09: Deletion request (tags: ["e", event_id] / ["a", "kind:pubkey:d"], ["k", kind])
+pub const KIND_DELETION: u32 = 5;
 pub const KIND_REPOST: u32 = 6;         // Repost/boost of another note
-#[allow(dead_code)]
 pub const KIND_REACTION: u32 = 7;       // Reaction (like, emoji)
 pub const KIND_ZAP_REQUEST: u32 = 9734; // NIP-57 Lightning zap request
 #[allow(dead_code)]
 pub const KIND_LONG_FORM: u32 = 30023;  // Long-form content (articles)
+/// NIP-59: Seal (a signed, NIP-44 encrypted rumor)
+pub const KIND_SEAL: u32 = 13;
+/// NIP-17: Private chat message (only ever sent as an unsigned rumor inside a seal)
+pub const KIND_CHAT_MESSAGE: u32 = 14;
+/// NIP-59: Gift wrap (a seal encrypted and signed with a one-off key)
+pub const KIND_GIFT_WRAP: u32 = 1059;
 /// NIP-65: Relay list metadata (tags: ["r", "relay_url"] or ["r", "url", "read"/"write"])
 pub const KIND_RELAY_LIST: u32 = 10002;
+/// NIP-17: Relays a user wants to receive private messages on (tags: ["relay", url])
+pub const KIND_DM_RELAYS: u32 = 10050;
+/// NIP-42: Client authentication to a relay (tags: ["relay", url], ["challenge", challenge])
+pub const KIND_AUTH: u32 = 22242;
+/// NIP-46: Remote signing request or response (NIP-44 encrypted JSON-RPC, tags: ["p", recipient])
+pub const KIND_NOSTR_CONNECT: u32 = 24133;
+/// NIP-78: Application-specific data (parameterized replaceable, tags: ["d", identifier])
+pub const KIND_APP_DATA: u32 = 30078;
 
 // A filter for requesting events from relays
 #[derive(Clone)]
@@ -175,6 +190,32 @@ impl EventHandler {
         }
     }
 
+    /// Like take_event, but a rumor (NIP-59) has no signature.
+    fn take_rumor(&self) -> Result<Event, String> {
+        Ok(Event {
+            id: self.id.clone().ok_or("Missing 'id' field")?,
+            pubkey: self.pubkey.clone().ok_or("Missing 'pubkey' field")?,
+            created_at: self.created_at,
+            kind: self.kind,
+            tags: self.tags.clone(),
+            content: self.content.clone(),
+            sig: self.sig.clone().unwrap_or_default(),
+        })
+    }
+
+    /// An event to be signed: only kind, content, tags and created_at need be present.
+    fn take_unsigned(&self) -> Event {
+        Event {
+            id: self.id.clone().unwrap_or_default(),
+            pubkey: self.pubkey.clone().unwrap_or_default(),
+            created_at: self.created_at,
+            kind: self.kind,
+            tags: self.tags.clone(),
+            content: self.content.clone(),
+            sig: String::new(),
+        }
+    }
+
     fn take_event(&self) -> Result<Event, String> {
         Ok(Event {
             id: self.id.clone().ok_or("Missing 'id' field")?,
@@ -350,6 +391,20 @@ pub fn parse_event(json_str: &str) -> Result<Event, String> {
     handler.take_event()
 }
 
+// Parse an unsigned event (NIP-59 rumor); sig is left empty
+pub fn parse_rumor(json_str: &str) -> Result<Event, String> {
+    let mut handler = EventHandler::new();
+    parse_json_str(json_str, &mut handler)?;
+    handler.take_rumor()
+}
+
+// Parse an event someone asks us to sign (NIP-46); id, pubkey and sig may be missing
+pub fn parse_unsigned_event(json_str: &str) -> Result<Event, String> {
+    let mut handler = EventHandler::new();
+    parse_json_str(json_str, &mut handler)?;
+    Ok(handler.take_unsigned())
+}
+
 // Parse profile metadata from a kind 0 event's content
 pub fn parse_profile(content: &str) -> Result<ProfileMetadata, String> {
     let mut handler = ProfileHandler::new();
@@ -363,6 +418,15 @@ pub fn parse_profile(content: &str) -> Result<ProfileMetadata, String> {
 
 // Convert an Event to JSON string
 pub fn event_to_json(event: &Event) -> String {
+    event_to_json_with_sig(event, true)
+}
+
+// Convert an unsigned event (NIP-59 rumor) to JSON: like event_to_json without "sig"
+pub fn rumor_to_json(event: &Event) -> String {
+    event_to_json_with_sig(event, false)
+}
+
+fn event_to_json_with_sig(event: &Event, include_sig: bool) -> String {
     let mut json = String::new();
     json.push_str("{\n");
     
@@ -408,12 +472,15 @@ pub fn event_to_json(event: &Event) -> String {
     // content
     json.push_str("  \"content\": \"");
     json.push_str(&escape_json_string(&event.content));
-    json.push_str("\",\n");
+    json.push('"');
     
     // sig
-    json.push_str("  \"sig\": \"");
-    json.push_str(&escape_json_string(&event.sig));
-    json.push_str("\"\n");
+    if include_sig {
+        json.push_str(",\n  \"sig\": \"");
+        json.push_str(&escape_json_string(&event.sig));
+        json.push('"');
+    }
+    json.push('\n');
     
     json.push_str("}");
     
@@ -637,9 +704,10 @@ pub fn filter_replies_to_event(event_id: String, limit: u32) -> Filter {
     }
 }
 
-/// Get the recipient pubkey (hex) from a kind 4 event's "p" tag. Returns None if missing or not kind 4.
-pub fn get_recipient_pubkey_from_kind4(event: &Event) -> Option<String> {
-    if event.kind != KIND_DM {
+/// Get the recipient pubkey (hex) from a direct message's "p" tag: a kind 4 event or a kind 14
+/// rumor. Returns None if missing or not a direct message.
+pub fn get_dm_recipient_pubkey(event: &Event) -> Option<String> {
+    if event.kind != KIND_DM && event.kind != KIND_CHAT_MESSAGE {
         return None;
     }
     for tag in &event.tags {
@@ -650,11 +718,12 @@ pub fn get_recipient_pubkey_from_kind4(event: &Event) -> Option<String> {
     None
 }
 
-/// For a kind 4 event, the "other" party (conversation partner) is the one that is not us.
+/// For a kind 4 event or kind 14 rumor, the "other" party (conversation partner) is the one
+/// that is not us.
 pub fn other_pubkey_in_dm(event: &Event, our_pubkey_hex: &str) -> Option<String> {
     let our = our_pubkey_hex.to_lowercase();
     let sender = event.pubkey.to_lowercase();
-    let recipient = get_recipient_pubkey_from_kind4(event)?.to_lowercase();
+    let recipient = get_dm_recipient_pubkey(event)?.to_lowercase();
     if sender == our {
         Some(recipient)
     } else if recipient == our {
@@ -692,6 +761,35 @@ pub fn filter_dms_sent(our_pubkey_hex: &str, limit: u32, since: Option<u64>) ->
     }
 }
 
+/// Filter for NIP-59 gift wraps addressed to us: kind 1059 with #p = our pubkey. Wraps carry
+/// randomised timestamps, so callers should reach back two days further than they need.
+pub fn filter_gift_wraps(our_pubkey_hex: &str, limit: u32, since: Option<u64>) -> Filter {
+    Filter {
+        ids: None,
+        authors: None,
+        kinds: Some(vec![KIND_GIFT_WRAP]),
+        since,
+        until: None,
+        limit: Some(limit),
+        p_tags: Some(vec![our_pubkey_hex.to_string()]),
+        e_tags: None,
+    }
+}
+
+/// Create a filter for NIP-46 messages sent to `our_pubkey_hex`, optionally only from `author`.
+pub fn filter_nostr_connect(our_pubkey_hex: &str, author: Option<&str>, since: u64) -> Filter {
+    Filter {
+        ids: None,
+        authors: author.map(|a| vec![a.to_string()]),
+        kinds: Some(vec![KIND_NOSTR_CONNECT]),
+        since: Some(since),
+        until: None,
+        limit: None,
+        p_tags: Some(vec![our_pubkey_hex.to_string()]),
+        e_tags: None,
+    }
+}
+
 /// Create a filter to fetch events by their IDs (e.g. for bookmarks).
 pub fn filter_events_by_ids(ids: Vec<String>) -> Filter {
     Filter {
@@ -706,6 +804,28 @@ pub fn filter_events_by_ids(ids: Vec<String>) -> Filter {
     }
 }
 
+/// NIP-09: deletion requests by `authors` for any of the event ids.
+pub fn filter_deletions_for_events(ids: Vec<String>, authors: Vec<String>) -> Filter {
+    Filter {
+        ids: None,
+        authors: Some(authors),
+        kinds: Some(vec![KIND_DELETION]),
+        since: None,
+        until: None,
+        limit: None,
+        p_tags: None,
+        e_tags: Some(ids),
+    }
+}
+
+/// NIP-09: whether `deletion` is a deletion request naming `event` by id, from its own author.
+pub fn is_deleted_by(event: &Event, deletion: &Event) -> bool {
+    deletion.kind == KIND_DELETION
+        && event.kind != KIND_DELETION
+        && deletion.pubkey.eq_ignore_ascii_case(&event.pubkey)
+        && deletion.tags.iter().any(|t| t.len() >= 2 && t[0] == "e" && t[1].eq_ignore_ascii_case(&event.id))
+}
+
 // Create a filter for profile metadata (kind 0) by author
 pub fn filter_profile_by_author(author_pubkey: &str) -> Filter {
     Filter {
@@ -981,6 +1101,56 @@ pub fn filter_relay_list_by_author(author_pubkey: &str) -> Filter {
     }
 }
 
+/// Filter for a user's NIP-17 DM relay list (kind 10050).
+pub fn filter_dm_relays_by_author(author_pubkey: &str) -> Filter {
+    Filter {
+        ids: None,
+        authors: Some(vec![author_pubkey.to_string()]),
+        kinds: Some(vec![KIND_DM_RELAYS]),
+        since: None,
+        until: None,
+        limit: Some(1),
+        p_tags: None,
+        e_tags: None,
+    }
+}
+
+/// Filter for a user's NIP-78 application data (kind 30078). Relay filters here have no #d,
+/// so pick the wanted identifier from the results with d_tag.
+pub fn filter_app_data_by_author(author_pubkey: &str, limit: u32) -> Filter {
+    Filter {
+        ids: None,
+        authors: Some(vec![author_pubkey.to_string()]),
+        kinds: Some(vec![KIND_APP_DATA]),
+        since: None,
+        until: None,
+        limit: Some(limit),
+        p_tags: None,
+        e_tags: None,
+    }
+}
+
+/// Value of an event's first "d" tag (parameterized replaceable identifier), if any.
+pub fn d_tag(event: &Event) -> Option<&str> {
+    event.tags.iter()
+        .find(|t| t.len() >= 2 && t[0] == "d")
+        .map(|t| t[1].as_str())
+}
+
+/// Relay URLs from a kind 10050 event's "relay" tags.
+pub fn parse_dm_relays(event: &Event) -> Vec<String> {
+    let mut urls: Vec<String> = Vec::new();
+    for tag in &event.tags {
+        if tag.len() >= 2 && tag[0] == "relay" {
+            let url = tag[1].trim();
+            if !url.is_empty() && !urls.iter().any(|u| u == url) {
+                urls.push(url.to_string());
+            }
+        }
+    }
+    urls
+}
+
 pub fn parse_relay_list(event: &Event) -> Result<Vec<String>, String> {
     if event.kind != KIND_RELAY_LIST {
         return Err(format!("Expected kind 10002 event, got kind {}", event.kind));
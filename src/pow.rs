@@ -0,0 +1,278 @@
+/*
+ * pow.rs
+ * Copyright (C) 2026 Chris Burdess
+ *
+ * This file is part of Plume, a Nostr desktop client.
+ *
+ * Plume is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Plume is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Plume.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! NIP-13 proof of work. Outgoing events get a `["nonce", n, target]` tag mined until their id
+//! has `target` leading zero bits; incoming events get a difficulty that spam filters can
+//! compare against a minimum.
+//!
+//! Mining splits the id serialization around the nonce, so each attempt only hashes the nonce
+//! and what follows it on top of a hasher primed with everything before it.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use sha2::{Digest, Sha256};
+
+use crate::config::Config;
+use crate::crypto;
+use crate::nostr::Event;
+use crate::relay_info;
+
+/// Highest difficulty we will mine: each extra bit doubles the work, and 40 bits is already
+/// hours on a desktop.
+pub const MAX_DIFFICULTY: u32 = 40;
+
+/// Times an event rejected with "pow:" is re-mined and sent again.
+pub const MAX_RETRIES: usize = 2;
+
+/// Bits added on a "pow:" rejection that doesn't say what difficulty the relay wants.
+const RETRY_STEP: u32 = 4;
+
+/// How often mine reports progress.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How often the reporting thread checks for a result or a cancel.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Attempts a mining thread makes between updates of the shared counter.
+const ATTEMPT_BATCH: u64 = 4096;
+
+/// Placeholder nonce used to find where the nonce goes in the serialized event.
+const NONCE_PLACEHOLDER: &str = "0";
+
+/// Bumped by cancel_all; a mining run stops when it no longer matches the value it started with.
+static CANCEL_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Stop every mining run in progress. Their mine calls return an error.
+pub fn cancel_all() {
+    CANCEL_GENERATION.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Number of leading zero bits of an event id (hex).
+pub fn leading_zero_bits(id_hex: &str) -> u32 {
+    let mut bits = 0;
+    for c in id_hex.chars() {
+        let nibble = match c.to_digit(16) {
+            Some(n) => n,
+            None => break,
+        };
+        if nibble != 0 {
+            return bits + nibble.leading_zeros() - 28;
+        }
+        bits += 4;
+    }
+    bits
+}
+
+fn hash_leading_zero_bits(hash: &[u8]) -> u32 {
+    let mut bits = 0;
+    for &byte in hash {
+        if byte != 0 {
+            return bits + byte.leading_zeros();
+        }
+        bits += 8;
+    }
+    bits
+}
+
+/// Target difficulty the event's nonce tag commits to, if it has one.
+pub fn committed_target(event: &Event) -> Option<u32> {
+    event
+        .tags
+        .iter()
+        .find(|t| t.len() >= 3 && t[0] == "nonce")
+        .and_then(|t| t[2].trim().parse().ok())
+}
+
+/// Difficulty of a received event for spam filtering: the leading zero bits of its id, capped
+/// at the target its nonce tag commits to, so a lucky hash counts for no more than the author
+/// aimed for.
+pub fn difficulty(event: &Event) -> u32 {
+    let bits = leading_zero_bits(&event.id);
+    match committed_target(event) {
+        Some(target) => bits.min(target),
+        None => bits,
+    }
+}
+
+/// Difficulty to mine before publishing to `relay_urls`: the highest of our per-relay settings
+/// and the min_pow_difficulty the relays advertise in NIP-11 (cached documents only).
+pub fn required_difficulty(cfg: &Config, relay_urls: &[String]) -> u32 {
+    let mut required = 0;
+    for url in relay_urls {
+        required = required.max(cfg.relay_pow_difficulty(url));
+        if let Some(info) = relay_info::cached_relay_info(url) {
+            if let Some(min) = info.limitation.min_pow_difficulty {
+                required = required.max(min.min(u32::MAX as u64) as u32);
+            }
+        }
+    }
+    required.min(MAX_DIFFICULTY)
+}
+
+/// Difficulty to retry with after a "pow:" rejection. Relays usually name the difficulty they
+/// want ("pow: difficulty 18 is less than 20"), so take the largest number in the message;
+/// otherwise go RETRY_STEP bits above what was sent.
+pub fn difficulty_for_rejection(message: &str, sent: u32) -> u32 {
+    let named = message
+        .split(|c: char| !c.is_ascii_digit())
+        .filter_map(|s| s.parse::<u32>().ok())
+        .max()
+        .unwrap_or(0);
+    if named > sent {
+        named
+    } else {
+        sent + RETRY_STEP
+    }
+}
+
+/// Mine a nonce tag into `event` until its id has `target` leading zero bits, with one thread
+/// per core. Any existing nonce tag is replaced and the id is filled in; sign the event
+/// afterwards, without changing it. `progress` is called with the number of attempts so far
+/// every PROGRESS_INTERVAL. Blocks until a nonce is found or cancel_all is called.
+pub fn mine<F: FnMut(u64)>(mut event: Event, target: u32, mut progress: F) -> Result<Event, String> {
+    if target > MAX_DIFFICULTY {
+        return Err(format!("Proof of work difficulty {} is above the maximum of {}", target, MAX_DIFFICULTY));
+    }
+    let generation = CANCEL_GENERATION.load(Ordering::SeqCst);
+    event.tags.retain(|t| t.first().map(|s| s.as_str()) != Some("nonce"));
+    event.tags.push(vec![String::from("nonce"), String::from(NONCE_PLACEHOLDER), target.to_string()]);
+
+    // The nonce tag is the last tag, and content can't hold an unescaped quote, so the last
+    // match is ours
+    let serialized = crypto::serialize_event_for_id(&event)?;
+    let head = String::from("\"nonce\",\"");
+    let at = serialized
+        .rfind(&format!("{}{}\"", head, NONCE_PLACEHOLDER))
+        .ok_or_else(|| String::from("Failed to locate nonce tag"))?
+        + head.len();
+    let mut primed = Sha256::new();
+    primed.update(&serialized.as_bytes()[..at]);
+    let suffix = &serialized.as_bytes()[at + NONCE_PLACEHOLDER.len()..];
+
+    let threads = std::thread::available_parallelism().map(|n| n.get() as u64).unwrap_or(1);
+    let done = AtomicBool::new(false);
+    let found = AtomicU64::new(u64::MAX);
+    let attempts = AtomicU64::new(0);
+    let mut cancelled = false;
+    std::thread::scope(|scope| {
+        for first in 0..threads {
+            let (primed, done, found, attempts) = (&primed, &done, &found, &attempts);
+            scope.spawn(move || {
+                let mut nonce = first;
+                let mut batch = 0;
+                while !done.load(Ordering::Relaxed) {
+                    let mut hasher = primed.clone();
+                    hasher.update(nonce.to_string().as_bytes());
+                    hasher.update(suffix);
+                    if hash_leading_zero_bits(&hasher.finalize()) >= target {
+                        let _ = found.compare_exchange(u64::MAX, nonce, Ordering::SeqCst, Ordering::SeqCst);
+                        done.store(true, Ordering::SeqCst);
+                        break;
+                    }
+                    nonce += threads;
+                    batch += 1;
+                    if batch == ATTEMPT_BATCH {
+                        attempts.fetch_add(batch, Ordering::Relaxed);
+                        batch = 0;
+                    }
+                }
+                attempts.fetch_add(batch, Ordering::Relaxed);
+            });
+        }
+        let mut last_report = Instant::now();
+        while !done.load(Ordering::SeqCst) {
+            std::thread::sleep(POLL_INTERVAL);
+            if CANCEL_GENERATION.load(Ordering::SeqCst) != generation {
+                cancelled = true;
+                done.store(true, Ordering::SeqCst);
+            } else if last_report.elapsed() >= PROGRESS_INTERVAL {
+                progress(attempts.load(Ordering::Relaxed));
+                last_report = Instant::now();
+            }
+        }
+    });
+
+    let nonce = found.load(Ordering::SeqCst);
+    if cancelled && nonce == u64::MAX {
+        return Err(String::from("Proof of work cancelled"));
+    }
+    progress(attempts.load(Ordering::Relaxed));
+    if let Some(tag) = event.tags.last_mut() {
+        tag[1] = nonce.to_string();
+    }
+    event.id = crypto::compute_event_id(&event)?;
+    if leading_zero_bits(&event.id) < target {
+        return Err(String::from("Proof of work produced an id below the target difficulty"));
+    }
+    Ok(event)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// cancel_all stops every run in the process, so tests that mine take turns.
+    fn mining_lock() -> std::sync::MutexGuard<'static, ()> {
+        static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+        LOCK.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    #[test]
+    fn test_leading_zero_bits() {
+        assert_eq!(leading_zero_bits("000000000e9d97a1ab09fc381030b346cdd7a142ad57e6df0b46dc9bef6c7e2d"), 36);
+        assert_eq!(leading_zero_bits("ffff"), 0);
+        assert_eq!(leading_zero_bits("1fff"), 3);
+    }
+
+    #[test]
+    fn test_difficulty_for_rejection() {
+        assert_eq!(difficulty_for_rejection("pow: difficulty 18 is less than 24", 18), 24);
+        assert_eq!(difficulty_for_rejection("pow: not enough work", 8), 8 + RETRY_STEP);
+    }
+
+    #[test]
+    fn test_mine_reaches_target() {
+        let _lock = mining_lock();
+        let pubkey = "a".repeat(64);
+        let mut event = crypto::new_event(&pubkey, 1, vec![vec![String::from("nonce"), String::from("1"), String::from("99")]], "say \"hi\"");
+        event.created_at = 1_700_000_000;
+        let mut reports = 0;
+        let mined = mine(event, 10, |_| reports += 1).unwrap();
+        assert!(reports > 0);
+        assert_eq!(mined.id, crypto::compute_event_id(&mined).unwrap());
+        assert!(leading_zero_bits(&mined.id) >= 10);
+        assert_eq!(mined.tags.iter().filter(|t| t[0] == "nonce").count(), 1);
+        assert_eq!(committed_target(&mined), Some(10));
+        assert_eq!(difficulty(&mined), 10);
+    }
+
+    #[test]
+    fn test_cancel_stops_mining() {
+        let _lock = mining_lock();
+        let unmineable = crypto::new_event(&"a".repeat(64), 1, Vec::new(), "");
+        let canceller = std::thread::spawn(|| {
+            std::thread::sleep(Duration::from_millis(100));
+            cancel_all();
+        });
+        assert!(mine(unmineable, MAX_DIFFICULTY, |_| {}).is_err());
+        canceller.join().unwrap();
+    }
+}
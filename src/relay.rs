@@ -18,21 +18,23 @@
  * along with Plume.  If not, see <http://www.gnu.org/licenses/>.
  */
 
-//! Async relay connections using our WebSocket client and JSON push parser.
-//! All functions are async; no blocking code.
+//! Async relay operations using our WebSocket client and JSON push parser.
+//! Connections are shared through the relay pool; all functions are async, no blocking code.
 
 use bytes::BytesMut;
 use std::collections::HashMap;
-use std::sync::{Mutex, OnceLock};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::time::Instant;
 use tokio::sync::mpsc;
 use tokio::time::Duration;
 
 use crate::debug_log;
+use crate::event_store;
 use crate::json::{JsonContentHandler, JsonNumber, JsonParser};
 use crate::nostr;
-use crate::websocket::connection::WebSocketConnection;
-use crate::websocket::{WebSocketClient, WebSocketHandler};
+use crate::relay_pool::{relay_pool, SupervisedSubscription};
+use crate::websocket::connection::{WebSocketConfig, WebSocketConnection};
+use crate::websocket::WebSocketClient;
 
 /// Connection timeout for WebSocket connect (seconds).
 const CONNECT_TIMEOUT_SECS: u64 = 5;
@@ -59,7 +61,7 @@ fn relay_backoff_map() -> &'static Mutex<HashMap<String, RelayBackoffState>> {
 
 /// Calculate how many seconds to wait given a failure count.
 /// 10, 20, 40, 80, 160, 300, 300, ...
-fn backoff_seconds(failures: u32) -> u64 {
+pub fn backoff_seconds(failures: u32) -> u64 {
     if failures == 0 {
         return 0;
     }
@@ -120,10 +122,11 @@ pub fn record_relay_success(relay_url: &str) {
 }
 
 /// Connect to a relay with timeout and backoff.
-/// Multiple operations (feed, DM, profile fetch) may connect to the same relay
-/// concurrently — each gets its own WebSocket connection.  The backoff system
+/// Feed, DM, profile fetch and publish share one pooled connection per relay
+/// (see relay_pool); this is what the pool calls to open it.  The backoff system
 /// only prevents retries while a relay is in its cooldown window after a failure.
-pub async fn connect_to_relay(relay_url: &str) -> Result<WebSocketConnection, String> {
+/// `config` sets the connection's message size limit and keepalive.
+pub async fn connect_to_relay(relay_url: &str, config: WebSocketConfig) -> Result<WebSocketConnection, String> {
     // Check backoff — if the relay recently failed, skip it
     {
         let map = relay_backoff_map().lock().unwrap();
@@ -141,7 +144,7 @@ pub async fn connect_to_relay(relay_url: &str) -> Result<WebSocketConnection, St
 
     let result = tokio::time::timeout(
         Duration::from_secs(CONNECT_TIMEOUT_SECS),
-        WebSocketClient::connect(relay_url),
+        WebSocketClient::connect_with_config(relay_url, config),
     ).await;
 
     match result {
@@ -166,12 +169,16 @@ pub async fn connect_to_relay(relay_url: &str) -> Result<WebSocketConnection, St
 
 pub enum RelayMessage {
     Event {
-        _subscription_id: String,
+        subscription_id: String,
         event: nostr::Event,
     },
-    EndOfStoredEvents { _subscription_id: String },
+    EndOfStoredEvents { subscription_id: String },
     Notice { message: String },
     Ok { event_id: String, success: bool, message: String },
+    /// The relay ended (or refused) a subscription.
+    Closed { subscription_id: String, message: String },
+    /// NIP-42 authentication challenge.
+    Auth { challenge: String },
     Unknown { _raw: String },
 }
 
@@ -179,6 +186,56 @@ pub enum StreamMessage {
     Event(nostr::Event),
     Eose,
     Notice(String),
+    /// The relay closed the subscription; an Eose follows for feed streams.
+    Closed {
+        relay_url: String,
+        prefix: Option<MessagePrefix>,
+        message: String,
+    },
+}
+
+/// Machine-readable prefix of an OK or CLOSED message (NIP-01), e.g. "rate-limited: slow down".
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MessagePrefix {
+    Duplicate,
+    Pow,
+    Blocked,
+    RateLimited,
+    Invalid,
+    AuthRequired,
+    Restricted,
+    Error,
+}
+
+impl MessagePrefix {
+    /// Parse the prefix of a relay message. None if it has no standard prefix.
+    pub fn parse(message: &str) -> Option<MessagePrefix> {
+        let (prefix, _) = message.split_once(':')?;
+        match prefix {
+            "duplicate" => Some(MessagePrefix::Duplicate),
+            "pow" => Some(MessagePrefix::Pow),
+            "blocked" => Some(MessagePrefix::Blocked),
+            "rate-limited" => Some(MessagePrefix::RateLimited),
+            "invalid" => Some(MessagePrefix::Invalid),
+            "auth-required" => Some(MessagePrefix::AuthRequired),
+            "restricted" => Some(MessagePrefix::Restricted),
+            "error" => Some(MessagePrefix::Error),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MessagePrefix::Duplicate => "duplicate",
+            MessagePrefix::Pow => "pow",
+            MessagePrefix::Blocked => "blocked",
+            MessagePrefix::RateLimited => "rate-limited",
+            MessagePrefix::Invalid => "invalid",
+            MessagePrefix::AuthRequired => "auth-required",
+            MessagePrefix::Restricted => "restricted",
+            MessagePrefix::Error => "error",
+        }
+    }
 }
 
 // ============================================================
@@ -195,6 +252,7 @@ struct RelayMessageHandler {
     ok_event_id: Option<String>,
     ok_success: bool,
     ok_message: Option<String>,
+    closed_message: Option<String>,
     current_field: Option<String>,
     event_id: Option<String>,
     event_pubkey: Option<String>,
@@ -220,6 +278,7 @@ impl RelayMessageHandler {
             ok_event_id: None,
             ok_success: false,
             ok_message: None,
+            closed_message: None,
             current_field: None,
             event_id: None,
             event_pubkey: None,
@@ -241,11 +300,18 @@ impl RelayMessageHandler {
         }
         match self.msg_type.as_deref() {
             Some("EOSE") => Ok(RelayMessage::EndOfStoredEvents {
-                _subscription_id: self.second_str.clone().unwrap_or_default(),
+                subscription_id: self.second_str.clone().unwrap_or_default(),
             }),
             Some("NOTICE") => Ok(RelayMessage::Notice {
                 message: self.second_str.clone().unwrap_or_else(|| "Unknown notice".to_string()),
             }),
+            Some("CLOSED") => Ok(RelayMessage::Closed {
+                subscription_id: self.second_str.clone().unwrap_or_default(),
+                message: self.closed_message.clone().unwrap_or_default(),
+            }),
+            Some("AUTH") => Ok(RelayMessage::Auth {
+                challenge: self.second_str.clone().unwrap_or_default(),
+            }),
             Some("OK") => Ok(RelayMessage::Ok {
                 event_id: self.ok_event_id.clone().unwrap_or_default(),
                 success: self.ok_success,
@@ -293,7 +359,7 @@ impl JsonContentHandler for RelayMessageHandler {
                 sig: self.event_sig.clone().unwrap_or_default(),
             };
             self.result = Some(RelayMessage::Event {
-                _subscription_id: sub_id_owned,
+                subscription_id: sub_id_owned,
                 event: ev,
             });
         }
@@ -341,6 +407,8 @@ impl JsonContentHandler for RelayMessageHandler {
                 self.ok_event_id = Some(s);
             } else if self.top_level_index == 4 && self.msg_type.as_deref() == Some("OK") {
                 self.ok_message = Some(s);
+            } else if self.top_level_index == 3 && self.msg_type.as_deref() == Some("CLOSED") {
+                self.closed_message = Some(s);
             }
         } else if self.tags_depth == 2 {
             self.current_tag.push(s);
@@ -392,182 +460,117 @@ pub fn parse_relay_message(message: &str) -> Result<RelayMessage, String> {
     handler.take_result()
 }
 
-// ============================================================
-// WebSocket handler for Nostr relay streams
-// ============================================================
-
-/// WebSocket handler: parses each text frame as JSON and sends StreamMessage to tx.
-struct NostrRelayHandler {
-    tx: mpsc::UnboundedSender<StreamMessage>,
-    should_stop: bool,
-    exit_on_eose: bool,
-    filter_kind_dm: Option<u32>,
-}
-
-impl WebSocketHandler for NostrRelayHandler {
-    fn connected(&mut self) {}
-
-    fn text_frame(&mut self, data: &[u8]) {
-        let text = match std::str::from_utf8(data) {
-            Ok(t) => t,
-            Err(e) => {
-                debug_log!("[relay] invalid UTF-8 in text frame ({} bytes): {}", data.len(), e);
-                return;
-            }
-        };
-        debug_log!("[relay] text frame ({} bytes): {}",
-            text.len(),
-            match text.char_indices().nth(120) { Some((idx, _)) => &text[..idx], None => text }
-        );
-        match parse_relay_message(text) {
-            Ok(RelayMessage::Event { event, .. }) => {
-                debug_log!("[relay] EVENT kind={} id={}", event.kind, &event.id[..8.min(event.id.len())]);
-                let send = match self.filter_kind_dm {
-                    Some(kind) => event.kind == kind,
-                    None => true,
-                };
-                if send && self.tx.send(StreamMessage::Event(event)).is_err() {
-                    self.should_stop = true;
-                }
-            }
-            Ok(RelayMessage::EndOfStoredEvents { .. }) => {
-                debug_log!("[relay] EOSE");
-                let _ = self.tx.send(StreamMessage::Eose);
-                if self.exit_on_eose {
-                    self.should_stop = true;
-                }
-            }
-            Ok(RelayMessage::Notice { message }) => {
-                debug_log!("[relay] NOTICE: {}", message);
-                let _ = self.tx.send(StreamMessage::Notice(message));
-            }
-            Ok(_) => {
-                debug_log!("[relay] other message type");
-            }
-            Err(e) => {
-                debug_log!("[relay] parse error: {}", e);
-            }
-        }
-    }
-
-    fn binary_frame(&mut self, _data: &[u8]) {}
-
-    fn close(&mut self, _code: Option<u16>, _reason: &str) {
-        self.should_stop = true;
-    }
-
-    fn ping(&mut self, _data: &[u8]) {}
-    fn pong(&mut self, _data: &[u8]) {}
-
-    fn failed(&mut self, _error: &std::io::Error) {
-        self.should_stop = true;
-    }
-
-    fn should_stop(&self) -> bool {
-        self.should_stop
-    }
-}
-
 // ============================================================
 // Async stream functions
 // ============================================================
 
-/// Run one relay's feed stream. Each text frame is parsed and turned into StreamMessage.
+/// Run one relay's feed stream over the pooled connection. Ends (with a single Eose) on EOSE,
+/// timeout or connection failure; the subscription is closed on return.
 pub async fn run_relay_feed_stream(
     relay_url: String,
     filter: nostr::Filter,
     timeout_seconds: u32,
     tx: mpsc::UnboundedSender<StreamMessage>,
 ) {
-    let conn = match connect_to_relay(&relay_url).await {
-        Ok(c) => c,
-        Err(e) => {
-            debug_log!("[relay] feed stream: {}", e);
-            let _ = tx.send(StreamMessage::Eose); // signal completion so EOSE count isn't stuck
-            return;
-        }
-    };
-
-    debug_log!("Connected to {}", relay_url);
-
-    let subscription_id = format!(
-        "plume_{}",
-        std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_millis()
-    );
-    let filter_json = nostr::filter_to_json(&filter);
-    let req_message = format!("[\"REQ\",\"{}\",{}]", subscription_id, filter_json);
-
-    let mut conn = conn;
-    debug_log!("[relay] sending REQ to {}: {}", relay_url, req_message);
-    if let Err(e) = conn.send_text(req_message.as_bytes()).await {
-        debug_log!("[relay] failed to send REQ to {}: {}", relay_url, e);
-        let _ = tx.send(StreamMessage::Eose);
-        return;
-    }
-    debug_log!("[relay] REQ sent to {}, waiting for data (timeout {}s)...", relay_url, timeout_seconds);
-
-    let mut handler = NostrRelayHandler {
-        tx: tx.clone(),
-        should_stop: false,
-        exit_on_eose: true,
-        filter_kind_dm: None,
-    };
+    let mut sub = relay_pool().subscribe(&relay_url, "plume_", &[filter]);
+    debug_log!("[relay] REQ {} sent to {}, waiting for data (timeout {}s)...", sub.id(), relay_url, timeout_seconds);
 
     let timeout_duration = Duration::from_secs(timeout_seconds as u64);
-    match tokio::time::timeout(timeout_duration, conn.run(&mut handler)).await {
-        Ok(Ok(())) => debug_log!("[relay] run completed normally for {}", relay_url),
-        Ok(Err(e)) => {
-            debug_log!("[relay] run error for {}: {}", relay_url, e);
-            let _ = tx.send(StreamMessage::Eose); // ensure count isn't stuck on error
+    let stream = async {
+        while let Some(msg) = sub.recv().await {
+            match msg {
+                RelayMessage::Event { event, .. } => {
+                    debug_log!("[relay] EVENT kind={} id={}", event.kind, &event.id[..8.min(event.id.len())]);
+                    event_store::store_event(&event);
+                    if event_store::is_deleted(&event) {
+                        // Its author asked for it to be deleted (NIP-09)
+                        continue;
+                    }
+                    if tx.send(StreamMessage::Event(event)).is_err() {
+                        return true; // receiver gone, nobody is counting EOSEs
+                    }
+                }
+                RelayMessage::EndOfStoredEvents { .. } => {
+                    debug_log!("[relay] EOSE from {}", relay_url);
+                    let _ = tx.send(StreamMessage::Eose);
+                    return true;
+                }
+                RelayMessage::Notice { message } => {
+                    let _ = tx.send(StreamMessage::Notice(message));
+                }
+                RelayMessage::Closed { message, .. } => {
+                    // Refused or ended by the relay: report why and finish now rather than at the timeout
+                    debug_log!("[relay] CLOSED from {}: {}", relay_url, message);
+                    let _ = tx.send(StreamMessage::Closed {
+                        relay_url: relay_url.clone(),
+                        prefix: MessagePrefix::parse(&message),
+                        message,
+                    });
+                    let _ = tx.send(StreamMessage::Eose);
+                    return true;
+                }
+                _ => {}
+            }
+        }
+        false
+    };
+    match tokio::time::timeout(timeout_duration, stream).await {
+        Ok(true) => debug_log!("[relay] feed stream completed normally for {}", relay_url),
+        Ok(false) => {
+            debug_log!("[relay] connection to {} ended before EOSE", relay_url);
+            let _ = tx.send(StreamMessage::Eose); // signal completion so EOSE count isn't stuck
         }
         Err(_) => {
-            debug_log!("[relay] run timed out for {}", relay_url);
+            debug_log!("[relay] feed stream timed out for {}", relay_url);
             let _ = tx.send(StreamMessage::Eose); // ensure count isn't stuck on timeout
         }
     }
 }
 
-/// Run a long-lived DM subscription (kind 4) with two filters. Does not exit on EOSE.
+/// Seconds NIP-59 gift wraps may be backdated by; a resubscription reaches this far back.
+pub const GIFT_WRAP_SINCE_SLACK: u64 = 2 * 24 * 60 * 60;
+
+/// Run a long-lived DM subscription (kind 4 and kind 1059 gift wraps) over the pooled connection.
+/// Does not exit on EOSE, and resubscribes after a disconnect; returns when the receiver goes away.
 pub async fn run_relay_dm_stream(
     relay_url: String,
-    filter_received: nostr::Filter,
-    filter_sent: nostr::Filter,
+    filters: Vec<nostr::Filter>,
     tx: mpsc::UnboundedSender<StreamMessage>,
 ) {
-    let conn = match connect_to_relay(&relay_url).await {
-        Ok(c) => c,
-        Err(e) => {
-            debug_log!("[relay] DM stream: {}", e);
+    let mut sub = SupervisedSubscription::new(&relay_url, "plume_dm_", filters)
+        .since_slack(GIFT_WRAP_SINCE_SLACK);
+    let mut eose_seen = false;
+    loop {
+        let sent = match sub.recv().await {
+            Some(RelayMessage::Event { event, .. })
+                if event.kind == nostr::KIND_DM || event.kind == nostr::KIND_GIFT_WRAP =>
+            {
+                tx.send(StreamMessage::Event(event))
+            }
+            Some(RelayMessage::EndOfStoredEvents { .. }) if !eose_seen => {
+                eose_seen = true;
+                tx.send(StreamMessage::Eose)
+            }
+            Some(RelayMessage::Notice { message }) => tx.send(StreamMessage::Notice(message)),
+            Some(_) => Ok(()),
+            None => {
+                if tx.is_closed() {
+                    return; // nobody listening; don't reconnect
+                }
+                debug_log!("[relay] DM stream to {} disconnected, will resubscribe", relay_url);
+                if eose_seen {
+                    Ok(())
+                } else {
+                    // Don't leave the initial sync waiting on us while we are reconnecting
+                    eose_seen = true;
+                    tx.send(StreamMessage::Eose)
+                }
+            }
+        };
+        if sent.is_err() {
             return;
         }
-    };
-
-    let subscription_id = format!(
-        "plume_dm_{}",
-        std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_millis()
-    );
-    let f1 = nostr::filter_to_json(&filter_received);
-    let f2 = nostr::filter_to_json(&filter_sent);
-    let req_message = format!("[\"REQ\",\"{}\",{},{}]", subscription_id, f1, f2);
-
-    let mut conn = conn;
-    if conn.send_text(req_message.as_bytes()).await.is_err() {
-        return;
-    }
-
-    let mut handler = NostrRelayHandler {
-        tx,
-        should_stop: false,
-        exit_on_eose: false,
-        filter_kind_dm: Some(nostr::KIND_DM),
-    };
-    let _ = conn.run(&mut handler).await;
+    }
 }
 
 // ============================================================
@@ -603,6 +606,9 @@ pub async fn fetch_notes_from_relay(
             StreamMessage::Notice(msg) => {
                 debug_log!("Notice from {}: {}", relay_url, msg);
             }
+            StreamMessage::Closed { message, .. } => {
+                debug_log!("Subscription closed by {}: {}", relay_url, message);
+            }
         }
     }
 
@@ -610,6 +616,35 @@ pub async fn fetch_notes_from_relay(
     Ok(events)
 }
 
+/// Fetch one page of stored events from a relay. Unlike fetch_notes_from_relay, only a real
+/// EOSE counts as the end: a timeout, lost connection or CLOSED is an error, so an empty page
+/// reliably means the relay has nothing more that matches. Events are not added to the local
+/// event store.
+pub async fn fetch_page_from_relay(
+    relay_url: &str,
+    filter: &nostr::Filter,
+    timeout_seconds: u32,
+) -> Result<Vec<nostr::Event>, String> {
+    let mut sub = relay_pool().subscribe(relay_url, "plume_page_", std::slice::from_ref(filter));
+    let mut events: Vec<nostr::Event> = Vec::new();
+    let page = async {
+        while let Some(msg) = sub.recv().await {
+            match msg {
+                RelayMessage::Event { event, .. } => events.push(event),
+                RelayMessage::EndOfStoredEvents { .. } => return Ok(()),
+                RelayMessage::Closed { message, .. } => return Err(format!("Closed by {}: {}", relay_url, message)),
+                _ => {}
+            }
+        }
+        Err(format!("Connection to {} ended before EOSE", relay_url))
+    };
+    match tokio::time::timeout(Duration::from_secs(timeout_seconds as u64), page).await {
+        Ok(Ok(())) => Ok(events),
+        Ok(Err(e)) => Err(e),
+        Err(_) => Err(format!("Timed out waiting for {}", relay_url)),
+    }
+}
+
 /// Fetch profile metadata for a public key from a relay.
 pub async fn fetch_profile_from_relay(
     relay_url: &str,
@@ -663,7 +698,16 @@ pub async fn fetch_profile_from_relays(
             }
         }
     }
-    Ok(None)
+    // No relay had it (or none could be reached): fall back to the local event store
+    let filter = nostr::filter_profile_by_author(pubkey);
+    match event_store::query(&filter).into_iter().next() {
+        Some(event) => {
+            let mut profile = nostr::parse_profile(&event.content)?;
+            profile.created_at = Some(event.created_at);
+            Ok(Some(profile))
+        }
+        None => Ok(None),
+    }
 }
 
 /// Fetch a user's contact list (who they follow) from a relay.
@@ -813,132 +857,171 @@ pub async fn fetch_relay_list_from_relays(
     Ok(Vec::new())
 }
 
+/// Fetch a user's NIP-17 DM relays (kind 10050) from multiple relays: the newest list found.
+pub async fn fetch_dm_relays_from_relays(
+    relay_urls: &[String],
+    pubkey: &str,
+    timeout_seconds: u32,
+) -> Vec<String> {
+    let filter = nostr::filter_dm_relays_by_author(pubkey);
+    let mut best_event: Option<nostr::Event> = None;
+    for relay_url in relay_urls {
+        match fetch_notes_from_relay(relay_url, &filter, timeout_seconds).await {
+            Ok(events) => {
+                for event in events {
+                    if event.kind == nostr::KIND_DM_RELAYS
+                        && best_event.as_ref().is_none_or(|b| event.created_at > b.created_at)
+                    {
+                        best_event = Some(event);
+                    }
+                }
+            }
+            Err(e) => {
+                debug_log!("Error fetching DM relays from {}: {}", relay_url, e);
+            }
+        }
+    }
+    best_event.map(|e| nostr::parse_dm_relays(&e)).unwrap_or_default()
+}
+
+/// Latest NIP-78 application data event (kind 30078) with identifier `d_tag` by `pubkey`
+/// across the given relays.
+pub async fn fetch_app_data_from_relays(
+    relay_urls: &[String],
+    pubkey: &str,
+    d_tag: &str,
+    timeout_seconds: u32,
+) -> Option<nostr::Event> {
+    let filter = nostr::filter_app_data_by_author(pubkey, 50);
+    let mut best_event: Option<nostr::Event> = None;
+    for relay_url in relay_urls {
+        match fetch_notes_from_relay(relay_url, &filter, timeout_seconds).await {
+            Ok(events) => {
+                for event in events {
+                    if event.kind == nostr::KIND_APP_DATA
+                        && nostr::d_tag(&event) == Some(d_tag)
+                        && best_event.as_ref().is_none_or(|b| event.created_at > b.created_at)
+                    {
+                        best_event = Some(event);
+                    }
+                }
+            }
+            Err(e) => {
+                debug_log!("Error fetching app data from {}: {}", relay_url, e);
+            }
+        }
+    }
+    best_event
+}
+
 // ============================================================
 // Event Publishing (async)
 // ============================================================
 
+#[derive(Clone)]
 pub struct PublishResult {
     pub relay_url: String,
     pub success: bool,
     pub message: String,
+    /// Standard prefix of the relay's OK message, if it had one.
+    pub prefix: Option<MessagePrefix>,
 }
 
-/// Publish an event to a single relay and wait for OK response (async).
+/// Publish an event to a single relay over the pooled connection and wait for OK response (async).
 pub async fn publish_event_to_relay(
     relay_url: &str,
     event: &nostr::Event,
     timeout_seconds: u32,
 ) -> Result<PublishResult, String> {
-    // Connect with timeout and backoff
-    let mut conn = connect_to_relay(relay_url).await?;
-
-    // Send the EVENT message
-    let event_json = nostr::event_to_json(event);
-    let publish_message = format!("[\"EVENT\",{}]", event_json);
-    conn.send_text(publish_message.as_bytes()).await
-        .map_err(|e| format!("Failed to send to {}: {}", relay_url, e))?;
-
-    // Wait for OK response using a handler
-    let (tx, mut rx) = mpsc::unbounded_channel::<RelayMessage>();
-
-    struct PublishHandler {
-        tx: mpsc::UnboundedSender<RelayMessage>,
-        should_stop: bool,
-    }
-
-    impl WebSocketHandler for PublishHandler {
-        fn connected(&mut self) {}
-        fn text_frame(&mut self, data: &[u8]) {
-            if let Ok(text) = std::str::from_utf8(data) {
-                if let Ok(msg) = parse_relay_message(text) {
-                    match &msg {
-                        RelayMessage::Ok { .. } | RelayMessage::Notice { .. } => {
-                            self.should_stop = true;
-                        }
-                        _ => {}
-                    }
-                    let _ = self.tx.send(msg);
-                }
-            }
-        }
-        fn binary_frame(&mut self, _data: &[u8]) {}
-        fn close(&mut self, _code: Option<u16>, _reason: &str) { self.should_stop = true; }
-        fn ping(&mut self, _data: &[u8]) {}
-        fn pong(&mut self, _data: &[u8]) {}
-        fn failed(&mut self, _error: &std::io::Error) { self.should_stop = true; }
-        fn should_stop(&self) -> bool { self.should_stop }
-    }
-
-    let mut handler = PublishHandler { tx, should_stop: false };
     let timeout_duration = Duration::from_secs(timeout_seconds as u64);
-    let _ = tokio::time::timeout(timeout_duration, conn.run(&mut handler)).await;
-
-    // Check what we got
-    while let Ok(msg) = rx.try_recv() {
-        match msg {
-            RelayMessage::Ok { event_id, success, message } => {
-                if event_id == event.id {
-                    return Ok(PublishResult {
-                        relay_url: relay_url.to_string(),
-                        success,
-                        message,
-                    });
-                }
-            }
-            RelayMessage::Notice { message } => {
-                if message.contains(&event.id) || message.to_lowercase().contains("error") {
-                    return Ok(PublishResult {
-                        relay_url: relay_url.to_string(),
-                        success: false,
-                        message,
-                    });
-                }
-            }
-            _ => {}
-        }
+    match tokio::time::timeout(timeout_duration, relay_pool().publish(relay_url, event)).await {
+        Ok(Ok((success, message))) => Ok(PublishResult {
+            relay_url: relay_url.to_string(),
+            success,
+            prefix: MessagePrefix::parse(&message),
+            message,
+        }),
+        Ok(Err(e)) => Err(e),
+        Err(_) => Ok(PublishResult {
+            relay_url: relay_url.to_string(),
+            success: false,
+            message: String::from("Timeout waiting for response"),
+            prefix: None,
+        }),
     }
-
-    Ok(PublishResult {
-        relay_url: relay_url.to_string(),
-        success: false,
-        message: String::from("Timeout waiting for response"),
-    })
 }
 
-/// Publish an event to multiple relays.
+/// Publish an event to several relays concurrently. Each relay's result is also sent on
+/// `progress` as it arrives. Returns once `quorum` relays have accepted the event (0 = wait for
+/// all of them); the remaining relays keep being tried in the background and still report
+/// on `progress`.
 pub async fn publish_event_to_relays(
-    relay_urls: &Vec<String>,
+    relay_urls: &[String],
     event: &nostr::Event,
     timeout_seconds: u32,
+    quorum: usize,
+    progress: Option<mpsc::UnboundedSender<PublishResult>>,
 ) -> Vec<PublishResult> {
-    let mut results: Vec<PublishResult> = Vec::new();
-    
+    event_store::store_event(event);
+
+    let event = Arc::new(event.clone());
+    let (tx, mut rx) = mpsc::unbounded_channel();
     for relay_url in relay_urls {
-        match publish_event_to_relay(relay_url, event, timeout_seconds).await {
-            Ok(result) => {
-                debug_log!("Publish to {}: success={}, message={}", 
-                         result.relay_url, result.success, result.message);
-                results.push(result);
-            }
-            Err(e) => {
-                debug_log!("Error publishing to {}: {}", relay_url, e);
-                results.push(PublishResult {
-                    relay_url: relay_url.to_string(),
-                    success: false,
-                    message: e,
-                });
-            }
+        let relay_url = relay_url.clone();
+        let event = event.clone();
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            let result = match publish_event_to_relay(&relay_url, &event, timeout_seconds).await {
+                Ok(result) => result,
+                Err(e) => {
+                    debug_log!("Error publishing to {}: {}", relay_url, e);
+                    PublishResult {
+                        relay_url,
+                        success: false,
+                        message: e,
+                        prefix: None,
+                    }
+                }
+            };
+            debug_log!("Publish to {}: success={}, message={}",
+                     result.relay_url, result.success, result.message);
+            let _ = tx.send(result);
+        });
+    }
+    drop(tx);
+
+    let mut results: Vec<PublishResult> = Vec::new();
+    let mut accepted = 0;
+    while let Some(result) = rx.recv().await {
+        if let Some(ref progress) = progress {
+            let _ = progress.send(result.clone());
+        }
+        if result.success {
+            accepted += 1;
+        }
+        results.push(result);
+        if quorum > 0 && accepted >= quorum && results.len() < relay_urls.len() {
+            // Quorum reached: let the slower relays report in the background
+            tokio::spawn(async move {
+                while let Some(result) = rx.recv().await {
+                    if let Some(ref progress) = progress {
+                        let _ = progress.send(result);
+                    }
+                }
+            });
+            break;
         }
     }
-    
-    return results;
+    results
 }
 
 // ============================================================
 // JSON helpers
 // ============================================================
 
-pub fn publish_results_to_json(results: &Vec<PublishResult>) -> String {
+/// Results of a publish as JSON. `total_count` is the number of relays published to; relays
+/// that had not answered when the quorum was reached are counted in `pending_count`.
+pub fn publish_results_to_json(results: &[PublishResult], total_count: usize) -> String {
     let mut json = String::new();
     json.push_str("{");
     
@@ -947,18 +1030,14 @@ pub fn publish_results_to_json(results: &Vec<PublishResult>) -> String {
     json.push_str(&success_count.to_string());
     
     json.push_str(",\"total_count\":");
-    json.push_str(&results.len().to_string());
+    json.push_str(&total_count.to_string());
+
+    json.push_str(",\"pending_count\":");
+    json.push_str(&total_count.saturating_sub(results.len()).to_string());
     
     json.push_str(",\"results\":[");
     for (i, result) in results.iter().enumerate() {
-        json.push_str("{");
-        json.push_str("\"relay_url\":\"");
-        json.push_str(&escape_json_string(&result.relay_url));
-        json.push_str("\",\"success\":");
-        json.push_str(if result.success { "true" } else { "false" });
-        json.push_str(",\"message\":\"");
-        json.push_str(&escape_json_string(&result.message));
-        json.push_str("\"}");
+        write_publish_result(&mut json, result);
         if i < results.len() - 1 {
             json.push_str(",");
         }
@@ -969,6 +1048,35 @@ pub fn publish_results_to_json(results: &Vec<PublishResult>) -> String {
     return json;
 }
 
+/// One relay's publish result as JSON, tagged with the event id (payload of "publish-result").
+pub fn publish_result_to_json(event_id: &str, result: &PublishResult) -> String {
+    let mut json = String::from("{\"event_id\":\"");
+    json.push_str(&escape_json_string(event_id));
+    json.push_str("\",\"result\":");
+    write_publish_result(&mut json, result);
+    json.push('}');
+    json
+}
+
+fn write_publish_result(json: &mut String, result: &PublishResult) {
+    json.push_str("{\"relay_url\":\"");
+    json.push_str(&escape_json_string(&result.relay_url));
+    json.push_str("\",\"success\":");
+    json.push_str(if result.success { "true" } else { "false" });
+    json.push_str(",\"message\":\"");
+    json.push_str(&escape_json_string(&result.message));
+    json.push_str("\",\"reason\":");
+    match result.prefix {
+        Some(prefix) => {
+            json.push('"');
+            json.push_str(prefix.as_str());
+            json.push('"');
+        }
+        None => json.push_str("null"),
+    }
+    json.push('}');
+}
+
 fn escape_json_string(input: &str) -> String {
     let mut output = String::new();
     for c in input.chars() {
@@ -0,0 +1,448 @@
+/*
+ * relay_info.rs
+ * Copyright (C) 2026 Chris Burdess
+ *
+ * This file is part of Plume, a Nostr desktop client.
+ *
+ * Plume is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Plume is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Plume.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! NIP-11 relay information documents: fetched over HTTP with `Accept: application/nostr+json`,
+//! parsed with the push JSON parser, and cached per relay.
+
+use bytes::BytesMut;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::config::escape_json_string;
+use crate::debug_log;
+use crate::json::{JsonContentHandler, JsonNumber, JsonParser};
+
+/// How long a fetched document stays fresh (seconds).
+const INFO_TTL_SECS: u64 = 3600;
+
+/// How long to remember that a relay has no usable document before asking again (seconds).
+const INFO_FAILURE_TTL_SECS: u64 = 300;
+
+/// HTTP timeout for the document fetch (seconds).
+const FETCH_TIMEOUT_SECS: u64 = 10;
+
+/// `limitation` object of a relay information document. Absent fields are None.
+#[derive(Clone, Debug, Default)]
+pub struct RelayLimitation {
+    pub max_message_length: Option<u64>,
+    pub max_subscriptions: Option<u64>,
+    pub max_filters: Option<u64>,
+    pub max_limit: Option<u64>,
+    pub max_subid_length: Option<u64>,
+    pub max_event_tags: Option<u64>,
+    pub max_content_length: Option<u64>,
+    pub min_pow_difficulty: Option<u64>,
+    pub auth_required: Option<bool>,
+    pub payment_required: Option<bool>,
+    pub restricted_writes: Option<bool>,
+    pub created_at_lower_limit: Option<u64>,
+    pub created_at_upper_limit: Option<u64>,
+}
+
+/// One entry of the `fees` object: category is "admission", "subscription" or "publication".
+#[derive(Clone, Debug, Default)]
+pub struct RelayFee {
+    pub category: String,
+    pub amount: u64,
+    pub unit: String,
+    pub period: Option<u64>,
+    pub kinds: Vec<u32>,
+}
+
+/// NIP-11 relay information document.
+#[derive(Clone, Debug, Default)]
+pub struct RelayInfo {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub pubkey: Option<String>,
+    pub contact: Option<String>,
+    pub supported_nips: Vec<u32>,
+    pub software: Option<String>,
+    pub version: Option<String>,
+    pub icon: Option<String>,
+    pub limitation: RelayLimitation,
+    pub fees: Vec<RelayFee>,
+}
+
+// ============================================================
+// Cache
+// ============================================================
+
+struct CacheEntry {
+    fetched_at: Instant,
+    /// None records a failed fetch.
+    info: Option<RelayInfo>,
+}
+
+fn info_cache() -> &'static Mutex<HashMap<String, CacheEntry>> {
+    static INSTANCE: OnceLock<Mutex<HashMap<String, CacheEntry>>> = OnceLock::new();
+    INSTANCE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Cache lookup: Some(Some(info)) fresh document, Some(None) recent failure, None not cached.
+fn cache_lookup(relay_url: &str) -> Option<Option<RelayInfo>> {
+    let cache = info_cache().lock().unwrap();
+    let entry = cache.get(relay_url)?;
+    let ttl = if entry.info.is_some() { INFO_TTL_SECS } else { INFO_FAILURE_TTL_SECS };
+    if entry.fetched_at.elapsed() < Duration::from_secs(ttl) {
+        Some(entry.info.clone())
+    } else {
+        None
+    }
+}
+
+/// The cached document for a relay, if one has been fetched and is still fresh.
+/// Never touches the network, so it is cheap enough to call before every send.
+pub fn cached_relay_info(relay_url: &str) -> Option<RelayInfo> {
+    cache_lookup(relay_url).flatten()
+}
+
+/// Get a relay's information document, from the cache if fresh, otherwise over HTTP.
+pub async fn fetch_relay_info(relay_url: &str) -> Result<RelayInfo, String> {
+    match cache_lookup(relay_url) {
+        Some(Some(info)) => return Ok(info),
+        Some(None) => return Err(format!("No relay information available for {}", relay_url)),
+        None => {}
+    }
+    let result = fetch_relay_info_uncached(relay_url).await;
+    if let Err(ref e) = result {
+        debug_log!("[nip11] {}: {}", relay_url, e);
+    }
+    info_cache().lock().unwrap().insert(relay_url.to_string(), CacheEntry {
+        fetched_at: Instant::now(),
+        info: result.as_ref().ok().cloned(),
+    });
+    result
+}
+
+async fn fetch_relay_info_uncached(relay_url: &str) -> Result<RelayInfo, String> {
+    let http_url = relay_http_url(relay_url)?;
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(FETCH_TIMEOUT_SECS))
+        .build()
+        .map_err(|e| format!("HTTP client: {}", e))?;
+    let resp = client
+        .get(&http_url)
+        .header("Accept", "application/nostr+json")
+        .send()
+        .await
+        .map_err(|e| format!("Relay info fetch: {}", e))?;
+    let status = resp.status();
+    if !status.is_success() {
+        return Err(format!("Relay info fetch: HTTP {}", status));
+    }
+    let body = resp.text().await.map_err(|e| format!("Relay info body: {}", e))?;
+    parse_relay_info(&body)
+}
+
+/// The HTTP(S) URL serving a relay's information document (ws → http, wss → https).
+fn relay_http_url(relay_url: &str) -> Result<String, String> {
+    let mut url = url::Url::parse(relay_url).map_err(|e| format!("Invalid relay URL: {}", e))?;
+    let scheme = match url.scheme() {
+        "ws" => "http",
+        "wss" => "https",
+        other => return Err(format!("Relay URL scheme must be ws or wss, not {}", other)),
+    };
+    url.set_scheme(scheme).map_err(|_| String::from("Invalid relay URL"))?;
+    Ok(url.to_string())
+}
+
+// ============================================================
+// Push-parser handler for relay information documents
+// ============================================================
+
+struct RelayInfoHandler {
+    depth: i32,
+    /// Key of the current top-level member (depth 1).
+    top_key: Option<String>,
+    /// Key within the limitation object, or the fee category within fees (depth 2).
+    section_key: Option<String>,
+    /// Key within a fee object (depth 4).
+    fee_key: Option<String>,
+    fee: RelayFee,
+    info: RelayInfo,
+}
+
+impl RelayInfoHandler {
+    fn new() -> Self {
+        Self {
+            depth: 0,
+            top_key: None,
+            section_key: None,
+            fee_key: None,
+            fee: RelayFee::default(),
+            info: RelayInfo::default(),
+        }
+    }
+
+    fn in_top(&self, key: &str) -> bool {
+        self.top_key.as_deref() == Some(key)
+    }
+}
+
+impl JsonContentHandler for RelayInfoHandler {
+    fn start_object(&mut self) {
+        self.depth += 1;
+        if self.depth == 4 && self.in_top("fees") {
+            self.fee = RelayFee {
+                category: self.section_key.clone().unwrap_or_default(),
+                ..RelayFee::default()
+            };
+            self.fee_key = None;
+        }
+    }
+
+    fn end_object(&mut self) {
+        if self.depth == 4 && self.in_top("fees") {
+            let fee = std::mem::take(&mut self.fee);
+            self.info.fees.push(fee);
+        }
+        self.depth -= 1;
+    }
+
+    fn start_array(&mut self) {
+        self.depth += 1;
+    }
+
+    fn end_array(&mut self) {
+        self.depth -= 1;
+    }
+
+    fn key(&mut self, key: &str) {
+        match self.depth {
+            1 => {
+                self.top_key = Some(key.to_string());
+                self.section_key = None;
+            }
+            2 => self.section_key = Some(key.to_string()),
+            4 => self.fee_key = Some(key.to_string()),
+            _ => {}
+        }
+    }
+
+    fn string_value(&mut self, value: &str) {
+        if self.depth == 1 {
+            let v = Some(value.to_string());
+            match self.top_key.as_deref() {
+                Some("name") => self.info.name = v,
+                Some("description") => self.info.description = v,
+                Some("pubkey") => self.info.pubkey = v,
+                Some("contact") => self.info.contact = v,
+                Some("software") => self.info.software = v,
+                Some("version") => self.info.version = v,
+                Some("icon") => self.info.icon = v,
+                _ => {}
+            }
+        } else if self.depth == 4 && self.in_top("fees") && self.fee_key.as_deref() == Some("unit") {
+            self.fee.unit = value.to_string();
+        }
+    }
+
+    fn number_value(&mut self, number: JsonNumber) {
+        let n = number.as_f64().max(0.0) as u64;
+        if self.depth == 2 && self.in_top("supported_nips") {
+            self.info.supported_nips.push(n as u32);
+        } else if self.depth == 2 && self.in_top("limitation") {
+            let l = &mut self.info.limitation;
+            match self.section_key.as_deref() {
+                Some("max_message_length") => l.max_message_length = Some(n),
+                Some("max_subscriptions") => l.max_subscriptions = Some(n),
+                Some("max_filters") => l.max_filters = Some(n),
+                Some("max_limit") => l.max_limit = Some(n),
+                Some("max_subid_length") => l.max_subid_length = Some(n),
+                Some("max_event_tags") => l.max_event_tags = Some(n),
+                Some("max_content_length") => l.max_content_length = Some(n),
+                Some("min_pow_difficulty") => l.min_pow_difficulty = Some(n),
+                Some("created_at_lower_limit") => l.created_at_lower_limit = Some(n),
+                Some("created_at_upper_limit") => l.created_at_upper_limit = Some(n),
+                _ => {}
+            }
+        } else if self.depth == 4 && self.in_top("fees") {
+            match self.fee_key.as_deref() {
+                Some("amount") => self.fee.amount = n,
+                Some("period") => self.fee.period = Some(n),
+                _ => {}
+            }
+        } else if self.depth == 5 && self.in_top("fees") && self.fee_key.as_deref() == Some("kinds") {
+            self.fee.kinds.push(n as u32);
+        }
+    }
+
+    fn boolean_value(&mut self, value: bool) {
+        if self.depth == 2 && self.in_top("limitation") {
+            let l = &mut self.info.limitation;
+            match self.section_key.as_deref() {
+                Some("auth_required") => l.auth_required = Some(value),
+                Some("payment_required") => l.payment_required = Some(value),
+                Some("restricted_writes") => l.restricted_writes = Some(value),
+                _ => {}
+            }
+        }
+    }
+
+    fn null_value(&mut self) {}
+}
+
+/// Parse a relay information document.
+pub fn parse_relay_info(body: &str) -> Result<RelayInfo, String> {
+    let mut handler = RelayInfoHandler::new();
+    let mut parser = JsonParser::new();
+    let mut buf = BytesMut::from(body.as_bytes());
+    parser.receive(&mut buf, &mut handler).map_err(|e| format!("Relay info parse error: {}", e))?;
+    parser.close(&mut handler).map_err(|e| format!("Relay info parse error: {}", e))?;
+    Ok(handler.info)
+}
+
+// ============================================================
+// JSON output
+// ============================================================
+
+/// Relay information as JSON for the frontend, with the relay URL it describes.
+pub fn relay_info_to_json(relay_url: &str, info: &RelayInfo) -> String {
+    let mut json = String::from("{");
+    write_string(&mut json, "url", Some(relay_url));
+    for (name, value) in [
+        ("name", &info.name),
+        ("description", &info.description),
+        ("pubkey", &info.pubkey),
+        ("contact", &info.contact),
+        ("software", &info.software),
+        ("version", &info.version),
+        ("icon", &info.icon),
+    ] {
+        json.push(',');
+        write_string(&mut json, name, value.as_deref());
+    }
+
+    json.push_str(",\"supported_nips\":[");
+    let nips: Vec<String> = info.supported_nips.iter().map(|n| n.to_string()).collect();
+    json.push_str(&nips.join(","));
+    json.push(']');
+
+    let l = &info.limitation;
+    json.push_str(",\"limitation\":{");
+    let mut fields: Vec<String> = Vec::new();
+    for (name, value) in [
+        ("max_message_length", l.max_message_length),
+        ("max_subscriptions", l.max_subscriptions),
+        ("max_filters", l.max_filters),
+        ("max_limit", l.max_limit),
+        ("max_subid_length", l.max_subid_length),
+        ("max_event_tags", l.max_event_tags),
+        ("max_content_length", l.max_content_length),
+        ("min_pow_difficulty", l.min_pow_difficulty),
+        ("created_at_lower_limit", l.created_at_lower_limit),
+        ("created_at_upper_limit", l.created_at_upper_limit),
+    ] {
+        if let Some(v) = value {
+            fields.push(format!("\"{}\":{}", name, v));
+        }
+    }
+    for (name, value) in [
+        ("auth_required", l.auth_required),
+        ("payment_required", l.payment_required),
+        ("restricted_writes", l.restricted_writes),
+    ] {
+        if let Some(v) = value {
+            fields.push(format!("\"{}\":{}", name, v));
+        }
+    }
+    json.push_str(&fields.join(","));
+    json.push('}');
+
+    json.push_str(",\"fees\":[");
+    for (i, fee) in info.fees.iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        json.push('{');
+        write_string(&mut json, "category", Some(&fee.category));
+        json.push_str(",\"amount\":");
+        json.push_str(&fee.amount.to_string());
+        json.push(',');
+        write_string(&mut json, "unit", Some(&fee.unit));
+        json.push_str(",\"period\":");
+        json.push_str(&fee.period.map(|p| p.to_string()).unwrap_or_else(|| String::from("null")));
+        json.push_str(",\"kinds\":[");
+        let kinds: Vec<String> = fee.kinds.iter().map(|k| k.to_string()).collect();
+        json.push_str(&kinds.join(","));
+        json.push_str("]}");
+    }
+    json.push_str("]}");
+    json
+}
+
+fn write_string(json: &mut String, name: &str, value: Option<&str>) {
+    json.push('"');
+    json.push_str(name);
+    json.push_str("\":");
+    match value {
+        Some(v) => {
+            json.push('"');
+            json.push_str(&escape_json_string(v));
+            json.push('"');
+        }
+        None => json.push_str("null"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOCUMENT: &str = r#"{"name":"Test relay","supported_nips":[1,11,42],"software":"git+https://example.com/relay",
+        "limitation":{"max_message_length":16384,"auth_required":true,"min_pow_difficulty":8},
+        "fees":{"admission":[{"amount":1000000,"unit":"msats"}],"publication":[{"kinds":[4],"amount":100,"unit":"msats"}]},
+        "icon":"https://example.com/icon.png"}"#;
+
+    #[test]
+    fn test_parse_document_fields() {
+        let info = parse_relay_info(DOCUMENT).unwrap();
+        assert_eq!(info.name.as_deref(), Some("Test relay"));
+        assert_eq!(info.supported_nips, vec![1, 11, 42]);
+        assert_eq!(info.icon.as_deref(), Some("https://example.com/icon.png"));
+    }
+
+    #[test]
+    fn test_parse_document_limitation() {
+        let info = parse_relay_info(DOCUMENT).unwrap();
+        assert_eq!(info.limitation.max_message_length, Some(16384));
+        assert_eq!(info.limitation.auth_required, Some(true));
+        assert_eq!(info.limitation.min_pow_difficulty, Some(8));
+    }
+
+    #[test]
+    fn test_parse_document_fees() {
+        let info = parse_relay_info(DOCUMENT).unwrap();
+        assert_eq!(info.fees.len(), 2);
+        assert_eq!(info.fees[0].category, "admission");
+        assert_eq!(info.fees[0].amount, 1000000);
+        assert_eq!(info.fees[1].category, "publication");
+        assert_eq!(info.fees[1].kinds, vec![4]);
+    }
+
+    #[test]
+    fn test_relay_http_url() {
+        assert_eq!(relay_http_url("wss://relay.example.com/").unwrap(), "https://relay.example.com/");
+        assert_eq!(relay_http_url("ws://relay.example.com/").unwrap(), "http://relay.example.com/");
+        assert!(relay_http_url("https://relay.example.com/").is_err());
+    }
+}
@@ -0,0 +1,992 @@
+/*
+ * relay_pool.rs
+ * Copyright (C) 2026 Chris Burdess
+ *
+ * This file is part of Plume, a Nostr desktop client.
+ *
+ * Plume is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Plume is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Plume.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Persistent relay connection pool: one WebSocket per relay URL, shared by the feed, DM,
+//! profile fetch and publish paths. REQ subscriptions are multiplexed over the connection by
+//! subscription id, and each relay message is routed back to the subscriber that owns it.
+//!
+//! The pool answers NIP-42 AUTH challenges with the signer set by `set_auth_signer`, and re-issues
+//! subscriptions and events the relay refused before authentication.
+//!
+//! Connection tasks run on the pool's own runtime, because commands and streams each create
+//! short-lived runtimes that would otherwise take the shared connections down with them.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::{Duration, Instant};
+
+use crate::debug_log;
+use crate::event_verifier;
+use crate::nostr;
+use crate::relay::{self, RelayMessage};
+use crate::relay_info::{self, RelayInfo};
+use crate::warn_log;
+use crate::websocket::connection::{WebSocketConfig, WebSocketConnection};
+use crate::websocket::WebSocketHandler;
+
+/// Close a pooled connection after it has had no subscriptions or pending publishes
+/// for this long (seconds).
+const IDLE_TIMEOUT_SECS: u64 = 60;
+
+/// Headroom over a relay's advertised max_message_length for the ["EVENT","<subscription id>",...]
+/// wrapper around the events it sends us.
+const MESSAGE_ENVELOPE_SLACK: usize = 1024;
+
+/// Largest incoming message we accept from any relay, however large its advertised limit.
+const MAX_INCOMING_MESSAGE_SIZE: usize = 32 * 1024 * 1024;
+
+/// Reply to a publish: `Ok((accepted, message))` from the relay's OK, or `Err` if the
+/// connection failed before the relay answered.
+type PublishReply = Result<(bool, String), String>;
+
+/// Work sent from callers to a relay's connection task.
+enum PoolCommand {
+    Subscribe {
+        subscription_id: String,
+        req_message: String,
+        tx: mpsc::UnboundedSender<RelayMessage>,
+    },
+    Unsubscribe {
+        subscription_id: String,
+    },
+    Publish {
+        event_id: String,
+        event_message: String,
+        reply: oneshot::Sender<PublishReply>,
+    },
+}
+
+/// Signs NIP-42 AUTH events for the pool: given (relay_url, challenge), returns a signed kind 22242
+/// event, or None if we should not authenticate to that relay.
+pub type AuthSigner = Arc<dyn Fn(&str, &str) -> Option<nostr::Event> + Send + Sync>;
+
+struct RelayEntry {
+    generation: u64,
+    commands: mpsc::UnboundedSender<PoolCommand>,
+}
+
+/// Pool of long-lived relay connections. Use `relay_pool()` to get the shared instance.
+pub struct RelayPool {
+    runtime: tokio::runtime::Runtime,
+    relays: Mutex<HashMap<String, RelayEntry>>,
+    next_id: AtomicU64,
+    auth_signer: RwLock<Option<AuthSigner>>,
+}
+
+/// The process-wide relay pool.
+pub fn relay_pool() -> &'static RelayPool {
+    static INSTANCE: OnceLock<RelayPool> = OnceLock::new();
+    INSTANCE.get_or_init(|| {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .thread_name("plume-relay-pool")
+            .enable_all()
+            .build()
+            .expect("failed to create relay pool runtime");
+        RelayPool {
+            runtime,
+            relays: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(1),
+            auth_signer: RwLock::new(None),
+        }
+    })
+}
+
+impl RelayPool {
+    /// Get the command channel for a relay, spawning its connection task if there is none.
+    fn commands_for(&self, relay_url: &str) -> mpsc::UnboundedSender<PoolCommand> {
+        let mut relays = self.relays.lock().unwrap();
+        if let Some(entry) = relays.get(relay_url) {
+            if !entry.commands.is_closed() {
+                return entry.commands.clone();
+            }
+        }
+        let generation = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = mpsc::unbounded_channel();
+        relays.insert(relay_url.to_string(), RelayEntry {
+            generation,
+            commands: tx.clone(),
+        });
+        self.runtime.spawn(run_connection(relay_url.to_string(), generation, rx));
+        tx
+    }
+
+    /// Set (or clear) how the pool answers NIP-42 AUTH challenges. Without a signer, challenges
+    /// are ignored and auth-required refusals are passed back to the caller.
+    pub fn set_auth_signer(&self, signer: Option<AuthSigner>) {
+        *self.auth_signer.write().unwrap() = signer;
+    }
+
+    fn auth_signer(&self) -> Option<AuthSigner> {
+        self.auth_signer.read().unwrap().clone()
+    }
+
+    /// Forget a relay's entry, but only if it still belongs to the given connection task.
+    fn remove_entry(&self, relay_url: &str, generation: u64) {
+        let mut relays = self.relays.lock().unwrap();
+        if relays.get(relay_url).map(|e| e.generation) == Some(generation) {
+            relays.remove(relay_url);
+        }
+    }
+
+    /// Open a REQ subscription on a relay. The subscription id is `prefix` followed by a unique
+    /// number. Messages for the subscription (EVENT, EOSE) and relay NOTICEs are delivered to the
+    /// returned `Subscription`; its stream ends if the connection fails or closes.
+    /// Dropping the `Subscription` sends CLOSE to the relay.
+    pub fn subscribe(&self, relay_url: &str, prefix: &str, filters: &[nostr::Filter]) -> Subscription {
+        let subscription_id = format!("{}{}", prefix, self.next_id.fetch_add(1, Ordering::Relaxed));
+        let mut req_message = format!("[\"REQ\",\"{}\"", subscription_id);
+        for filter in filters {
+            req_message.push(',');
+            req_message.push_str(&nostr::filter_to_json(filter));
+        }
+        req_message.push(']');
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let commands = self.commands_for(relay_url);
+        // If the connection task has already gone, tx is dropped here and the stream ends at once.
+        let _ = commands.send(PoolCommand::Subscribe {
+            subscription_id: subscription_id.clone(),
+            req_message,
+            tx,
+        });
+        Subscription {
+            id: subscription_id,
+            rx,
+            commands,
+        }
+    }
+
+    /// Send an EVENT to a relay and wait for its OK. Returns `(accepted, message)`.
+    /// Callers should apply their own timeout.
+    pub async fn publish(&self, relay_url: &str, event: &nostr::Event) -> Result<(bool, String), String> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        let event_message = format!("[\"EVENT\",{}]", nostr::event_to_json(event));
+        self.commands_for(relay_url)
+            .send(PoolCommand::Publish {
+                event_id: event.id.clone(),
+                event_message,
+                reply: reply_tx,
+            })
+            .map_err(|_| format!("Connection to {} closed", relay_url))?;
+        reply_rx
+            .await
+            .map_err(|_| format!("Connection to {} closed", relay_url))?
+    }
+}
+
+/// A REQ subscription on a pooled connection. Dropping it sends CLOSE to the relay.
+pub struct Subscription {
+    id: String,
+    rx: mpsc::UnboundedReceiver<RelayMessage>,
+    commands: mpsc::UnboundedSender<PoolCommand>,
+}
+
+impl Subscription {
+    /// The subscription id sent in the REQ.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Next message for this subscription, or None once the connection has gone away.
+    pub async fn recv(&mut self) -> Option<RelayMessage> {
+        self.rx.recv().await
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        let _ = self.commands.send(PoolCommand::Unsubscribe {
+            subscription_id: self.id.clone(),
+        });
+    }
+}
+
+/// A long-lived subscription that survives disconnects. When the connection goes away it waits
+/// on the relay backoff schedule, then re-issues the REQ with `since` set to the newest
+/// `created_at` seen so far. Events at exactly that timestamp are delivered again by the relay
+/// (since is inclusive), so they are de-duplicated by id.
+pub struct SupervisedSubscription {
+    relay_url: String,
+    prefix: String,
+    filters: Vec<nostr::Filter>,
+    current: Option<Subscription>,
+    /// Newest created_at seen, and the ids of the events seen with it.
+    last_created_at: Option<u64>,
+    ids_at_last: HashSet<String>,
+    /// How far before the newest created_at to resubscribe from, for events whose timestamps
+    /// are deliberately backdated (NIP-59 gift wraps).
+    since_slack: u64,
+    /// Reconnects since we last heard anything from the relay.
+    failures: u32,
+}
+
+impl SupervisedSubscription {
+    pub fn new(relay_url: &str, prefix: &str, filters: Vec<nostr::Filter>) -> Self {
+        Self {
+            relay_url: relay_url.to_string(),
+            prefix: prefix.to_string(),
+            filters,
+            current: None,
+            last_created_at: None,
+            ids_at_last: HashSet::new(),
+            since_slack: 0,
+            failures: 0,
+        }
+    }
+
+    pub fn since_slack(mut self, seconds: u64) -> Self {
+        self.since_slack = seconds;
+        self
+    }
+
+    /// Next message. Returns None each time the connection is lost; calling recv() again waits
+    /// out the backoff and resubscribes.
+    pub async fn recv(&mut self) -> Option<RelayMessage> {
+        if self.current.is_none() {
+            self.resubscribe().await;
+        }
+        loop {
+            let msg = self.current.as_mut()?.recv().await;
+            let msg = match msg {
+                Some(msg) => msg,
+                None => {
+                    debug_log!("[pool] supervised subscription to {} lost", self.relay_url);
+                    self.current = None;
+                    self.failures += 1;
+                    return None;
+                }
+            };
+            self.failures = 0;
+            if let RelayMessage::Event { event, .. } = &msg {
+                if !self.track(event) {
+                    continue;
+                }
+            }
+            return Some(msg);
+        }
+    }
+
+    /// Record an event's timestamp. Returns false if it is a repeat of one already delivered.
+    fn track(&mut self, event: &nostr::Event) -> bool {
+        match self.last_created_at {
+            Some(last) if event.created_at < last => true,
+            Some(last) if event.created_at == last => self.ids_at_last.insert(event.id.clone()),
+            _ => {
+                self.last_created_at = Some(event.created_at);
+                self.ids_at_last.clear();
+                self.ids_at_last.insert(event.id.clone());
+                true
+            }
+        }
+    }
+
+    async fn resubscribe(&mut self) {
+        if self.failures > 0 {
+            let wait = relay::backoff_seconds(self.failures)
+                .max(relay::check_relay_backoff(&self.relay_url).unwrap_or(0));
+            debug_log!("[pool] resubscribing to {} in {}s", self.relay_url, wait);
+            tokio::time::sleep(Duration::from_secs(wait)).await;
+        }
+        let mut filters = self.filters.clone();
+        if let Some(last) = self.last_created_at {
+            let last = last.saturating_sub(self.since_slack);
+            for filter in filters.iter_mut() {
+                filter.since = Some(filter.since.map_or(last, |s| s.max(last)));
+            }
+        }
+        self.current = Some(relay_pool().subscribe(&self.relay_url, &self.prefix, &filters));
+    }
+}
+
+// ============================================================
+// Connection task
+// ============================================================
+
+/// WebSocket handler that parses text frames into relay messages for the connection task to route.
+struct MessageCollector {
+    messages: Vec<RelayMessage>,
+    closed: bool,
+}
+
+impl WebSocketHandler for MessageCollector {
+    fn connected(&mut self) {}
+
+    fn text_frame(&mut self, data: &[u8]) {
+        let text = match std::str::from_utf8(data) {
+            Ok(t) => t,
+            Err(e) => {
+                debug_log!("[pool] invalid UTF-8 in text frame ({} bytes): {}", data.len(), e);
+                return;
+            }
+        };
+        match relay::parse_relay_message(text) {
+            Ok(msg) => self.messages.push(msg),
+            Err(e) => debug_log!("[pool] parse error: {}", e),
+        }
+    }
+
+    fn binary_frame(&mut self, _data: &[u8]) {}
+
+    fn close(&mut self, _code: Option<u16>, _reason: &str) {
+        self.closed = true;
+    }
+
+    fn ping(&mut self, _data: &[u8]) {}
+    fn pong(&mut self, _data: &[u8]) {}
+
+    fn failed(&mut self, _error: &std::io::Error) {
+        self.closed = true;
+    }
+}
+
+/// An open REQ on a connection; the REQ is kept so it can be re-issued after AUTH.
+struct ActiveSubscription {
+    tx: mpsc::UnboundedSender<RelayMessage>,
+    req_message: String,
+    /// CLOSED message while the subscription waits for NIP-42 authentication to finish.
+    awaiting_auth: Option<String>,
+    /// Already re-issued once after authentication.
+    retried: bool,
+}
+
+/// An EVENT awaiting the relay's OK.
+struct PendingPublish {
+    event_message: String,
+    waiters: Vec<oneshot::Sender<PublishReply>>,
+    /// Rejection message while the event waits for NIP-42 authentication to finish.
+    awaiting_auth: Option<String>,
+    /// Already re-sent once after authentication.
+    retried: bool,
+}
+
+/// State of one pooled connection: the open subscriptions, the publishes awaiting OK and
+/// NIP-42 authentication.
+struct ConnectionState {
+    relay_url: String,
+    subscriptions: HashMap<String, ActiveSubscription>,
+    publishes: HashMap<String, PendingPublish>,
+    /// Id of our AUTH event while we wait for the relay's OK to it.
+    auth_event_id: Option<String>,
+    authenticated: bool,
+    /// Messages produced by commands and routing, to be sent by flush().
+    outbox: Vec<String>,
+}
+
+impl ConnectionState {
+    fn new(relay_url: &str) -> Self {
+        Self {
+            relay_url: relay_url.to_string(),
+            subscriptions: HashMap::new(),
+            publishes: HashMap::new(),
+            auth_event_id: None,
+            authenticated: false,
+            outbox: Vec::new(),
+        }
+    }
+
+    fn is_idle(&mut self) -> bool {
+        // Drop waiters whose caller has given up (timed out).
+        self.publishes.retain(|_, p| {
+            p.waiters.retain(|w| !w.is_closed());
+            !p.waiters.is_empty()
+        });
+        self.subscriptions.is_empty() && self.publishes.is_empty()
+    }
+
+    /// Apply a caller's command. Anything to send goes to the outbox.
+    fn handle_command(&mut self, command: PoolCommand) {
+        match command {
+            PoolCommand::Subscribe { subscription_id, req_message, tx } => {
+                let info = relay_info::cached_relay_info(&self.relay_url);
+                if let Some(message) = info.as_ref().and_then(|i| exceeds_max_message_length(i, &req_message)) {
+                    let _ = tx.send(RelayMessage::Closed { subscription_id, message });
+                    return;
+                }
+                // A relay that advertises auth_required would only refuse the REQ while our
+                // AUTH is under way, so hold it until authentication finishes.
+                let auth_required = info.and_then(|i| i.limitation.auth_required).unwrap_or(false);
+                let awaiting_auth = if auth_required && !self.authenticated && self.auth_event_id.is_some() {
+                    Some(String::from("auth-required: relay requires authentication"))
+                } else {
+                    debug_log!("[pool] sending REQ to {}: {}", self.relay_url, req_message);
+                    self.outbox.push(req_message.clone());
+                    None
+                };
+                self.subscriptions.insert(subscription_id, ActiveSubscription {
+                    tx,
+                    req_message,
+                    awaiting_auth,
+                    retried: false,
+                });
+            }
+            PoolCommand::Unsubscribe { subscription_id } => {
+                if self.subscriptions.remove(&subscription_id).is_some() {
+                    self.outbox.push(format!("[\"CLOSE\",\"{}\"]", subscription_id));
+                }
+            }
+            PoolCommand::Publish { event_id, event_message, reply } => {
+                let info = relay_info::cached_relay_info(&self.relay_url);
+                if let Some(message) = info.as_ref().and_then(|i| exceeds_max_message_length(i, &event_message)) {
+                    let _ = reply.send(Ok((false, message)));
+                    return;
+                }
+                // If the send fails, the connection closes and fail_all answers the waiter.
+                self.outbox.push(event_message.clone());
+                self.publishes.entry(event_id)
+                    .or_insert_with(|| PendingPublish {
+                        event_message,
+                        waiters: Vec::new(),
+                        awaiting_auth: None,
+                        retried: false,
+                    })
+                    .waiters.push(reply);
+            }
+        }
+    }
+
+    /// Send whatever commands and routing queued up (REQs, CLOSEs, EVENTs and AUTH).
+    async fn flush(&mut self, conn: &mut WebSocketConnection) -> Result<(), String> {
+        for message in self.outbox.drain(..) {
+            conn.send_text(message.as_bytes()).await
+                .map_err(|e| format!("failed to send: {}", e))?;
+        }
+        Ok(())
+    }
+
+    /// Deliver a parsed relay message to the subscriber or publisher it belongs to.
+    fn route(&mut self, msg: RelayMessage) {
+        match msg {
+            RelayMessage::Event { subscription_id, event } => {
+                self.send_to_subscriber(subscription_id.clone(), RelayMessage::Event { subscription_id, event });
+            }
+            RelayMessage::EndOfStoredEvents { subscription_id } => {
+                self.send_to_subscriber(subscription_id.clone(), RelayMessage::EndOfStoredEvents { subscription_id });
+            }
+            RelayMessage::Ok { event_id, success, message } => {
+                debug_log!("[pool] OK from {} for {}: {} {}", self.relay_url, event_id, success, message);
+                if self.auth_event_id.as_deref() == Some(event_id.as_str()) {
+                    self.auth_result(success, &message);
+                } else if !success && message.starts_with("auth-required:") {
+                    self.auth_required(&event_id, message);
+                } else {
+                    self.resolve_publish(&event_id, Ok((success, message)));
+                }
+            }
+            RelayMessage::Notice { message } => {
+                debug_log!("[pool] NOTICE from {}: {}", self.relay_url, message);
+                // A notice naming one of our pending events is the relay's answer to it.
+                let matching: Vec<String> = self.publishes.keys()
+                    .filter(|id| message.contains(id.as_str()))
+                    .cloned()
+                    .collect();
+                for id in matching {
+                    self.resolve_publish(&id, Ok((false, message.clone())));
+                }
+                self.subscriptions.retain(|_, sub| sub.tx.send(RelayMessage::Notice { message: message.clone() }).is_ok());
+            }
+            RelayMessage::Closed { subscription_id, message } => {
+                debug_log!("[pool] CLOSED from {} for {}: {}", self.relay_url, subscription_id, message);
+                if message.starts_with("auth-required:") {
+                    self.subscription_auth_required(&subscription_id, message);
+                } else {
+                    self.close_subscription(&subscription_id, message);
+                }
+            }
+            RelayMessage::Auth { challenge } => self.authenticate(&challenge),
+            RelayMessage::Unknown { .. } => {
+                debug_log!("[pool] other message type from {}", self.relay_url);
+            }
+        }
+    }
+
+    /// Answer a NIP-42 challenge, if the pool has a signer that agrees to authenticate to this relay.
+    fn authenticate(&mut self, challenge: &str) {
+        self.authenticated = false;
+        let event = relay_pool().auth_signer().and_then(|sign| sign(&self.relay_url, challenge));
+        match event {
+            Some(event) => {
+                debug_log!("[pool] authenticating to {}", self.relay_url);
+                self.outbox.push(format!("[\"AUTH\",{}]", nostr::event_to_json(&event)));
+                self.auth_event_id = Some(event.id);
+            }
+            None => {
+                debug_log!("[pool] not authenticating to {}", self.relay_url);
+                self.auth_event_id = None;
+                self.fail_awaiting_auth();
+            }
+        }
+    }
+
+    /// The relay answered our AUTH. On success, re-issue everything it may have refused before.
+    fn auth_result(&mut self, success: bool, message: &str) {
+        self.auth_event_id = None;
+        if !success {
+            warn_log!("[pool] authentication to {} failed: {}", self.relay_url, message);
+            self.fail_awaiting_auth();
+            return;
+        }
+        debug_log!("[pool] authenticated to {}", self.relay_url);
+        self.authenticated = true;
+        for sub in self.subscriptions.values_mut() {
+            if sub.awaiting_auth.take().is_some() {
+                sub.retried = true;
+                self.outbox.push(sub.req_message.clone());
+            }
+        }
+        for publish in self.publishes.values_mut() {
+            if publish.awaiting_auth.take().is_some() {
+                publish.retried = true;
+                self.outbox.push(publish.event_message.clone());
+            }
+        }
+    }
+
+    /// An EVENT was refused with "auth-required:". Hold it while authentication is under way,
+    /// re-send it once if we have authenticated since it was sent, otherwise report the refusal.
+    fn auth_required(&mut self, event_id: &str, message: String) {
+        let auth_in_progress = self.auth_event_id.is_some();
+        let authenticated = self.authenticated;
+        let publish = match self.publishes.get_mut(event_id) {
+            Some(p) if !p.retried => p,
+            _ => return self.resolve_publish(event_id, Ok((false, message))),
+        };
+        if auth_in_progress {
+            publish.awaiting_auth = Some(message);
+        } else if authenticated {
+            publish.retried = true;
+            self.outbox.push(publish.event_message.clone());
+        } else {
+            self.resolve_publish(event_id, Ok((false, message)));
+        }
+    }
+
+    /// A REQ was refused with "auth-required:". Same policy as for EVENTs: hold it while
+    /// authentication is under way, re-issue it once if already authenticated, otherwise pass
+    /// the CLOSED on to the subscriber.
+    fn subscription_auth_required(&mut self, subscription_id: &str, message: String) {
+        let auth_in_progress = self.auth_event_id.is_some();
+        let authenticated = self.authenticated;
+        let sub = match self.subscriptions.get_mut(subscription_id) {
+            Some(sub) if !sub.retried => sub,
+            _ => return self.close_subscription(subscription_id, message),
+        };
+        if auth_in_progress {
+            sub.awaiting_auth = Some(message);
+        } else if authenticated {
+            sub.retried = true;
+            self.outbox.push(sub.req_message.clone());
+        } else {
+            self.close_subscription(subscription_id, message);
+        }
+    }
+
+    /// The relay closed a subscription: tell the subscriber and end its stream.
+    fn close_subscription(&mut self, subscription_id: &str, message: String) {
+        if let Some(sub) = self.subscriptions.remove(subscription_id) {
+            let _ = sub.tx.send(RelayMessage::Closed {
+                subscription_id: subscription_id.to_string(),
+                message,
+            });
+        }
+    }
+
+    fn fail_awaiting_auth(&mut self) {
+        let held_subscriptions: Vec<(String, String)> = self.subscriptions.iter_mut()
+            .filter_map(|(id, sub)| sub.awaiting_auth.take().map(|m| (id.clone(), m)))
+            .collect();
+        for (id, message) in held_subscriptions {
+            self.close_subscription(&id, message);
+        }
+        let held: Vec<(String, String)> = self.publishes.iter_mut()
+            .filter_map(|(id, p)| p.awaiting_auth.take().map(|m| (id.clone(), m)))
+            .collect();
+        for (id, message) in held {
+            self.resolve_publish(&id, Ok((false, message)));
+        }
+    }
+
+    fn resolve_publish(&mut self, event_id: &str, reply: PublishReply) {
+        if let Some(publish) = self.publishes.remove(event_id) {
+            for w in publish.waiters {
+                let _ = w.send(reply.clone());
+            }
+        }
+    }
+
+    fn send_to_subscriber(&mut self, subscription_id: String, msg: RelayMessage) {
+        match self.subscriptions.get(&subscription_id) {
+            // A send error means the subscriber has gone; its Unsubscribe will send CLOSE.
+            Some(sub) => {
+                let _ = sub.tx.send(msg);
+            }
+            None => debug_log!("[pool] message for unknown subscription {} from {}", subscription_id, self.relay_url),
+        }
+    }
+
+    /// Fail everything still pending when the connection goes away. Dropping the subscription
+    /// senders ends each subscriber's stream.
+    fn fail_all(&mut self, reason: &str) {
+        self.subscriptions.clear();
+        for (_, publish) in self.publishes.drain() {
+            for w in publish.waiters {
+                let _ = w.send(Err(reason.to_string()));
+            }
+        }
+    }
+}
+
+/// The "invalid:" refusal for a message longer than the relay's advertised max_message_length.
+fn exceeds_max_message_length(info: &RelayInfo, message: &str) -> Option<String> {
+    let max = info.limitation.max_message_length?;
+    if message.len() as u64 > max {
+        Some(format!("invalid: message is {} bytes, relay max_message_length is {}", message.len(), max))
+    } else {
+        None
+    }
+}
+
+/// WebSocket options for a connection to `relay_url`. A relay that advertises a
+/// max_message_length above our default can store (and so send back) events that large, so
+/// the incoming limit is raised to match, up to MAX_INCOMING_MESSAGE_SIZE. It is never lowered:
+/// the advertised limit is for what the relay accepts, not what it sends.
+fn connection_config(relay_url: &str) -> WebSocketConfig {
+    let mut config = WebSocketConfig::default();
+    let advertised = relay_info::cached_relay_info(relay_url).and_then(|i| i.limitation.max_message_length);
+    if let Some(max) = advertised {
+        let limit = usize::try_from(max).unwrap_or(usize::MAX).saturating_add(MESSAGE_ENVELOPE_SLACK);
+        config.max_message_size = config.max_message_size.max(limit.min(MAX_INCOMING_MESSAGE_SIZE));
+        config.max_frame_size = config.max_frame_size.max(config.max_message_size);
+    }
+    config
+}
+
+/// Connection task for one relay: connect, then multiplex commands and incoming messages until
+/// the relay goes away, stops answering pings, or the connection has been idle for IDLE_TIMEOUT_SECS.
+async fn run_connection(relay_url: String, generation: u64, mut commands: mpsc::UnboundedReceiver<PoolCommand>) {
+    let mut state = ConnectionState::new(&relay_url);
+
+    // Fetch (or refresh) the NIP-11 document alongside the connection so later sends can
+    // respect the relay's limits. The size limit for this connection comes from the cached
+    // document, so a relay's first connection uses the default.
+    let info_url = relay_url.clone();
+    tokio::spawn(async move {
+        let _ = relay_info::fetch_relay_info(&info_url).await;
+    });
+
+    let mut conn = match relay::connect_to_relay(&relay_url, connection_config(&relay_url)).await {
+        Ok(c) => c,
+        Err(e) => {
+            debug_log!("[pool] {}", e);
+            relay_pool().remove_entry(&relay_url, generation);
+            drain_commands(&mut commands, &e);
+            return;
+        }
+    };
+    debug_log!("[pool] connected to {}", relay_url);
+
+    let mut collector = MessageCollector {
+        messages: Vec::new(),
+        closed: false,
+    };
+    let mut idle_since: Option<Instant> = None;
+    let reason = loop {
+        if state.is_idle() {
+            idle_since.get_or_insert_with(Instant::now);
+        } else {
+            idle_since = None;
+        }
+        let idle_deadline = idle_since.unwrap_or_else(Instant::now) + Duration::from_secs(IDLE_TIMEOUT_SECS);
+        let keepalive_deadline = conn.keepalive_deadline();
+
+        tokio::select! {
+            command = commands.recv() => {
+                match command {
+                    Some(command) => {
+                        state.handle_command(command);
+                        if let Err(e) = state.flush(&mut conn).await {
+                            break e;
+                        }
+                    }
+                    None => break String::from("pool shut down"),
+                }
+            }
+            read = conn.read_frames(&mut collector) => {
+                match read {
+                    Ok(true) => {}
+                    Ok(false) => break String::from("connection closed by relay"),
+                    Err(e) => break format!("read error: {}", e),
+                }
+                let messages = std::mem::take(&mut collector.messages);
+                for msg in event_verifier::verify_relay_messages(&relay_url, messages).await {
+                    state.route(msg);
+                }
+                if collector.closed {
+                    break String::from("relay sent close");
+                }
+                if let Err(e) = state.flush(&mut conn).await {
+                    break e;
+                }
+                if let Err(e) = conn.send_pending_pong().await {
+                    break format!("failed to send pong: {}", e);
+                }
+            }
+            _ = tokio::time::sleep_until(keepalive_deadline.unwrap_or_else(Instant::now)), if keepalive_deadline.is_some() => {
+                if let Err(e) = conn.keepalive().await {
+                    // Half-open or unresponsive: back off before the next attempt to reach it
+                    relay::record_relay_failure(&relay_url);
+                    break format!("keepalive failed: {}", e);
+                }
+            }
+            _ = tokio::time::sleep_until(idle_deadline), if idle_since.is_some() => {
+                break String::from("idle");
+            }
+        }
+    };
+
+    debug_log!("[pool] closing connection to {}: {}", relay_url, reason);
+    let _ = conn.send_close(Some(1000), "").await;
+    relay_pool().remove_entry(&relay_url, generation);
+    let reason = format!("Connection to {} closed: {}", relay_url, reason);
+    state.fail_all(&reason);
+    drain_commands(&mut commands, &reason);
+}
+
+/// Refuse any commands that arrived after the connection task decided to exit.
+fn drain_commands(commands: &mut mpsc::UnboundedReceiver<PoolCommand>, reason: &str) {
+    commands.close();
+    while let Ok(command) = commands.try_recv() {
+        if let PoolCommand::Publish { reply, .. } = command {
+            let _ = reply.send(Err(reason.to_string()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(json: &str) -> RelayMessage {
+        relay::parse_relay_message(json).unwrap()
+    }
+
+    fn event_json(id: &str) -> String {
+        format!(
+            "{{\"id\":\"{}\",\"pubkey\":\"{}\",\"created_at\":1,\"kind\":1,\"tags\":[],\"content\":\"hi\",\"sig\":\"{}\"}}",
+            id,
+            "b".repeat(64),
+            "c".repeat(128)
+        )
+    }
+
+    fn subscribe(state: &mut ConnectionState, subscription_id: &str) -> mpsc::UnboundedReceiver<RelayMessage> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        state.handle_command(PoolCommand::Subscribe {
+            subscription_id: subscription_id.to_string(),
+            req_message: format!("[\"REQ\",\"{}\",{{}}]", subscription_id),
+            tx,
+        });
+        rx
+    }
+
+    fn publish(state: &mut ConnectionState, event_id: &str) -> oneshot::Receiver<PublishReply> {
+        let (reply, rx) = oneshot::channel();
+        state.handle_command(PoolCommand::Publish {
+            event_id: event_id.to_string(),
+            event_message: format!("[\"EVENT\",{}]", event_json(event_id)),
+            reply,
+        });
+        rx
+    }
+
+    /// Signs AUTH for every test relay except those with "noauth" in the URL. The signer is
+    /// process-wide, so every test that needs one installs this same one.
+    fn install_auth_signer() {
+        relay_pool().set_auth_signer(Some(Arc::new(|relay_url: &str, challenge: &str| {
+            if relay_url.contains("noauth") {
+                return None;
+            }
+            Some(nostr::Event {
+                id: format!("auth-{}", challenge),
+                pubkey: "b".repeat(64),
+                created_at: 1,
+                kind: nostr::KIND_AUTH,
+                tags: vec![vec![String::from("challenge"), challenge.to_string()]],
+                content: String::new(),
+                sig: "c".repeat(128),
+            })
+        })));
+    }
+
+    #[test]
+    fn test_route_by_subscription_id() {
+        let mut state = ConnectionState::new("wss://route.example.com");
+        let mut a = subscribe(&mut state, "a1");
+        let mut b = subscribe(&mut state, "b2");
+        assert_eq!(state.outbox.len(), 2);
+
+        state.route(message(&format!("[\"EVENT\",\"b2\",{}]", event_json(&"1".repeat(64)))));
+        state.route(message("[\"EOSE\",\"a1\"]"));
+        state.route(message("[\"EOSE\",\"unknown\"]"));
+
+        assert!(matches!(a.try_recv(), Ok(RelayMessage::EndOfStoredEvents { subscription_id }) if subscription_id == "a1"));
+        assert!(a.try_recv().is_err());
+        assert!(matches!(b.try_recv(), Ok(RelayMessage::Event { subscription_id, .. }) if subscription_id == "b2"));
+        assert!(b.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_unsubscribe_sends_close() {
+        let mut state = ConnectionState::new("wss://close.example.com");
+        let _rx = subscribe(&mut state, "s1");
+        state.handle_command(PoolCommand::Unsubscribe { subscription_id: String::from("s1") });
+        // A second CLOSE for the same subscription is not sent
+        state.handle_command(PoolCommand::Unsubscribe { subscription_id: String::from("s1") });
+        assert_eq!(state.outbox, vec![String::from("[\"REQ\",\"s1\",{}]"), String::from("[\"CLOSE\",\"s1\"]")]);
+        assert!(state.subscriptions.is_empty());
+    }
+
+    #[test]
+    fn test_closed_ends_subscription() {
+        let mut state = ConnectionState::new("wss://closed.example.com");
+        let mut rx = subscribe(&mut state, "s1");
+        state.route(message("[\"CLOSED\",\"s1\",\"error: shutting down\"]"));
+        assert!(matches!(rx.try_recv(), Ok(RelayMessage::Closed { message, .. }) if message == "error: shutting down"));
+        assert!(state.subscriptions.is_empty());
+    }
+
+    #[test]
+    fn test_idle_once_nothing_is_pending() {
+        let mut state = ConnectionState::new("wss://idle.example.com");
+        assert!(state.is_idle());
+        let _sub = subscribe(&mut state, "s1");
+        assert!(!state.is_idle());
+        state.handle_command(PoolCommand::Unsubscribe { subscription_id: String::from("s1") });
+        assert!(state.is_idle());
+
+        let reply = publish(&mut state, &"1".repeat(64));
+        assert!(!state.is_idle());
+        // The caller gave up waiting for the OK
+        drop(reply);
+        assert!(state.is_idle());
+    }
+
+    #[test]
+    fn test_ok_resolves_publish() {
+        let mut state = ConnectionState::new("wss://ok.example.com");
+        let id = "1".repeat(64);
+        let mut first = publish(&mut state, &id);
+        let mut second = publish(&mut state, &id);
+        state.route(message(&format!("[\"OK\",\"{}\",true,\"\"]", id)));
+        assert!(matches!(first.try_recv(), Ok(Ok((true, _)))));
+        assert!(matches!(second.try_recv(), Ok(Ok((true, _)))));
+        assert!(state.publishes.is_empty());
+    }
+
+    #[test]
+    fn test_notice_naming_event_rejects_publish() {
+        let mut state = ConnectionState::new("wss://notice.example.com");
+        let id = "2".repeat(64);
+        let mut reply = publish(&mut state, &id);
+        state.route(message(&format!("[\"NOTICE\",\"blocked: {}\"]", id)));
+        assert!(matches!(reply.try_recv(), Ok(Ok((false, m))) if m.starts_with("blocked:")));
+    }
+
+    #[test]
+    fn test_fail_all_ends_everything() {
+        let mut state = ConnectionState::new("wss://fail.example.com");
+        let mut sub = subscribe(&mut state, "s1");
+        let mut reply = publish(&mut state, &"3".repeat(64));
+        state.fail_all("gone");
+        assert!(matches!(sub.try_recv(), Err(mpsc::error::TryRecvError::Disconnected)));
+        assert!(matches!(reply.try_recv(), Ok(Err(e)) if e == "gone"));
+    }
+
+    #[test]
+    fn test_drain_commands_refuses_publishes() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let (reply, mut reply_rx) = oneshot::channel();
+        let sent = tx.send(PoolCommand::Publish {
+            event_id: "1".repeat(64),
+            event_message: String::new(),
+            reply,
+        });
+        assert!(sent.is_ok());
+        drain_commands(&mut rx, "connect failed");
+        assert!(matches!(reply_rx.try_recv(), Ok(Err(e)) if e == "connect failed"));
+        // Later commands are refused by the closed channel
+        assert!(tx.send(PoolCommand::Unsubscribe { subscription_id: String::from("s1") }).is_err());
+    }
+
+    #[test]
+    fn test_auth_required_subscription_reissued_after_auth() {
+        install_auth_signer();
+        let mut state = ConnectionState::new("wss://auth.example.com");
+        let mut rx = subscribe(&mut state, "s1");
+        state.route(message("[\"AUTH\",\"xyz\"]"));
+        assert!(state.outbox.iter().any(|m| m.starts_with("[\"AUTH\",")));
+        state.outbox.clear();
+
+        // Refused while our AUTH is in flight: held, not passed on
+        state.route(message("[\"CLOSED\",\"s1\",\"auth-required: sign in\"]"));
+        assert!(rx.try_recv().is_err());
+        assert!(state.outbox.is_empty());
+
+        state.route(message("[\"OK\",\"auth-xyz\",true,\"\"]"));
+        assert_eq!(state.outbox, vec![String::from("[\"REQ\",\"s1\",{}]")]);
+        state.outbox.clear();
+
+        // Refused again after the retry: passed on to the subscriber
+        state.route(message("[\"CLOSED\",\"s1\",\"auth-required: still no\"]"));
+        assert!(matches!(rx.try_recv(), Ok(RelayMessage::Closed { .. })));
+        assert!(state.outbox.is_empty());
+    }
+
+    #[test]
+    fn test_auth_required_publish_resent_after_auth() {
+        install_auth_signer();
+        let mut state = ConnectionState::new("wss://auth-publish.example.com");
+        let id = "4".repeat(64);
+        let mut reply = publish(&mut state, &id);
+        state.route(message("[\"AUTH\",\"abc\"]"));
+        state.outbox.clear();
+        state.route(message(&format!("[\"OK\",\"{}\",false,\"auth-required: sign in\"]", id)));
+        assert!(reply.try_recv().is_err());
+
+        state.route(message("[\"OK\",\"auth-abc\",true,\"\"]"));
+        assert_eq!(state.outbox.len(), 1);
+        assert!(state.outbox[0].starts_with("[\"EVENT\","));
+        state.route(message(&format!("[\"OK\",\"{}\",true,\"\"]", id)));
+        assert!(matches!(reply.try_recv(), Ok(Ok((true, _)))));
+    }
+
+    #[test]
+    fn test_auth_required_without_auth_is_passed_on() {
+        install_auth_signer();
+        let mut state = ConnectionState::new("wss://noauth.example.com");
+        let mut rx = subscribe(&mut state, "s1");
+        state.route(message("[\"AUTH\",\"xyz\"]"));
+        assert!(!state.outbox.iter().any(|m| m.starts_with("[\"AUTH\",")));
+        state.route(message("[\"CLOSED\",\"s1\",\"auth-required: sign in\"]"));
+        assert!(matches!(rx.try_recv(), Ok(RelayMessage::Closed { message, .. }) if message.starts_with("auth-required:")));
+    }
+}
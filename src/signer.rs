@@ -0,0 +1,500 @@
+/*
+ * signer.rs
+ * Copyright (C) 2026 Chris Burdess
+ *
+ * This file is part of Plume, a Nostr desktop client.
+ *
+ * Plume is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Plume is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Plume.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Signing and encryption for the active profile. A profile either holds its secret key
+//! (unlocked for the session) or delegates to a NIP-46 remote signer ("bunker"), which is asked
+//! over relays to sign events and to encrypt and decrypt on its behalf. Everything that signs
+//! or encrypts as the user goes through `Signer`, so both kinds of profile can post, react,
+//! zap and send messages.
+//!
+//! A remote signer is paired either from a `bunker://` URI given by the signer, or by showing
+//! the signer a `nostrconnect://` URI and waiting for it to connect back. Requests are signed
+//! with a client key of our own, kept in the profile config.
+
+use std::sync::{Arc, Mutex, OnceLock};
+use tokio::sync::mpsc;
+use tokio::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::crypto;
+use crate::debug_log;
+use crate::keys;
+use crate::nip46::{self, BunkerUri};
+use crate::nostr::{self, Event, KIND_CHAT_MESSAGE, KIND_GIFT_WRAP};
+use crate::relay::RelayMessage;
+use crate::relay_pool::relay_pool;
+
+/// How long to wait for the remote signer to answer a request (seconds). Generous, because the
+/// signer may ask the user to approve it first.
+const REQUEST_TIMEOUT_SECS: u64 = 60;
+
+/// How long to wait for a remote signer to connect back to a nostrconnect:// URI (seconds).
+const NOSTRCONNECT_TIMEOUT_SECS: u64 = 120;
+
+/// How long to wait for a relay to accept a request (seconds).
+const PUBLISH_TIMEOUT_SECS: u64 = 10;
+
+/// Name we give remote signers to show the user.
+const CLIENT_NAME: &str = "Plume";
+
+/// Called with the URL when a remote signer needs the user to approve a request in a browser.
+pub type AuthUrlHandler = Arc<dyn Fn(&str) + Send + Sync>;
+
+fn auth_url_handler() -> &'static Mutex<Option<AuthUrlHandler>> {
+    static INSTANCE: OnceLock<Mutex<Option<AuthUrlHandler>>> = OnceLock::new();
+    INSTANCE.get_or_init(|| Mutex::new(None))
+}
+
+pub fn set_auth_url_handler(handler: Option<AuthUrlHandler>) {
+    *auth_url_handler().lock().unwrap() = handler;
+}
+
+/// Signs and encrypts as the user: locally with their secret key, or through a remote signer.
+#[derive(Clone)]
+pub enum Signer {
+    Local { secret_key: String, public_key: String },
+    Remote(RemoteSigner),
+}
+
+/// A NIP-46 remote signer. The client key signs our requests; the remote signer's key signs
+/// its responses and may differ from the user's key it signs with.
+#[derive(Clone)]
+pub struct RemoteSigner {
+    client_secret: String,
+    client_pubkey: String,
+    remote_pubkey: String,
+    user_pubkey: String,
+    relays: Vec<String>,
+}
+
+/// The signer for a profile: its unlocked key if it has one, otherwise its remote signer.
+pub fn signer_for(cfg: &Config) -> Result<Signer, String> {
+    if let Some(secret_key) = cfg.private_key.as_deref().filter(|k| !k.is_empty()) {
+        return Ok(Signer::Local {
+            secret_key: secret_key.to_string(),
+            public_key: crypto::get_public_key_from_secret(secret_key)?,
+        });
+    }
+    if let (Some(uri), Some(client_secret)) = (&cfg.remote_signer, &cfg.remote_signer_client_key) {
+        let bunker = nip46::parse_bunker_uri(uri)?;
+        let user_pubkey = keys::public_key_to_hex(&cfg.public_key).map_err(|e| format!("Public key: {}", e))?;
+        return RemoteSigner::new(client_secret.clone(), &bunker, user_pubkey).map(Signer::Remote);
+    }
+    Err(String::from("No private key or remote signer configured."))
+}
+
+impl Signer {
+    /// The user's public key (hex).
+    pub fn public_key(&self) -> &str {
+        match self {
+            Signer::Local { public_key, .. } => public_key,
+            Signer::Remote(remote) => &remote.user_pubkey,
+        }
+    }
+
+    /// Sign an event built for `public_key()`, filling in its id and sig.
+    pub async fn sign_event(&self, mut event: Event) -> Result<Event, String> {
+        match self {
+            Signer::Local { secret_key, .. } => {
+                crypto::sign_event(&mut event, secret_key)?;
+                Ok(event)
+            }
+            Signer::Remote(remote) => remote.sign_event(event).await,
+        }
+    }
+
+    /// Only legacy kind 4 DMs need this; we send NIP-17 messages.
+    #[allow(dead_code)]
+    pub async fn nip04_encrypt(&self, plaintext: &str, peer_pubkey_hex: &str) -> Result<String, String> {
+        match self {
+            Signer::Local { secret_key, .. } => crypto::nip04_encrypt(plaintext, secret_key, peer_pubkey_hex),
+            Signer::Remote(remote) => remote.request("nip04_encrypt", &[peer_pubkey_hex, plaintext]).await,
+        }
+    }
+
+    pub async fn nip04_decrypt(&self, ciphertext: &str, peer_pubkey_hex: &str) -> Result<String, String> {
+        match self {
+            Signer::Local { secret_key, .. } => crypto::nip04_decrypt(ciphertext, secret_key, peer_pubkey_hex),
+            Signer::Remote(remote) => remote.request("nip04_decrypt", &[peer_pubkey_hex, ciphertext]).await,
+        }
+    }
+
+    pub async fn nip44_encrypt(&self, plaintext: &str, peer_pubkey_hex: &str) -> Result<String, String> {
+        match self {
+            Signer::Local { secret_key, .. } => crypto::nip44_encrypt(plaintext, secret_key, peer_pubkey_hex),
+            Signer::Remote(remote) => remote.request("nip44_encrypt", &[peer_pubkey_hex, plaintext]).await,
+        }
+    }
+
+    pub async fn nip44_decrypt(&self, ciphertext: &str, peer_pubkey_hex: &str) -> Result<String, String> {
+        match self {
+            Signer::Local { secret_key, .. } => crypto::nip44_decrypt(ciphertext, secret_key, peer_pubkey_hex),
+            Signer::Remote(remote) => remote.request("nip44_decrypt", &[peer_pubkey_hex, ciphertext]).await,
+        }
+    }
+}
+
+impl RemoteSigner {
+    fn new(client_secret: String, bunker: &BunkerUri, user_pubkey: String) -> Result<RemoteSigner, String> {
+        Ok(RemoteSigner {
+            client_pubkey: crypto::get_public_key_from_secret(&client_secret)?,
+            client_secret,
+            remote_pubkey: bunker.remote_pubkey.clone(),
+            user_pubkey,
+            relays: bunker.relays.clone(),
+        })
+    }
+
+    /// The user's public key (hex).
+    pub fn user_pubkey(&self) -> &str {
+        &self.user_pubkey
+    }
+
+    /// The hex client key our requests are signed with, to keep in the profile config.
+    pub fn client_secret(&self) -> &str {
+        &self.client_secret
+    }
+
+    /// bunker:// URI (without a secret) to reconnect to this signer with.
+    pub fn bunker_uri(&self) -> String {
+        nip46::bunker_uri_to_string(&BunkerUri {
+            remote_pubkey: self.remote_pubkey.clone(),
+            relays: self.relays.clone(),
+            secret: None,
+        })
+    }
+
+    /// Connect to the remote signer in a bunker:// URI with a new client key, and ask it for
+    /// the user's public key.
+    pub async fn connect_bunker(uri: &str) -> Result<RemoteSigner, String> {
+        let bunker = nip46::parse_bunker_uri(uri)?;
+        let mut signer = RemoteSigner::new(crypto::random_secret_key_hex(), &bunker, String::new())?;
+        let mut params = vec![bunker.remote_pubkey.as_str()];
+        if let Some(ref secret) = bunker.secret {
+            params.push(secret);
+        }
+        signer.request("connect", &params).await?;
+        signer.fetch_user_pubkey().await?;
+        Ok(signer)
+    }
+
+    async fn fetch_user_pubkey(&mut self) -> Result<(), String> {
+        let pubkey = self.request("get_public_key", &[]).await?;
+        self.user_pubkey = keys::public_key_to_hex(pubkey.trim())
+            .map_err(|e| format!("Remote signer returned an invalid public key: {}", e))?;
+        Ok(())
+    }
+
+    async fn sign_event(&self, mut event: Event) -> Result<Event, String> {
+        if event.pubkey.to_lowercase() != self.user_pubkey {
+            return Err(format!(
+                "Public key mismatch: event has {}, but the remote signer signs for {}",
+                event.pubkey, self.user_pubkey
+            ));
+        }
+        // Send the event with its id, so the signer can show and check exactly what it signs
+        event.id = crypto::compute_event_id(&event)?;
+        let signed = self.request("sign_event", &[&nostr::rumor_to_json(&event)]).await?;
+        let signed = nostr::parse_event(&signed).map_err(|e| format!("Remote signer returned an invalid event: {}", e))?;
+        if signed.pubkey.to_lowercase() != self.user_pubkey
+            || signed.kind != event.kind
+            || signed.content != event.content
+            || signed.tags != event.tags
+        {
+            return Err(String::from("Remote signer signed a different event"));
+        }
+        if !crypto::verify_event_id(&signed)? || !crypto::verify_event_signature(&signed)? {
+            return Err(String::from("Remote signer returned an invalid signature"));
+        }
+        Ok(signed)
+    }
+
+    /// Send a request and wait for the signer's result.
+    async fn request(&self, method: &str, params: &[&str]) -> Result<String, String> {
+        let id = nip46::new_request_id();
+        let params: Vec<String> = params.iter().map(|p| p.to_string()).collect();
+        let json = nip46::request_to_json(&id, method, &params);
+        let event = nip46::create_message_event(&json, &self.client_secret, &self.remote_pubkey)?;
+        // Listen before publishing, so a fast answer is not missed
+        let mut responses = listen(&self.relays, &self.client_pubkey, Some(&self.remote_pubkey));
+        publish(&self.relays, &event).await?;
+        debug_log!("[nip46] sent {} request {}", method, id);
+
+        let deadline = Instant::now() + Duration::from_secs(REQUEST_TIMEOUT_SECS);
+        loop {
+            let event = match tokio::time::timeout_at(deadline, responses.recv()).await {
+                Ok(Some(event)) => event,
+                Ok(None) => return Err(String::from("Lost connection to the remote signer's relays")),
+                Err(_) => return Err(format!("Remote signer did not answer {} in time", method)),
+            };
+            let message = match nip46::open_message_event(&event, &self.client_secret) {
+                Ok(m) if m.id == id => m,
+                Ok(_) => continue,
+                Err(e) => {
+                    debug_log!("[nip46] unreadable response {}: {}", event.id, e);
+                    continue;
+                }
+            };
+            // The signer wants the user to approve in a browser; its real answer follows
+            if message.result.as_deref() == Some("auth_url") {
+                if let Some(url) = message.error.as_deref() {
+                    let handler = auth_url_handler().lock().unwrap().clone();
+                    if let Some(handler) = handler {
+                        handler(url);
+                    }
+                }
+                continue;
+            }
+            if let Some(error) = message.error.filter(|e| !e.is_empty()) {
+                return Err(format!("Remote signer: {}", error));
+            }
+            return Ok(message.result.unwrap_or_default());
+        }
+    }
+}
+
+/// A nostrconnect:// pairing waiting for the remote signer to connect back.
+struct PendingConnect {
+    client_secret: String,
+    relays: Vec<String>,
+    secret: String,
+    responses: mpsc::UnboundedReceiver<Event>,
+}
+
+fn pending_connect() -> &'static Mutex<Option<PendingConnect>> {
+    static INSTANCE: OnceLock<Mutex<Option<PendingConnect>>> = OnceLock::new();
+    INSTANCE.get_or_init(|| Mutex::new(None))
+}
+
+/// Start pairing with a remote signer through `relays`: returns the nostrconnect:// URI to show
+/// the user, and starts listening for the signer. Call `finish_nostrconnect` to wait for it.
+pub fn start_nostrconnect(relays: &[String]) -> Result<String, String> {
+    if relays.is_empty() {
+        return Err(String::from("At least one relay is required"));
+    }
+    let client_secret = crypto::random_secret_key_hex();
+    let client_pubkey = crypto::get_public_key_from_secret(&client_secret)?;
+    let secret = nip46::new_request_id();
+    let uri = nip46::nostrconnect_uri(&client_pubkey, relays, &secret, CLIENT_NAME);
+    let responses = listen(relays, &client_pubkey, None);
+    *pending_connect().lock().unwrap() = Some(PendingConnect {
+        client_secret,
+        relays: relays.to_vec(),
+        secret,
+        responses,
+    });
+    Ok(uri)
+}
+
+/// Wait for the remote signer to connect to the URI from `start_nostrconnect`, then ask it for
+/// the user's public key.
+pub async fn finish_nostrconnect() -> Result<RemoteSigner, String> {
+    let mut pending = pending_connect()
+        .lock()
+        .unwrap()
+        .take()
+        .ok_or_else(|| String::from("No remote signer connection in progress"))?;
+    let deadline = Instant::now() + Duration::from_secs(NOSTRCONNECT_TIMEOUT_SECS);
+    let remote_pubkey = loop {
+        let event = match tokio::time::timeout_at(deadline, pending.responses.recv()).await {
+            Ok(Some(event)) => event,
+            Ok(None) => return Err(String::from("Lost connection to the relays")),
+            Err(_) => return Err(String::from("No remote signer connected in time")),
+        };
+        match nip46::open_message_event(&event, &pending.client_secret) {
+            // Only a signer that read the URI knows the secret
+            Ok(m) if m.result.as_deref() == Some(pending.secret.as_str()) => break event.pubkey.to_lowercase(),
+            Ok(_) => continue,
+            Err(e) => {
+                debug_log!("[nip46] unreadable connect response {}: {}", event.id, e);
+                continue;
+            }
+        }
+    };
+    let bunker = BunkerUri {
+        remote_pubkey,
+        relays: pending.relays.clone(),
+        secret: None,
+    };
+    let mut signer = RemoteSigner::new(pending.client_secret, &bunker, String::new())?;
+    signer.fetch_user_pubkey().await?;
+    Ok(signer)
+}
+
+/// Subscribe on each relay to NIP-46 messages for `our_pubkey_hex` (from `author` only, if
+/// given). The subscriptions close when the receiver is dropped.
+fn listen(relays: &[String], our_pubkey_hex: &str, author: Option<&str>) -> mpsc::UnboundedReceiver<Event> {
+    let since = crypto::now_secs().saturating_sub(10);
+    let filter = nostr::filter_nostr_connect(our_pubkey_hex, author, since);
+    let (tx, rx) = mpsc::unbounded_channel();
+    for relay_url in relays {
+        let mut subscription = relay_pool().subscribe(relay_url, "plume_nip46_", std::slice::from_ref(&filter));
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    msg = subscription.recv() => match msg {
+                        Some(RelayMessage::Event { event, .. }) => {
+                            if tx.send(event).is_err() {
+                                break;
+                            }
+                        }
+                        Some(_) => {}
+                        None => break,
+                    },
+                    _ = tx.closed() => break,
+                }
+            }
+        });
+    }
+    rx
+}
+
+/// Publish a request to every relay; succeeds once any relay accepts it.
+async fn publish(relays: &[String], event: &Event) -> Result<(), String> {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    for relay_url in relays {
+        let relay_url = relay_url.clone();
+        let event = event.clone();
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            let timeout = Duration::from_secs(PUBLISH_TIMEOUT_SECS);
+            let result = match tokio::time::timeout(timeout, relay_pool().publish(&relay_url, &event)).await {
+                Ok(Ok((true, _))) => Ok(()),
+                Ok(Ok((false, message))) => Err(format!("{}: {}", relay_url, message)),
+                Ok(Err(e)) => Err(format!("{}: {}", relay_url, e)),
+                Err(_) => Err(format!("{}: timed out", relay_url)),
+            };
+            let _ = tx.send(result);
+        });
+    }
+    drop(tx);
+    let mut errors = Vec::new();
+    while let Some(result) = rx.recv().await {
+        match result {
+            Ok(()) => return Ok(()),
+            Err(e) => errors.push(e),
+        }
+    }
+    Err(format!("No relay accepted the remote signer request ({})", errors.join("; ")))
+}
+
+// ============================================================
+// NIP-17 private messages (NIP-59 gift wraps)
+// ============================================================
+
+/// A NIP-17 direct message: the kind 14 rumor (kept locally), one gift wrap for the recipient
+/// and one for ourselves so our other clients can read what we sent.
+pub struct PrivateDm {
+    pub rumor: Event,
+    pub recipient_wrap: Event,
+    pub self_wrap: Event,
+}
+
+pub async fn create_private_dm(signer: &Signer, recipient_pubkey_hex: &str, plaintext: &str) -> Result<PrivateDm, String> {
+    let our_pubkey = signer.public_key().to_string();
+    let tags = vec![vec![String::from("p"), recipient_pubkey_hex.to_string()]];
+    let rumor = crypto::create_rumor(KIND_CHAT_MESSAGE, plaintext, tags, &our_pubkey)?;
+    let rumor_json = nostr::rumor_to_json(&rumor);
+    let recipient_seal = crypto::create_seal(&our_pubkey, signer.nip44_encrypt(&rumor_json, recipient_pubkey_hex).await?);
+    let recipient_seal = signer.sign_event(recipient_seal).await?;
+    let self_seal = crypto::create_seal(&our_pubkey, signer.nip44_encrypt(&rumor_json, &our_pubkey).await?);
+    let self_seal = signer.sign_event(self_seal).await?;
+    Ok(PrivateDm {
+        recipient_wrap: crypto::create_gift_wrap(&recipient_seal, recipient_pubkey_hex)?,
+        self_wrap: crypto::create_gift_wrap(&self_seal, &our_pubkey)?,
+        rumor,
+    })
+}
+
+/// Open a gift wrap addressed to us and return the rumor inside.
+pub async fn unwrap_gift_wrap(signer: &Signer, wrap: &Event) -> Result<Event, String> {
+    if wrap.kind != KIND_GIFT_WRAP {
+        return Err(format!("Not a gift wrap (kind {})", wrap.kind));
+    }
+    let seal = crypto::open_seal(&signer.nip44_decrypt(&wrap.content, &wrap.pubkey).await?)?;
+    let rumor_json = signer.nip44_decrypt(&seal.content, &seal.pubkey).await?;
+    crypto::open_rumor(&seal, &rumor_json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(secret_key: &str) -> Signer {
+        Signer::Local {
+            secret_key: secret_key.to_string(),
+            public_key: crypto::get_public_key_from_secret(secret_key).unwrap(),
+        }
+    }
+
+    fn alice() -> Signer {
+        local("0000000000000000000000000000000000000000000000000000000000000001")
+    }
+
+    fn bob() -> Signer {
+        local("0000000000000000000000000000000000000000000000000000000000000002")
+    }
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(future)
+    }
+
+    #[test]
+    fn test_private_dm_wrap_hides_sender() {
+        let (alice, bob) = (alice(), bob());
+        let dm = block_on(create_private_dm(&alice, bob.public_key(), "hi")).unwrap();
+        assert!(dm.rumor.sig.is_empty());
+        assert_ne!(dm.recipient_wrap.pubkey, alice.public_key());
+        assert!(dm.recipient_wrap.created_at <= crypto::now_secs());
+    }
+
+    #[test]
+    fn test_private_dm_recipient_reads_message() {
+        let (alice, bob) = (alice(), bob());
+        let dm = block_on(create_private_dm(&alice, bob.public_key(), "hi \"bob\"")).unwrap();
+        let received = block_on(unwrap_gift_wrap(&bob, &dm.recipient_wrap)).unwrap();
+        assert_eq!(received.id, dm.rumor.id);
+        assert_eq!(received.kind, KIND_CHAT_MESSAGE);
+        assert_eq!(received.pubkey, alice.public_key());
+        assert_eq!(received.content, "hi \"bob\"");
+    }
+
+    #[test]
+    fn test_private_dm_self_copy_only_for_sender() {
+        let (alice, bob) = (alice(), bob());
+        let dm = block_on(create_private_dm(&alice, bob.public_key(), "hi")).unwrap();
+        assert_eq!(block_on(unwrap_gift_wrap(&alice, &dm.self_wrap)).unwrap().id, dm.rumor.id);
+        assert!(block_on(unwrap_gift_wrap(&bob, &dm.self_wrap)).is_err());
+    }
+
+    #[test]
+    fn test_seal_not_signed_by_author_rejected() {
+        let (alice, bob) = (alice(), bob());
+        let mallory = local("0000000000000000000000000000000000000000000000000000000000000003");
+        block_on(async {
+            let dm = create_private_dm(&alice, bob.public_key(), "hi").await.unwrap();
+            let encrypted = mallory.nip44_encrypt(&nostr::rumor_to_json(&dm.rumor), bob.public_key()).await.unwrap();
+            let forged_seal = mallory.sign_event(crypto::create_seal(mallory.public_key(), encrypted)).await.unwrap();
+            let forged_wrap = crypto::create_gift_wrap(&forged_seal, bob.public_key()).unwrap();
+            assert!(unwrap_gift_wrap(&bob, &forged_wrap).await.is_err());
+        });
+    }
+}
@@ -27,7 +27,7 @@ use tokio::net::TcpStream;
 use url::Url;
 
 use crate::debug_log;
-use crate::websocket::connection::WebSocketConnection;
+use crate::websocket::connection::{WebSocketConfig, WebSocketConnection};
 use crate::websocket::handshake::{
     build_handshake_request, parse_101_response, verify_accept,
 };
@@ -40,7 +40,13 @@ impl WebSocketClient {
     /// Connect to the given WebSocket URL (ws:// or wss://), perform the opening handshake,
     /// and return a `WebSocketConnection`. Call `connected()` on your handler, then use
     /// `conn.run(handler)` to drive the read loop and `conn.send_text()` etc. to send.
+    #[allow(dead_code)]
     pub async fn connect(url: &str) -> io::Result<WebSocketConnection> {
+        Self::connect_with_config(url, WebSocketConfig::default()).await
+    }
+
+    /// Like `connect`, with explicit per-connection options (message size limit, keepalive).
+    pub async fn connect_with_config(url: &str, config: WebSocketConfig) -> io::Result<WebSocketConnection> {
         let url = Url::parse(url).map_err(|e| {
             io::Error::new(io::ErrorKind::InvalidInput, e.to_string())
         })?;
@@ -113,6 +119,6 @@ impl WebSocketClient {
         if !leftover.is_empty() {
             debug_log!("[ws] handshake leftover: {} bytes", leftover.len());
         }
-        Ok(WebSocketConnection::new(stream, leftover))
+        Ok(WebSocketConnection::new(stream, leftover, config))
     }
 }
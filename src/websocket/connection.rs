@@ -23,22 +23,77 @@
 use bytes::BytesMut;
 use std::io;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::time::{Duration, Instant};
 
 use crate::debug_log;
-use crate::websocket::frame::{encode_frame, FrameHandler, FrameParser, OP_BINARY, OP_CLOSE, OP_PING, OP_PONG, OP_TEXT};
+use crate::websocket::frame::{
+    encode_frame, FrameHandler, FrameParser, CLOSE_INVALID_PAYLOAD, CLOSE_MESSAGE_TOO_BIG, CLOSE_PROTOCOL_ERROR,
+    MAX_CONTROL_PAYLOAD, OP_BINARY, OP_CLOSE, OP_CONTINUATION, OP_PING, OP_PONG, OP_TEXT,
+};
 use crate::websocket::stream::WsStream;
 use crate::websocket::WebSocketHandler;
 
-/// WebSocket connection after successful handshake. Use run() to drive the read loop with a handler;
-/// use send_text/send_binary/send_ping/send_close to send frames.
+/// Default maximum size of a reassembled message (8 MiB).
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 8 * 1024 * 1024;
+
+/// Default maximum payload of a single incoming frame. Relays usually send a whole message in
+/// one frame, so this matches the message limit.
+pub const DEFAULT_MAX_FRAME_SIZE: usize = DEFAULT_MAX_MESSAGE_SIZE;
+
+/// Default interval after which a quiet connection is pinged (seconds).
+pub const DEFAULT_PING_INTERVAL_SECS: u64 = 30;
+
+/// Default time with nothing received before a connection is considered dead (seconds).
+/// Three missed pings.
+pub const DEFAULT_IDLE_TIMEOUT_SECS: u64 = 90;
+
+/// Per-connection options.
+#[derive(Clone, Debug)]
+pub struct WebSocketConfig {
+    /// Largest message we accept, after reassembling fragments. A larger message closes the
+    /// connection with 1009.
+    pub max_message_size: usize,
+    /// Largest single frame we accept. Checked when the frame header arrives, before any of
+    /// the payload is buffered; a larger frame closes the connection with 1009.
+    pub max_frame_size: usize,
+    /// Send a ping when nothing has been sent or received for this long. None disables pings.
+    pub ping_interval: Option<Duration>,
+    /// Treat the connection as dead when nothing (not even a pong) has been received for this
+    /// long. None disables the check.
+    pub idle_timeout: Option<Duration>,
+}
+
+impl Default for WebSocketConfig {
+    fn default() -> Self {
+        Self {
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            ping_interval: Some(Duration::from_secs(DEFAULT_PING_INTERVAL_SECS)),
+            idle_timeout: Some(Duration::from_secs(DEFAULT_IDLE_TIMEOUT_SECS)),
+        }
+    }
+}
+
+/// WebSocket connection after successful handshake. Use run() to drive the read loop with a handler
+/// (or read_frames() to interleave reads with other work); use send_text/send_binary/send_ping/send_close
+/// to send frames. Pings from the peer are answered with pongs, and keepalive() pings a quiet peer
+/// and detects a dead one.
 pub struct WebSocketConnection {
     stream: WsStream,
     read_buf: BytesMut,
     frame_parser: FrameParser,
+    assembler: MessageAssembler,
+    leftover_pending: bool,
+    /// Set once a protocol error has closed the connection; later reads fail with it.
+    failed: Option<String>,
+    ping_interval: Option<Duration>,
+    idle_timeout: Option<Duration>,
+    last_received: Instant,
+    last_ping_sent: Instant,
 }
 
 impl WebSocketConnection {
-    pub(crate) fn new(stream: WsStream, initial_data: &[u8]) -> Self {
+    pub(crate) fn new(stream: WsStream, initial_data: &[u8], config: WebSocketConfig) -> Self {
         let mut read_buf = BytesMut::with_capacity(8192);
         if !initial_data.is_empty() {
             read_buf.extend_from_slice(initial_data);
@@ -46,53 +101,43 @@ impl WebSocketConnection {
         Self {
             stream,
             read_buf,
-            frame_parser: FrameParser::new(),
+            frame_parser: FrameParser::new(config.max_frame_size),
+            assembler: MessageAssembler::new(config.max_message_size),
+            leftover_pending: !initial_data.is_empty(),
+            failed: None,
+            ping_interval: config.ping_interval,
+            idle_timeout: config.idle_timeout,
+            last_received: Instant::now(),
+            last_ping_sent: Instant::now(),
         }
     }
 
-    /// Run the read loop, calling the handler for each frame. Returns when the connection closes,
-    /// an error occurs (handler.failed is called before return), or handler.should_stop() is true.
+    /// Run the read loop, calling the handler for each message and keeping the connection alive.
+    /// Returns when the connection closes, an error occurs (handler.failed is called before return),
+    /// or handler.should_stop() is true.
+    #[allow(dead_code)]
     pub async fn run(&mut self, handler: &mut (dyn WebSocketHandler + Send)) -> io::Result<()> {
-        // Process any data already in the buffer (leftover from handshake)
-        if !self.read_buf.is_empty() {
-            debug_log!("[ws] processing {} leftover bytes", self.read_buf.len());
-            let mut adapter = FrameToHandlerAdapter { handler };
-            if let Err(e) = self.frame_parser.receive(&mut self.read_buf, &mut adapter) {
-                debug_log!("[ws] frame parse error on leftover: {}", e);
-                handler.failed(&e);
-                return Err(e);
-            }
-            if handler.should_stop() {
-                debug_log!("[ws] handler stopped after leftover processing");
-                return Ok(());
-            }
-        }
         debug_log!("[ws] entering read loop");
         loop {
-            let mut tmp = [0u8; 8192];
-            let n = match self.stream.read(&mut tmp).await {
-                Ok(0) => {
-                    debug_log!("[ws] stream EOF");
-                    return Ok(());
-                }
-                Ok(n) => n,
-                Err(e) => {
-                    debug_log!("[ws] stream read error: {}", e);
-                    handler.failed(&e);
-                    return Err(e);
+            let deadline = self.keepalive_deadline();
+            let result = tokio::select! {
+                read = self.read_frames(handler) => match read {
+                    Ok(true) => self.send_pending_pong().await,
+                    Ok(false) => {
+                        debug_log!("[ws] stream EOF");
+                        return Ok(());
+                    }
+                    Err(e) => Err(e),
+                },
+                _ = tokio::time::sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                    self.keepalive().await
                 }
             };
-            debug_log!("[ws] read {} bytes from stream (buf now {})", n, self.read_buf.len() + n);
-            self.read_buf.extend_from_slice(&tmp[..n]);
-            {
-                let mut adapter = FrameToHandlerAdapter { handler };
-                if let Err(e) = self.frame_parser.receive(&mut self.read_buf, &mut adapter) {
-                    debug_log!("[ws] frame parse error: {}", e);
-                    handler.failed(&e);
-                    return Err(e);
-                }
+            if let Err(e) = result {
+                debug_log!("[ws] read error: {}", e);
+                handler.failed(&e);
+                return Err(e);
             }
-            debug_log!("[ws] after frame parse, buf remaining: {}", self.read_buf.len());
             if handler.should_stop() {
                 debug_log!("[ws] handler requested stop");
                 return Ok(());
@@ -100,6 +145,101 @@ impl WebSocketConnection {
         }
     }
 
+    /// Read once from the stream and dispatch every complete message (and control frame) to the handler.
+    /// Returns Ok(false) on EOF. Cancel-safe: if the future is dropped while waiting for data
+    /// nothing is lost, so callers can race it against other work in `tokio::select!`.
+    pub async fn read_frames(&mut self, handler: &mut (dyn WebSocketHandler + Send)) -> io::Result<bool> {
+        if let Some(reason) = &self.failed {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, reason.clone()));
+        }
+        // Process any data already in the buffer (leftover from handshake)
+        if self.leftover_pending {
+            self.leftover_pending = false;
+            debug_log!("[ws] processing {} leftover bytes", self.read_buf.len());
+            if let Err((code, reason)) = self.dispatch(handler) {
+                return Err(self.fail(code, reason).await);
+            }
+            return Ok(true);
+        }
+        let mut tmp = [0u8; 8192];
+        let n = self.stream.read(&mut tmp).await?;
+        if n == 0 {
+            return Ok(false);
+        }
+        self.last_received = Instant::now();
+        debug_log!("[ws] read {} bytes from stream (buf now {})", n, self.read_buf.len() + n);
+        self.read_buf.extend_from_slice(&tmp[..n]);
+        if let Err((code, reason)) = self.dispatch(handler) {
+            return Err(self.fail(code, reason).await);
+        }
+        debug_log!("[ws] after frame parse, buf remaining: {}", self.read_buf.len());
+        Ok(true)
+    }
+
+    /// Answer the most recent ping received by read_frames(), if any. Call this after each
+    /// read_frames(); it is kept separate so that read_frames() never writes and stays cancel-safe.
+    pub async fn send_pending_pong(&mut self) -> io::Result<()> {
+        match self.assembler.pending_pong.take() {
+            Some(data) => self.send_pong(&data).await,
+            None => Ok(()),
+        }
+    }
+
+    /// When keepalive() next has work to do, or None if pings and idle detection are both disabled.
+    pub fn keepalive_deadline(&self) -> Option<Instant> {
+        let ping = self.ping_interval.map(|i| self.last_ping_sent.max(self.last_received) + i);
+        let idle = self.idle_timeout.map(|t| self.last_received + t);
+        match (ping, idle) {
+            (Some(p), Some(i)) => Some(p.min(i)),
+            (p, i) => p.or(i),
+        }
+    }
+
+    /// Keepalive tick, to be called at keepalive_deadline(). Pings the peer once the connection
+    /// has been quiet for the ping interval, and fails with TimedOut once nothing has been received
+    /// for the idle timeout (e.g. a half-open TCP connection).
+    pub async fn keepalive(&mut self) -> io::Result<()> {
+        let now = Instant::now();
+        if let Some(timeout) = self.idle_timeout {
+            if now >= self.last_received + timeout {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("nothing received for {}s", timeout.as_secs()),
+                ));
+            }
+        }
+        if let Some(interval) = self.ping_interval {
+            if now >= self.last_ping_sent.max(self.last_received) + interval {
+                debug_log!("[ws] sending keepalive ping");
+                self.last_ping_sent = now;
+                self.send_ping(&[]).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Parse buffered frames and hand complete messages to the handler.
+    /// On a protocol violation returns the close code to send and a short reason.
+    fn dispatch(&mut self, handler: &mut (dyn WebSocketHandler + Send)) -> Result<(), (u16, String)> {
+        let mut adapter = FrameToHandlerAdapter {
+            handler,
+            assembler: &mut self.assembler,
+        };
+        let parsed = self.frame_parser.receive(&mut self.read_buf, &mut adapter);
+        if let Some(error) = self.assembler.error.take() {
+            return Err(error);
+        }
+        parsed.map_err(|e| (e.close_code, e.reason.to_string()))
+    }
+
+    /// Close the connection after a protocol error and return the error for the caller.
+    async fn fail(&mut self, code: u16, reason: String) -> io::Error {
+        debug_log!("[ws] protocol error, closing with {}: {}", code, reason);
+        self.failed = Some(reason.clone());
+        let _ = self.send_close(Some(code), "").await;
+        io::Error::new(io::ErrorKind::InvalidData, reason)
+    }
+
     /// Send a text frame.
     pub async fn send_text(&mut self, data: &[u8]) -> io::Result<()> {
         self.send_frame(OP_TEXT, data).await
@@ -112,9 +252,8 @@ impl WebSocketConnection {
     }
 
     /// Send a ping frame.
-    #[allow(dead_code)]
     pub async fn send_ping(&mut self, data: &[u8]) -> io::Result<()> {
-        if data.len() > 125 {
+        if data.len() > MAX_CONTROL_PAYLOAD {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidInput,
                 "ping payload max 125 bytes",
@@ -124,9 +263,8 @@ impl WebSocketConnection {
     }
 
     /// Send a pong frame (e.g. in response to ping).
-    #[allow(dead_code)]
     pub async fn send_pong(&mut self, data: &[u8]) -> io::Result<()> {
-        if data.len() > 125 {
+        if data.len() > MAX_CONTROL_PAYLOAD {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidInput,
                 "pong payload max 125 bytes",
@@ -136,14 +274,13 @@ impl WebSocketConnection {
     }
 
     /// Send a close frame. Reason is UTF-8; code is optional (e.g. 1000 = normal).
-    #[allow(dead_code)]
     pub async fn send_close(&mut self, code: Option<u16>, reason: &str) -> io::Result<()> {
         let mut payload = Vec::new();
         if let Some(c) = code {
             payload.extend_from_slice(&c.to_be_bytes());
         }
         payload.extend_from_slice(reason.as_bytes());
-        if payload.len() > 125 {
+        if payload.len() > MAX_CONTROL_PAYLOAD {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidInput,
                 "close payload max 125 bytes",
@@ -156,37 +293,197 @@ impl WebSocketConnection {
         let mut mask_key = [0u8; 4];
         rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut mask_key);
         let mut out = BytesMut::with_capacity(14 + payload.len());
-        encode_frame(opcode, payload, &mask_key, &mut out)?;
+        encode_frame(opcode, payload, &mask_key, &mut out);
         self.stream.write_all(&out).await?;
         self.stream.flush().await?;
         Ok(())
     }
 }
 
-/// Adapts FrameHandler callbacks to WebSocketHandler.
-struct FrameToHandlerAdapter<'a> {
-    handler: &'a mut (dyn WebSocketHandler + Send),
+/// Reassembles fragmented messages (RFC 6455 §5.4). Control frames may arrive between the
+/// fragments of a data message and are passed through at once.
+struct MessageAssembler {
+    max_message_size: usize,
+    /// Opcode of the data message being reassembled, if any.
+    opcode: Option<u8>,
+    buf: Vec<u8>,
+    /// First protocol violation seen: (close code, reason). Frames after it are ignored.
+    error: Option<(u16, String)>,
+    /// Payload of the latest ping, still to be answered. Only the most recent ping needs a
+    /// pong (RFC 6455 §5.5.3).
+    pending_pong: Option<Vec<u8>>,
 }
 
-impl FrameHandler for FrameToHandlerAdapter<'_> {
-    fn frame(&mut self, opcode: u8, _fin: bool, data: &[u8]) {
-        debug_log!("[ws] frame: opcode={} fin={} len={}", opcode, _fin, data.len());
+impl MessageAssembler {
+    fn new(max_message_size: usize) -> Self {
+        Self {
+            max_message_size,
+            opcode: None,
+            buf: Vec::new(),
+            error: None,
+            pending_pong: None,
+        }
+    }
+
+    fn fail(&mut self, code: u16, reason: &str) {
+        self.opcode = None;
+        self.buf = Vec::new();
+        self.error = Some((code, reason.to_string()));
+    }
+
+    fn frame(&mut self, opcode: u8, fin: bool, data: &[u8], handler: &mut (dyn WebSocketHandler + Send)) {
+        if self.error.is_some() {
+            return;
+        }
         match opcode {
-            OP_TEXT => self.handler.text_frame(data),
-            OP_BINARY => self.handler.binary_frame(data),
-            OP_CLOSE => {
-                let (code, reason) = if data.len() >= 2 {
-                    let code = u16::from_be_bytes([data[0], data[1]]);
-                    let reason = std::str::from_utf8(&data[2..]).unwrap_or("").to_string();
-                    (Some(code), reason)
+            OP_CLOSE | OP_PING | OP_PONG => {
+                if !fin {
+                    return self.fail(CLOSE_PROTOCOL_ERROR, "fragmented control frame");
+                }
+                if opcode == OP_PING {
+                    self.pending_pong = Some(data.to_vec());
+                }
+                deliver_control(opcode, data, handler);
+            }
+            OP_TEXT | OP_BINARY => {
+                if self.opcode.is_some() {
+                    return self.fail(CLOSE_PROTOCOL_ERROR, "new message before previous one finished");
+                }
+                if data.len() > self.max_message_size {
+                    return self.fail(CLOSE_MESSAGE_TOO_BIG, "message too big");
+                }
+                if fin {
+                    self.deliver(opcode, data, handler);
                 } else {
-                    (None, String::new())
+                    self.opcode = Some(opcode);
+                    self.buf.extend_from_slice(data);
+                }
+            }
+            OP_CONTINUATION => {
+                let message_opcode = match self.opcode {
+                    Some(op) => op,
+                    None => return self.fail(CLOSE_PROTOCOL_ERROR, "continuation frame without a message"),
                 };
-                self.handler.close(code, &reason);
+                if self.buf.len() + data.len() > self.max_message_size {
+                    return self.fail(CLOSE_MESSAGE_TOO_BIG, "message too big");
+                }
+                self.buf.extend_from_slice(data);
+                if fin {
+                    let message = std::mem::take(&mut self.buf);
+                    self.opcode = None;
+                    self.deliver(message_opcode, &message, handler);
+                }
             }
-            OP_PING => self.handler.ping(data),
-            OP_PONG => self.handler.pong(data),
-            _ => {}
+            _ => self.fail(CLOSE_PROTOCOL_ERROR, "reserved opcode"),
+        }
+    }
+
+    fn deliver(&mut self, opcode: u8, data: &[u8], handler: &mut (dyn WebSocketHandler + Send)) {
+        if opcode == OP_TEXT {
+            if std::str::from_utf8(data).is_err() {
+                return self.fail(CLOSE_INVALID_PAYLOAD, "invalid UTF-8 in text message");
+            }
+            handler.text_frame(data);
+        } else {
+            handler.binary_frame(data);
+        }
+    }
+}
+
+fn deliver_control(opcode: u8, data: &[u8], handler: &mut (dyn WebSocketHandler + Send)) {
+    match opcode {
+        OP_CLOSE => {
+            let (code, reason) = if data.len() >= 2 {
+                let code = u16::from_be_bytes([data[0], data[1]]);
+                let reason = std::str::from_utf8(&data[2..]).unwrap_or("").to_string();
+                (Some(code), reason)
+            } else {
+                (None, String::new())
+            };
+            handler.close(code, &reason);
         }
+        OP_PING => handler.ping(data),
+        _ => handler.pong(data),
+    }
+}
+
+/// Adapts FrameHandler callbacks to WebSocketHandler, reassembling fragmented messages on the way.
+struct FrameToHandlerAdapter<'a> {
+    handler: &'a mut (dyn WebSocketHandler + Send),
+    assembler: &'a mut MessageAssembler,
+}
+
+impl FrameHandler for FrameToHandlerAdapter<'_> {
+    fn frame(&mut self, opcode: u8, fin: bool, data: &[u8]) {
+        debug_log!("[ws] frame: opcode={} fin={} len={}", opcode, fin, data.len());
+        self.assembler.frame(opcode, fin, data, self.handler);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Recorder {
+        texts: Vec<String>,
+        pings: usize,
+    }
+
+    impl WebSocketHandler for Recorder {
+        fn connected(&mut self) {}
+        fn text_frame(&mut self, data: &[u8]) {
+            self.texts.push(String::from_utf8(data.to_vec()).unwrap());
+        }
+        fn binary_frame(&mut self, _data: &[u8]) {}
+        fn close(&mut self, _code: Option<u16>, _reason: &str) {}
+        fn ping(&mut self, _data: &[u8]) {
+            self.pings += 1;
+        }
+        fn pong(&mut self, _data: &[u8]) {}
+        fn failed(&mut self, _error: &std::io::Error) {}
+    }
+
+    #[test]
+    fn test_fragmented_message_with_interleaved_ping() {
+        let mut assembler = MessageAssembler::new(1024);
+        let mut handler = Recorder::default();
+        assembler.frame(OP_TEXT, false, b"[\"EVENT\",", &mut handler);
+        assembler.frame(OP_PING, true, b"", &mut handler);
+        assembler.frame(OP_CONTINUATION, false, b"\"sub\",", &mut handler);
+        assembler.frame(OP_CONTINUATION, true, b"{}]", &mut handler);
+        assert!(assembler.error.is_none());
+        assert_eq!(handler.pings, 1);
+        assert_eq!(assembler.pending_pong, Some(Vec::new()));
+        assert_eq!(handler.texts, vec!["[\"EVENT\",\"sub\",{}]".to_string()]);
+    }
+
+    #[test]
+    fn test_fragmentation_errors() {
+        let mut handler = Recorder::default();
+
+        let mut assembler = MessageAssembler::new(1024);
+        assembler.frame(OP_CONTINUATION, true, b"x", &mut handler);
+        assert_eq!(assembler.error.map(|e| e.0), Some(CLOSE_PROTOCOL_ERROR));
+
+        let mut assembler = MessageAssembler::new(1024);
+        assembler.frame(OP_TEXT, false, b"a", &mut handler);
+        assembler.frame(OP_TEXT, true, b"b", &mut handler);
+        assert_eq!(assembler.error.map(|e| e.0), Some(CLOSE_PROTOCOL_ERROR));
+
+        let mut assembler = MessageAssembler::new(4);
+        assembler.frame(OP_TEXT, false, b"abc", &mut handler);
+        assembler.frame(OP_CONTINUATION, true, b"de", &mut handler);
+        assert_eq!(assembler.error.map(|e| e.0), Some(CLOSE_MESSAGE_TOO_BIG));
+
+        // UTF-8 is checked on the whole message, so a character split across frames is fine
+        let mut assembler = MessageAssembler::new(1024);
+        assembler.frame(OP_TEXT, false, &[0xc3], &mut handler);
+        assembler.frame(OP_CONTINUATION, true, &[0xa9], &mut handler);
+        assert!(assembler.error.is_none());
+        assembler.frame(OP_TEXT, true, &[0xc3], &mut handler);
+        assert_eq!(assembler.error.map(|e| e.0), Some(CLOSE_INVALID_PAYLOAD));
+
+        assert_eq!(handler.texts, vec!["é".to_string()]);
     }
 }
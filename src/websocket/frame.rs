@@ -21,10 +21,8 @@
 //! WebSocket frame format (RFC 6455 §5): parser for receive, encoder for send (with masking).
 
 use bytes::{Buf, BufMut, BytesMut};
-use std::io;
 
 // Opcodes
-#[allow(dead_code)]
 pub const OP_CONTINUATION: u8 = 0;
 pub const OP_TEXT: u8 = 1;
 pub const OP_BINARY: u8 = 2;
@@ -32,8 +30,28 @@ pub const OP_CLOSE: u8 = 8;
 pub const OP_PING: u8 = 9;
 pub const OP_PONG: u8 = 10;
 
-/// Max payload length we accept for data frames (64 KiB). Control frames are ≤125.
-pub const MAX_FRAME_PAYLOAD: usize = 65536;
+/// Max payload length of a control frame (RFC 6455 §5.5).
+pub const MAX_CONTROL_PAYLOAD: usize = 125;
+
+/// Close code: protocol error (RFC 6455 §7.4.1).
+pub const CLOSE_PROTOCOL_ERROR: u16 = 1002;
+/// Close code: message data not consistent with its type (e.g. invalid UTF-8 in a text message).
+pub const CLOSE_INVALID_PAYLOAD: u16 = 1007;
+/// Close code: message too big to process.
+pub const CLOSE_MESSAGE_TOO_BIG: u16 = 1009;
+
+/// Frame-level protocol violation, with the close code to send for it.
+#[derive(Debug)]
+pub struct FrameError {
+    pub close_code: u16,
+    pub reason: &'static str,
+}
+
+impl FrameError {
+    fn protocol(reason: &'static str) -> Self {
+        Self { close_code: CLOSE_PROTOCOL_ERROR, reason }
+    }
+}
 
 /// Callback for completed frames (receive path).
 pub trait FrameHandler {
@@ -41,7 +59,9 @@ pub trait FrameHandler {
 }
 
 /// Push parser for WebSocket frames (server → client: no masking).
+/// Frames longer than `max_frame_size` are rejected as soon as their header is read.
 pub struct FrameParser {
+    max_frame_size: usize,
     state: FrameState,
     opcode: u8,
     fin: bool,
@@ -58,8 +78,9 @@ enum FrameState {
 }
 
 impl FrameParser {
-    pub fn new() -> Self {
+    pub fn new(max_frame_size: usize) -> Self {
         Self {
+            max_frame_size,
             state: FrameState::Header1,
             opcode: 0,
             fin: false,
@@ -73,7 +94,7 @@ impl FrameParser {
         &mut self,
         buf: &mut BytesMut,
         handler: &mut H,
-    ) -> Result<(), io::Error> {
+    ) -> Result<(), FrameError> {
         loop {
             match self.state {
                 FrameState::Header1 => {
@@ -86,37 +107,37 @@ impl FrameParser {
                     self.opcode = b0 & 0x0f;
                     let mask = (b1 & 0x80) != 0;
                     let len7 = b1 & 0x7f;
+                    if b0 & 0x70 != 0 {
+                        // No extensions are negotiated, so RSV1-3 must be clear
+                        return Err(FrameError::protocol("reserved bits set"));
+                    }
                     if mask {
-                        return Err(io::Error::new(
-                            io::ErrorKind::InvalidData,
-                            "server frame must not be masked",
-                        ));
+                        return Err(FrameError::protocol("server frame must not be masked"));
                     }
                     if len7 == 126 {
                         self.state = FrameState::ExtendedLen2;
                     } else if len7 == 127 {
                         self.state = FrameState::ExtendedLen8;
                     } else {
-                        self.payload_len = len7 as u64;
-                        self.payload_read = 0;
-                        self.state = FrameState::Payload;
+                        self.start_payload(len7 as u64)?;
                     }
                 }
                 FrameState::ExtendedLen2 => {
                     if buf.len() < 2 {
                         return Ok(());
                     }
-                    self.payload_len = buf.get_u16() as u64;
-                    self.payload_read = 0;
-                    self.state = FrameState::Payload;
+                    let len = buf.get_u16() as u64;
+                    self.start_payload(len)?;
                 }
                 FrameState::ExtendedLen8 => {
                     if buf.len() < 8 {
                         return Ok(());
                     }
-                    self.payload_len = buf.get_u64();
-                    self.payload_read = 0;
-                    self.state = FrameState::Payload;
+                    let len = buf.get_u64();
+                    if len & (1 << 63) != 0 {
+                        return Err(FrameError::protocol("frame length has most significant bit set"));
+                    }
+                    self.start_payload(len)?;
                 }
                 FrameState::Payload => {
                     let need = (self.payload_len - self.payload_read) as usize;
@@ -127,24 +148,11 @@ impl FrameParser {
                         continue;
                     }
                     if buf.len() < need {
+                        // Reserve once for the whole frame rather than growing piecemeal
+                        buf.reserve(need - buf.len());
                         return Ok(());
                     }
                     let payload = buf.split_to(need);
-                    let is_control = self.opcode == OP_CLOSE
-                        || self.opcode == OP_PING
-                        || self.opcode == OP_PONG;
-                    if is_control && payload.len() > 125 {
-                        return Err(io::Error::new(
-                            io::ErrorKind::InvalidData,
-                            "control frame payload too long",
-                        ));
-                    }
-                    if !is_control && payload.len() > MAX_FRAME_PAYLOAD {
-                        return Err(io::Error::new(
-                            io::ErrorKind::InvalidData,
-                            "data frame payload too long",
-                        ));
-                    }
                     handler.frame(self.opcode, self.fin, &payload);
                     self.state = FrameState::Header1;
                     continue;
@@ -152,23 +160,39 @@ impl FrameParser {
             }
         }
     }
+
+    /// Length is known: check it against the limits before any payload is buffered.
+    fn start_payload(&mut self, len: u64) -> Result<(), FrameError> {
+        let is_control = self.opcode == OP_CLOSE
+            || self.opcode == OP_PING
+            || self.opcode == OP_PONG;
+        if is_control && len > MAX_CONTROL_PAYLOAD as u64 {
+            return Err(FrameError::protocol("control frame payload too long"));
+        }
+        if len > self.max_frame_size as u64 {
+            return Err(FrameError {
+                close_code: CLOSE_MESSAGE_TOO_BIG,
+                reason: "data frame payload too long",
+            });
+        }
+        self.payload_len = len;
+        self.payload_read = 0;
+        self.state = FrameState::Payload;
+        Ok(())
+    }
 }
 
-/// Encode and write one frame (client → server: must mask). Uses `mask_key` (4 bytes) for XOR.
+/// Encode one frame (client → server: must mask) into `out`. Uses `mask_key` (4 bytes) for XOR.
+/// Payloads of any size are encoded, using the 16- or 64-bit extended length as needed.
 pub fn encode_frame(
     opcode: u8,
     payload: &[u8],
     mask_key: &[u8; 4],
     out: &mut BytesMut,
-) -> io::Result<()> {
+) {
     let len = payload.len();
-    if len > MAX_FRAME_PAYLOAD {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidInput,
-            "payload too long",
-        ));
-    }
     let fin: u8 = 0x80;
+    out.reserve(14 + len);
     out.put_u8(fin | (opcode & 0x0f));
     if len < 126 {
         out.put_u8(0x80 | (len as u8));
@@ -180,8 +204,82 @@ pub fn encode_frame(
         out.put_u64(len as u64);
     }
     out.put_slice(mask_key);
-    for (i, &b) in payload.iter().enumerate() {
-        out.put_u8(b ^ mask_key[i % 4]);
+    let start = out.len();
+    out.put_slice(payload);
+    for (i, b) in out[start..].iter_mut().enumerate() {
+        *b ^= mask_key[i % 4];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Collect(Vec<(u8, bool, Vec<u8>)>);
+
+    impl FrameHandler for Collect {
+        fn frame(&mut self, opcode: u8, fin: bool, data: &[u8]) {
+            self.0.push((opcode, fin, data.to_vec()));
+        }
+    }
+
+    /// Unmasked server frame header with a 64-bit extended length.
+    fn header_64(opcode: u8, len: u64) -> BytesMut {
+        let mut buf = BytesMut::new();
+        buf.put_u8(0x80 | opcode);
+        buf.put_u8(127);
+        buf.put_u64(len);
+        buf
+    }
+
+    #[test]
+    fn test_receive_64bit_length() {
+        let payload = vec![b'x'; 70_000];
+        let mut wire = header_64(OP_TEXT, payload.len() as u64);
+        wire.put_slice(&payload);
+
+        let mut parser = FrameParser::new(1 << 20);
+        let mut handler = Collect(Vec::new());
+        // Feed in two pieces to exercise the partial-payload path
+        let mut buf = wire.split_to(30_000);
+        parser.receive(&mut buf, &mut handler).unwrap();
+        assert!(handler.0.is_empty());
+        buf.unsplit(wire);
+        parser.receive(&mut buf, &mut handler).unwrap();
+        assert_eq!(handler.0.len(), 1);
+        assert_eq!(handler.0[0].0, OP_TEXT);
+        assert!(handler.0[0].1);
+        assert_eq!(handler.0[0].2, payload);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_oversized_frame_rejected_at_header() {
+        let mut parser = FrameParser::new(65_536);
+        let mut handler = Collect(Vec::new());
+        // Header only: the limit must trip before any payload arrives
+        let mut buf = header_64(OP_TEXT, 65_537);
+        let err = parser.receive(&mut buf, &mut handler).unwrap_err();
+        assert_eq!(err.close_code, CLOSE_MESSAGE_TOO_BIG);
+
+        let mut parser = FrameParser::new(65_536);
+        let mut buf = header_64(OP_TEXT, 1 << 63);
+        let err = parser.receive(&mut buf, &mut handler).unwrap_err();
+        assert_eq!(err.close_code, CLOSE_PROTOCOL_ERROR);
+    }
+
+    #[test]
+    fn test_encode_64bit_length() {
+        let payload: Vec<u8> = (0..100_000u32).map(|i| i as u8).collect();
+        let mask = [0x12, 0x34, 0x56, 0x78];
+        let mut out = BytesMut::new();
+        encode_frame(OP_BINARY, &payload, &mask, &mut out);
+        assert_eq!(out.len(), 2 + 8 + 4 + payload.len());
+        assert_eq!(out[0], 0x80 | OP_BINARY);
+        assert_eq!(out[1], 0x80 | 127);
+        assert_eq!(u64::from_be_bytes(out[2..10].try_into().unwrap()), payload.len() as u64);
+        assert_eq!(&out[10..14], &mask);
+        let unmasked: Vec<u8> = out[14..].iter().enumerate().map(|(i, b)| b ^ mask[i % 4]).collect();
+        assert_eq!(unmasked, payload);
     }
-    Ok(())
 }
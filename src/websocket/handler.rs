@@ -26,16 +26,17 @@ pub trait WebSocketHandler {
     /// Handshake succeeded; connection is now in WebSocket frame mode.
     fn connected(&mut self);
 
-    /// Text frame payload. Data is valid only for the duration of the call.
+    /// Text message payload, reassembled from fragments and checked to be valid UTF-8.
+    /// Data is valid only for the duration of the call.
     fn text_frame(&mut self, data: &[u8]);
 
-    /// Binary frame payload.
+    /// Binary message payload, reassembled from fragments.
     fn binary_frame(&mut self, data: &[u8]);
 
     /// Close frame (optional code + reason). Connection will close after return.
     fn close(&mut self, code: Option<u16>, reason: &str);
 
-    /// Ping received. Implementation may send Pong automatically or handler can send later.
+    /// Ping received. The connection answers it with a pong; this is for information only.
     fn ping(&mut self, data: &[u8]);
 
     /// Pong received (e.g. in response to our Ping).
@@ -26,7 +26,7 @@ console.log('[Plume] app.js module loading');
 
 import { state, getEffectiveRelays } from './modules/state.js';
 import { invoke } from './modules/tauri.js';
-import { debugLog, escapeHtml, showConfirm } from './modules/utils.js';
+import { debugLog, escapeHtml, showConfirm, updatePowProgressBanner } from './modules/utils.js';
 import { validatePublicKey } from './modules/keys.js';
 import { loadConfig, saveConfig, updateUIFromConfig, setSavingState, updateFeedInitialState } from './modules/config.js';
 import { isNoteMuted, isContentUnreadable, ensureMutedConfig, handleMuteClick, loadMutedPanel, renderMutedPanels, saveMutedFromPanel } from './modules/muting.js';
@@ -35,7 +35,7 @@ import {
     setCardAvatar, isNoteLiked, performZap, performLike,
     openLikeEmojiModal, closeLikeEmojiModal, handleLikeMouseDown, handleLikeMouseUp, handleLikeMouseLeave,
     createNoteCard, createRepostCard, verifyNote, resolveNostrEmbeds, displayNotes,
-    isNoteBookmarked
+    isNoteBookmarked, copyNoteLink, deleteNote, removeDeletedNotes, describeDeletionResult
 } from './modules/notes.js';
 import {
     startInitialFeedFetch, pollForNewNotes, fetchNotesFirehoseOnHomeClick,
@@ -43,7 +43,7 @@ import {
 } from './modules/feed.js';
 import {
     updateSidebarAvatar, fetchProfile, openProfileForUser,
-    openProfileQRModal, closeProfileQRModal, openEditProfileInSettings,
+    openProfileQRModal, closeProfileQRModal, copyProfileLink, openEditProfileInSettings,
     handleEditProfileSubmit, loadProfileFeed, generateNewKeyPair, updateProfileDisplay,
     setSwitchView as profileSetSwitchView, setUpdateUIFromConfig as profileSetUpdateUIFromConfig
 } from './modules/profile.js';
@@ -54,21 +54,24 @@ import {
 import {
     loadMessagesView, selectConversation, loadConversationMessages,
     sendMessage, updateMessagesNavUnread, updateSendButtonState,
-    checkUnreadDmsOnStartup, startDmStream
+    refreshUnreadDms, markConversationRead, startDmStream,
+    updateDmBackfillProgress, finishDmBackfill, searchMessages
 } from './modules/messages.js';
 import {
     openCompose, closeCompose, updateCharCount, handleComposeSubmit
 } from './modules/compose.js';
 import {
     openSettings, closeSettings, showSettingsPanel,
-    populateKeysPanel, copyNsecToClipboard, saveKeysPanel,
+    populateKeysPanel, copyNsecToClipboard, copyNcryptsecToClipboard, saveKeysPanel,
     saveHomeFeedModeFromPanel, saveZapsFromPanel, saveMediaServerFromPanel,
     handleSettingsSubmit, setUpdateUIFromConfig as settingsSetUpdateUIFromConfig
 } from './modules/settings.js';
 import { updateRelayList, bindRelayPanelHandlers, runRelayTests } from './modules/relays.js';
+import { loadBunkerPanel, bindBunkerPanelHandlers } from './modules/bunker.js';
 import {
     updateSidebarAuthState, showMutedTooltip, populateWelcomeProfiles,
-    handleWelcomeLogin, handleWelcomeGenerate, handleProfileSelect, handleLogout,
+    handleWelcomeLogin, handleWelcomeGenerate, handleProfileSelect, handleLogout, unlockActiveProfile,
+    handleBunkerConnect, handleNostrconnectStart,
     setSwitchView as authSetSwitchView
 } from './modules/auth.js';
 import {
@@ -151,6 +154,8 @@ async function init() {
         // Wire welcome screen buttons (sync, before any awaits)
         document.getElementById('welcome-login-btn')?.addEventListener('click', handleWelcomeLogin);
         document.getElementById('welcome-generate-btn')?.addEventListener('click', handleWelcomeGenerate);
+        document.getElementById('welcome-bunker-btn')?.addEventListener('click', handleBunkerConnect);
+        document.getElementById('welcome-nostrconnect-btn')?.addEventListener('click', handleNostrconnectStart);
 
         // Wire logout button (sync, before any awaits)
         var logoutBtnEl = document.getElementById('logout-btn');
@@ -192,6 +197,7 @@ async function init() {
         document.getElementById('settings-muted-save')?.addEventListener('click', saveMutedFromPanel);
         document.getElementById('settings-follows-save')?.addEventListener('click', saveFollowsPanel);
         document.getElementById('settings-zaps-save')?.addEventListener('click', saveZapsFromPanel);
+        bindBunkerPanelHandlers();
         var settingsKeysForm = document.getElementById('settings-keys-form');
         if (settingsKeysForm) {
             settingsKeysForm.addEventListener('submit', function(e) {
@@ -200,6 +206,7 @@ async function init() {
             });
         }
         document.getElementById('settings-keys-copy-nsec')?.addEventListener('click', copyNsecToClipboard);
+        document.getElementById('settings-keys-copy-ncryptsec')?.addEventListener('click', copyNcryptsecToClipboard);
 
         // Messages view: conversation list, send button, Message from profile
         var messagesListEl = document.querySelector('.messages-list');
@@ -209,11 +216,26 @@ async function init() {
                 if (item) {
                     var other = item.getAttribute('data-other-pubkey');
                     if (other) {
+                        // Search results open their conversation at the message found
+                        state.highlightMessageId = item.getAttribute('data-message-id');
                         selectConversation(other);
                     }
                 }
             });
         }
+        var messagesSearchInput = document.getElementById('messages-search-input');
+        if (messagesSearchInput) {
+            var messagesSearchTimer = null;
+            messagesSearchInput.addEventListener('input', function() {
+                if (messagesSearchTimer) {
+                    clearTimeout(messagesSearchTimer);
+                }
+                messagesSearchTimer = setTimeout(function() {
+                    messagesSearchTimer = null;
+                    searchMessages(messagesSearchInput.value);
+                }, 300);
+            });
+        }
         var messageSendBtn = document.getElementById('message-send-btn');
         var messageInput = document.getElementById('message-input');
         if (messageSendBtn) {
@@ -255,16 +277,65 @@ async function init() {
                     dmReloadTimer = setTimeout(function() {
                         dmReloadTimer = null;
                         loadConversationMessages(state.selectedConversation);
+                        markConversationRead(state.selectedConversation);
                     }, 500);
                 } else {
-                    state.unreadMessageCount = (state.unreadMessageCount || 0) + 1;
-                    updateMessagesNavUnread();
+                    refreshUnreadDms();
+                }
+            });
+
+            // A remote signer wants the user to approve a request in the browser
+            window.__TAURI__.event.listen('remote-signer-auth-url', function(ev) {
+                if (typeof ev.payload === 'string' && /^https?:\/\//.test(ev.payload)) {
+                    window.open(ev.payload, '_blank');
+                }
+            });
+
+            // Notes their authors have asked to delete (NIP-09): take them off screen
+            window.__TAURI__.event.listen('notes-deleted', function(ev) {
+                removeDeletedNotes(ev.payload);
+            });
+
+            // Proof of work being mined for a post: show progress with a Cancel button
+            window.__TAURI__.event.listen('pow-progress', function(ev) {
+                var progress;
+                try {
+                    progress = JSON.parse(ev.payload);
+                }
+                catch (e) {
+                    return;
+                }
+                updatePowProgressBanner(progress, function() {
+                    invoke('cancel_pow').catch(function(err) { console.error('Failed to cancel proof of work:', err); });
+                });
+            });
+
+            // Another app connected to our bunker: tell the user and refresh the panel if open
+            window.__TAURI__.event.listen('bunker-app-connected', function() {
+                var panel = document.getElementById('settings-panel-bunker');
+                if (panel && panel.style.display !== 'none') {
+                    loadBunkerPanel();
                 }
+                var t = window.PlumeI18n && window.PlumeI18n.t ? window.PlumeI18n.t.bind(window.PlumeI18n) : function(k) { return k; };
+                alert(t('settings.bunkerAppConnected') || 'An app connected to your bunker.');
+            });
+
+            // Older DMs being fetched from relays after the initial sync
+            window.__TAURI__.event.listen('dm-backfill-progress', function(ev) {
+                try {
+                    updateDmBackfillProgress(JSON.parse(ev.payload));
+                }
+                catch (e) {
+                    console.warn('dm-backfill-progress:', e);
+                }
+            });
+            window.__TAURI__.event.listen('dm-backfill-done', function(ev) {
+                finishDmBackfill(ev.payload);
             });
 
             // Initial DM sync complete — re-count unread and refresh messages view
             window.__TAURI__.event.listen('dm-sync-done', function() {
-                checkUnreadDmsOnStartup();
+                refreshUnreadDms();
                 // If the user is viewing messages, refresh to pick up newly synced messages
                 if (state.currentView === 'messages') {
                     if (state.selectedConversation) {
@@ -453,6 +524,7 @@ async function init() {
         if (closeProfileQrBtn) {
             closeProfileQrBtn.addEventListener('click', closeProfileQRModal);
         }
+        document.getElementById('profile-qr-copy-link')?.addEventListener('click', copyProfileLink);
         if (profileQrModal) {
             profileQrModal.addEventListener('click', function(e) {
                 if (e.target === e.currentTarget) {
@@ -606,6 +678,21 @@ async function init() {
                 var note = (state.notes && state.notes.find(function(n) { return n.id === noteId; })) ||
                     (state.profileNotes && state.profileNotes.find(function(n) { return n.id === noteId; })) ||
                     (state.bookmarkNotes && state.bookmarkNotes.find(function(n) { return n.id === noteId; }));
+                if (repostBtn.classList.contains('reposted')) {
+                    // Already reposted this session: retract it (NIP-09)
+                    repostBtn.disabled = true;
+                    invoke('undo_response', { event_id: noteId, kind: 6 })
+                        .then(function(result) {
+                            repostBtn.classList.remove('reposted');
+                            alert(describeDeletionResult(result));
+                        })
+                        .catch(function(err) {
+                            console.error('Undo repost failed:', err);
+                            alert((window.PlumeI18n && window.PlumeI18n.t ? window.PlumeI18n.t('errors.failedToDelete') : 'Failed to delete') + ': ' + err);
+                        })
+                        .finally(function() { repostBtn.disabled = false; });
+                    return;
+                }
                 var contentOpt = note ? JSON.stringify(note) : null;
                 repostBtn.disabled = true;
                 invoke('post_repost', { eventId: noteId, authorPubkey: pubkey, contentOptional: contentOpt })
@@ -619,6 +706,20 @@ async function init() {
                     .finally(function() { repostBtn.disabled = false; });
                 return;
             }
+            var shareBtn = e.target.closest('.note-action[data-action="share"]');
+            if (shareBtn) {
+                e.preventDefault();
+                e.stopPropagation();
+                copyNoteLink(shareBtn.dataset.noteId, shareBtn.dataset.pubkey, parseInt(shareBtn.dataset.kind, 10) || 1);
+                return;
+            }
+            var deleteBtn = e.target.closest('.note-action[data-action="delete"]');
+            if (deleteBtn) {
+                e.preventDefault();
+                e.stopPropagation();
+                deleteNote(deleteBtn.dataset.noteId, parseInt(deleteBtn.dataset.kind, 10) || 1, deleteBtn);
+                return;
+            }
             var bookmarkBtn = e.target.closest('.note-action[data-action="bookmark"]');
             if (bookmarkBtn) {
                 e.preventDefault();
@@ -725,12 +826,13 @@ async function init() {
         console.log('[Plume] App config loaded, active_profile:', appConfig.active_profile || 'none');
 
         if (appConfig.active_profile) {
-            // Logged in – load profile config and go to feed
+            // Logged in – load profile config, unlock the private key and go to feed
             await loadConfig();
+            await unlockActiveProfile();
             updateSidebarAuthState();
             switchView('feed');
             // Check for unread DMs already in the local store
-            checkUnreadDmsOnStartup();
+            refreshUnreadDms();
             // Start the DM stream early so messages sync in the background.
             // When sync completes, dm-sync-done re-counts unread for accuracy.
             startDmStream();
@@ -20,7 +20,7 @@
 
 import { state, DEFAULT_RELAYS } from './state.js';
 import { invoke } from './tauri.js';
-import { escapeHtml, showConfirm } from './utils.js';
+import { escapeHtml, showConfirm, showPasswordPrompt } from './utils.js';
 import { updateUIFromConfig } from './config.js';
 import { updateSidebarAvatar } from './profile.js';
 import { startInitialFeedFetch } from './feed.js';
@@ -37,7 +37,8 @@ export function setSwitchView(fn) {
 // State 3 (full auth): All enabled
 export function updateSidebarAuthState() {
     var hasProfile = !!(state.config && state.config.public_key);
-    var hasNsec = !!(state.config && state.config.private_key);
+    // A remote signer (NIP-46) signs for profiles that keep no private key here
+    var hasNsec = !!(state.config && (state.config.private_key || state.config.remote_signer));
     var t = window.PlumeI18n && window.PlumeI18n.t ? window.PlumeI18n.t.bind(window.PlumeI18n) : function(k) { return k; };
 
     var navMessages = document.querySelector('.nav-item[data-view="messages"]');
@@ -159,7 +160,7 @@ export async function populateWelcomeProfiles() {
             '<div class="known-profile-name">' + escapeHtml(name) + '</div>' +
             '<div class="known-profile-npub">' + escapeHtml(shortNpub) + '</div>' +
             '</div>';
-        li.addEventListener('click', function() { handleProfileSelect(p.npub); });
+        li.addEventListener('click', function() { handleProfileSelect(p.npub, p.has_key); });
         // Handle avatar load error
         var img = li.querySelector('.known-profile-avatar');
         if (img) {
@@ -175,20 +176,53 @@ export async function populateWelcomeProfiles() {
     });
 }
 
+// Ask for the password of the active profile's encrypted key until it unlocks or the user
+// cancels (the profile then stays read-only).
+export async function unlockActiveProfile() {
+    if (!state.config || !state.config.encrypted_private_key || state.config.private_key) {
+        return;
+    }
+    var t = window.PlumeI18n && window.PlumeI18n.t ? window.PlumeI18n.t.bind(window.PlumeI18n) : function(k) { return k; };
+    while (true) {
+        var password = await showPasswordPrompt(t('auth.unlockPrompt') || 'Enter your password to unlock your private key, or cancel to continue read-only.');
+        if (password === null) {
+            return;
+        }
+        try {
+            var configJson = await invoke('unlock_profile', { password: password });
+            state.config = JSON.parse(configJson);
+            updateUIFromConfig();
+            updateSidebarAuthState();
+            return;
+        } catch (err) {
+            alert(typeof err === 'string' ? err : (err.message || 'Unlock failed'));
+        }
+    }
+}
+
 export async function handleWelcomeLogin() {
     var npubEl = document.getElementById('welcome-npub');
     var nsecEl = document.getElementById('welcome-nsec');
+    var passwordEl = document.getElementById('welcome-password');
     var errorEl = document.getElementById('welcome-login-error');
     if (errorEl) {
         errorEl.textContent = '';
     }
 
+    var mnemonicEl = document.getElementById('welcome-mnemonic');
+    var mnemonicPassphraseEl = document.getElementById('welcome-mnemonic-passphrase');
+    var accountEl = document.getElementById('welcome-mnemonic-account');
+    var t = window.PlumeI18n && window.PlumeI18n.t ? window.PlumeI18n.t.bind(window.PlumeI18n) : function(k) { return k; };
+
     var npub = (npubEl ? npubEl.value : '').trim();
     var nsec = (nsecEl ? nsecEl.value : '').trim();
+    var password = passwordEl ? passwordEl.value : '';
+    var mnemonic = (mnemonicEl ? mnemonicEl.value : '').trim();
+    var account = accountEl ? parseInt(accountEl.value, 10) : 0;
 
-    if (!npub) {
+    if (!npub && !mnemonic) {
         if (errorEl) {
-            errorEl.textContent = 'Public key is required';
+            errorEl.textContent = t('welcome.publicKeyOrSeedRequired') || 'Enter a public key or a seed phrase';
         }
         return;
     }
@@ -196,31 +230,25 @@ export async function handleWelcomeLogin() {
     try {
         var configJson = await invoke('login_with_keys', {
             public_key: npub,
-            private_key: nsec || null
+            private_key: nsec || null,
+            password: password || null,
+            mnemonic: mnemonic || null,
+            mnemonic_passphrase: mnemonicPassphraseEl ? (mnemonicPassphraseEl.value || null) : null,
+            account: isNaN(account) || account < 0 ? 0 : account
         });
-        var cfg = JSON.parse(configJson);
-        state.config = cfg;
-        state.publicKeyHex = cfg.public_key || null;
-        state.publicKeyNpub = null;
-        if (cfg.public_key) {
-            try { state.publicKeyNpub = await invoke('convert_hex_to_npub', { hex_key: cfg.public_key }); } catch (e) {}
+        if (nsecEl) {
+            nsecEl.value = '';
         }
-        state.homeFeedMode = cfg.home_feed_mode || 'firehose';
-        updateUIFromConfig();
-        updateSidebarAuthState();
-
-        // Refresh app config
-        try {
-            var appJson = await invoke('get_app_config');
-            state.appConfig = JSON.parse(appJson);
-        } catch (e) {}
-
-        state.initialFeedLoadDone = false;
-        state.notes = [];
-        _switchView('feed');
-        startInitialFeedFetch();
-        // Fetch profile from relays in background to update sidebar avatar and local config
-        fetchProfile();
+        if (mnemonicEl) {
+            mnemonicEl.value = '';
+        }
+        if (mnemonicPassphraseEl) {
+            mnemonicPassphraseEl.value = '';
+        }
+        if (passwordEl) {
+            passwordEl.value = '';
+        }
+        await enterLoggedInProfile(configJson);
     } catch (err) {
         if (errorEl) {
             errorEl.textContent = typeof err === 'string' ? err : (err.message || 'Login failed');
@@ -228,6 +256,117 @@ export async function handleWelcomeLogin() {
     }
 }
 
+// Show the profile the backend just logged in to (config JSON from a login command).
+async function enterLoggedInProfile(configJson) {
+    var cfg = JSON.parse(configJson);
+    state.config = cfg;
+    state.publicKeyHex = cfg.public_key || null;
+    state.publicKeyNpub = null;
+    if (cfg.public_key) {
+        try { state.publicKeyNpub = await invoke('convert_hex_to_npub', { hex_key: cfg.public_key }); } catch (e) {}
+    }
+    state.homeFeedMode = cfg.home_feed_mode || 'firehose';
+    updateUIFromConfig();
+    updateSidebarAuthState();
+
+    // Refresh app config
+    try {
+        var appJson = await invoke('get_app_config');
+        state.appConfig = JSON.parse(appJson);
+    } catch (e) {}
+
+    state.initialFeedLoadDone = false;
+    state.notes = [];
+    _switchView('feed');
+    startInitialFeedFetch();
+    // Fetch profile from relays in background to update sidebar avatar and local config
+    fetchProfile();
+}
+
+function showSignerError(err) {
+    var errorEl = document.getElementById('welcome-signer-error');
+    if (errorEl) {
+        errorEl.textContent = err ? (typeof err === 'string' ? err : (err.message || String(err))) : '';
+    }
+}
+
+// Log in through the NIP-46 remote signer in the bunker:// URI the user pasted.
+export async function handleBunkerConnect() {
+    var t = window.PlumeI18n && window.PlumeI18n.t ? window.PlumeI18n.t.bind(window.PlumeI18n) : function(k) { return k; };
+    var uriEl = document.getElementById('welcome-bunker');
+    var btn = document.getElementById('welcome-bunker-btn');
+    var uri = (uriEl ? uriEl.value : '').trim();
+    showSignerError(null);
+    if (uri.indexOf('bunker://') !== 0) {
+        showSignerError(t('welcome.bunkerRequired') || 'Paste a bunker:// URI from your signer app');
+        return;
+    }
+    var originalText = btn ? btn.textContent : '';
+    try {
+        if (btn) {
+            btn.disabled = true;
+            btn.textContent = t('welcome.connectingSigner') || 'Waiting for your signer...';
+        }
+        var configJson = await invoke('connect_remote_signer', { uri: uri });
+        if (uriEl) {
+            uriEl.value = '';
+        }
+        await enterLoggedInProfile(configJson);
+    } catch (err) {
+        showSignerError(err);
+    } finally {
+        if (btn) {
+            btn.disabled = false;
+            btn.textContent = originalText;
+        }
+    }
+}
+
+// Show a nostrconnect:// link (and QR code) for the user's signer app, then wait for it to connect.
+export async function handleNostrconnectStart() {
+    var t = window.PlumeI18n && window.PlumeI18n.t ? window.PlumeI18n.t.bind(window.PlumeI18n) : function(k) { return k; };
+    var btn = document.getElementById('welcome-nostrconnect-btn');
+    var panel = document.getElementById('welcome-nostrconnect');
+    var uriEl = document.getElementById('welcome-nostrconnect-uri');
+    var qrEl = document.getElementById('welcome-nostrconnect-qr');
+    showSignerError(null);
+    try {
+        if (btn) {
+            btn.disabled = true;
+        }
+        var relays = (state.config && state.config.relays && state.config.relays.length) ? state.config.relays : DEFAULT_RELAYS;
+        var uri = await invoke('start_nostrconnect', { relays: relays });
+        if (uriEl) {
+            uriEl.value = uri;
+        }
+        if (qrEl) {
+            qrEl.innerHTML = '';
+            try {
+                var svg = await invoke('generate_qr_svg', { data: uri });
+                qrEl.innerHTML = svg
+                    .replace(/fill="#000000"/g, 'fill="currentColor"')
+                    .replace(/fill="#ffffff"/g, 'fill="transparent"');
+            } catch (e) {
+                console.warn('QR generation failed:', e);
+            }
+        }
+        if (panel) {
+            panel.style.display = 'flex';
+        }
+        var configJson = await invoke('finish_nostrconnect');
+        await enterLoggedInProfile(configJson);
+    } catch (err) {
+        showSignerError(err);
+    } finally {
+        if (btn) {
+            btn.disabled = false;
+        }
+        if (panel) {
+            panel.style.display = 'none';
+        }
+    }
+}
+
 export async function handleWelcomeGenerate() {
     var btn = document.getElementById('welcome-generate-btn');
     if (!btn) {
@@ -236,11 +375,17 @@ export async function handleWelcomeGenerate() {
     var t = window.PlumeI18n && window.PlumeI18n.t ? window.PlumeI18n.t.bind(window.PlumeI18n) : function(k) { return k; };
     var originalText = btn.textContent;
 
+    var password = await showPasswordPrompt(t('auth.newPasswordPrompt') || 'Choose a password to encrypt your private key. You will need it each time you start Plume.');
+    if (!password) {
+        return;
+    }
+
     try {
         btn.disabled = true;
         btn.textContent = t('profile.generating') || 'Generating...';
 
-        var result = await invoke('generate_keypair');
+        var locale = window.PlumeI18n && window.PlumeI18n.getLocale ? window.PlumeI18n.getLocale() : 'en';
+        var result = await invoke('generate_keypair', { password: password, locale: locale });
         var keys = JSON.parse(result);
 
         state.config = state.config || {};
@@ -257,7 +402,9 @@ export async function handleWelcomeGenerate() {
             state.appConfig = JSON.parse(appJson);
         } catch (e) {}
 
-        alert(t('profile.newIdentityCreated') + '\n\nPublic Key (npub):\n' + keys.npub + '\n\nSecret Key (nsec):\n' + keys.nsec + '\n\n' + t('profile.saveNsecWarning'));
+        alert(t('profile.newIdentityCreated') + '\n\nPublic Key (npub):\n' + keys.npub + '\n\n' +
+            (t('profile.seedPhraseLabel') || 'Seed phrase (write these words down in order):') + '\n' + keys.mnemonic + '\n\n' +
+            (t('profile.saveSeedWarning') || 'IMPORTANT: Keep your seed phrase somewhere safe and private.'));
 
         state.initialFeedLoadDone = false;
         state.notes = [];
@@ -271,9 +418,14 @@ export async function handleWelcomeGenerate() {
     }
 }
 
-export async function handleProfileSelect(npub) {
+export async function handleProfileSelect(npub, hasKey) {
+    var password = null;
+    if (hasKey) {
+        var t = window.PlumeI18n && window.PlumeI18n.t ? window.PlumeI18n.t.bind(window.PlumeI18n) : function(k) { return k; };
+        password = await showPasswordPrompt(t('auth.unlockPrompt') || 'Enter your password to unlock your private key, or cancel to continue read-only.');
+    }
     try {
-        var configJson = await invoke('switch_profile', { npub: npub });
+        var configJson = await invoke('switch_profile', { npub: npub, password: password || null });
         var cfg = JSON.parse(configJson);
         state.config = cfg;
         state.publicKeyHex = cfg.public_key || null;
@@ -322,6 +474,9 @@ export async function handleLogout() {
         state.config = {
             public_key: '',
             private_key: null,
+            encrypted_private_key: null,
+            remote_signer: null,
+            remote_signer_client_key: null,
             relays: DEFAULT_RELAYS.slice(),
             name: 'Anonymous',
             about: null,
@@ -337,7 +492,10 @@ export async function handleLogout() {
             muted_words: [],
             muted_hashtags: [],
             bookmarks: [],
+            relay_pow_difficulty: {},
+            min_feed_pow: 0,
             default_zap_amount: 42,
+            publish_quorum: 1,
             hide_encrypted_notes: true
         };
         state.publicKeyHex = null;
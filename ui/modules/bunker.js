@@ -0,0 +1,199 @@
+/*
+ * modules/bunker.js
+ * Copyright (C) 2026 Chris Burdess
+ *
+ * This file is part of Plume, a Nostr desktop client.
+ *
+ * Plume is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Plume is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Plume.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+import { invoke } from './tauri.js';
+import { escapeHtml, shortenKey } from './utils.js';
+
+function showBunkerError(message) {
+    var errorEl = document.getElementById('settings-bunker-error');
+    if (errorEl) {
+        errorEl.textContent = message || '';
+    }
+}
+
+// Render the bunker panel from the status returned by the backend
+function renderBunkerStatus(status) {
+    const t = window.PlumeI18n && window.PlumeI18n.t ? window.PlumeI18n.t.bind(window.PlumeI18n) : function(k) { return k; };
+    var relaysEl = document.getElementById('settings-bunker-relays');
+    if (relaysEl && document.activeElement !== relaysEl) {
+        relaysEl.value = (status.settings.relays || []).join('\n');
+    }
+    var toggleBtn = document.getElementById('settings-bunker-toggle');
+    if (toggleBtn) {
+        toggleBtn.textContent = status.running ? t('settings.bunkerStop') : t('settings.bunkerStart');
+        toggleBtn.dataset.running = status.running ? 'true' : 'false';
+    }
+    var stateEl = document.getElementById('settings-bunker-state');
+    if (stateEl) {
+        stateEl.textContent = status.running ? t('settings.bunkerRunning') : t('settings.bunkerStopped');
+        stateEl.classList.toggle('bunker-state-running', !!status.running);
+    }
+    var uriEl = document.getElementById('settings-bunker-uri');
+    if (uriEl) {
+        uriEl.value = status.uri || '';
+    }
+    var appsEl = document.getElementById('settings-bunker-apps');
+    if (!appsEl) {
+        return;
+    }
+    var apps = status.settings.apps || [];
+    bunkerApps = apps;
+    if (apps.length === 0) {
+        appsEl.innerHTML = '<li class="text-muted">' + escapeHtml(t('settings.bunkerNoApps')) + '</li>';
+        return;
+    }
+    appsEl.innerHTML = apps.map(function(app) {
+        return '<li class="bunker-app" data-app-pubkey="' + escapeHtml(app.pubkey) + '">' +
+            '<div class="bunker-app-header">' +
+            '<input type="text" class="bunker-app-name" value="' + escapeHtml(app.name || '') + '">' +
+            '<span class="bunker-app-pubkey">' + escapeHtml(shortenKey(app.pubkey)) + '</span>' +
+            '</div>' +
+            '<div class="bunker-app-policy">' +
+            '<label>' + escapeHtml(t('settings.bunkerAllowedKinds')) +
+            ' <input type="text" class="bunker-app-kinds" value="' + escapeHtml((app.allowed_kinds || []).join(', ')) + '"></label>' +
+            '<label>' + escapeHtml(t('settings.bunkerRateLimit')) +
+            ' <input type="number" class="bunker-app-rate" min="0" max="10000" step="1" value="' + (app.requests_per_minute || 0) + '"></label>' +
+            '<label class="checkbox-label"><input type="checkbox" class="bunker-app-encryption"' + (app.allow_encryption ? ' checked' : '') + '> ' +
+            escapeHtml(t('settings.bunkerAllowEncryption')) + '</label>' +
+            '</div>' +
+            requestedPermissionsHtml(app, t) +
+            '<div class="bunker-app-actions">' +
+            '<button type="button" class="btn btn-ghost bunker-app-remove">' + escapeHtml(t('settings.bunkerRemoveApp')) + '</button>' +
+            '<button type="button" class="btn bunker-app-save">' + escapeHtml(t('accountModal.save')) + '</button>' +
+            '</div>' +
+            '</li>';
+    }).join('');
+}
+
+// What an app asked for when it connected, with a button to copy it into the policy form.
+// Nothing is granted until the user saves the policy.
+function requestedPermissionsHtml(app, t) {
+    var kinds = app.requested_kinds || [];
+    if (kinds.length === 0 && !app.requested_encryption) {
+        return '';
+    }
+    var lines = [];
+    if (kinds.length > 0) {
+        lines.push(escapeHtml(t('settings.bunkerRequestedKinds', { kinds: kinds.join(', ') })));
+    }
+    if (app.requested_encryption) {
+        lines.push(escapeHtml(t('settings.bunkerRequestedEncryption')));
+    }
+    return '<div class="bunker-app-requested">' +
+        '<p>' + lines.join('<br>') + '</p>' +
+        '<button type="button" class="btn btn-small bunker-app-grant">' + escapeHtml(t('settings.bunkerGrantRequested')) + '</button>' +
+        '</div>';
+}
+
+// Fill the policy form with what the app asked for; it takes effect when saved
+function grantRequested(item, app) {
+    var kindsEl = item.querySelector('.bunker-app-kinds');
+    var kinds = kindsEl.value.split(/[\s,]+/).filter(function(k) { return k.length > 0; });
+    (app.requested_kinds || []).forEach(function(k) {
+        if (kinds.indexOf(String(k)) === -1) {
+            kinds.push(String(k));
+        }
+    });
+    kindsEl.value = kinds.join(', ');
+    if (app.requested_encryption) {
+        item.querySelector('.bunker-app-encryption').checked = true;
+    }
+}
+
+var bunkerApps = [];
+
+async function runBunkerCommand(command, args) {
+    showBunkerError('');
+    try {
+        var json = await invoke(command, args || {});
+        renderBunkerStatus(JSON.parse(json));
+    } catch (e) {
+        console.error('[Plume] ' + command + ' failed:', e);
+        showBunkerError(String(e));
+    }
+}
+
+export function loadBunkerPanel() {
+    return runBunkerCommand('get_bunker_status');
+}
+
+function readRelays() {
+    var relaysEl = document.getElementById('settings-bunker-relays');
+    return (relaysEl ? relaysEl.value : '').split(/[\s,]+/).filter(function(r) { return r.length > 0; });
+}
+
+async function toggleBunker() {
+    var toggleBtn = document.getElementById('settings-bunker-toggle');
+    if (toggleBtn && toggleBtn.dataset.running === 'true') {
+        await runBunkerCommand('stop_bunker');
+        return;
+    }
+    // Start with whatever relays are in the box
+    await runBunkerCommand('save_bunker_relays', { relays: readRelays() });
+    await runBunkerCommand('start_bunker');
+}
+
+async function copyBunkerUri() {
+    const t = window.PlumeI18n && window.PlumeI18n.t ? window.PlumeI18n.t.bind(window.PlumeI18n) : function(k) { return k; };
+    var uriEl = document.getElementById('settings-bunker-uri');
+    if (uriEl && uriEl.value && navigator.clipboard) {
+        await navigator.clipboard.writeText(uriEl.value);
+        alert(t('settings.bunkerUriCopied'));
+    }
+}
+
+function saveAppPolicy(item) {
+    var kinds = item.querySelector('.bunker-app-kinds').value
+        .split(/[\s,]+/)
+        .map(function(k) { return parseInt(k, 10); })
+        .filter(function(k) { return !isNaN(k) && k >= 0; });
+    var rate = parseInt(item.querySelector('.bunker-app-rate').value, 10);
+    return runBunkerCommand('set_bunker_app_policy', {
+        app_pubkey: item.dataset.appPubkey,
+        name: item.querySelector('.bunker-app-name').value,
+        allowed_kinds: kinds,
+        allow_encryption: item.querySelector('.bunker-app-encryption').checked,
+        requests_per_minute: isNaN(rate) || rate < 0 ? 0 : rate
+    });
+}
+
+export function bindBunkerPanelHandlers() {
+    document.getElementById('settings-bunker-toggle')?.addEventListener('click', toggleBunker);
+    document.getElementById('settings-bunker-save-relays')?.addEventListener('click', function() {
+        runBunkerCommand('save_bunker_relays', { relays: readRelays() });
+    });
+    document.getElementById('settings-bunker-copy-uri')?.addEventListener('click', copyBunkerUri);
+    document.getElementById('settings-bunker-apps')?.addEventListener('click', function(e) {
+        var item = e.target.closest('.bunker-app');
+        if (!item) {
+            return;
+        }
+        if (e.target.closest('.bunker-app-grant')) {
+            var app = bunkerApps.find(function(a) { return a.pubkey === item.dataset.appPubkey; });
+            if (app) {
+                grantRequested(item, app);
+            }
+        } else if (e.target.closest('.bunker-app-save')) {
+            saveAppPolicy(item);
+        } else if (e.target.closest('.bunker-app-remove')) {
+            runBunkerCommand('remove_bunker_app', { app_pubkey: item.dataset.appPubkey });
+        }
+    });
+}
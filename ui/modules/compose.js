@@ -150,8 +150,8 @@ export async function handleComposeSubmit(event) {
         return;
     }
 
-    // Check if we have a private key
-    if (!state.config || !state.config.private_key) {
+    // Check if we have a private key or a remote signer
+    if (!state.config || !(state.config.private_key || state.config.remote_signer)) {
         showComposeError(t('composeModal.noPrivateKey'));
         return;
     }
@@ -70,6 +70,9 @@ export async function loadConfig() {
         state.config = {
             public_key: '',
             private_key: null,
+            encrypted_private_key: null,
+            remote_signer: null,
+            remote_signer_client_key: null,
             relays: DEFAULT_RELAYS.slice(),
             name: 'Anonymous',
             about: null,
@@ -85,7 +88,11 @@ export async function loadConfig() {
             muted_words: [],
             muted_hashtags: [],
             bookmarks: [],
+            auth_denied_relays: [],
+            relay_pow_difficulty: {},
+            min_feed_pow: 0,
             default_zap_amount: 42,
+            publish_quorum: 1,
             hide_encrypted_notes: true
         };
         updateUIFromConfig();
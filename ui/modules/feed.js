@@ -169,11 +169,13 @@ export async function startInitialFeedFetch() {
                     authors = null;
                 }
             }
+            // Notes from people we follow skip the proof of work spam filter
             await invoke('start_feed_stream', {
                 relay_urls: effectiveRelays,
                 limit: FEED_LIMIT,
                 authors: authors,
-                since: null
+                since: null,
+                min_pow: authors ? 0 : ((state.config && state.config.min_feed_pow) || 0)
             });
         } catch (error) {
             console.error('Feed stream failed:', error);
@@ -73,6 +73,16 @@ export async function validatePublicKey(key) {
         return { valid: false, hex: null, npub: null, error: 'Key is empty' };
     }
 
+    // name@domain: resolve through the domain's NIP-05 nostr.json
+    if (key.indexOf('@') !== -1) {
+        try {
+            const info = JSON.parse(await invoke('parse_key', { key: key }));
+            return { valid: true, hex: info.hex, npub: info.npub, relays: info.relays || [], error: null };
+        } catch (error) {
+            return { valid: false, hex: null, npub: null, error: error.toString() };
+        }
+    }
+
     try {
         const hex = await publicKeyToHex(key);
         if (hex) {
@@ -86,6 +96,17 @@ export async function validatePublicKey(key) {
     return { valid: false, hex: null, npub: null, error: 'Invalid key format' };
 }
 
+// Check a NIP-05 identifier against the pubkey (hex) of the profile claiming it.
+// Returns { identifier, status: 'verified' | 'mismatch' | 'unreachable', pubkey, relays } or null.
+export async function verifyNip05(identifier, pubkey) {
+    try {
+        return JSON.parse(await invoke('verify_nip05', { identifier: identifier, pubkey: pubkey }));
+    } catch (error) {
+        console.error('Failed to verify NIP-05:', error);
+        return null;
+    }
+}
+
 // Validate and normalize a secret key (returns hex or null)
 export async function validateSecretKey(key) {
     if (!key || key.trim() === '') {
@@ -51,6 +51,11 @@ export async function loadMessagesView() {
 
     // Reset stale state from previous profile / session
     state.selectedConversation = null;
+    state.messagesSearchQuery = '';
+    var searchInput = document.getElementById('messages-search-input');
+    if (searchInput) {
+        searchInput.value = '';
+    }
     if (paneEl) {
         paneEl.style.display = 'none';
         var msgContainer = document.getElementById('messages-chat-messages');
@@ -93,11 +98,12 @@ export async function loadMessagesView() {
                     ? '<img src="' + escapeHtml(picture) + '" alt="" class="conversation-avatar" onerror="this.style.display=\'none\';this.nextElementSibling.style.display=\'flex\'">'
                       + '<span class="conversation-avatar conversation-avatar-placeholder" style="display:none"><img src="icons/user.svg" alt="" class="icon-sm"></span>'
                     : '<span class="conversation-avatar conversation-avatar-placeholder"><img src="icons/user.svg" alt="" class="icon-sm"></span>';
+                const unread = c.unread > 0 ? '<span class="conversation-unread-badge" title="' + escapeHtml(t('messages.unreadCount', { count: c.unread })) + '">' + (c.unread > 99 ? '99+' : c.unread) + '</span>' : '';
                 html += '<div class="conversation-item" role="button" tabindex="0" data-other-pubkey="' + escapeHtml(other) + '" title="' + escapeHtml(other) + '">'
                     + avatarHtml
                     + '<div class="conversation-item-info"><span class="conversation-item-name">' + name + '</span>'
                     + (ts ? '<span class="conversation-item-meta">' + escapeHtml(ts) + '</span>' : '')
-                    + '</div></div>';
+                    + '</div>' + unread + '</div>';
                 if (!cached && other) {
                     uncachedPubkeys.push(other);
                 }
@@ -163,13 +169,9 @@ export async function loadMessagesView() {
 
 export function selectConversation(otherPubkeyHex) {
     state.selectedConversation = otherPubkeyHex;
-    // User is actively reading messages — clear the unread badge and persist the read timestamp
+    // User is reading this conversation — move its read marker and recount unread
     if (otherPubkeyHex) {
-        state.unreadMessageCount = 0;
-        updateMessagesNavUnread();
-        invoke('mark_dms_read').catch(function(e) {
-            console.warn('mark_dms_read:', e);
-        });
+        markConversationRead(otherPubkeyHex);
     }
     const paneEl = document.querySelector('.messages-chat-pane');
     const emptyEl = document.querySelector('.messages-chat-empty');
@@ -200,6 +202,20 @@ export function selectConversation(otherPubkeyHex) {
     loadConversationMessages(otherPubkeyHex);
 }
 
+/// Mark a conversation read, drop its badge in the list and refresh the nav unread count.
+export function markConversationRead(otherPubkeyHex) {
+    var item = document.querySelector('.conversation-item[data-other-pubkey="' + (otherPubkeyHex || '').replace(/"/g, '\\"') + '"]');
+    var badge = item ? item.querySelector('.conversation-unread-badge') : null;
+    if (badge) {
+        badge.remove();
+    }
+    invoke('mark_conversation_read', { other_pubkey_hex: otherPubkeyHex })
+        .then(refreshUnreadDms)
+        .catch(function(e) {
+            console.warn('mark_conversation_read:', e);
+        });
+}
+
 export async function loadConversationMessages(otherPubkeyHex) {
     const container = document.getElementById('messages-chat-messages');
     if (!container) {
@@ -232,7 +248,63 @@ export function renderMessages(container, messages) {
         html += '<div class="' + cls + '" data-id="' + escapeHtml(m.id) + '"><div class="message-content">' + escapeHtml(m.content) + '</div><div class="message-meta">' + escapeHtml(new Date(m.created_at * 1000).toLocaleString()) + '</div></div>';
     }
     container.innerHTML = html;
-    container.scrollTop = container.scrollHeight;
+    // Opened from a search result: show that message, otherwise the latest
+    var highlighted = state.highlightMessageId
+        ? container.querySelector('[data-id="' + state.highlightMessageId.replace(/"/g, '\\"') + '"]')
+        : null;
+    state.highlightMessageId = null;
+    if (highlighted) {
+        highlighted.classList.add('message-highlight');
+        highlighted.scrollIntoView({ block: 'center' });
+    } else {
+        container.scrollTop = container.scrollHeight;
+    }
+}
+
+/// Search all conversations and list the matching messages in place of the conversations.
+/// "quoted words" match as a phrase, word* as a prefix. An empty query brings the list back.
+export async function searchMessages(query) {
+    const listEl = document.querySelector('.messages-list');
+    if (!listEl) {
+        return;
+    }
+    query = (query || '').trim();
+    if (!query) {
+        if (state.messagesSearchQuery) {
+            loadMessagesView();
+        }
+        return;
+    }
+    state.messagesSearchQuery = query;
+    const t = window.PlumeI18n && window.PlumeI18n.t ? window.PlumeI18n.t.bind(window.PlumeI18n) : function(k) { return k; };
+    listEl.innerHTML = '<div class="placeholder-message"><p>' + escapeHtml(t('messages.searching')) + '</p></div>';
+    try {
+        const json = await invoke('search_messages', { query: query });
+        if (state.messagesSearchQuery !== query) {
+            return; // superseded by a newer search
+        }
+        const hits = json ? JSON.parse(json) : [];
+        if (hits.length === 0) {
+            listEl.innerHTML = '<div class="placeholder-message"><p>' + escapeHtml(t('messages.noSearchResults')) + '</p></div>';
+            return;
+        }
+        let html = '';
+        for (let i = 0; i < hits.length; i++) {
+            const h = hits[i];
+            const cached = state.profileCache && state.profileCache[h.conversation] ? state.profileCache[h.conversation] : null;
+            const name = (cached && cached.name) ? escapeHtml(cached.name) : shortenPubkey(h.conversation);
+            const ts = new Date(h.created_at * 1000).toLocaleDateString();
+            html += '<div class="conversation-item message-search-hit" role="button" tabindex="0" data-other-pubkey="' + escapeHtml(h.conversation) + '" data-message-id="' + escapeHtml(h.id) + '">'
+                + '<div class="conversation-item-info"><span class="conversation-item-name">' + name + '</span>'
+                + '<span class="message-search-snippet">' + escapeHtml(h.snippet) + '</span>'
+                + '<span class="conversation-item-meta">' + escapeHtml(ts) + '</span>'
+                + '</div></div>';
+        }
+        listEl.innerHTML = html;
+    } catch (e) {
+        console.error('search_messages failed:', e);
+        listEl.innerHTML = '<div class="placeholder-message"><p>' + escapeHtml(String(e && e.message ? e.message : e)) + '</p></div>';
+    }
 }
 
 export async function sendMessage() {
@@ -287,6 +359,64 @@ export async function sendMessage() {
     }
 }
 
+/// Show how far the backfill of older DMs has got, from a dm-backfill-progress payload
+/// ({ relay_url, fetched, oldest, done }), summed over relays.
+export function updateDmBackfillProgress(progress) {
+    if (!progress || !progress.relay_url) {
+        return;
+    }
+    state.dmBackfill[progress.relay_url] = progress;
+    var fetched = 0;
+    var oldest = null;
+    var active = false;
+    Object.keys(state.dmBackfill).forEach(function(url) {
+        var p = state.dmBackfill[url];
+        fetched += p.fetched || 0;
+        if (p.oldest && (oldest === null || p.oldest < oldest)) {
+            oldest = p.oldest;
+        }
+        if (!p.done) {
+            active = true;
+        }
+    });
+    var statusEl = document.getElementById('messages-backfill-status');
+    if (!statusEl) {
+        return;
+    }
+    if (!active) {
+        statusEl.style.display = 'none';
+        return;
+    }
+    const t = window.PlumeI18n && window.PlumeI18n.t ? window.PlumeI18n.t.bind(window.PlumeI18n) : function(k) { return k; };
+    statusEl.textContent = t('messages.backfillProgress', {
+        count: fetched,
+        date: oldest ? new Date(oldest * 1000).toLocaleDateString() : '…'
+    });
+    statusEl.style.display = '';
+}
+
+/// Backfill of older DMs finished: hide the status and pick up what it fetched.
+export function finishDmBackfill(fetched) {
+    state.dmBackfill = {};
+    var statusEl = document.getElementById('messages-backfill-status');
+    if (statusEl) {
+        statusEl.style.display = 'none';
+    }
+    if (!fetched) {
+        return;
+    }
+    refreshUnreadDms();
+    if (state.currentView === 'messages') {
+        if (state.selectedConversation) {
+            loadConversationMessages(state.selectedConversation);
+        } else if (state.messagesSearchQuery) {
+            searchMessages(state.messagesSearchQuery);
+        } else {
+            loadMessagesView();
+        }
+    }
+}
+
 /// Enable/disable the send button based on whether the input has text.
 export function updateSendButtonState() {
     var input = document.getElementById('message-input');
@@ -296,15 +426,13 @@ export function updateSendButtonState() {
     }
 }
 
-/// Check for unread DMs on startup by comparing conversation timestamps
-/// against the persisted dm_last_read_at.  Sets the badge accordingly.
-export function checkUnreadDmsOnStartup() {
+/// Count unread messages (newer than each conversation's read marker) in the local store
+/// and set the nav badge accordingly.
+export function refreshUnreadDms() {
     invoke('count_unread_dms')
         .then(function(count) {
-            if (count > 0) {
-                state.unreadMessageCount = count;
-                updateMessagesNavUnread();
-            }
+            state.unreadMessageCount = count;
+            updateMessagesNavUnread();
         })
         .catch(function(e) {
             console.warn('count_unread_dms:', e);
@@ -20,7 +20,7 @@
 
 import { state, DEFAULT_LIKE_EMOJI, LIKE_EMOJI_LIST, NOSTR_EMBED_MAX_DEPTH, getEffectiveRelays } from './state.js';
 import { invoke } from './tauri.js';
-import { escapeHtml, escapeCssAttr, shortenKey, formatTimestamp, sanitizeUrl } from './utils.js';
+import { escapeHtml, escapeCssAttr, shortenKey, formatTimestamp, sanitizeUrl, showConfirm } from './utils.js';
 import { isNoteMuted, isContentUnreadable } from './muting.js';
 
 var likeLongPressTimer = null;
@@ -352,6 +352,111 @@ export function performLike(noteId, pubkey, emoji, likeBtn) {
         });
 }
 
+// Retract our like of a note (NIP-09 deletion of our reactions to it) and update UI on success
+export function performUnlike(noteId, likeBtn) {
+    if (!noteId) {
+        return;
+    }
+    var btn = likeBtn;
+    if (btn) {
+        btn.disabled = true;
+    }
+    invoke('undo_response', { event_id: noteId, kind: 7 })
+        .then(function() {
+            if (state.likedNoteIds) {
+                delete state.likedNoteIds[noteId];
+            }
+            if (btn) {
+                var img = btn.querySelector('img');
+                if (img) {
+                    img.src = 'icons/heart.svg';
+                }
+                btn.classList.remove('liked');
+            }
+        })
+        .catch(function(err) {
+            console.error('Unlike failed:', err);
+            alert((window.PlumeI18n && window.PlumeI18n.t ? window.PlumeI18n.t('errors.failedToDelete') : 'Failed to delete') + ': ' + err);
+        })
+        .finally(function() {
+            if (btn) {
+                btn.disabled = false;
+            }
+        });
+}
+
+// Text listing which relays acknowledged a deletion (publish results JSON from delete_event / undo_response)
+export function describeDeletionResult(result) {
+    var t = window.PlumeI18n && window.PlumeI18n.t ? window.PlumeI18n.t.bind(window.PlumeI18n) : function(k) { return k; };
+    var data = typeof result === 'string' ? JSON.parse(result) : result;
+    var lines = [t('note.deletedOnRelays', { success: data.success_count, total: data.total_count })];
+    (data.results || []).forEach(function(r) {
+        lines.push((r.success ? '✓ ' : '✗ ') + r.relay_url + (!r.success && r.message ? ' – ' + r.message : ''));
+    });
+    return lines.join('\n');
+}
+
+// Remove notes (by event id) from the note lists in state and from every rendered card
+export function removeDeletedNotes(ids) {
+    if (!Array.isArray(ids) || ids.length === 0) {
+        return;
+    }
+    ['notes', 'profileNotes', 'bookmarkNotes'].forEach(function(key) {
+        if (Array.isArray(state[key])) {
+            state[key] = state[key].filter(function(n) { return ids.indexOf(n.id) === -1; });
+        }
+    });
+    ids.forEach(function(id) {
+        document.querySelectorAll('.note-card[data-note-id="' + escapeCssAttr(id) + '"]').forEach(function(card) {
+            card.remove();
+        });
+    });
+}
+
+// Ask the relays to delete one of our notes or reposts (NIP-09), then show which relays acknowledged it
+export async function deleteNote(noteId, kind, deleteBtn) {
+    if (!noteId) {
+        return;
+    }
+    var t = window.PlumeI18n && window.PlumeI18n.t ? window.PlumeI18n.t.bind(window.PlumeI18n) : function(k) { return k; };
+    var confirmed = await showConfirm(t('note.deleteConfirm'));
+    if (!confirmed) {
+        return;
+    }
+    if (deleteBtn) {
+        deleteBtn.disabled = true;
+    }
+    try {
+        var result = await invoke('delete_event', { event_id: noteId, kind: kind || 1, reason: null });
+        removeDeletedNotes([noteId]);
+        alert(describeDeletionResult(result));
+    } catch (err) {
+        console.error('Delete failed:', err);
+        alert(t('errors.failedToDelete') + ': ' + err);
+        if (deleteBtn) {
+            deleteBtn.disabled = false;
+        }
+    }
+}
+
+// Copy a nostr:nevent link to the note (with our relays as hints) to the clipboard
+export async function copyNoteLink(noteId, pubkey, kind) {
+    if (!noteId) {
+        return;
+    }
+    var t = window.PlumeI18n && window.PlumeI18n.t ? window.PlumeI18n.t.bind(window.PlumeI18n) : function(k) { return k; };
+    try {
+        var link = await invoke('share_note', { event_id: noteId, author: pubkey || null, kind: kind || null });
+        if (link && navigator.clipboard) {
+            await navigator.clipboard.writeText(link);
+            alert(t('note.linkCopied') || 'Link to note copied to clipboard.');
+        }
+    } catch (err) {
+        console.error('Share failed:', err);
+        alert((t('errors.failedToShare') || 'Failed to create link') + ': ' + err);
+    }
+}
+
 export function openLikeEmojiModal(noteId, pubkey, likeBtn) {
     state.pendingLikeNoteId = noteId;
     state.pendingLikePubkey = pubkey;
@@ -432,7 +537,9 @@ export function handleLikeMouseUp(e) {
         var noteId = likeButtonMouseDown.noteId;
         var pubkey = likeButtonMouseDown.pubkey;
         var releaseInside = e.target && btn && btn.contains(e.target);
-        if (releaseInside) {
+        if (releaseInside && isNoteLiked(noteId)) {
+            performUnlike(noteId, btn);
+        } else if (releaseInside) {
             performLike(noteId, pubkey, DEFAULT_LIKE_EMOJI, btn);
         }
     }
@@ -476,6 +583,9 @@ export function createNoteCard(note, noteIndex, idPrefix, replyToPubkey, isBookm
         safeReplyToPubkey = escapeHtml(replyToPubkey);
     }
     const replyingToLabel = t('note.replyingTo');
+    const deleteHtml = note.pubkey && note.pubkey === state.publicKeyHex
+        ? `<button type="button" class="note-action" title="${escapeHtml(t('note.delete'))}" aria-label="${escapeHtml(t('note.delete'))}" data-action="delete" data-note-id="${safeId}" data-kind="${Number(note.kind) || 1}"><img src="icons/trash.svg" alt="${escapeHtml(t('note.delete'))}" class="icon-trash"></button>`
+        : '';
 
     const card = document.createElement('div');
     card.className = 'note-card';
@@ -506,6 +616,8 @@ export function createNoteCard(note, noteIndex, idPrefix, replyToPubkey, isBookm
                     <button type="button" class="note-action${liked ? ' liked' : ''}" title="${escapeHtml(t('note.like'))}" aria-label="${escapeHtml(t('note.like'))}" data-action="like" data-note-id="${safeId}" data-pubkey="${safePubkey}"><img src="icons/${liked ? 'heart-filled' : 'heart'}.svg" alt="${escapeHtml(t('note.like'))}" class="icon-heart"></button>
                     <button type="button" class="note-action" title="${escapeHtml(t('note.repost'))}" aria-label="${escapeHtml(t('note.repost'))}" data-action="repost" data-note-id="${safeId}" data-pubkey="${safePubkey}"><img src="icons/repost.svg" alt="${escapeHtml(t('note.repost'))}" class="icon-repost"></button>
                     <button type="button" class="note-action" title="${escapeHtml(isBookmarked ? (t('note.unbookmark') || 'Unbookmark') : t('note.bookmark'))}" aria-label="${escapeHtml(isBookmarked ? (t('note.unbookmark') || 'Unbookmark') : t('note.bookmark'))}" data-action="bookmark" data-note-id="${safeId}"><img src="icons/${isBookmarked ? 'bookmark-filled' : 'bookmark'}.svg" alt="${escapeHtml(isBookmarked ? (t('note.unbookmark') || 'Unbookmark') : t('note.bookmark'))}" class="icon-bookmark"></button>
+                    <button type="button" class="note-action" title="${escapeHtml(t('note.share'))}" aria-label="${escapeHtml(t('note.share'))}" data-action="share" data-note-id="${safeId}" data-pubkey="${safePubkey}" data-kind="${Number(note.kind) || 1}"><img src="icons/share.svg" alt="${escapeHtml(t('note.share'))}" class="icon-share"></button>
+                    ${deleteHtml}
                 </div>
             </div>
         </div>
@@ -540,6 +652,9 @@ export function createRepostCard(repostEvent, noteIndex, idPrefix) {
     }
     const viewProfile = t('note.viewProfile');
     const repostedLabel = t('note.reposted');
+    const deleteHtml = repostEvent.pubkey && repostEvent.pubkey === state.publicKeyHex
+        ? `<button type="button" class="note-action" title="${escapeHtml(t('note.delete'))}" aria-label="${escapeHtml(t('note.delete'))}" data-action="delete" data-note-id="${safeId}" data-kind="6"><img src="icons/trash.svg" alt="${escapeHtml(t('note.delete'))}" class="icon-trash"></button>`
+        : '';
     var origBlock = '';
     var safeOrigPubkey = '';
     var origTime = '';
@@ -590,6 +705,7 @@ export function createRepostCard(repostEvent, noteIndex, idPrefix) {
             <button type="button" class="note-action" title="${escapeHtml(t('note.reply'))}" aria-label="${escapeHtml(t('note.reply'))}" data-action="reply" data-note-id="${safeId}" data-pubkey="${safePubkey}"><img src="icons/reply.svg" alt="${escapeHtml(t('note.reply'))}" class="icon-reply"></button>
             <button type="button" class="note-action zap-muted" title="${escapeHtml(t('note.zapNoWallet'))}" aria-label="${escapeHtml(t('note.zap'))}" data-action="zap" data-zap-target-pubkey="${safeOrigPubkey || ''}" data-zap-event-id="${parsed && parsed.id ? escapeHtml(parsed.id) : ''}" disabled><img src="icons/zap.svg" alt="${escapeHtml(t('note.zap'))}" class="icon-zap"></button>
             <button type="button" class="note-action" title="${escapeHtml(t('note.repost'))}" aria-label="${escapeHtml(t('note.repost'))}" data-action="repost" data-note-id="${safeId}" data-pubkey="${safePubkey}"><img src="icons/repost.svg" alt="${escapeHtml(t('note.repost'))}" class="icon-repost"></button>
+            ${deleteHtml}
         </div>
     `;
     return card;
@@ -671,15 +787,15 @@ export function processNoteContent(content, depth) {
     let html = escapeHtml(content);
 
     // Handle nostr: URIs (before URL linkification so they don't get turned into <a> tags)
-    var nostrRegex = /nostr:(n(?:event|profile|pub|ote)1[a-z0-9]+)/gi;
+    var nostrRegex = /nostr:(n(?:event|profile|pub|ote|addr)1[a-z0-9]+)/gi;
     html = html.replace(nostrRegex, function(fullMatch, bech32) {
         var lower = bech32.toLowerCase();
         if (lower.startsWith('npub1') || lower.startsWith('nprofile1')) {
             // Profile reference: inline link placeholder (resolved async after DOM insert)
             return '<a class="nostr-profile-link" data-nostr-ref="' + escapeHtml(lower) + '" data-pubkey="" href="#">@' + escapeHtml(shortenKey(lower)) + '</a>';
         }
-        if (lower.startsWith('note1') || lower.startsWith('nevent1')) {
-            // Note/event reference: embed if within depth limit
+        if (lower.startsWith('note1') || lower.startsWith('nevent1') || lower.startsWith('naddr1')) {
+            // Note/event (or addressable event: article, list...) reference: embed if within depth limit
             if (depth < NOSTR_EMBED_MAX_DEPTH) {
                 return '<div class="nostr-embed-placeholder" data-nostr-ref="' + escapeHtml(lower) + '" data-depth="' + depth + '"><span class="embed-loading">Loading referenced note...</span></div>';
             }
@@ -815,11 +931,16 @@ export async function resolveNostrEmbeds(container) {
     // --- 4. Resolve note/event embeds ---
     var eventIdsToFetch = new Set();
     var embedInfo = {};  // bech32 -> { eventId, relayHints }
+    var addressRefs = new Set();  // naddr bech32 strings, fetched by coordinate
     embedPlaceholders.forEach(function(el) {
         var d = decoded[el.dataset.nostrRef];
         if (!d) {
             return;
         }
+        if (d.type === 'naddr') {
+            addressRefs.add(el.dataset.nostrRef);
+            return;
+        }
         var eid = d.event_id;
         if (eid) {
             eventIdsToFetch.add(eid);
@@ -844,11 +965,28 @@ export async function resolveNostrEmbeds(container) {
             console.warn('[Plume] Failed to fetch embedded events:', e);
         }
 
-        // Ensure profiles for embedded note authors
-        var embeddedNotes = Object.values(fetchedEvents);
-        if (embeddedNotes.length > 0) {
-            await ensureProfilesForNotes(embeddedNotes);
+    }
+
+    // Addressable events (naddr): latest version by kind, author and d-tag
+    await Promise.all(Array.from(addressRefs).map(async function(ref) {
+        var d = decoded[ref];
+        var relays = Array.from(new Set((d.relays || []).concat(getEffectiveRelays())));
+        try {
+            var json = await invoke('fetch_addressable_event', { relay_urls: relays, kind: d.kind, pubkey: d.pubkey, identifier: d.identifier || '' });
+            var found = json ? JSON.parse(json) : [];
+            if (found.length > 0) {
+                fetchedEvents[found[0].id] = found[0];
+                embedInfo[ref] = { eventId: found[0].id, relayHints: d.relays || [] };
+            }
+        } catch (e) {
+            console.warn('[Plume] Failed to fetch addressable event:', e);
         }
+    }));
+
+    // Ensure profiles for embedded note authors
+    var embeddedNotes = Object.values(fetchedEvents);
+    if (embeddedNotes.length > 0) {
+        await ensureProfilesForNotes(embeddedNotes);
     }
 
     // Replace placeholders with embedded note cards
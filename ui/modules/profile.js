@@ -20,7 +20,8 @@
 
 import { state, getEffectiveRelays, FEED_LIMIT } from './state.js';
 import { invoke } from './tauri.js';
-import { escapeHtml, debugLog } from './utils.js';
+import { escapeHtml, debugLog, showPasswordPrompt } from './utils.js';
+import { verifyNip05 } from './keys.js';
 import { saveConfig, setSavingState } from './config.js';
 import { isNoteMuted, isUserMuted } from './muting.js';
 import { createNoteCard, createRepostCard, verifyNote, verifyRepostOriginal, ensureProfilesForNotes, setCardAvatar, getReplyToPubkey, resolveNostrEmbeds } from './notes.js';
@@ -265,6 +266,25 @@ export function openProfileQRModal() {
     });
 }
 
+// Copy a nostr:nprofile link (with our relays as hints) for the profile in the QR modal
+export async function copyProfileLink() {
+    var t = window.PlumeI18n && window.PlumeI18n.t ? window.PlumeI18n.t.bind(window.PlumeI18n) : function(k) { return k; };
+    var pubkey = state.viewedProfilePubkey || state.publicKeyHex || (state.config && state.config.public_key);
+    if (!pubkey) {
+        return;
+    }
+    try {
+        var link = await invoke('share_profile', { pubkey: pubkey });
+        if (link && navigator.clipboard) {
+            await navigator.clipboard.writeText(link);
+            alert(t('profile.profileLinkCopied') || 'Link to profile copied to clipboard.');
+        }
+    } catch (err) {
+        console.error('Share failed:', err);
+        alert((t('errors.failedToShare') || 'Failed to create link') + ': ' + err);
+    }
+}
+
 export function closeProfileQRModal() {
     var modal = document.getElementById('profile-qr-modal');
     if (modal) {
@@ -717,13 +737,17 @@ export async function generateNewKeyPair() {
 
     // Skip confirmation for now - just generate
     debugLog('Proceeding to generate keys...');
+    const password = await showPasswordPrompt(window.PlumeI18n && window.PlumeI18n.t ? window.PlumeI18n.t('auth.newPasswordPrompt') : 'Choose a password to encrypt your private key.');
+    if (!password) {
+        return;
+    }
 
     try {
         btn.disabled = true;
         btn.textContent = (window.PlumeI18n && window.PlumeI18n.t ? window.PlumeI18n.t('profile.generating') : 'Generating...');
         debugLog('Calling invoke generate_keypair...');
 
-        const result = await invoke('generate_keypair');
+        const result = await invoke('generate_keypair', { password: password });
         debugLog('Got result: ' + result);
         const keys = JSON.parse(result);
 
@@ -752,6 +776,27 @@ export async function generateNewKeyPair() {
     }
 }
 
+// Mark the profile's NIP-05 identifier verified, mismatched or unreachable once the domain answers
+function showNip05Status(el, identifier, pubkey) {
+    el.className = 'nip05';
+    el.removeAttribute('title');
+    if (!identifier || !pubkey) {
+        return;
+    }
+    el.dataset.pubkey = pubkey;
+    verifyNip05(identifier, pubkey).then(function(result) {
+        // Ignore the answer if another profile is shown by now
+        if (!result || el.dataset.pubkey !== pubkey || el.textContent !== identifier) {
+            return;
+        }
+        const t = window.PlumeI18n && window.PlumeI18n.t ? window.PlumeI18n.t.bind(window.PlumeI18n) : function(k) { return k; };
+        const domain = result.identifier.split('@').pop();
+        const titles = { verified: 'profile.nip05Verified', mismatch: 'profile.nip05Mismatch', unreachable: 'profile.nip05Unreachable' };
+        el.className = 'nip05 nip05-' + result.status;
+        el.title = t(titles[result.status], { domain: domain });
+    });
+}
+
 // Update the profile display (profile page from state.viewedProfile; sidebar from state.profile)
 export function updateProfileDisplay() {
     const nameEl = document.getElementById('profile-name');
@@ -803,6 +848,7 @@ export function updateProfileDisplay() {
         if (nip05El) {
             nip05El.textContent = profile.nip05 || '';
             nip05El.style.display = profile.nip05 ? 'block' : 'none';
+            showNip05Status(nip05El, profile.nip05, viewingOwn ? state.publicKeyHex : state.viewedProfilePubkey);
         }
         if (websiteEl) {
             if (profile.website) {
@@ -39,15 +39,28 @@ export function updateRelayList() {
         state.config.relays = [];
     }
 
+    if (!Array.isArray(state.config.auth_denied_relays)) {
+        state.config.auth_denied_relays = [];
+    }
+    if (!state.config.relay_pow_difficulty || typeof state.config.relay_pow_difficulty !== 'object') {
+        state.config.relay_pow_difficulty = {};
+    }
+
     const deleteLabel = t('settings.relayDelete');
     const unknownTitle = t('relays.statusUnknown');
+    const authLabel = t('relays.autoAuth');
+    const powLabel = t('relays.powDifficulty');
     state.config.relays.forEach((relay, index) => {
         const li = document.createElement('li');
         li.className = 'relay-item';
         li.dataset.index = String(index);
         const esc = escapeHtml(relay);
+        const authChecked = state.config.auth_denied_relays.indexOf(relay) === -1 ? ' checked' : '';
+        const pow = state.config.relay_pow_difficulty[relay] || 0;
         li.innerHTML = `
-            <span class="relay-url">${esc}</span>
+            <span class="relay-url" id="relay-url-${index}">${esc}</span>
+            <label class="relay-auth-toggle" title="${escapeHtml(authLabel)}"><input type="checkbox" class="relay-auth-checkbox" data-index="${index}"${authChecked}> AUTH</label>
+            <label class="relay-pow-setting" title="${escapeHtml(powLabel)}">PoW <input type="number" class="relay-pow-input" data-index="${index}" min="0" max="40" step="1" value="${pow}"></label>
             <div class="relay-status" id="relay-status-${index}" title="${escapeHtml(unknownTitle)}" aria-label="${escapeHtml(unknownTitle)}"></div>
             <button type="button" class="btn btn-small btn-ghost relay-delete-btn" data-index="${index}" aria-label="${escapeHtml(deleteLabel)}">×</button>
         `;
@@ -67,6 +80,8 @@ export function bindRelayPanelHandlers() {
 
     list.removeEventListener('click', handleRelayListClick);
     list.addEventListener('click', handleRelayListClick);
+    list.removeEventListener('change', handleRelayPowChange);
+    list.addEventListener('change', handleRelayPowChange);
 
     if (addBtn) {
         addBtn.onclick = function() {
@@ -107,6 +122,12 @@ export function bindRelayPanelHandlers() {
     }
     if (saveBtn) {
         saveBtn.onclick = function() {
+            var quorumEl = document.getElementById('settings-relays-publish-quorum');
+            if (quorumEl && state.config) {
+                var raw = parseInt(quorumEl.value, 10);
+                state.config.publish_quorum = isNaN(raw) ? 1 : Math.max(0, Math.min(100, raw));
+                quorumEl.value = state.config.publish_quorum;
+            }
             saveConfig().catch(function(err) { console.error('Failed to save relays:', err); });
         };
     }
@@ -114,11 +135,28 @@ export function bindRelayPanelHandlers() {
 
 export function handleRelayListClick(e) {
     var target = e.target;
+    if (target.classList && target.classList.contains('relay-auth-checkbox')) {
+        var authIdx = parseInt(target.getAttribute('data-index'), 10);
+        if (!state.config || !Array.isArray(state.config.relays) || isNaN(authIdx) || authIdx < 0 || authIdx >= state.config.relays.length) {
+            return;
+        }
+        var relayUrl = state.config.relays[authIdx];
+        var denied = Array.isArray(state.config.auth_denied_relays) ? state.config.auth_denied_relays : [];
+        denied = denied.filter(function(r) { return r !== relayUrl; });
+        if (!target.checked) {
+            denied.push(relayUrl);
+        }
+        state.config.auth_denied_relays = denied;
+        return;
+    }
     if (target.classList && target.classList.contains('relay-delete-btn')) {
         var idx = parseInt(target.getAttribute('data-index'), 10);
         if (!state.config || !Array.isArray(state.config.relays) || isNaN(idx) || idx < 0 || idx >= state.config.relays.length) {
             return;
         }
+        if (state.config.relay_pow_difficulty) {
+            delete state.config.relay_pow_difficulty[state.config.relays[idx]];
+        }
         state.config.relays.splice(idx, 1);
         updateRelayList();
         bindRelayPanelHandlers();
@@ -127,6 +165,30 @@ export function handleRelayListClick(e) {
     }
 }
 
+// Per-relay NIP-13 difficulty to mine into events we publish there (0 = none).
+export function handleRelayPowChange(e) {
+    var target = e.target;
+    if (!target.classList || !target.classList.contains('relay-pow-input')) {
+        return;
+    }
+    var idx = parseInt(target.getAttribute('data-index'), 10);
+    if (!state.config || !Array.isArray(state.config.relays) || isNaN(idx) || idx < 0 || idx >= state.config.relays.length) {
+        return;
+    }
+    var raw = parseInt(target.value, 10);
+    var difficulty = isNaN(raw) ? 0 : Math.max(0, Math.min(40, raw));
+    target.value = difficulty;
+    var relayUrl = state.config.relays[idx];
+    if (!state.config.relay_pow_difficulty || typeof state.config.relay_pow_difficulty !== 'object') {
+        state.config.relay_pow_difficulty = {};
+    }
+    if (difficulty > 0) {
+        state.config.relay_pow_difficulty[relayUrl] = difficulty;
+    } else {
+        delete state.config.relay_pow_difficulty[relayUrl];
+    }
+}
+
 // Test all relays asynchronously when the relay list panel is visible; update status dots (grey=unknown, green=ok, red=failed).
 // Relays currently in the connection backoff list are immediately shown as red without a connection test.
 export function runRelayTests() {
@@ -138,6 +200,23 @@ export function runRelayTests() {
     }
     var relays = state.config.relays.slice();
 
+    // Show each relay's NIP-11 name and description as a tooltip on its URL
+    relays.forEach(function(relayUrl, index) {
+        invoke('fetch_relay_info', { relayUrl: relayUrl })
+            .then(function(json) {
+                var el = document.getElementById('relay-url-' + index);
+                if (!el) {
+                    return;
+                }
+                var info = JSON.parse(json);
+                var parts = [info.name, info.description].filter(function(s) { return s; });
+                if (parts.length > 0) {
+                    el.title = parts.join(' — ');
+                }
+            })
+            .catch(function() {});
+    });
+
     // First, check which relays are in the backoff list and mark them red immediately
     invoke('get_relay_backoff_status', { relay_urls: relays })
         .then(function(json) {
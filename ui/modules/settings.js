@@ -26,6 +26,7 @@ import { hexToNpub, validatePublicKey, validateSecretKey } from './keys.js';
 import { updateRelayList, bindRelayPanelHandlers, runRelayTests } from './relays.js';
 import { loadFollowsPanel } from './follows.js';
 import { loadMutedPanel } from './muting.js';
+import { loadBunkerPanel } from './bunker.js';
 import { fetchProfile } from './profile.js';
 import { startInitialFeedFetch } from './feed.js';
 
@@ -127,6 +128,10 @@ export function showSettingsPanel(key) {
         if (hideEncryptedCb) {
             hideEncryptedCb.checked = !state.config || state.config.hide_encrypted_notes !== false;
         }
+        var minPowEl = document.getElementById('home-feed-min-pow');
+        if (minPowEl) {
+            minPowEl.value = (state.config && state.config.min_feed_pow) || 0;
+        }
     }
     if (key === 'media') {
         var urlEl = document.getElementById('settings-media-server-url');
@@ -144,6 +149,13 @@ export function showSettingsPanel(key) {
         updateRelayList();
         bindRelayPanelHandlers();
         runRelayTests();
+        var quorumEl = document.getElementById('settings-relays-publish-quorum');
+        if (quorumEl) {
+            quorumEl.value = (state.config && state.config.publish_quorum != null) ? state.config.publish_quorum : 1;
+        }
+    }
+    if (key === 'bunker') {
+        loadBunkerPanel();
     }
     if (key === 'zaps') {
         var amountEl = document.getElementById('settings-zaps-default-amount');
@@ -178,6 +190,14 @@ export async function populateKeysPanel() {
     if (copyNsecBtn) {
         copyNsecBtn.style.display = (state.config && state.config.private_key) ? 'inline-block' : 'none';
     }
+    var copyNcryptsecBtn = document.getElementById('settings-keys-copy-ncryptsec');
+    if (copyNcryptsecBtn) {
+        copyNcryptsecBtn.style.display = (state.config && state.config.encrypted_private_key) ? 'inline-block' : 'none';
+    }
+    var passwordEl = document.getElementById('settings-keys-password');
+    if (passwordEl) {
+        passwordEl.value = '';
+    }
     if (!state.config) {
         return;
     }
@@ -210,7 +230,40 @@ export async function copyNsecToClipboard() {
     }
 }
 
-// Save Keys panel: validate npub/nsec, store hex, save config
+// Copy the stored NIP-49 encrypted key (ncryptsec) to the clipboard
+export async function copyNcryptsecToClipboard() {
+    try {
+        var ncryptsec = await invoke('export_ncryptsec');
+        if (ncryptsec && navigator.clipboard) {
+            await navigator.clipboard.writeText(ncryptsec);
+            var t = window.PlumeI18n && window.PlumeI18n.t ? window.PlumeI18n.t.bind(window.PlumeI18n) : function(k) { return k; };
+            alert(t('accountModal.ncryptsecCopied') || 'Encrypted private key (ncryptsec) copied to clipboard.');
+        }
+    } catch (e) {
+        console.error('Failed to copy ncryptsec:', e);
+        alert('Failed to copy encrypted private key: ' + e);
+    }
+}
+
+// Store a new private key (nsec, hex or ncryptsec) encrypted with the password. The key goes
+// straight to the backend; state.config only picks up the unlocked copy it returns.
+async function storePrivateKey(privateKey, password) {
+    var configJson = await invoke('set_private_key', { private_key: privateKey, password: password });
+    var cfg = JSON.parse(configJson);
+    state.config.private_key = cfg.private_key;
+    state.config.encrypted_private_key = cfg.encrypted_private_key;
+}
+
+// Validate a private key entered in a form: ncryptsec is checked by the backend when it is
+// decrypted, anything else must be nsec or hex.
+async function validateEnteredSecretKey(key) {
+    if (key.startsWith('ncryptsec1')) {
+        return { valid: true, error: null };
+    }
+    return validateSecretKey(key);
+}
+
+// Save Keys panel: validate npub/nsec, save config, store a new private key encrypted
 export async function saveKeysPanel(event) {
     if (event) {
         event.preventDefault();
@@ -229,7 +282,7 @@ export async function saveKeysPanel(event) {
         nsecError.textContent = '';
     }
     var publicKeyHex = null;
-    var privateKeyHex = null;
+    var privateKey = null;
     var npubRaw = (npubEl && npubEl.value) ? npubEl.value.trim() : '';
     if (!npubRaw) {
         if (npubError) {
@@ -245,24 +298,43 @@ export async function saveKeysPanel(event) {
         return;
     }
     publicKeyHex = pubResult.hex;
+    var passwordEl = document.getElementById('settings-keys-password');
+    var password = passwordEl ? passwordEl.value : '';
     if (nsecEl && nsecEl.value.trim()) {
-        var privResult = await validateSecretKey(nsecEl.value.trim());
+        var privResult = await validateEnteredSecretKey(nsecEl.value.trim());
         if (!privResult.valid) {
             if (nsecError) {
                 nsecError.textContent = privResult.error || 'Invalid private key';
             }
             return;
         }
-        privateKeyHex = privResult.hex;
+        if (!password) {
+            if (nsecError) {
+                var t = window.PlumeI18n && window.PlumeI18n.t ? window.PlumeI18n.t.bind(window.PlumeI18n) : function(k) { return k; };
+                nsecError.textContent = t('auth.passwordRequired') || 'A password is required to store a private key';
+            }
+            return;
+        }
+        privateKey = nsecEl.value.trim();
     }
     var saveBtn = document.querySelector('#settings-keys-form button[type="submit"]');
     var restoreBtn = setSavingState(saveBtn);
     state.config.public_key = publicKeyHex;
-    state.config.private_key = privateKeyHex || state.config.private_key || null;
     state.publicKeyHex = publicKeyHex;
     state.publicKeyNpub = pubResult.npub || null;
     try {
         await saveConfig();
+        if (privateKey) {
+            try {
+                await storePrivateKey(privateKey, password);
+                nsecEl.value = '';
+                passwordEl.value = '';
+            } catch (e) {
+                if (nsecError) {
+                    nsecError.textContent = typeof e === 'string' ? e : (e.message || 'Invalid private key');
+                }
+            }
+        }
         if (_updateUIFromConfig) {
             _updateUIFromConfig();
         }
@@ -282,6 +354,12 @@ export function saveHomeFeedModeFromPanel() {
     state.homeFeedMode = mode;
     var hideEncryptedCb = document.getElementById('home-feed-hide-encrypted');
     state.config.hide_encrypted_notes = hideEncryptedCb ? hideEncryptedCb.checked : true;
+    var minPowEl = document.getElementById('home-feed-min-pow');
+    if (minPowEl) {
+        var minPow = parseInt(minPowEl.value, 10);
+        state.config.min_feed_pow = isNaN(minPow) ? 0 : Math.max(0, Math.min(64, minPow));
+        minPowEl.value = state.config.min_feed_pow;
+    }
     var restoreBtn = setSavingState(document.getElementById('home-feed-panel-save'));
     saveConfig().then(function() {
         // Clear existing feed state so the next visit to feed reloads with the new mode
@@ -353,22 +431,24 @@ export async function handleSettingsSubmit(event) {
         publicKeyNpub = pubKeyResult.npub;
     }
 
-    // Validate private key (if provided)
-    let privateKeyHex = null;
+    // Validate private key (if provided); it is stored encrypted with the password
+    const keyPassword = document.getElementById('input-key-password').value;
 
     if (privateKeyInput) {
-        const privKeyResult = await validateSecretKey(privateKeyInput);
+        const privKeyResult = await validateEnteredSecretKey(privateKeyInput);
         if (!privKeyResult.valid) {
             showValidationError('input-private-key', privKeyResult.error);
             return;
         }
-        privateKeyHex = privKeyResult.hex;
+        if (!keyPassword) {
+            showValidationError('input-private-key', (window.PlumeI18n && window.PlumeI18n.t ? window.PlumeI18n.t('auth.passwordRequired') : null) || 'A password is required to store a private key');
+            return;
+        }
     }
 
     // Update config - store hex format internally
     state.config.name = displayName;
     state.config.public_key = publicKeyHex;
-    state.config.private_key = privateKeyHex;
 
     // Update our display cache
     state.publicKeyHex = publicKeyHex;
@@ -376,6 +456,14 @@ export async function handleSettingsSubmit(event) {
 
     // Save and update UI
     await saveConfig();
+    if (privateKeyInput) {
+        try {
+            await storePrivateKey(privateKeyInput, keyPassword);
+        } catch (e) {
+            showValidationError('input-private-key', typeof e === 'string' ? e : (e.message || 'Invalid private key'));
+            return;
+        }
+    }
     if (_updateUIFromConfig) {
         _updateUIFromConfig();
     }
@@ -46,13 +46,20 @@ export const state = {
     config: {
         public_key: '',
         private_key: null,
+        encrypted_private_key: null,
+        remote_signer: null,
+        remote_signer_client_key: null,
         relays: DEFAULT_RELAYS.slice(),
         name: 'Anonymous',
         about: null, picture: null, nip05: null, banner: null, website: null, lud16: null,
         home_feed_mode: 'firehose',
         media_server_url: 'https://blossom.primal.net',
         following: [], muted_users: [], muted_words: [], muted_hashtags: [], bookmarks: [],
+        auth_denied_relays: [],
+        relay_pow_difficulty: {},
+        min_feed_pow: 0,
         default_zap_amount: 42,
+        publish_quorum: 1,
         hide_encrypted_notes: true
     },
     currentView: 'feed',
@@ -94,6 +101,9 @@ export const state = {
     selectedConversation: null,   // other_pubkey (hex) or null
     openConversationWith: null,   // when opening Messages from Profile "Message", set to that pubkey
     dmStreamStarted: false,
+    messagesSearchQuery: '',      // query whose results are listed in place of the conversations
+    highlightMessageId: null,     // message to scroll to when its conversation opens (from search)
+    dmBackfill: {},               // relay_url -> { fetched, oldest, done } while older DMs are fetched
     // Follows settings panel: working copy [{ pubkey (hex), checked, listOrder }], sort key
     followsPanelList: [],
     followsPanelSort: 'name',
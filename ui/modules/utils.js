@@ -76,6 +76,50 @@ export function showConfirm(message) {
     });
 }
 
+// Password dialog (native prompt() is blocked too). Resolves the password, or null if cancelled.
+export function showPasswordPrompt(message) {
+    return new Promise(function(resolve) {
+        var modal = document.getElementById('password-dialog');
+        var form = document.getElementById('password-dialog-form');
+        var msgEl = document.getElementById('password-dialog-message');
+        var input = document.getElementById('password-dialog-input');
+        var cancelBtn = document.getElementById('password-dialog-cancel');
+        if (!modal || !form || !msgEl || !input || !cancelBtn) {
+            resolve(null);
+            return;
+        }
+        msgEl.textContent = message;
+        input.value = '';
+        modal.classList.add('active');
+        modal.setAttribute('aria-hidden', 'false');
+
+        function finish(value) {
+            input.value = '';
+            modal.classList.remove('active');
+            modal.setAttribute('aria-hidden', 'true');
+            form.removeEventListener('submit', onSubmit);
+            cancelBtn.removeEventListener('click', onCancel);
+            modal.removeEventListener('click', onBackdrop);
+            resolve(value);
+        }
+        function onSubmit(e) {
+            e.preventDefault();
+            finish(input.value);
+        }
+        function onCancel() { finish(null); }
+        function onBackdrop(e) {
+            if (e.target === modal) {
+                finish(null);
+            }
+        }
+
+        form.addEventListener('submit', onSubmit);
+        cancelBtn.addEventListener('click', onCancel);
+        modal.addEventListener('click', onBackdrop);
+        input.focus();
+    });
+}
+
 // Shorten a key for display
 export function shortenKey(key) {
     if (!key || key.length <= 16) {
@@ -193,3 +237,30 @@ export function showRelayHealthBanner() {
         }
     }, 15000);
 }
+
+/// Show or update the banner shown while proof of work (NIP-13) is mined for a post, with a
+/// Cancel button. Removed when progress.done is set.
+export function updatePowProgressBanner(progress, onCancel) {
+    var banner = document.getElementById('pow-progress-banner');
+    if (progress.done) {
+        if (banner) {
+            banner.remove();
+        }
+        return;
+    }
+    var t = window.PlumeI18n && window.PlumeI18n.t ? window.PlumeI18n.t.bind(window.PlumeI18n) : function(k) { return k; };
+    if (!banner) {
+        banner = document.createElement('div');
+        banner.id = 'pow-progress-banner';
+        banner.className = 'pow-progress-banner';
+        banner.innerHTML = '<span class="pow-progress-text"></span>'
+            + '<button type="button" class="btn btn-small btn-ghost pow-progress-cancel">' + escapeHtml(t('composeModal.powCancel') || 'Cancel') + '</button>';
+        banner.querySelector('.pow-progress-cancel').addEventListener('click', onCancel);
+        var main = document.querySelector('.main-content') || document.body;
+        main.insertBefore(banner, main.firstChild);
+    }
+    banner.querySelector('.pow-progress-text').textContent = t('composeModal.powMining', {
+        difficulty: progress.difficulty,
+        attempts: Number(progress.attempts || 0).toLocaleString()
+    });
+}